@@ -0,0 +1,17 @@
+//! Decodes arbitrary input as a token, which must fail with a diagnostic rather than panic
+//!
+//! Run with `cargo +nightly fuzz run decode_token` from the repository root.
+
+#![no_main]
+
+use azure_token_validator::token::{decode_unverified, extract_token, inspect_raw};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = decode_unverified(input);
+    let _ = inspect_raw(input);
+    let _ = decode_unverified(&extract_token(input).token);
+});
@@ -0,0 +1,77 @@
+//! actix-web extractor for requests authenticated with Azure AD bearer tokens
+//!
+//! Register a `web::Data<TokenValidator>` as app data and add an
+//! [`AzureClaims`] argument to any handler that requires a valid token.
+//! Requests without one are rejected with `401 Unauthorized`.
+
+use actix_web::dev::Payload;
+use actix_web::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse, ResponseError};
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+
+use crate::token::{bearer_token, Claims, TokenValidator};
+
+pub use crate::auth::AuthError;
+
+/// Claims of a validated bearer token
+#[derive(Debug, Clone)]
+pub struct AzureClaims(pub Claims);
+
+impl AzureClaims {
+    /// Unwraps the validated claims
+    pub fn into_inner(self) -> Claims {
+        self.0
+    }
+}
+
+impl Deref for AzureClaims {
+    type Target = Claims;
+
+    fn deref(&self) -> &Claims {
+        &self.0
+    }
+}
+
+impl FromRequest for AzureClaims {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let validator = req.app_data::<web::Data<TokenValidator>>().cloned();
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(bearer_token)
+            .map(str::to_string);
+
+        Box::pin(async move {
+            let validator = validator.ok_or_else(|| {
+                actix_web::error::ErrorInternalServerError(
+                    "AzureClaims requires web::Data<TokenValidator> to be registered",
+                )
+            })?;
+            let token = token.ok_or(AuthError::MissingToken)?;
+            let claims = validator
+                .validate_token(&token)
+                .await
+                .map_err(AuthError::InvalidToken)?;
+            Ok(AzureClaims(claims))
+        })
+    }
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::UNAUTHORIZED
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::Unauthorized()
+            .insert_header((WWW_AUTHENTICATE, self.challenge()))
+            .body(self.to_string())
+    }
+}
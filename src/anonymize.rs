@@ -0,0 +1,212 @@
+//! Replace the identifying values of a token to share it in bug reports
+//!
+//! The anonymized token keeps the structure of the original: the same
+//! claims, value lengths and character classes, and unchanged timestamps.
+//! It is signed with a key generated for the run, whose public JWK is
+//! returned so the token can still be validated offline.
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rsa::pkcs1v15::SigningKey;
+use rsa::rand_core::{OsRng, RngCore};
+use rsa::signature::{SignatureEncoding, Signer};
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+/// Claims identifying the user or session, replaced wherever they appear, e.g. also in `act`
+const IDENTIFYING_CLAIMS: &[&str] = &[
+    "oid",
+    "sub",
+    "upn",
+    "unique_name",
+    "email",
+    "preferred_username",
+    "name",
+    "given_name",
+    "family_name",
+    "login_hint",
+    "verified_primary_email",
+    "verified_secondary_email",
+    "ipaddr",
+    "puid",
+    "altsecid",
+    "onprem_sid",
+    "sid",
+    "aio",
+    "uti",
+];
+
+/// Header parameters naming the signing key, replaced by the throwaway key's ID
+const KEY_ID_PARAMETERS: &[&str] = &["kid", "x5t"];
+
+/// Size of the throwaway signing key, that of Azure AD's keys
+const KEY_BITS: usize = 2048;
+
+/// A token with its identifying values replaced
+#[derive(Debug)]
+pub struct Anonymized {
+    pub token: String,
+    /// Key set with the public key the token is signed with
+    pub jwks: Value,
+    /// Claims whose values were replaced
+    pub replaced: Vec<String>,
+}
+
+/// Replaces the identifying values of a JWT and signs it with a throwaway RS256 key
+///
+/// Each value is replaced by one of the same length and character classes,
+/// and equal values by equal replacements, so e.g. `upn` and `email` still
+/// match. Replacements are derived from a random salt and differ per run.
+pub fn anonymize(token: &str) -> Result<Anonymized> {
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    let [header, payload, _signature] = parts.as_slice() else {
+        bail!("Only signed JWTs (three segments) can be anonymized");
+    };
+    let mut header: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header)?)
+        .context("Token header is not JSON")?;
+    let mut payload: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload)?)
+        .context("Token payload is not JSON")?;
+
+    let mut salt = [0u8; 32];
+    OsRng.fill_bytes(&mut salt);
+    let faker = Faker { salt };
+
+    let mut replaced = Vec::new();
+    faker.replace_claims(&mut payload, &mut replaced);
+    replaced.sort();
+    replaced.dedup();
+
+    let kid = header["kid"]
+        .as_str()
+        .map(|kid| faker.fake(kid))
+        .unwrap_or_else(|| "anonymized".to_string());
+    if let Value::Object(header) = &mut header {
+        for parameter in KEY_ID_PARAMETERS {
+            if header.contains_key(*parameter) {
+                header.insert(parameter.to_string(), Value::from(kid.clone()));
+            }
+        }
+        header.insert("alg".to_string(), Value::from("RS256"));
+    }
+
+    let key = RsaPrivateKey::new(&mut OsRng, KEY_BITS).context("Failed to generate a key")?;
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&payload)?)
+    );
+    let signature = SigningKey::<Sha256>::new(key.clone()).sign(signing_input.as_bytes());
+
+    Ok(Anonymized {
+        token: format!(
+            "{}.{}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        ),
+        jwks: json!({
+            "keys": [{
+                "kid": kid,
+                "kty": "RSA",
+                "use": "sig",
+                "n": URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+                "e": URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+            }]
+        }),
+        replaced,
+    })
+}
+
+/// Derives stable fake values from a salt
+struct Faker {
+    salt: [u8; 32],
+}
+
+impl Faker {
+    /// Replaces the identifying claims anywhere in a JSON value, recording their names
+    fn replace_claims(&self, value: &mut Value, replaced: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if IDENTIFYING_CLAIMS.contains(&key.as_str()) {
+                        if self.replace_strings(value) {
+                            replaced.push(key.clone());
+                        }
+                    } else {
+                        self.replace_claims(value, replaced);
+                    }
+                }
+            }
+            Value::Array(items) => items
+                .iter_mut()
+                .for_each(|item| self.replace_claims(item, replaced)),
+            _ => {}
+        }
+    }
+
+    /// Replaces a string or the strings of an array, returning whether any was replaced
+    fn replace_strings(&self, value: &mut Value) -> bool {
+        match value {
+            Value::String(text) => {
+                *text = self.fake(text);
+                true
+            }
+            Value::Array(items) => items
+                .iter_mut()
+                .fold(false, |any, item| self.replace_strings(item) | any),
+            _ => false,
+        }
+    }
+
+    /// Replaces each letter and digit of a value, keeping separators such as `@`, `.` and `-`
+    ///
+    /// GUIDs stay valid GUIDs: their hex digits are replaced by hex digits.
+    fn fake(&self, value: &str) -> String {
+        const LOWER: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+        const UPPER: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        const DIGITS: &[u8] = b"0123456789";
+        const HEX: &[u8] = b"0123456789abcdef";
+
+        let guid = is_guid(value);
+        let stream = self.keystream(value, value.len());
+        value
+            .chars()
+            .zip(stream)
+            .map(|(c, byte)| {
+                let alphabet = match c {
+                    _ if guid && c.is_ascii_hexdigit() => HEX,
+                    'a'..='z' => LOWER,
+                    'A'..='Z' => UPPER,
+                    '0'..='9' => DIGITS,
+                    _ => return c,
+                };
+                alphabet[byte as usize % alphabet.len()] as char
+            })
+            .collect()
+    }
+
+    /// Derives `len` pseudo-random bytes from the salt and a value
+    fn keystream(&self, value: &str, len: usize) -> Vec<u8> {
+        let mut stream = Vec::with_capacity(len + 32);
+        let mut counter: u32 = 0;
+        while stream.len() < len {
+            let mut hasher = Sha256::new();
+            hasher.update(self.salt);
+            hasher.update(counter.to_be_bytes());
+            hasher.update(value.as_bytes());
+            stream.extend_from_slice(&hasher.finalize());
+            counter += 1;
+        }
+        stream
+    }
+}
+
+fn is_guid(value: &str) -> bool {
+    value.len() == 36
+        && value.char_indices().all(|(index, c)| match index {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
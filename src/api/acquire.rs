@@ -0,0 +1,340 @@
+//! Token acquisition behind one interface
+//!
+//! [`AcquireToken`] is implemented for each way this crate obtains tokens, so
+//! a pipeline can acquire, validate and test a token without caring where it
+//! comes from.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::api::managed_identity::{ManagedIdentityClient, ManagedIdentitySource};
+use crate::api::oauth::{
+    ClientCredentials, DeviceCode, TokenEndpointClient, TokenEndpointError, TokenResponse,
+};
+use crate::token::{Clock, SystemClock};
+
+/// Program run for [`AzureCli`], a batch file on Windows
+const AZ: &str = if cfg!(windows) { "az.cmd" } else { "az" };
+
+/// Future returned by [`AcquireToken::acquire_token`]
+pub type TokenFuture<'a> = Pin<Box<dyn Future<Output = Result<AcquiredToken>> + Send + 'a>>;
+
+/// Access token obtained by an [`AcquireToken`] provider
+#[derive(Debug, Clone)]
+pub struct AcquiredToken {
+    pub access_token: String,
+    /// Unix time the token expires at, if the provider reports it
+    pub expires_on: Option<u64>,
+    /// Refresh token, returned by delegated flows
+    pub refresh_token: Option<String>,
+}
+
+impl AcquiredToken {
+    fn from_response(response: TokenResponse) -> Result<Self> {
+        Ok(AcquiredToken {
+            access_token: response
+                .access_token
+                .context("The token endpoint returned no access token")?,
+            expires_on: Some(SystemClock.now() + response.expires_in),
+            refresh_token: response.refresh_token,
+        })
+    }
+}
+
+/// Source of access tokens
+///
+/// Providers are displayed as the source tokens are requested from.
+pub trait AcquireToken: fmt::Display + Send + Sync {
+    /// Acquires an access token for `resource`, e.g. `https://graph.microsoft.com`
+    fn acquire_token<'a>(&'a self, resource: &'a str) -> TokenFuture<'a>;
+}
+
+/// Gets the v2 scope granting the permissions configured for `resource`
+pub fn default_scope(resource: &str) -> String {
+    format!("{}/.default", resource.trim_end_matches('/'))
+}
+
+/// Tokens of the managed identity of this VM, AKS node, App Service or Function
+pub struct ManagedIdentity {
+    client: ManagedIdentityClient,
+    client_id: Option<String>,
+}
+
+impl ManagedIdentity {
+    /// Uses the managed identity endpoint detected from the environment
+    ///
+    /// `client_id` selects a user-assigned identity; the system-assigned
+    /// identity is used when it is `None`.
+    pub fn new(client_id: Option<String>) -> Result<Self> {
+        let client = ManagedIdentityClient::new(ManagedIdentitySource::detect())?;
+        Ok(Self::with_client(client, client_id))
+    }
+
+    /// Requests tokens with a preconfigured managed identity client
+    pub fn with_client(client: ManagedIdentityClient, client_id: Option<String>) -> Self {
+        ManagedIdentity { client, client_id }
+    }
+}
+
+impl fmt::Display for ManagedIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "managed identity via {}", self.client.source())
+    }
+}
+
+impl AcquireToken for ManagedIdentity {
+    fn acquire_token<'a>(&'a self, resource: &'a str) -> TokenFuture<'a> {
+        Box::pin(async move {
+            let token = self
+                .client
+                .get_token(resource, self.client_id.as_deref())
+                .await?;
+            // IMDS returns expires_on as a string of Unix seconds
+            let expires_on = token.extra.get("expires_on").and_then(|value| match value {
+                Value::String(value) => value.parse().ok(),
+                value => value.as_u64(),
+            });
+            Ok(AcquiredToken {
+                access_token: token.access_token,
+                expires_on,
+                refresh_token: None,
+            })
+        })
+    }
+}
+
+/// App tokens requested with a client secret (client credentials flow)
+pub struct ClientSecret {
+    client: TokenEndpointClient,
+    credentials: ClientCredentials,
+}
+
+impl ClientSecret {
+    /// Requests tokens of the app registration from the token endpoint of its tenant
+    pub fn new(client: TokenEndpointClient, credentials: ClientCredentials) -> Self {
+        ClientSecret {
+            client,
+            credentials,
+        }
+    }
+}
+
+impl fmt::Display for ClientSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "client credentials of {} via {}",
+            self.credentials.client_id,
+            self.client.token_endpoint()
+        )
+    }
+}
+
+impl AcquireToken for ClientSecret {
+    fn acquire_token<'a>(&'a self, resource: &'a str) -> TokenFuture<'a> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .client_credentials(&self.credentials, &default_scope(resource))
+                .await?;
+            AcquiredToken::from_response(response)
+        })
+    }
+}
+
+/// App tokens exchanged for a federated token, e.g. of AKS workload identity
+pub struct FederatedCredential {
+    client: TokenEndpointClient,
+    client_id: String,
+    token_file: PathBuf,
+}
+
+impl FederatedCredential {
+    /// Exchanges the token in `token_file` for tokens of the app registration `client_id`
+    ///
+    /// The file is read for every acquisition, since projected tokens are rotated.
+    pub fn new(
+        client: TokenEndpointClient,
+        client_id: impl Into<String>,
+        token_file: impl Into<PathBuf>,
+    ) -> Self {
+        FederatedCredential {
+            client,
+            client_id: client_id.into(),
+            token_file: token_file.into(),
+        }
+    }
+}
+
+impl fmt::Display for FederatedCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "federated token {} via {}",
+            self.token_file.display(),
+            self.client.token_endpoint()
+        )
+    }
+}
+
+impl AcquireToken for FederatedCredential {
+    fn acquire_token<'a>(&'a self, resource: &'a str) -> TokenFuture<'a> {
+        Box::pin(async move {
+            let assertion = tokio::fs::read_to_string(&self.token_file)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to read federated token file {}",
+                        self.token_file.display()
+                    )
+                })?;
+            let response = self
+                .client
+                .client_assertion(&self.client_id, assertion.trim(), &default_scope(resource))
+                .await?;
+            AcquiredToken::from_response(response)
+        })
+    }
+}
+
+/// User tokens from the device code flow, signing in on another device
+pub struct DeviceCodeFlow {
+    client: TokenEndpointClient,
+    client_id: String,
+    prompt: Box<dyn Fn(&DeviceCode) + Send + Sync>,
+}
+
+impl DeviceCodeFlow {
+    /// Signs in to the public client app registration `client_id`
+    ///
+    /// `prompt` is called with the code once it is issued and must show the
+    /// user its `message`.
+    pub fn new(
+        client: TokenEndpointClient,
+        client_id: impl Into<String>,
+        prompt: impl Fn(&DeviceCode) + Send + Sync + 'static,
+    ) -> Self {
+        DeviceCodeFlow {
+            client,
+            client_id: client_id.into(),
+            prompt: Box::new(prompt),
+        }
+    }
+}
+
+impl fmt::Display for DeviceCodeFlow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "device code sign-in to {} via {}",
+            self.client_id,
+            self.client.device_code_endpoint()
+        )
+    }
+}
+
+impl AcquireToken for DeviceCodeFlow {
+    fn acquire_token<'a>(&'a self, resource: &'a str) -> TokenFuture<'a> {
+        Box::pin(async move {
+            let code = self
+                .client
+                .device_code(&self.client_id, &default_scope(resource))
+                .await?;
+            (self.prompt)(&code);
+
+            let deadline = SystemClock.now() + code.expires_in;
+            let mut interval = code.interval;
+            while SystemClock.now() < deadline {
+                tokio::time::sleep(Duration::from_secs(interval)).await;
+                let error = match self.client.device_code_token(&self.client_id, &code).await {
+                    Ok(response) => return AcquiredToken::from_response(response),
+                    Err(e) => e,
+                };
+                match error.downcast_ref::<TokenEndpointError>() {
+                    Some(pending) if pending.error == "authorization_pending" => {}
+                    Some(slow_down) if slow_down.error == "slow_down" => interval += 5,
+                    _ => return Err(error),
+                }
+            }
+            bail!("The device code expired before the sign-in was completed")
+        })
+    }
+}
+
+/// Tokens of the account signed in to the Azure CLI, with `az account get-access-token`
+#[derive(Debug, Clone, Default)]
+pub struct AzureCli {
+    tenant: Option<String>,
+}
+
+/// The parts of the output of `az account get-access-token`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CliToken {
+    access_token: String,
+    /// Unix seconds, only returned by Azure CLI 2.54 and later
+    #[serde(default, rename = "expires_on")]
+    expires_on: Option<u64>,
+}
+
+impl AzureCli {
+    /// Uses the tenant of the current subscription
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests tokens of another tenant the account is signed in to
+    pub fn with_tenant(tenant: impl Into<String>) -> Self {
+        AzureCli {
+            tenant: Some(tenant.into()),
+        }
+    }
+}
+
+impl fmt::Display for AzureCli {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.tenant {
+            Some(tenant) => write!(f, "Azure CLI (tenant {})", tenant),
+            None => write!(f, "Azure CLI"),
+        }
+    }
+}
+
+impl AcquireToken for AzureCli {
+    fn acquire_token<'a>(&'a self, resource: &'a str) -> TokenFuture<'a> {
+        Box::pin(async move {
+            let mut command = Command::new(AZ);
+            command
+                .args(["account", "get-access-token", "--resource", resource])
+                .args(["--output", "json"]);
+            if let Some(tenant) = &self.tenant {
+                command.args(["--tenant", tenant]);
+            }
+            let output = command
+                .output()
+                .await
+                .context("Failed to run az, is the Azure CLI installed and on the PATH?")?;
+            if !output.status.success() {
+                bail!(
+                    "az account get-access-token failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+
+            let token: CliToken = serde_json::from_slice(&output.stdout)
+                .context("Unexpected output from az account get-access-token")?;
+            Ok(AcquiredToken {
+                access_token: token.access_token,
+                expires_on: token.expires_on,
+                refresh_token: None,
+            })
+        })
+    }
+}
@@ -1,60 +1,792 @@
-use anyhow::{anyhow, Result};
-use reqwest::{header, Client};
-use serde_json::Value;
+use anyhow::{bail, Context, Result};
+#[cfg(feature = "net")]
+use reqwest::header;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+#[cfg(feature = "net")]
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::time::Duration;
+
+#[cfg(feature = "net")]
+use super::identity::DirectoryIdentity;
+use super::throttle;
+#[cfg(feature = "net")]
+use super::throttle::RateLimiter;
+#[cfg(feature = "net")]
+use crate::token::spans::Span;
+
+/// Base URL of the Graph v1.0 API
+const GRAPH_BASE_URL: &str = "https://graph.microsoft.com/v1.0";
+
+/// Maximum number of requests Graph accepts in one `$batch` call
+pub const MAX_BATCH_SIZE: usize = 20;
+
+/// Number of times a throttled request is retried by default
+#[cfg(feature = "net")]
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Response headers kept with a [`GraphError`] to diagnose throttling and failures
+const DIAGNOSTIC_HEADERS: &[&str] = &[
+    "request-id",
+    "client-request-id",
+    "x-ms-ags-diagnostic",
+    "date",
+    "retry-after",
+    "x-ms-throttle-limit-percentage",
+    "x-ms-throttle-scope",
+    "x-ms-throttle-information",
+    "x-ms-resource-unit",
+];
 
 /// Microsoft Graph API client
+///
+/// Throttled requests (429 and 503) are retried after the delay in their
+/// `Retry-After` header. With a [`RateLimiter`], requests are also paced
+/// client-side so that tests do not get the user throttled in the first place.
+#[cfg(feature = "net")]
 pub struct GraphClient {
     client: Client,
+    rate_limiter: Option<RateLimiter>,
+    max_retries: u32,
 }
 
+#[cfg(feature = "net")]
+impl Default for GraphClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "net")]
 impl GraphClient {
     /// Creates a new Graph API client
     pub fn new() -> Self {
+        Self::with_client(Client::new())
+    }
+
+    /// Creates a Graph API client sending requests with a preconfigured HTTP client
+    ///
+    /// Lets embedders share connection pools, proxies and default headers.
+    pub fn with_client(client: Client) -> Self {
         GraphClient {
-            client: Client::new(),
+            client,
+            rate_limiter: None,
+            max_retries: DEFAULT_MAX_RETRIES,
         }
     }
 
+    /// Paces requests with a rate limiter, which may be shared with other clients
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Sets how often a throttled request is retried (0 disables retries)
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
     /// Calls the /me endpoint to get user information
     pub async fn get_me(&self, token: &str) -> Result<Value> {
+        self.call_endpoint(token, "me").await
+    }
+
+    /// Calls a custom Graph API endpoint
+    pub async fn call_endpoint(&self, token: &str, endpoint: &str) -> Result<Value> {
+        self.send(token, &GraphRequest::new(endpoint)).await
+    }
+
+    /// Sends a request with query parameters and custom headers
+    pub async fn send(&self, token: &str, request: &GraphRequest) -> Result<Value> {
+        let url = request.url();
+
         let response = self
-            .client
-            .get("https://graph.microsoft.com/v1.0/me")
-            .header(header::AUTHORIZATION, format!("Bearer {}", token))
-            .header(header::ACCEPT, "application/json")
-            .send()
+            .execute(1, || {
+                let mut builder = self
+                    .client
+                    .get(&url)
+                    .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                    .header(header::ACCEPT, "application/json");
+                for (name, value) in request.headers() {
+                    builder = builder.header(name.as_str(), value.as_str());
+                }
+                builder
+            })
             .await?;
 
         if !response.status().is_success() {
-            return Err(anyhow!("Graph API error: {}", response.status()));
+            return Err(GraphError::from_response(&url, response).await.into());
         }
 
         Ok(response.json().await?)
     }
 
-    /// Calls a custom Graph API endpoint
-    pub async fn call_endpoint(&self, token: &str, endpoint: &str) -> Result<Value> {
-        let url = if endpoint.starts_with("https://") {
-            endpoint.to_string()
-        } else {
-            format!(
-                "https://graph.microsoft.com/v1.0/{}",
-                endpoint.trim_start_matches('/')
-            )
+    /// Gets the IDs of all groups and directory roles a user is a member of, transitively
+    ///
+    /// This is the endpoint to fall back to on a groups overage. `user` is the
+    /// object ID or UPN of the user.
+    pub async fn get_member_objects(&self, token: &str, user: &str) -> Result<Vec<String>> {
+        let url = endpoint_url(&format!("users/{}/getMemberObjects", user));
+        let body = json!({ "securityEnabledOnly": false });
+
+        let response = self
+            .execute(1, || {
+                self.client
+                    .post(&url)
+                    .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                    .header(header::ACCEPT, "application/json")
+                    .json(&body)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(GraphError::from_response(&url, response).await.into());
+        }
+
+        let body: Value = response.json().await?;
+        Ok(body["value"]
+            .as_array()
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Looks up the device a token was issued on by its `deviceid` claim
+    ///
+    /// Returns `None` if no device object has that ID, e.g. after the device
+    /// was deleted. Reading devices needs `Device.Read.All` or a directory role.
+    pub async fn get_device(&self, token: &str, device_id: &str) -> Result<Option<GraphDevice>> {
+        let request = GraphRequest::new("devices")
+            .filter(format!("deviceId eq '{}'", device_id.replace('\'', "''")))
+            .select(&[
+                "id",
+                "deviceId",
+                "displayName",
+                "operatingSystem",
+                "operatingSystemVersion",
+                "trustType",
+                "isCompliant",
+                "isManaged",
+                "accountEnabled",
+                "approximateLastSignInDateTime",
+            ]);
+        let body = self.send(token, &request).await?;
+        match body["value"].as_array().and_then(|devices| devices.first()) {
+            Some(device) => Ok(Some(
+                serde_json::from_value(device.clone()).context("Unexpected Graph device")?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the signed-in user, their organization and their licenses in one `$batch`
+    ///
+    /// `me` is a `/me` response fetched before, e.g. by a Graph test, which is
+    /// then not requested again. Parts the token may not read are left out.
+    pub async fn get_directory_identity(
+        &self,
+        token: &str,
+        me: Option<Value>,
+    ) -> Result<DirectoryIdentity> {
+        let mut requests = vec![
+            GraphRequest::new("organization").select(&[
+                "id",
+                "displayName",
+                "tenantType",
+                "verifiedDomains",
+            ]),
+            GraphRequest::new("me/licenseDetails").select(&["skuPartNumber"]),
+        ];
+        if me.is_none() {
+            requests.push(GraphRequest::new("me").select(&[
+                "id",
+                "displayName",
+                "userPrincipalName",
+                "mail",
+                "jobTitle",
+            ]));
+        }
+        let mut responses = self.batch(token, &requests).await?.into_iter();
+
+        let mut identity = DirectoryIdentity::default();
+        let mut read = |name: &str, response: Option<GraphBatchResponse>| -> Option<Value> {
+            match response?.into_result() {
+                Ok(body) => Some(body),
+                Err(e) => {
+                    identity.errors.push(format!("{}: {}", name, e));
+                    None
+                }
+            }
         };
+        let organization = read("organization", responses.next());
+        let licenses = read("licenses", responses.next());
+        let me = me.or_else(|| read("user", responses.next()));
+
+        identity.organization = organization
+            .and_then(|body| serde_json::from_value(body["value"].get(0)?.clone()).ok());
+        identity.licenses = licenses.map(|body| {
+            body["value"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|license| license["skuPartNumber"].as_str())
+                .map(str::to_string)
+                .collect()
+        });
+        identity.user = me.and_then(|body| serde_json::from_value(body).ok());
+        Ok(identity)
+    }
+
+    /// Sends many requests with `$batch`, one round-trip per 20 requests
+    ///
+    /// The responses are returned in the order of the requests. A failing
+    /// request only fails its own response; the call fails only if the batch
+    /// itself is rejected. Throttled requests are retried after their `Retry-After`.
+    pub async fn batch(
+        &self,
+        token: &str,
+        requests: &[GraphRequest],
+    ) -> Result<Vec<GraphBatchResponse>> {
+        let mut responses = Vec::with_capacity(requests.len());
+        for chunk in requests.chunks(MAX_BATCH_SIZE) {
+            let mut chunk_responses = self.send_batch(token, chunk).await?;
+
+            for attempt in 0..self.max_retries {
+                let throttled: Vec<usize> = (0..chunk_responses.len())
+                    .filter(|&index| chunk_responses[index].is_throttled())
+                    .collect();
+                if throttled.is_empty() {
+                    break;
+                }
+
+                let retry_after = throttled
+                    .iter()
+                    .filter_map(|&index| chunk_responses[index].retry_after())
+                    .max();
+                self.back_off(throttle::retry_delay(retry_after, attempt))
+                    .await;
+
+                let retry: Vec<GraphRequest> = throttled
+                    .iter()
+                    .map(|&index| chunk[index].clone())
+                    .collect();
+                let retried = self.send_batch(token, &retry).await?;
+                for (index, response) in throttled.into_iter().zip(retried) {
+                    chunk_responses[index] = response;
+                }
+            }
+            responses.extend(chunk_responses);
+        }
+        Ok(responses)
+    }
+
+    /// Sends one `$batch` call of at most 20 requests
+    async fn send_batch(
+        &self,
+        token: &str,
+        requests: &[GraphRequest],
+    ) -> Result<Vec<GraphBatchResponse>> {
+        let body = batch_body(requests)?;
+        let weight = requests.len() as u32;
 
         let response = self
-            .client
-            .get(&url)
-            .header(header::AUTHORIZATION, format!("Bearer {}", token))
-            .header(header::ACCEPT, "application/json")
-            .send()
+            .execute(weight, || {
+                self.client
+                    .post(endpoint_url("$batch"))
+                    .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                    .header(header::ACCEPT, "application/json")
+                    .json(&body)
+            })
             .await?;
 
         if !response.status().is_success() {
-            return Err(anyhow!("Graph API error: {} - {}", response.status(), url));
+            return Err(GraphError::from_response(&endpoint_url("$batch"), response)
+                .await
+                .into());
         }
+        batch_responses(requests.len(), response.json().await?)
+    }
 
-        Ok(response.json().await?)
+    /// Sends a request within the rate limit, retrying while it is throttled
+    ///
+    /// `weight` is the number of Graph requests the call counts as.
+    async fn execute(
+        &self,
+        weight: u32,
+        build: impl Fn() -> RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let span = Span::start("graph_request");
+        let result = self.send_with_retries(weight, build).await;
+        if let Ok(response) = &result {
+            span.attribute("url", response.url().as_str());
+            span.attribute("status", response.status().as_str());
+        }
+        span.outcome(&result);
+        result
+    }
+
+    async fn send_with_retries(
+        &self,
+        weight: u32,
+        build: impl Fn() -> RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let mut attempt = 0;
+        loop {
+            let permit = match &self.rate_limiter {
+                Some(limiter) => Some(limiter.acquire(weight).await),
+                None => None,
+            };
+            let response = build().send().await?;
+            drop(permit);
+
+            if !throttle::is_throttled(response.status()) || attempt >= self.max_retries {
+                return Ok(response);
+            }
+            let retry_after = throttle::retry_after(response.headers());
+            self.back_off(throttle::retry_delay(retry_after, attempt))
+                .await;
+            attempt += 1;
+        }
+    }
+
+    /// Waits before a retry, holding back all requests sharing the rate limiter
+    async fn back_off(&self, delay: Duration) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.pause(delay).await;
+        }
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Response to one request of a `$batch` call
+#[derive(Debug, Clone, Deserialize)]
+pub struct GraphBatchResponse {
+    pub id: String,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Map<String, Value>,
+    #[serde(default)]
+    pub body: Value,
+}
+
+impl GraphBatchResponse {
+    /// Returns whether the request succeeded (2xx status)
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Returns whether the request was throttled and should be retried
+    pub fn is_throttled(&self) -> bool {
+        StatusCode::from_u16(self.status).is_ok_and(throttle::is_throttled)
+    }
+
+    /// Gets the delay requested by the `Retry-After` header of the response
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("retry-after"))
+            .and_then(|(_, value)| match value {
+                Value::String(value) => throttle::parse_retry_after(value),
+                value => value.as_u64().map(Duration::from_secs),
+            })
+    }
+
+    /// Converts the response into the body, or an error like [`GraphClient::send`] would return
+    pub fn into_result(self) -> Result<Value> {
+        if self.is_success() {
+            return Ok(self.body);
+        }
+        let headers = self
+            .headers
+            .iter()
+            .filter(|(name, _)| is_diagnostic_header(name))
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                (name.to_ascii_lowercase(), value)
+            })
+            .collect();
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::BAD_GATEWAY);
+        Err(GraphError::new(status, None, headers, self.body).into())
+    }
+}
+
+/// Error response from Microsoft Graph
+///
+/// Graph describes failures with an OData error payload. The request ID and
+/// date of its `innerError` identify the request in Microsoft support tickets.
+#[derive(Debug, Clone)]
+pub struct GraphError {
+    pub status: StatusCode,
+    /// URL of the request, `None` for a request of a `$batch` call
+    pub url: Option<String>,
+    /// OData error code, e.g. `Authorization_RequestDenied`
+    pub code: Option<String>,
+    pub message: Option<String>,
+    pub request_id: Option<String>,
+    pub date: Option<String>,
+    /// Throttling and diagnostic headers of the response, with lowercase names
+    pub headers: Vec<(String, String)>,
+    /// The response body, as a string if it is not JSON and null if empty
+    pub body: Value,
+}
+
+impl GraphError {
+    /// Parses the OData error of a response, falling back to its headers for the request ID and date
+    pub(crate) fn new(
+        status: StatusCode,
+        url: Option<String>,
+        headers: Vec<(String, String)>,
+        body: Value,
+    ) -> Self {
+        let error = &body["error"];
+        let inner = error.get("innerError").or_else(|| error.get("innererror"));
+        let field = |value: Option<&Value>| value.and_then(Value::as_str).map(str::to_string);
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _)| header == name)
+                .map(|(_, value)| value.clone())
+        };
+
+        GraphError {
+            status,
+            url,
+            code: field(error.get("code")),
+            message: field(error.get("message")),
+            request_id: field(inner.and_then(|inner| inner.get("request-id")))
+                .or_else(|| header("request-id")),
+            date: field(inner.and_then(|inner| inner.get("date"))).or_else(|| header("date")),
+            headers,
+            body,
+        }
+    }
+
+    /// Builds the error from the parts of a failed response
+    pub(crate) fn from_parts(
+        status: StatusCode,
+        url: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Self {
+        let headers = headers
+            .iter()
+            .filter(|(name, _)| is_diagnostic_header(name.as_str()))
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let body = match body {
+            [] => Value::Null,
+            body => serde_json::from_slice(body)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned())),
+        };
+        GraphError::new(status, Some(url.to_string()), headers, body)
+    }
+
+    /// Reads the error from a failed response
+    #[cfg(feature = "net")]
+    async fn from_response(url: &str, response: reqwest::Response) -> Self {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await.unwrap_or_default();
+        GraphError::from_parts(status, url, &headers, &body)
+    }
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Graph API error: {}", self.status)?;
+        match (&self.code, &self.message) {
+            (Some(code), Some(message)) => write!(f, " - {}: {}", code, message)?,
+            (Some(detail), None) | (None, Some(detail)) => write!(f, " - {}", detail)?,
+            (None, None) => {}
+        }
+        match &self.url {
+            Some(url) => write!(f, " ({})", url),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+fn is_diagnostic_header(name: &str) -> bool {
+    DIAGNOSTIC_HEADERS
+        .iter()
+        .any(|header| header.eq_ignore_ascii_case(name))
+}
+
+/// Builds the `$batch` request body, using the index of each request as its ID
+pub(crate) fn batch_body(requests: &[GraphRequest]) -> Result<Value> {
+    let requests = requests
+        .iter()
+        .enumerate()
+        .map(|(index, request)| {
+            let headers: Map<String, Value> = request
+                .headers()
+                .iter()
+                .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+                .collect();
+            Ok(json!({
+                "id": index.to_string(),
+                "method": "GET",
+                "url": request.batch_url()?,
+                "headers": headers,
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(json!({ "requests": requests }))
+}
+
+/// Maps the responses of a `$batch` call back to the order of its requests
+pub(crate) fn batch_responses(count: usize, body: Value) -> Result<Vec<GraphBatchResponse>> {
+    let responses: Vec<GraphBatchResponse> = serde_json::from_value(body["responses"].clone())
+        .context("Invalid Graph API $batch response")?;
+
+    let mut ordered: Vec<Option<GraphBatchResponse>> = vec![None; count];
+    for response in responses {
+        let index: usize = response
+            .id
+            .parse()
+            .ok()
+            .filter(|index| *index < count)
+            .with_context(|| {
+                format!("Unexpected request ID '{}' in $batch response", response.id)
+            })?;
+        ordered[index] = Some(response);
+    }
+    ordered
+        .into_iter()
+        .enumerate()
+        .map(|(index, response)| {
+            response
+                .with_context(|| format!("No response for request {} in $batch response", index))
+        })
+        .collect()
+}
+
+/// GET request to a Graph endpoint with OData query options and custom headers
+///
+/// ```
+/// use azure_token_validator::api::GraphRequest;
+///
+/// let request = GraphRequest::new("users")
+///     .select(&["displayName", "mail"])
+///     .filter("startswith(displayName,'A')")
+///     .top(5)
+///     .eventual_consistency();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GraphRequest {
+    endpoint: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+}
+
+impl GraphRequest {
+    /// Creates a request for an endpoint relative to the Graph v1.0 API, or an absolute URL
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        GraphRequest {
+            endpoint: endpoint.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Restricts the returned properties (`$select`)
+    pub fn select<S: AsRef<str>>(self, fields: &[S]) -> Self {
+        let fields: Vec<&str> = fields.iter().map(AsRef::as_ref).collect();
+        self.query("$select", fields.join(","))
+    }
+
+    /// Filters a collection with an OData expression (`$filter`)
+    pub fn filter(self, expression: impl Into<String>) -> Self {
+        self.query("$filter", expression)
+    }
+
+    /// Limits the page size of a collection (`$top`)
+    pub fn top(self, count: u32) -> Self {
+        self.query("$top", count.to_string())
+    }
+
+    /// Adds a query parameter
+    pub fn query(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+
+    /// Adds a request header
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets `ConsistencyLevel: eventual`, required by advanced queries on directory objects
+    pub fn eventual_consistency(self) -> Self {
+        self.header("ConsistencyLevel", "eventual")
+    }
+
+    /// Gets the full URL including the query parameters
+    pub fn url(&self) -> String {
+        let mut url = endpoint_url(&self.endpoint);
+        for (name, value) in &self.query {
+            url.push(if url.contains('?') { '&' } else { '?' });
+            url.push_str(name);
+            url.push('=');
+            // OData does not decode '+' as a space, unlike form encoding
+            let value: String = url::form_urlencoded::byte_serialize(value.as_bytes()).collect();
+            url.push_str(&value.replace('+', "%20"));
+        }
+        url
+    }
+
+    /// Gets the custom request headers
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Gets the URL relative to the Graph v1.0 API, as used in `$batch` calls
+    pub(crate) fn batch_url(&self) -> Result<String> {
+        let url = self.url();
+        match url.strip_prefix(GRAPH_BASE_URL) {
+            Some(relative) if relative.starts_with('/') => Ok(relative.to_string()),
+            _ => bail!("'{}' is not a Graph v1.0 URL and cannot be batched", url),
+        }
+    }
+}
+
+/// Resolves an endpoint relative to the Graph v1.0 API, keeping absolute URLs as is
+pub(crate) fn endpoint_url(endpoint: &str) -> String {
+    if endpoint.starts_with("https://") {
+        endpoint.to_string()
+    } else {
+        format!("{}/{}", GRAPH_BASE_URL, endpoint.trim_start_matches('/'))
+    }
+}
+
+/// Microsoft Graph user (`/me`, `/users/{id}`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphUser {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub user_principal_name: Option<String>,
+    pub mail: Option<String>,
+    pub job_title: Option<String>,
+}
+
+/// Microsoft Graph group (`/groups/{id}`, `/me/memberOf`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphGroup {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub mail: Option<String>,
+    pub security_enabled: Option<bool>,
+    #[serde(default)]
+    pub group_types: Vec<String>,
+}
+
+/// Microsoft Graph device (`/devices`), registered or joined to the tenant
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphDevice {
+    pub id: String,
+    pub device_id: Option<String>,
+    pub display_name: Option<String>,
+    pub operating_system: Option<String>,
+    pub operating_system_version: Option<String>,
+    /// How the device is known to Azure AD: `Workplace` (registered), `AzureAd` or `ServerAd` (joined)
+    pub trust_type: Option<String>,
+    /// Whether Intune reports the device compliant, `None` if it is not managed
+    pub is_compliant: Option<bool>,
+    pub is_managed: Option<bool>,
+    pub account_enabled: Option<bool>,
+    pub approximate_last_sign_in_date_time: Option<String>,
+}
+
+/// Microsoft Graph organization (`/organization`), whose ID is the tenant ID
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphOrganization {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub tenant_type: Option<String>,
+    #[serde(default)]
+    pub verified_domains: Vec<VerifiedDomain>,
+}
+
+impl GraphOrganization {
+    /// Gets the default verified domain of the tenant
+    pub fn default_domain(&self) -> Option<&str> {
+        self.verified_domains
+            .iter()
+            .find(|domain| domain.is_default)
+            .map(|domain| domain.name.as_str())
+    }
+}
+
+/// Domain verified by an organization
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifiedDomain {
+    pub name: String,
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+/// Graph API response deserialized into a known resource type where possible
+#[derive(Debug, Clone)]
+pub enum GraphResource {
+    User(GraphUser),
+    Group(GraphGroup),
+    Organization(GraphOrganization),
+    Collection(Vec<GraphResource>),
+    Other(Value),
+}
+
+impl GraphResource {
+    /// Determines the resource type from `@odata.type` or the entity set in `@odata.context`
+    pub fn from_value(value: &Value) -> Self {
+        let entity_set = value["@odata.context"]
+            .as_str()
+            .and_then(|context| context.split_once('#'))
+            .map(|(_, fragment)| fragment.split(['/', '(']).next().unwrap_or_default())
+            .unwrap_or_default();
+
+        match &value["value"] {
+            Value::Array(items) if value.get("id").is_none() => GraphResource::Collection(
+                items
+                    .iter()
+                    .map(|item| Self::from_item(item, entity_set))
+                    .collect(),
+            ),
+            _ => Self::from_item(value, entity_set),
+        }
+    }
+
+    fn from_item(value: &Value, entity_set: &str) -> Self {
+        let kind = match value["@odata.type"].as_str() {
+            Some(odata_type) => odata_type.trim_start_matches("#microsoft.graph."),
+            None => entity_set,
+        };
+
+        let typed = match kind {
+            "user" | "users" => serde_json::from_value(value.clone()).map(GraphResource::User),
+            "group" | "groups" => serde_json::from_value(value.clone()).map(GraphResource::Group),
+            "organization" => {
+                serde_json::from_value(value.clone()).map(GraphResource::Organization)
+            }
+            _ => return GraphResource::Other(value.clone()),
+        };
+        typed.unwrap_or_else(|_| GraphResource::Other(value.clone()))
     }
 }
@@ -0,0 +1,95 @@
+//! User agent and headers sent with every outbound request
+//!
+//! Azure AD and Microsoft Graph log the `client-request-id` header of each
+//! request, so quoting it lets support find the requests of a run.
+
+use anyhow::{Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, ClientBuilder};
+
+/// User agent sent when none is configured
+pub const DEFAULT_USER_AGENT: &str = concat!("azure-token-validator/", env!("CARGO_PKG_VERSION"));
+
+/// Correlation header of Azure AD and Microsoft Graph
+pub const CLIENT_REQUEST_ID: &str = "client-request-id";
+
+/// Correlation header of Azure Resource Manager, Key Vault and Storage
+const MS_CLIENT_REQUEST_ID: &str = "x-ms-client-request-id";
+
+/// User agent, extra headers and client request ID of the HTTP clients of a run
+#[derive(Debug, Clone)]
+pub struct HttpOptions {
+    user_agent: String,
+    headers: HeaderMap,
+    client_request_id: String,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        HttpOptions {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            headers: HeaderMap::new(),
+            client_request_id: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+}
+
+impl HttpOptions {
+    /// Uses the default user agent and a new random client request ID
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the `User-Agent` of all requests
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Adds a header sent with all requests
+    ///
+    /// A `client-request-id` header replaces the generated client request ID.
+    pub fn header(mut self, name: &str, value: &str) -> Result<Self> {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid header name '{}'", name))?;
+        let header_value = HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid value for header '{}'", name))?;
+        if name == CLIENT_REQUEST_ID {
+            self.client_request_id = value.to_string();
+        } else {
+            self.headers.append(name, header_value);
+        }
+        Ok(self)
+    }
+
+    /// Gets the ID sent as `client-request-id` and `x-ms-client-request-id`
+    pub fn client_request_id(&self) -> &str {
+        &self.client_request_id
+    }
+
+    /// Creates a client builder with the user agent and headers, e.g. to set timeouts
+    pub fn client_builder(&self) -> ClientBuilder {
+        let mut headers = self.headers.clone();
+        // Validated when set, or generated
+        if let Ok(id) = HeaderValue::from_str(&self.client_request_id) {
+            headers.insert(CLIENT_REQUEST_ID, id.clone());
+            headers.insert(MS_CLIENT_REQUEST_ID, id);
+        }
+        // Asks Azure AD to echo the ID in its responses
+        headers.insert("return-client-request-id", HeaderValue::from_static("true"));
+        Client::builder()
+            .user_agent(&self.user_agent)
+            .default_headers(headers)
+    }
+
+    /// Creates a client sending the user agent and headers
+    ///
+    /// # Panics
+    ///
+    /// Like `Client::new`, if the TLS backend cannot be initialized.
+    pub fn client(&self) -> Client {
+        self.client_builder()
+            .build()
+            .expect("TLS backend cannot be initialized")
+    }
+}
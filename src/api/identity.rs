@@ -0,0 +1,103 @@
+//! Identity of a signed-in user as both the token and the directory describe it
+//!
+//! Claims are a snapshot taken at sign-in while the directory is current, so
+//! a renamed user or a changed UPN shows up as a mismatch between the two.
+
+use crate::api::graph::{GraphOrganization, GraphUser};
+use crate::token::Claims;
+
+/// The user, organization and licenses read from Microsoft Graph
+///
+/// Parts the token has no permission for are `None`, with the reason in `errors`.
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryIdentity {
+    pub user: Option<GraphUser>,
+    pub organization: Option<GraphOrganization>,
+    /// SKU part numbers of the licenses assigned to the user, e.g. `ENTERPRISEPACK`
+    pub licenses: Option<Vec<String>>,
+    pub errors: Vec<String>,
+}
+
+/// One fact of an identity, as the token and the directory state it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityFact {
+    pub name: &'static str,
+    pub token: Option<String>,
+    pub directory: Option<String>,
+}
+
+impl IdentityFact {
+    fn new(name: &'static str, token: Option<&str>, directory: Option<&str>) -> Self {
+        IdentityFact {
+            name,
+            token: token.map(str::to_string),
+            directory: directory.map(str::to_string),
+        }
+    }
+
+    /// Returns whether token and directory state different values, ignoring case
+    pub fn is_mismatch(&self) -> bool {
+        match (&self.token, &self.directory) {
+            (Some(token), Some(directory)) => !token.eq_ignore_ascii_case(directory),
+            _ => false,
+        }
+    }
+}
+
+impl DirectoryIdentity {
+    /// Lines up the claims of a token with the directory, one fact per attribute
+    ///
+    /// Facts that neither side states are left out.
+    pub fn facts(&self, claims: &Claims) -> Vec<IdentityFact> {
+        let user = self.user.as_ref();
+        let organization = self.organization.as_ref();
+        let licenses = self.licenses.as_ref().map(|licenses| match licenses {
+            licenses if licenses.is_empty() => "none".to_string(),
+            licenses => licenses.join(", "),
+        });
+
+        [
+            IdentityFact::new(
+                "Display name",
+                claims.name.as_deref(),
+                user.and_then(|user| user.display_name.as_deref()),
+            ),
+            IdentityFact::new(
+                "User principal name",
+                claims
+                    .user_principal_name()
+                    .or(claims.preferred_username.as_deref()),
+                user.and_then(|user| user.user_principal_name.as_deref()),
+            ),
+            IdentityFact::new(
+                "Mail",
+                claims.email.as_deref(),
+                user.and_then(|user| user.mail.as_deref()),
+            ),
+            IdentityFact::new(
+                "Object ID",
+                claims.object_id(),
+                user.map(|user| user.id.as_str()),
+            ),
+            IdentityFact::new(
+                "Tenant ID",
+                claims.tenant_id(),
+                organization.map(|organization| organization.id.as_str()),
+            ),
+            IdentityFact::new(
+                "Tenant name",
+                None,
+                organization.and_then(|organization| organization.display_name.as_deref()),
+            ),
+            IdentityFact::new(
+                "Default domain",
+                None,
+                organization.and_then(GraphOrganization::default_domain),
+            ),
+            IdentityFact::new("Licenses", None, licenses.as_deref()),
+        ]
+        .into_iter()
+        .filter(|fact| fact.token.is_some() || fact.directory.is_some())
+        .collect()
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::{Context, Result};
+use reqwest::{header, Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::api::oauth::TokenEndpointError;
+
+/// Azure Instance Metadata Service token endpoint (VMs, VM scale sets, AKS nodes)
+const IMDS_ENDPOINT: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+
+/// Where the managed identity token is requested from
+#[derive(Debug, Clone)]
+pub enum ManagedIdentitySource {
+    /// Instance Metadata Service
+    Imds,
+    /// App Service / Functions identity endpoint from `IDENTITY_ENDPOINT` and `IDENTITY_HEADER`
+    AppService { endpoint: String, secret: String },
+}
+
+impl ManagedIdentitySource {
+    /// Detects the source from the environment, falling back to IMDS
+    pub fn detect() -> Self {
+        match (
+            std::env::var("IDENTITY_ENDPOINT"),
+            std::env::var("IDENTITY_HEADER"),
+        ) {
+            (Ok(endpoint), Ok(secret)) => ManagedIdentitySource::AppService { endpoint, secret },
+            _ => ManagedIdentitySource::Imds,
+        }
+    }
+}
+
+impl fmt::Display for ManagedIdentitySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManagedIdentitySource::Imds => write!(f, "IMDS ({})", IMDS_ENDPOINT),
+            ManagedIdentitySource::AppService { endpoint, .. } => {
+                write!(f, "App Service ({})", endpoint)
+            }
+        }
+    }
+}
+
+/// Token returned by a managed identity endpoint
+#[derive(Debug, Deserialize)]
+pub struct ManagedIdentityToken {
+    pub access_token: String,
+    pub token_type: String,
+    #[serde(default)]
+    pub resource: Option<String>,
+    #[serde(default)]
+    pub client_id: Option<String>,
+    // Additional fields such as expires_on (format differs between sources)
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// Managed identity token client
+pub struct ManagedIdentityClient {
+    client: Client,
+    source: ManagedIdentitySource,
+}
+
+impl ManagedIdentityClient {
+    /// Creates a new managed identity client for the given source
+    pub fn new(source: ManagedIdentitySource) -> Result<Self> {
+        // The metadata endpoints are link-local and must never go through a proxy
+        let client = Client::builder()
+            .no_proxy()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+
+        Ok(Self::with_client(source, client))
+    }
+
+    /// Creates a managed identity client sending requests with a preconfigured HTTP client
+    ///
+    /// The client should not use a proxy, the metadata endpoints are link-local.
+    pub fn with_client(source: ManagedIdentitySource, client: Client) -> Self {
+        ManagedIdentityClient { client, source }
+    }
+
+    /// Gets the source tokens are requested from
+    pub fn source(&self) -> &ManagedIdentitySource {
+        &self.source
+    }
+
+    /// Requests a token for the given resource
+    ///
+    /// `client_id` selects a user-assigned identity; the system-assigned
+    /// identity is used when it is `None`.
+    pub async fn get_token(
+        &self,
+        resource: &str,
+        client_id: Option<&str>,
+    ) -> Result<ManagedIdentityToken> {
+        let mut request = self.build_request(resource);
+        if let Some(client_id) = client_id {
+            request = request.query(&[("client_id", client_id)]);
+        }
+
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Managed identity endpoint unreachable: {}", self.source))?;
+
+        let status = response.status();
+        let body: Value = response.json().await?;
+
+        if !status.is_success() {
+            return Err(TokenEndpointError {
+                status,
+                error: body["error"]
+                    .as_str()
+                    .unwrap_or("unknown_error")
+                    .to_string(),
+                description: body["error_description"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                body,
+            }
+            .into());
+        }
+
+        Ok(serde_json::from_value(body)?)
+    }
+
+    fn build_request(&self, resource: &str) -> RequestBuilder {
+        match &self.source {
+            ManagedIdentitySource::Imds => self
+                .client
+                .get(IMDS_ENDPOINT)
+                .header("Metadata", "true")
+                .query(&[("api-version", "2018-02-01"), ("resource", resource)]),
+            ManagedIdentitySource::AppService { endpoint, secret } => self
+                .client
+                .get(endpoint)
+                .header("X-IDENTITY-HEADER", secret)
+                .header(header::ACCEPT, "application/json")
+                .query(&[("api-version", "2019-08-01"), ("resource", resource)]),
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::token::claims::Claims;
+use crate::token::cloud::Cloud;
+use crate::token::jwk::JwksResponse;
+use crate::token::requirements::RequirementCheck;
+use crate::token::verify::issuer_tenant;
+
+/// Placeholder for the tenant in the issuer of multi-tenant metadata documents
+const TENANT_PLACEHOLDER: &str = "{tenantid}";
+
+/// OpenID Connect discovery document of an Azure AD tenant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub jwks_uri: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    // Additional fields such as cloud_instance_name
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// Metadata published for an app registration by the v1 and v2 endpoints
+#[derive(Debug, Clone)]
+pub struct AppMetadata {
+    pub client_id: String,
+    pub v1: OpenIdConfiguration,
+    pub v2: OpenIdConfiguration,
+    /// Keys at the app-specific `jwks_uri` of the v2 metadata
+    pub keys: JwksResponse,
+}
+
+/// How a domain signs its users in, from the user realm endpoint
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserRealm {
+    /// `Managed` (cloud accounts), `Federated` (e.g. AD FS) or `Unknown`
+    pub name_space_type: String,
+    pub domain_name: Option<String>,
+    /// Name of the organization shown on its sign-in page
+    pub federation_brand_name: Option<String>,
+    pub cloud_instance_name: Option<String>,
+    /// Sign-in page of the identity provider of a federated domain
+    #[serde(rename = "AuthURL")]
+    pub auth_url: Option<String>,
+}
+
+/// Public metadata of a tenant, looked up by tenant ID or domain
+#[derive(Debug, Clone)]
+pub struct TenantInfo {
+    /// Tenant ID or domain the metadata was looked up for
+    pub tenant: String,
+    pub configuration: OpenIdConfiguration,
+    /// Only looked up for domains, the realm endpoint needs a sign-in name
+    pub realm: Option<UserRealm>,
+}
+
+impl TenantInfo {
+    /// Gets the tenant ID from the issuer of the discovery document
+    pub fn tenant_id(&self) -> Option<&str> {
+        issuer_tenant(&self.configuration.issuer)
+    }
+
+    /// Gets the cloud the tenant is registered in, e.g. `microsoftonline.us`
+    pub fn cloud_instance(&self) -> Option<&str> {
+        self.configuration
+            .extra
+            .get("cloud_instance_name")
+            .and_then(Value::as_str)
+    }
+
+    /// Describes the region the tenant's data is kept in (`tenant_region_scope`)
+    pub fn region(&self) -> Option<String> {
+        let scope = self
+            .configuration
+            .extra
+            .get("tenant_region_scope")
+            .and_then(Value::as_str)?;
+        let name = match scope {
+            "NA" => "North America",
+            "EU" => "Europe",
+            "AS" => "Asia",
+            "OC" => "Oceania",
+            "AF" => "Africa",
+            "SA" => "South America",
+            "WW" => "Worldwide",
+            "USGov" => "US Government",
+            _ => return Some(scope.to_string()),
+        };
+        Some(format!("{} ({})", name, scope))
+    }
+
+    /// Gets the sovereign sub-scope of the tenant, e.g. `GCC` or `DOD`
+    pub fn region_sub_scope(&self) -> Option<&str> {
+        self.configuration
+            .extra
+            .get("tenant_region_sub_scope")
+            .and_then(Value::as_str)
+    }
+}
+
+/// Client for the OpenID Connect discovery endpoints of Azure AD
+pub struct MetadataClient {
+    client: Client,
+}
+
+impl Default for MetadataClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetadataClient {
+    /// Creates a new metadata client
+    pub fn new() -> Self {
+        Self::with_client(Client::new())
+    }
+
+    /// Creates a metadata client sending requests with a preconfigured HTTP client
+    pub fn with_client(client: Client) -> Self {
+        MetadataClient { client }
+    }
+
+    /// Gets the discovery document URL of a tenant, v2 if `v2` is set
+    ///
+    /// With a client ID, the document points at the app-specific key set,
+    /// which includes the custom signing keys of apps that have them.
+    pub fn discovery_uri(cloud: Cloud, tenant: &str, v2: bool, client_id: Option<&str>) -> String {
+        let version = if v2 { "v2.0/" } else { "" };
+        let mut uri = format!(
+            "https://{}/{}/{}.well-known/openid-configuration",
+            cloud.authority_host(),
+            tenant,
+            version
+        );
+        if let Some(client_id) = client_id {
+            uri.push_str(&format!("?appid={}", client_id));
+        }
+        uri
+    }
+
+    /// Fetches the discovery document at `uri`
+    pub async fn openid_configuration(&self, uri: &str) -> Result<OpenIdConfiguration> {
+        self.get_json(uri).await
+    }
+
+    /// Fetches the v1 and v2 metadata and the signing keys of an app registration
+    pub async fn app_metadata(
+        &self,
+        cloud: Cloud,
+        tenant: &str,
+        client_id: &str,
+    ) -> Result<AppMetadata> {
+        let v1_uri = Self::discovery_uri(cloud, tenant, false, Some(client_id));
+        let v2_uri = Self::discovery_uri(cloud, tenant, true, Some(client_id));
+        let (v1, v2) = tokio::try_join!(
+            self.openid_configuration(&v1_uri),
+            self.openid_configuration(&v2_uri)
+        )?;
+        let keys = self.get_json(&v2.jwks_uri).await?;
+
+        Ok(AppMetadata {
+            client_id: client_id.to_string(),
+            v1,
+            v2,
+            keys,
+        })
+    }
+
+    /// Gets the user realm URL of a domain, for any account name in it
+    pub fn user_realm_uri(cloud: Cloud, domain: &str) -> String {
+        format!(
+            "https://{}/getuserrealm.srf?login=user@{}&json=1",
+            cloud.authority_host(),
+            domain
+        )
+    }
+
+    /// Looks up the public metadata of a tenant by tenant ID or domain
+    ///
+    /// Domains are also looked up at the user realm endpoint, for the brand
+    /// name and federation of the domain; a failure there is not an error.
+    pub async fn tenant_info(&self, cloud: Cloud, tenant: &str) -> Result<TenantInfo> {
+        let uri = Self::discovery_uri(cloud, tenant, true, None);
+        let configuration = self
+            .openid_configuration(&uri)
+            .await
+            .with_context(|| format!("Failed to look up tenant '{}' in {}", tenant, cloud))?;
+        let realm = match tenant.contains('.') {
+            true => self
+                .get_json(&Self::user_realm_uri(cloud, tenant))
+                .await
+                .ok(),
+            false => None,
+        };
+        Ok(TenantInfo {
+            tenant: tenant.to_string(),
+            configuration,
+            realm,
+        })
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, uri: &str) -> Result<T> {
+        let response = self.client.get(uri).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Failed to fetch {}: {}", uri, response.status()));
+        }
+        Ok(response.json().await?)
+    }
+}
+
+impl AppMetadata {
+    /// Checks that a token fits the tenant, cloud, endpoints and keys of the app registration
+    pub fn check(&self, header: &Value, claims: &Claims) -> Vec<RequirementCheck> {
+        let token_tenant = claims.tenant_id().or_else(|| issuer_tenant(&claims.iss));
+        // The issuers of multi-tenant metadata hold a placeholder for the token's tenant
+        let issuer = |config: &OpenIdConfiguration| match token_tenant {
+            Some(tenant) => config.issuer.replace(TENANT_PLACEHOLDER, tenant),
+            None => config.issuer.clone(),
+        };
+        let (v1_issuer, v2_issuer) = (issuer(&self.v1), issuer(&self.v2));
+
+        vec![
+            self.check_tenant(token_tenant),
+            check_cloud(claims, &self.v2),
+            check_issuer(claims, &v1_issuer, &v2_issuer),
+            self.check_application(claims),
+            self.check_signing_key(header),
+        ]
+    }
+
+    fn check_tenant(&self, token_tenant: Option<&str>) -> RequirementCheck {
+        let metadata_tenant = issuer_tenant(&self.v2.issuer).unwrap_or_default();
+        let (passed, detail) = match token_tenant {
+            None => (false, "Token names no tenant (tid)".to_string()),
+            Some(_) if metadata_tenant == TENANT_PLACEHOLDER => (
+                true,
+                "Metadata of a multi-tenant endpoint (common/organizations) accepts any tenant"
+                    .to_string(),
+            ),
+            Some(tenant) if tenant.eq_ignore_ascii_case(metadata_tenant) => {
+                (true, format!("Token was issued by tenant {}", tenant))
+            }
+            Some(tenant) => (
+                false,
+                format!(
+                    "Token was issued by tenant {}, the metadata is of tenant {}",
+                    tenant, metadata_tenant
+                ),
+            ),
+        };
+        RequirementCheck {
+            name: "Tenant",
+            passed,
+            detail,
+        }
+    }
+
+    fn check_application(&self, claims: &Claims) -> RequirementCheck {
+        let client_id = self.client_id.as_str();
+        let app_id_uri = format!("api://{}", client_id);
+        let audiences = claims.audiences();
+
+        let (passed, detail) = if audiences
+            .iter()
+            .any(|aud| aud.eq_ignore_ascii_case(client_id) || aud.eq_ignore_ascii_case(&app_id_uri))
+        {
+            (true, format!("Token is issued for app {}", client_id))
+        } else if claims
+            .authorized_party()
+            .is_some_and(|azp| azp.eq_ignore_ascii_case(client_id))
+        {
+            (
+                true,
+                format!(
+                    "Token was requested by app {} for {}, only that API can validate it",
+                    client_id,
+                    claims.audience_display()
+                ),
+            )
+        } else {
+            (
+                false,
+                format!(
+                    "Token is for {} and was requested by {}, neither is app {} (or its api:// URI)",
+                    claims.audience_display(),
+                    claims.authorized_party().unwrap_or("-"),
+                    client_id
+                ),
+            )
+        };
+        RequirementCheck {
+            name: "Application",
+            passed,
+            detail,
+        }
+    }
+
+    fn check_signing_key(&self, header: &Value) -> RequirementCheck {
+        let (passed, detail) = match header["kid"].as_str() {
+            None => (false, "Token header has no key ID (kid)".to_string()),
+            Some(kid) if self.keys.find_key(kid).is_some() => (
+                true,
+                format!("Key '{}' is published at {}", kid, self.v2.jwks_uri),
+            ),
+            Some(kid) => (
+                false,
+                format!(
+                    "Key '{}' is not published at {}: the token was signed by another issuer",
+                    kid, self.v2.jwks_uri
+                ),
+            ),
+        };
+        RequirementCheck {
+            name: "Signing key",
+            passed,
+            detail,
+        }
+    }
+}
+
+fn check_cloud(claims: &Claims, metadata: &OpenIdConfiguration) -> RequirementCheck {
+    let token_cloud = Cloud::of_issuer(&claims.iss);
+    let metadata_cloud = Cloud::of_issuer(&metadata.issuer);
+    let (passed, detail) = match (token_cloud, metadata_cloud) {
+        (Some(token), Some(metadata)) if token == metadata => {
+            (true, format!("Token and app are in the {}", token))
+        }
+        (Some(token), Some(metadata)) => (
+            false,
+            format!(
+                "Token was issued in the {}, the app's metadata is from the {}",
+                token, metadata
+            ),
+        ),
+        _ => (
+            false,
+            format!("Issuer {} is not an Azure AD cloud", claims.iss),
+        ),
+    };
+    RequirementCheck {
+        name: "Cloud",
+        passed,
+        detail,
+    }
+}
+
+/// Checks the issuer against the v1 and v2 metadata and the token's `ver` claim
+fn check_issuer(claims: &Claims, v1_issuer: &str, v2_issuer: &str) -> RequirementCheck {
+    let endpoint = if claims.iss == v2_issuer {
+        "2.0"
+    } else if claims.iss == v1_issuer {
+        "1.0"
+    } else {
+        return RequirementCheck {
+            name: "Issuer",
+            passed: false,
+            detail: format!(
+                "Issuer {} matches neither the v1 ({}) nor the v2 ({}) metadata",
+                claims.iss, v1_issuer, v2_issuer
+            ),
+        };
+    };
+
+    let (passed, detail) = match claims.version() {
+        Some(ver) if ver != endpoint => (
+            false,
+            format!(
+                "Token claims version {} but has the v{} issuer {}: validate it against the v{} metadata",
+                ver, endpoint, claims.iss, endpoint
+            ),
+        ),
+        // Azure AD issues tokens in the version configured on the API, whichever endpoint was used
+        _ if endpoint == "1.0" => (
+            true,
+            format!(
+                "v1 token issued by {}. If your API requested it from the v2 endpoint, set \
+                 accessTokenAcceptedVersion to 2 in the API's manifest to receive v2 tokens",
+                claims.iss
+            ),
+        ),
+        _ => (true, format!("v2 token issued by {}", claims.iss)),
+    };
+    RequirementCheck {
+        name: "Issuer",
+        passed,
+        detail,
+    }
+}
@@ -1,4 +1,46 @@
+#[cfg(feature = "net")]
+pub mod acquire;
 pub mod graph;
+#[cfg(feature = "net")]
+pub mod http;
+#[cfg(feature = "net")]
+pub mod identity;
+#[cfg(feature = "net")]
+pub mod managed_identity;
+#[cfg(feature = "net")]
+pub mod metadata;
+#[cfg(feature = "net")]
+pub mod oauth;
+#[cfg(feature = "net")]
+pub mod probe;
+#[cfg(feature = "net")]
+pub mod resource;
+pub mod throttle;
 
 // Re-export for easier imports
+#[cfg(feature = "net")]
+pub use acquire::{
+    AcquireToken, AcquiredToken, AzureCli, ClientSecret, DeviceCodeFlow, FederatedCredential,
+    ManagedIdentity,
+};
+#[cfg(feature = "net")]
 pub use graph::GraphClient;
+pub use graph::{GraphBatchResponse, GraphDevice, GraphError, GraphRequest, GraphResource};
+#[cfg(feature = "net")]
+pub use http::HttpOptions;
+#[cfg(feature = "net")]
+pub use identity::{DirectoryIdentity, IdentityFact};
+#[cfg(feature = "net")]
+pub use managed_identity::{ManagedIdentityClient, ManagedIdentitySource, ManagedIdentityToken};
+#[cfg(feature = "net")]
+pub use metadata::{AppMetadata, MetadataClient, OpenIdConfiguration, TenantInfo, UserRealm};
+#[cfg(feature = "net")]
+pub use oauth::{
+    ClientCredentials, DeviceCode, TokenEndpointClient, TokenEndpointError, TokenResponse,
+};
+#[cfg(feature = "net")]
+pub use probe::{probe_endpoint, EndpointProbe};
+#[cfg(feature = "net")]
+pub use resource::{ResourceApi, ResourceClient, ResourceTest};
+#[cfg(feature = "net")]
+pub use throttle::RateLimiter;
@@ -0,0 +1,259 @@
+use anyhow::Result;
+use reqwest::{header, Client, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Confidential client credentials of an app registration
+#[derive(Debug, Clone)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// Successful response from the Azure AD token endpoint
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub token_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Seconds until the access token expires, a string in v1 endpoint responses
+    #[serde(deserialize_with = "number_or_string")]
+    pub expires_in: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    // Additional fields such as ext_expires_in
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+/// Deserializes a number that the v1 endpoint and AD FS return as a string
+fn number_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(u64),
+        String(String),
+    }
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(number) => Ok(number),
+        NumberOrString::String(text) => text.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// Error response from the Azure AD token endpoint
+///
+/// The full response body is kept so it can be shown verbatim, since fields
+/// like `suberror` and `claims` are needed to understand interaction errors.
+#[derive(Debug, thiserror::Error)]
+#[error("Token endpoint error ({status}): {error} - {description}")]
+pub struct TokenEndpointError {
+    pub status: StatusCode,
+    pub error: String,
+    pub description: String,
+    pub body: Value,
+}
+
+/// Device authorization response of the device code flow
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceCode {
+    pub device_code: String,
+    /// Code the user enters at the verification URI
+    pub user_code: String,
+    pub verification_uri: String,
+    /// Seconds until the device code expires
+    pub expires_in: u64,
+    /// Seconds to wait between polls for the token
+    #[serde(default = "default_poll_interval")]
+    pub interval: u64,
+    /// Instructions to show the user
+    pub message: String,
+}
+
+fn default_poll_interval() -> u64 {
+    5
+}
+
+/// Azure AD OAuth 2.0 v2 token endpoint client
+pub struct TokenEndpointClient {
+    client: Client,
+    tenant_id: String,
+}
+
+impl TokenEndpointClient {
+    /// Creates a new token endpoint client for the given tenant
+    pub fn new(tenant_id: impl Into<String>) -> Self {
+        Self::with_client(tenant_id, Client::new())
+    }
+
+    /// Creates a token endpoint client sending requests with a preconfigured HTTP client
+    pub fn with_client(tenant_id: impl Into<String>, client: Client) -> Self {
+        TokenEndpointClient {
+            client,
+            tenant_id: tenant_id.into(),
+        }
+    }
+
+    /// Gets the v2 token endpoint URL of the tenant
+    pub fn token_endpoint(&self) -> String {
+        format!(
+            "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
+            self.tenant_id
+        )
+    }
+
+    /// Gets the v2 device authorization endpoint URL of the tenant
+    pub fn device_code_endpoint(&self) -> String {
+        format!(
+            "https://login.microsoftonline.com/{}/oauth2/v2.0/devicecode",
+            self.tenant_id
+        )
+    }
+
+    /// Requests an app token with a client secret (client credentials flow)
+    pub async fn client_credentials(
+        &self,
+        credentials: &ClientCredentials,
+        scope: &str,
+    ) -> Result<TokenResponse> {
+        self.request_token(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", &credentials.client_id),
+            ("client_secret", &credentials.client_secret),
+            ("scope", scope),
+        ])
+        .await
+    }
+
+    /// Starts the device code flow, returning the code the user signs in with
+    ///
+    /// The app registration must allow public client flows.
+    pub async fn device_code(&self, client_id: &str, scope: &str) -> Result<DeviceCode> {
+        self.post(
+            &self.device_code_endpoint(),
+            &[("client_id", client_id), ("scope", scope)],
+        )
+        .await
+    }
+
+    /// Polls once for the tokens of a device code flow
+    ///
+    /// Fails with an `authorization_pending` [`TokenEndpointError`] until the
+    /// user has signed in.
+    pub async fn device_code_token(
+        &self,
+        client_id: &str,
+        device_code: &DeviceCode,
+    ) -> Result<TokenResponse> {
+        self.request_token(&[
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+            ("client_id", client_id),
+            ("device_code", &device_code.device_code),
+        ])
+        .await
+    }
+
+    /// Exchanges an incoming access token for a downstream token (on-behalf-of flow)
+    pub async fn on_behalf_of(
+        &self,
+        credentials: &ClientCredentials,
+        assertion: &str,
+        scope: &str,
+    ) -> Result<TokenResponse> {
+        self.request_token(&[
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("client_id", &credentials.client_id),
+            ("client_secret", &credentials.client_secret),
+            ("assertion", assertion),
+            ("scope", scope),
+            ("requested_token_use", "on_behalf_of"),
+        ])
+        .await
+    }
+
+    /// Exchanges a federated credential (e.g. a Kubernetes service account token)
+    /// for an app token using the client credentials flow with a client assertion
+    pub async fn client_assertion(
+        &self,
+        client_id: &str,
+        assertion: &str,
+        scope: &str,
+    ) -> Result<TokenResponse> {
+        self.request_token(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", client_id),
+            (
+                "client_assertion_type",
+                "urn:ietf:params:oauth:client-assertion-type:jwt-bearer",
+            ),
+            ("client_assertion", assertion),
+            ("scope", scope),
+        ])
+        .await
+    }
+
+    /// Redeems a refresh token for new tokens
+    ///
+    /// Public clients redeem refresh tokens without a secret.
+    pub async fn refresh(
+        &self,
+        client_id: &str,
+        client_secret: Option<&str>,
+        refresh_token: &str,
+        scope: &str,
+    ) -> Result<TokenResponse> {
+        let mut params = vec![
+            ("grant_type", "refresh_token"),
+            ("client_id", client_id),
+            ("refresh_token", refresh_token),
+            ("scope", scope),
+        ];
+        if let Some(secret) = client_secret {
+            params.push(("client_secret", secret));
+        }
+
+        self.request_token(&params).await
+    }
+
+    /// Posts a token request and parses the success or error response
+    async fn request_token(&self, params: &[(&str, &str)]) -> Result<TokenResponse> {
+        self.post(&self.token_endpoint(), params).await
+    }
+
+    /// Posts a form to an endpoint of the tenant and parses the success or error response
+    async fn post<T: DeserializeOwned>(&self, url: &str, params: &[(&str, &str)]) -> Result<T> {
+        let response = self
+            .client
+            .post(url)
+            .header(header::ACCEPT, "application/json")
+            .form(params)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body: Value = response.json().await?;
+
+        if !status.is_success() {
+            return Err(TokenEndpointError {
+                status,
+                error: body["error"]
+                    .as_str()
+                    .unwrap_or("unknown_error")
+                    .to_string(),
+                description: body["error_description"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                body,
+            }
+            .into());
+        }
+
+        Ok(serde_json::from_value(body)?)
+    }
+}
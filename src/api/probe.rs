@@ -0,0 +1,144 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::Url;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::timeout;
+use tokio_native_tls::{native_tls, TlsConnector};
+
+/// Maximum time a single probe attempt may take
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Timings of the phases of a single HTTPS request
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeTiming {
+    pub dns: Duration,
+    pub connect: Duration,
+    pub tls: Duration,
+    pub ttfb: Duration,
+    pub total: Duration,
+    pub status: u16,
+}
+
+/// Results of probing one endpoint several times
+#[derive(Debug)]
+pub struct EndpointProbe {
+    pub url: String,
+    pub attempts: Vec<Result<ProbeTiming, String>>,
+}
+
+impl EndpointProbe {
+    /// Gets the timings of all successful attempts
+    pub fn successes(&self) -> Vec<ProbeTiming> {
+        self.attempts
+            .iter()
+            .filter_map(|attempt| attempt.as_ref().ok().copied())
+            .collect()
+    }
+
+    /// Gets the error messages of all failed attempts
+    pub fn failures(&self) -> Vec<&str> {
+        self.attempts
+            .iter()
+            .filter_map(|attempt| attempt.as_ref().err().map(String::as_str))
+            .collect()
+    }
+}
+
+/// Probes an HTTPS endpoint `attempts` times, timing each phase separately
+///
+/// Every attempt opens a fresh connection, so DNS, TCP and TLS costs are
+/// measured each time instead of being hidden by connection pooling.
+pub async fn probe_endpoint(url: &str, attempts: usize) -> EndpointProbe {
+    let mut results = Vec::with_capacity(attempts);
+    for _ in 0..attempts {
+        let result = match timeout(ATTEMPT_TIMEOUT, probe_once(url)).await {
+            Ok(result) => result.map_err(|e| format!("{:#}", e)),
+            Err(_) => Err(format!("Timed out after {}s", ATTEMPT_TIMEOUT.as_secs())),
+        };
+        results.push(result);
+    }
+
+    EndpointProbe {
+        url: url.to_string(),
+        attempts: results,
+    }
+}
+
+/// Gets the given percentile (0-100) of a set of durations using nearest-rank
+pub fn percentile(values: &[Duration], percentile: f64) -> Option<Duration> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort();
+    let rank = ((percentile / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+async fn probe_once(url: &str) -> Result<ProbeTiming> {
+    let url = Url::parse(url)?;
+    let host = url.host_str().context("URL has no host")?;
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    let start = Instant::now();
+    let address = lookup_host((host, port))
+        .await
+        .context("DNS lookup failed")?
+        .next()
+        .context("DNS lookup returned no addresses")?;
+    let dns = start.elapsed();
+
+    let phase = Instant::now();
+    let stream = TcpStream::connect(address)
+        .await
+        .context("TCP connect failed")?;
+    let connect = phase.elapsed();
+
+    let phase = Instant::now();
+    let connector = TlsConnector::from(native_tls::TlsConnector::new()?);
+    let mut stream = connector
+        .connect(host, stream)
+        .await
+        .context("TLS handshake failed")?;
+    let tls = phase.elapsed();
+
+    let phase = Instant::now();
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nAccept: application/json\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut first = [0u8; 1];
+    stream
+        .read_exact(&mut first)
+        .await
+        .context("No response received")?;
+    let ttfb = phase.elapsed();
+
+    let mut response = first.to_vec();
+    stream.read_to_end(&mut response).await?;
+    let total = start.elapsed();
+
+    // Status line: HTTP/1.1 200 OK
+    let status = String::from_utf8_lossy(&response)
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| anyhow!("Malformed HTTP response"))?;
+
+    Ok(ProbeTiming {
+        dns,
+        connect,
+        tls,
+        ttfb,
+        total,
+        status,
+    })
+}
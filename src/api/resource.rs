@@ -0,0 +1,168 @@
+use anyhow::{anyhow, bail, Result};
+use reqwest::{header, Client, StatusCode};
+use serde_json::Value;
+use std::fmt;
+
+use crate::token::{audience, Claims};
+
+/// Azure APIs that tokens can be live-tested against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceApi {
+    Graph,
+    ResourceManager,
+    KeyVault,
+    Storage,
+}
+
+impl ResourceApi {
+    /// Determines the API a single audience value belongs to
+    pub fn from_audience(aud: &str) -> Option<Self> {
+        match audience::lookup(aud)?.app_id {
+            "00000003-0000-0000-c000-000000000000" => Some(ResourceApi::Graph),
+            "797f4846-ba00-4fd7-ba43-dac1f8f63013" => Some(ResourceApi::ResourceManager),
+            "cfa8b339-82a2-471a-a3c9-0fc0be7a4093" => Some(ResourceApi::KeyVault),
+            "e406a681-f3d4-42a8-90b6-c2b029497af1" => Some(ResourceApi::Storage),
+            _ => None,
+        }
+    }
+
+    /// Determines the API a token was issued for from its `aud` claim
+    pub fn detect(claims: &Claims) -> Option<Self> {
+        claims
+            .audiences()
+            .iter()
+            .find_map(|aud| Self::from_audience(aud))
+    }
+}
+
+impl fmt::Display for ResourceApi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ResourceApi::Graph => "Microsoft Graph",
+            ResourceApi::ResourceManager => "Azure Resource Manager",
+            ResourceApi::KeyVault => "Azure Key Vault",
+            ResourceApi::Storage => "Azure Storage",
+        })
+    }
+}
+
+/// Outcome of a successful test call against a resource API
+#[derive(Debug, Clone)]
+pub struct ResourceTest {
+    pub url: String,
+    pub status: u16,
+    pub summary: String,
+}
+
+/// Client calling a cheap read endpoint of ARM, Key Vault or Storage with a token
+pub struct ResourceClient {
+    client: Client,
+}
+
+impl Default for ResourceClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResourceClient {
+    /// Creates a new resource API client
+    pub fn new() -> Self {
+        Self::with_client(Client::new())
+    }
+
+    /// Creates a resource API client sending requests with a preconfigured HTTP client
+    pub fn with_client(client: Client) -> Self {
+        ResourceClient { client }
+    }
+
+    /// Lists the subscriptions visible to the token
+    pub async fn test_resource_manager(&self, token: &str) -> Result<ResourceTest> {
+        let url = "https://management.azure.com/subscriptions?api-version=2022-12-01";
+        let (status, body) = self.get(token, url, &[]).await?;
+
+        let body: Value = serde_json::from_str(&body)?;
+        let count = body["value"].as_array().map_or(0, Vec::len);
+        Ok(ResourceTest {
+            url: url.to_string(),
+            status,
+            summary: format!("{} subscription(s) visible", count),
+        })
+    }
+
+    /// Lists the first secret of a vault, given by name or URL
+    pub async fn test_key_vault(&self, token: &str, vault: &str) -> Result<ResourceTest> {
+        let vault_url = if vault.starts_with("https://") {
+            vault.trim_end_matches('/').to_string()
+        } else {
+            format!("https://{}.vault.azure.net", vault)
+        };
+        let url = format!("{}/secrets?api-version=7.4&maxresults=1", vault_url);
+        let (status, body) = self.get(token, &url, &[]).await?;
+
+        let body: Value = serde_json::from_str(&body)?;
+        let listed = body["value"].as_array().map_or(0, Vec::len);
+        Ok(ResourceTest {
+            url,
+            status,
+            summary: match listed {
+                0 => "Vault has no secrets".to_string(),
+                _ => "Secrets can be listed".to_string(),
+            },
+        })
+    }
+
+    /// Lists the first blob container of a storage account
+    pub async fn test_storage(&self, token: &str, account: &str) -> Result<ResourceTest> {
+        let url = format!(
+            "https://{}.blob.core.windows.net/?comp=list&maxresults=1",
+            account
+        );
+        let (status, body) = self
+            .get(token, &url, &[("x-ms-version", "2023-11-03")])
+            .await?;
+
+        Ok(ResourceTest {
+            url,
+            status,
+            summary: match body.contains("<Container>") {
+                true => "Containers can be listed".to_string(),
+                false => "Account has no containers".to_string(),
+            },
+        })
+    }
+
+    /// Sends an authenticated GET request and returns the status and body
+    async fn get(&self, token: &str, url: &str, headers: &[(&str, &str)]) -> Result<(u16, String)> {
+        let mut builder = self
+            .client
+            .get(url)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token));
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+
+        let response = builder.send().await?;
+        let status = response.status();
+        match status {
+            StatusCode::UNAUTHORIZED => {
+                match response
+                    .headers()
+                    .get(header::WWW_AUTHENTICATE)
+                    .and_then(|value| value.to_str().ok())
+                {
+                    Some(challenge) => {
+                        bail!("Token was rejected (401 Unauthorized): {}", challenge)
+                    }
+                    None => bail!("Token was rejected (401 Unauthorized)"),
+                }
+            }
+            StatusCode::FORBIDDEN => Err(anyhow!(
+                "Token was accepted but lacks permission (403 Forbidden) - {}",
+                url
+            )),
+            status if !status.is_success() => Err(anyhow!("API error: {} - {}", status, url)),
+            status => Ok((status.as_u16(), response.text().await?)),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+#[cfg(feature = "net")]
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "net")]
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+#[cfg(feature = "net")]
+use tokio::time::{sleep_until, Instant};
+
+/// Delay before the first retry of a throttled request without a `Retry-After` header
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Client-side limit on the request rate to an API
+///
+/// Clones share the same limit, so one limiter can pace all requests of a
+/// tool run. At most `requests_per_second` requests start per second and
+/// at most that many (at least one) are in flight at the same time.
+#[cfg(feature = "net")]
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    interval: Duration,
+    permits: Arc<Semaphore>,
+    next_slot: Arc<Mutex<Instant>>,
+}
+
+#[cfg(feature = "net")]
+impl RateLimiter {
+    /// Creates a limiter allowing the given number of requests per second
+    pub fn new(requests_per_second: f64) -> Self {
+        let requests_per_second = requests_per_second.max(0.001);
+        RateLimiter {
+            interval: Duration::from_secs_f64(1.0 / requests_per_second),
+            permits: Arc::new(Semaphore::new(requests_per_second.ceil() as usize)),
+            next_slot: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Waits until `weight` requests may be sent, e.g. the size of a `$batch` call
+    ///
+    /// The returned permit limits concurrency and should be held until the
+    /// response has been received.
+    pub async fn acquire(&self, weight: u32) -> OwnedSemaphorePermit {
+        let permit = Arc::clone(&self.permits)
+            .acquire_owned()
+            .await
+            .expect("rate limiter semaphore is never closed");
+
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval * weight.max(1);
+            slot
+        };
+        sleep_until(slot).await;
+        permit
+    }
+
+    /// Holds back all requests sharing this limiter for the given time
+    pub async fn pause(&self, delay: Duration) {
+        let mut next_slot = self.next_slot.lock().await;
+        *next_slot = (*next_slot).max(Instant::now() + delay);
+    }
+}
+
+/// Returns whether a status asks the client to slow down and retry
+pub fn is_throttled(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+}
+
+/// Parses the `Retry-After` header (delay in seconds)
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    parse_retry_after(headers.get(RETRY_AFTER)?.to_str().ok()?)
+}
+
+/// Parses a `Retry-After` value given in seconds
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// Gets the delay before a retry, preferring the server's `Retry-After`
+///
+/// Without one, the delay doubles with each attempt.
+pub fn retry_delay(retry_after: Option<Duration>, attempt: u32) -> Duration {
+    retry_after.unwrap_or(DEFAULT_RETRY_DELAY * 2u32.pow(attempt))
+}
@@ -0,0 +1,22 @@
+//! Rejections shared by the HTTP framework integrations
+
+use thiserror::Error;
+
+/// Reasons a request is rejected by the HTTP integrations
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("Missing bearer token")]
+    MissingToken,
+    #[error("Invalid token: {0:#}")]
+    InvalidToken(anyhow::Error),
+}
+
+impl AuthError {
+    /// Gets the `WWW-Authenticate` challenge for this error as described in RFC 6750
+    pub fn challenge(&self) -> &'static str {
+        match self {
+            AuthError::MissingToken => "Bearer",
+            AuthError::InvalidToken(_) => "Bearer error=\"invalid_token\"",
+        }
+    }
+}
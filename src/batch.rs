@@ -0,0 +1,212 @@
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use azure_token_validator::token::{failure_code, Timezone, TokenValidator};
+
+/// Columns written to CSV exports, in order
+const CSV_COLUMNS: &[&str] = &[
+    "line", "subject", "tenant", "audience", "expiry", "verdict", "error",
+];
+
+/// Validation result of one token of a batch
+#[derive(Debug, Serialize)]
+pub struct BatchRecord {
+    pub line: usize,
+    pub subject: Option<String>,
+    pub tenant: Option<String>,
+    pub audience: Option<String>,
+    pub expiry: Option<String>,
+    pub verdict: &'static str,
+    pub error: Option<String>,
+    /// Machine-readable failure reason, see [`failure_code`]
+    #[serde(skip)]
+    pub reason: Option<&'static str>,
+    /// Time taken to validate the token
+    #[serde(skip)]
+    pub elapsed: Duration,
+}
+
+impl BatchRecord {
+    fn csv_row(&self) -> String {
+        let line = self.line.to_string();
+        let fields = [
+            Some(line.as_str()),
+            self.subject.as_deref(),
+            self.tenant.as_deref(),
+            self.audience.as_deref(),
+            self.expiry.as_deref(),
+            Some(self.verdict),
+            self.error.as_deref(),
+        ];
+        fields
+            .iter()
+            .map(|field| csv_escape(field.unwrap_or_default()))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// Reads tokens from a file (or stdin for `-`), one per line
+///
+/// Blank lines and lines starting with `#` are skipped. Tokens are returned
+/// with their 1-based line number.
+pub fn read_tokens(path: &Path) -> Result<Vec<(usize, String)>> {
+    let content = if path == Path::new("-") {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?
+    };
+
+    Ok(content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| (number, line.to_string()))
+        .collect())
+}
+
+/// Finds everything that looks like a compact JWT in a line of text, e.g. a log line
+pub fn find_tokens(line: &str) -> impl Iterator<Item = &str> {
+    static JWT: OnceLock<Regex> = OnceLock::new();
+    JWT.get_or_init(|| {
+        Regex::new(r"eyJ[A-Za-z0-9_-]+\.eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*")
+            .expect("valid JWT pattern")
+    })
+    .find_iter(line)
+    .map(|found| found.as_str())
+}
+
+/// Validates the tokens found in stdin as lines arrive, writing one NDJSON verdict per token
+///
+/// Each token is validated once, later occurrences are skipped. Runs until
+/// stdin is closed, so logs can be piped through it (`kubectl logs -f`).
+pub async fn stream(validator: &TokenValidator, timezone: Timezone) -> Result<()> {
+    // Only digests are kept so the tokens of a long-running stream are not held in memory
+    let mut seen = HashSet::new();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut number = 0;
+    while let Some(line) = lines.next_line().await? {
+        number += 1;
+        for token in find_tokens(&line) {
+            if !seen.insert(Sha256::digest(token.as_bytes())) {
+                continue;
+            }
+            let record = validate_record(validator, number, token, timezone).await;
+            let mut stdout = io::stdout().lock();
+            writeln!(stdout, "{}", serde_json::to_string(&record)?)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Decodes and validates one token of a batch, with its expiry displayed in `timezone`
+pub async fn validate_record(
+    validator: &TokenValidator,
+    line: usize,
+    token: &str,
+    timezone: Timezone,
+) -> BatchRecord {
+    let claims = match validator.decode_token(token) {
+        Ok((_, claims)) => claims,
+        Err(e) => {
+            return BatchRecord {
+                line,
+                subject: None,
+                tenant: None,
+                audience: None,
+                expiry: None,
+                verdict: "undecodable",
+                error: Some(e.to_string()),
+                reason: Some("malformed"),
+                elapsed: Duration::ZERO,
+            }
+        }
+    };
+
+    let started = Instant::now();
+    let result = validator.validate_token(token).await;
+    let elapsed = started.elapsed();
+    BatchRecord {
+        line,
+        subject: Some(claims.sub.clone()),
+        tenant: claims.tenant_id().map(str::to_string),
+        audience: Some(claims.audience_display()),
+        expiry: Some(timezone.format_timestamp(claims.exp)),
+        verdict: if result.is_ok() { "valid" } else { "invalid" },
+        reason: result.as_ref().err().map(failure_code),
+        error: result.err().map(|e| e.to_string()),
+        elapsed,
+    }
+}
+
+/// File formats of batch exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    /// Determines the format from the file extension (.csv, .ndjson or .jsonl)
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "ndjson" | "jsonl" => Ok(ExportFormat::Ndjson),
+            _ => bail!(
+                "Cannot tell the export format from '{}', use a .csv, .ndjson or .jsonl file",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Writes the records to a file in the given format
+pub fn export(path: &Path, format: ExportFormat, records: &[BatchRecord]) -> Result<()> {
+    let mut out = String::new();
+    match format {
+        ExportFormat::Csv => {
+            out.push_str(&CSV_COLUMNS.join(","));
+            out.push('\n');
+            for record in records {
+                out.push_str(&record.csv_row());
+                out.push('\n');
+            }
+        }
+        ExportFormat::Ndjson => {
+            for record in records {
+                out.push_str(&serde_json::to_string(record)?);
+                out.push('\n');
+            }
+        }
+    }
+
+    fs::write(path, out).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Quotes a CSV field if it contains separators, quotes or line breaks
+pub fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::Result;
+use reqwest::blocking::Client;
+use reqwest::header;
+use serde_json::Value;
+
+use crate::api::graph::{batch_body, batch_responses, endpoint_url, MAX_BATCH_SIZE};
+use crate::api::{GraphBatchResponse, GraphError, GraphRequest};
+
+/// Blocking Microsoft Graph API client
+///
+/// Mirrors [`crate::api::GraphClient`] with synchronous methods.
+pub struct GraphClient {
+    client: Client,
+}
+
+impl Default for GraphClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GraphClient {
+    /// Creates a new Graph API client
+    pub fn new() -> Self {
+        Self::with_client(Client::new())
+    }
+
+    /// Creates a Graph API client sending requests with a preconfigured HTTP client
+    pub fn with_client(client: Client) -> Self {
+        GraphClient { client }
+    }
+
+    /// Calls the /me endpoint to get user information
+    pub fn get_me(&self, token: &str) -> Result<Value> {
+        self.call_endpoint(token, "me")
+    }
+
+    /// Calls a custom Graph API endpoint
+    pub fn call_endpoint(&self, token: &str, endpoint: &str) -> Result<Value> {
+        self.send(token, &GraphRequest::new(endpoint))
+    }
+
+    /// Sends a request with query parameters and custom headers
+    pub fn send(&self, token: &str, request: &GraphRequest) -> Result<Value> {
+        let url = request.url();
+
+        let mut builder = self
+            .client
+            .get(&url)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::ACCEPT, "application/json");
+        for (name, value) in request.headers() {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+
+        let response = builder.send()?;
+        if !response.status().is_success() {
+            return Err(error_from_response(&url, response).into());
+        }
+
+        Ok(response.json()?)
+    }
+
+    /// Sends many requests with `$batch`, one round-trip per 20 requests
+    ///
+    /// The responses are returned in the order of the requests.
+    pub fn batch(&self, token: &str, requests: &[GraphRequest]) -> Result<Vec<GraphBatchResponse>> {
+        let mut responses = Vec::with_capacity(requests.len());
+        for chunk in requests.chunks(MAX_BATCH_SIZE) {
+            let response = self
+                .client
+                .post(endpoint_url("$batch"))
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .header(header::ACCEPT, "application/json")
+                .json(&batch_body(chunk)?)
+                .send()?;
+
+            if !response.status().is_success() {
+                return Err(error_from_response(&endpoint_url("$batch"), response).into());
+            }
+            responses.extend(batch_responses(chunk.len(), response.json()?)?);
+        }
+        Ok(responses)
+    }
+}
+
+/// Reads the OData error from a failed response
+fn error_from_response(url: &str, response: reqwest::blocking::Response) -> GraphError {
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.bytes().unwrap_or_default();
+    GraphError::from_parts(status, url, &headers, &body)
+}
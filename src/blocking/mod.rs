@@ -0,0 +1,12 @@
+//! Synchronous variants of the validator and Graph client
+//!
+//! These are built on `reqwest::blocking` and can be used without an async
+//! runtime. Like `reqwest::blocking`, they must not be called from within an
+//! async context.
+
+pub mod graph;
+pub mod validator;
+
+// Re-export for easier imports
+pub use graph::GraphClient;
+pub use validator::TokenValidator;
@@ -0,0 +1,204 @@
+use anyhow::{anyhow, Result};
+use reqwest::blocking::Client;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use crate::token::claims::Claims;
+use crate::token::clock::Clock;
+use crate::token::jwk::CachedKeys;
+use crate::token::jwk::{JwksResponse, DEFAULT_TTL, MIN_REFETCH_AGE};
+use crate::token::spans::Span;
+use crate::token::telemetry;
+use crate::token::verify::{
+    check_algorithm, check_time_claims, configured_jwks_uri, decode_step, decode_unverified,
+    jwks_uri, tenant_config, token_format, verify_signature_only, verify_with_jwks,
+};
+use crate::token::{AzureTokenFormat, ValidatorConfig};
+
+/// Blocking Azure AD token validator
+///
+/// Validates tokens like [`crate::token::TokenValidator`], with synchronous
+/// methods. Keys are cached for [`DEFAULT_TTL`] and fetched again when a
+/// token names a key missing from them, at most every 30 seconds. Unlike the
+/// async validator's [`KeyStore`](crate::token::KeyStore), failed fetches are
+/// not remembered, concurrent fetches are not coalesced and the clock skew is
+/// not measured.
+pub struct TokenValidator {
+    client: Client,
+    jwks_cache: RwLock<HashMap<String, CachedKeys>>,
+    config: ValidatorConfig,
+}
+
+impl TokenValidator {
+    /// Creates a new token validator with the given configuration
+    pub fn new(config: ValidatorConfig) -> Self {
+        Self::with_client(config, Client::new())
+    }
+
+    /// Creates a new token validator fetching keys with a preconfigured HTTP client
+    pub fn with_client(config: ValidatorConfig, client: Client) -> Self {
+        TokenValidator {
+            client,
+            jwks_cache: RwLock::new(HashMap::new()),
+            config,
+        }
+    }
+
+    /// Replaces the clock used for time-based claim checks, e.g. to freeze time in tests
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.config.clock = Arc::new(clock);
+        self
+    }
+
+    /// Gets the JWKS URI for the given format and tenant
+    pub fn get_jwks_uri(&self, format: AzureTokenFormat) -> String {
+        jwks_uri(&self.config.tenant_id, format)
+    }
+
+    /// Gets the JWKS URI for a token, resolving the `auto` tenant from its claims
+    ///
+    /// Tokens of the configured AD FS server use the server's own keys.
+    pub fn jwks_uri_for(&self, claims: &Claims) -> Result<String> {
+        configured_jwks_uri(&self.config, claims)
+    }
+
+    /// Determines the token format based on the issuer claim
+    pub fn determine_token_format(&self, claims: &Claims) -> AzureTokenFormat {
+        token_format(claims)
+    }
+
+    /// Decodes a token without validation to inspect its claims
+    pub fn decode_token(&self, token: &str) -> Result<(Value, Claims)> {
+        decode_unverified(token)
+    }
+
+    /// Fetches JWKS from the given URI
+    pub fn fetch_jwks(&self, uri: &str) -> Result<Arc<JwksResponse>> {
+        let started = Instant::now();
+        let span = Span::start("fetch_jwks");
+        span.attribute("uri", uri);
+        let result = self.download_jwks(uri);
+        span.outcome(&result);
+        telemetry::record_jwks_fetch(result.is_ok(), started.elapsed());
+        result
+    }
+
+    fn download_jwks(&self, uri: &str) -> Result<Arc<JwksResponse>> {
+        let response = self.client.get(uri).send()?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Failed to fetch JWKS: {}", response.status()));
+        }
+
+        let jwks = Arc::new(response.json::<JwksResponse>()?);
+        self.jwks_cache
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(uri.to_string(), CachedKeys::new(jwks.clone()));
+        Ok(jwks)
+    }
+
+    /// Gets JWKS from cache or fetches if not cached or older than [`DEFAULT_TTL`]
+    pub fn get_jwks(&self, uri: &str) -> Result<Arc<JwksResponse>> {
+        let cached = self
+            .cached(uri)
+            .filter(|keys| keys.age() < DEFAULT_TTL)
+            .map(|keys| keys.jwks);
+
+        telemetry::record_jwks_lookup(cached.is_some());
+        match cached {
+            Some(jwks) => Ok(jwks),
+            None => self.fetch_jwks(uri),
+        }
+    }
+
+    fn cached(&self, uri: &str) -> Option<CachedKeys> {
+        self.jwks_cache
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(uri)
+            .cloned()
+    }
+
+    /// Validates a token against Azure AD public keys
+    pub fn validate_token(&self, token: &str) -> Result<Claims> {
+        self.validate_traced(token, None)
+    }
+
+    /// Validates a token that must be issued by `tenant_id`, whatever tenant is configured
+    ///
+    /// See [`crate::token::TokenValidator::validate_token_for_tenant`].
+    pub fn validate_token_for_tenant(&self, token: &str, tenant_id: &str) -> Result<Claims> {
+        self.validate_traced(token, Some(tenant_id))
+    }
+
+    fn validate_traced(&self, token: &str, tenant_id: Option<&str>) -> Result<Claims> {
+        let started = Instant::now();
+        let span = Span::start("validate_token");
+        span.attribute("tenant", tenant_id.unwrap_or(&self.config.tenant_id));
+        let result = {
+            let _entered = span.enter();
+            self.validate(token, tenant_id)
+        };
+        span.outcome(&result);
+        telemetry::record_validation(&result, started.elapsed());
+        result
+    }
+
+    fn validate(&self, token: &str, tenant_id: Option<&str>) -> Result<Claims> {
+        let (header, claims) = decode_step(token)?;
+        let config = match tenant_id {
+            Some(tenant_id) => Cow::Owned(tenant_config(&self.config, tenant_id, &claims)),
+            None => Cow::Borrowed(&self.config),
+        };
+        check_algorithm(&config, &header)?;
+        check_time_claims(&config, &claims)?;
+
+        let jwks = self.signing_keys(&config, &header, &claims)?;
+        verify_with_jwks(&config, token, &header, &claims, &jwks)
+    }
+
+    /// Verifies only the signature of a token against the keys of its tenant
+    ///
+    /// Expired tokens and tokens for other audiences pass as long as Azure AD
+    /// signed them, e.g. to verify tokens found in logs after an incident.
+    pub fn validate_signature(&self, token: &str) -> Result<Claims> {
+        let span = Span::start("validate_signature");
+        span.attribute("tenant", self.config.tenant_id.as_str());
+        let result = {
+            let _entered = span.enter();
+            decode_step(token).and_then(|(header, claims)| {
+                let jwks = self.signing_keys(&self.config, &header, &claims)?;
+                verify_signature_only(&self.config, token, &header, claims, &jwks)
+            })
+        };
+        span.outcome(&result);
+        result
+    }
+
+    /// Gets the keys of the token's tenant, refetched if they lack the token's key
+    fn signing_keys(
+        &self,
+        config: &ValidatorConfig,
+        header: &Value,
+        claims: &Claims,
+    ) -> Result<Arc<JwksResponse>> {
+        let uri = configured_jwks_uri(config, claims)?;
+        let mut jwks = self.get_jwks(&uri)?;
+        // Keys rotated since they were cached are only found in a fresh key set
+        if let Some(kid) = header["kid"].as_str() {
+            let refetch = self
+                .cached(&uri)
+                .is_none_or(|keys| keys.age() >= MIN_REFETCH_AGE);
+            if jwks.find_key(kid).is_none() && refetch {
+                // Keep the cached keys if the refetch fails, the key is reported as unknown
+                if let Ok(fresh) = self.fetch_jwks(&uri) {
+                    jwks = fresh;
+                }
+            }
+        }
+        Ok(jwks)
+    }
+}
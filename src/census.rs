@@ -0,0 +1,154 @@
+//! Census of the signing keys of many tenants
+//!
+//! Platforms accepting tokens of many tenants depend on the signing
+//! certificates of each of them. The census fetches the key sets of a list of
+//! tenants and records every key with the expiry of its certificate, so
+//! upcoming rotations can be tracked in a spreadsheet.
+
+use anyhow::Result;
+use std::path::Path;
+use tokio::task::JoinSet;
+
+use azure_token_validator::api::RateLimiter;
+use azure_token_validator::token::verify::cloud_jwks_uri;
+use azure_token_validator::token::{AzureTokenFormat, Cloud, KeyStore, Timezone};
+
+use crate::batch::{csv_escape, read_tokens};
+
+/// Certificates expiring within this many seconds are reported as expiring soon
+pub const EXPIRY_WARNING: u64 = 30 * 24 * 60 * 60;
+
+/// Columns of the CSV output, in order
+const CSV_COLUMNS: &[&str] = &[
+    "tenant",
+    "kid",
+    "thumbprint",
+    "expires",
+    "days_left",
+    "error",
+];
+
+/// A signing key of a tenant, or the failure to fetch the tenant's keys
+#[derive(Debug)]
+pub struct KeyRecord {
+    pub tenant: String,
+    pub kid: Option<String>,
+    pub thumbprint: Option<String>,
+    /// When the key's certificate expires, as seconds since the Unix epoch
+    pub expires_at: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl KeyRecord {
+    fn csv_row(&self, now: u64, timezone: Timezone) -> String {
+        let expires = self
+            .expires_at
+            .map(|expires_at| timezone.format_timestamp(expires_at));
+        let days_left = self
+            .expires_at
+            .map(|expires_at| ((expires_at as i64 - now as i64) / 86_400).to_string());
+        let fields = [
+            Some(self.tenant.as_str()),
+            self.kid.as_deref(),
+            self.thumbprint.as_deref(),
+            expires.as_deref(),
+            days_left.as_deref(),
+            self.error.as_deref(),
+        ];
+        fields
+            .iter()
+            .map(|field| csv_escape(field.unwrap_or_default()))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// Reads tenant IDs or domains from a file (or stdin for `-`), one per line
+///
+/// Blank lines and lines starting with `#` are skipped.
+pub fn read_tenants(path: &Path) -> Result<Vec<String>> {
+    Ok(read_tokens(path)?
+        .into_iter()
+        .map(|(_, tenant)| tenant)
+        .collect())
+}
+
+/// Fetches the signing keys of each tenant concurrently, paced by `limiter`
+///
+/// Records are returned in the order the tenants were given, one per key or
+/// one with the error for a tenant whose keys could not be fetched.
+pub async fn census(
+    tenants: &[String],
+    cloud: Cloud,
+    keys: &KeyStore,
+    limiter: &RateLimiter,
+) -> Vec<KeyRecord> {
+    let mut tasks = JoinSet::new();
+    for (index, tenant) in tenants.iter().enumerate() {
+        let uri = cloud_jwks_uri(cloud, tenant, AzureTokenFormat::V2);
+        let keys = keys.clone();
+        let limiter = limiter.clone();
+        tasks.spawn(async move {
+            let _permit = limiter.acquire(1).await;
+            (index, keys.get(&uri).await)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok(result) => results.push(result),
+            Err(e) => eprintln!("⚠️  Key fetch task failed: {}", e),
+        }
+    }
+    results.sort_by_key(|(index, _)| *index);
+
+    results
+        .into_iter()
+        .flat_map(|(index, result)| {
+            let tenant = &tenants[index];
+            match result {
+                Ok(jwks) => jwks
+                    .keys
+                    .iter()
+                    .map(|jwk| KeyRecord {
+                        tenant: tenant.clone(),
+                        kid: Some(jwk.kid.clone()),
+                        thumbprint: jwk.thumbprint(),
+                        expires_at: jwk.certificate_expiry(),
+                        error: None,
+                    })
+                    .collect(),
+                Err(e) => vec![KeyRecord {
+                    tenant: tenant.clone(),
+                    kid: None,
+                    thumbprint: None,
+                    expires_at: None,
+                    error: Some(format!("{:#}", e)),
+                }],
+            }
+        })
+        .collect()
+}
+
+/// Formats the records as CSV with a header row, days left counted from `now`
+///
+/// Expiry dates are written in `timezone`.
+pub fn to_csv(records: &[KeyRecord], now: u64, timezone: Timezone) -> String {
+    let mut out = CSV_COLUMNS.join(",");
+    out.push('\n');
+    for record in records {
+        out.push_str(&record.csv_row(now, timezone));
+        out.push('\n');
+    }
+    out
+}
+
+/// Counts the keys whose certificate expires within [`EXPIRY_WARNING`] of `now`
+pub fn expiring(records: &[KeyRecord], now: u64) -> usize {
+    records
+        .iter()
+        .filter_map(|record| record.expires_at)
+        .filter(|expires_at| *expires_at <= now.saturating_add(EXPIRY_WARNING))
+        .count()
+}
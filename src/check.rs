@@ -0,0 +1,264 @@
+//! Smoke test pipelines: acquire a token, validate it, assert its claims and call Graph
+//!
+//! A pipeline is described by a YAML spec, e.g.
+//!
+//! ```yaml
+//! acquire:
+//!   method: client-secret
+//!   tenant: contoso.onmicrosoft.com
+//!   client_id: 00000000-0000-0000-0000-000000000000
+//!   client_secret_env: SMOKE_TEST_SECRET
+//! require:
+//!   roles: [User.Read.All]
+//! claims:
+//!   idtyp: app
+//! graph:
+//!   - organization
+//! ```
+
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use azure_token_validator::api::{
+    AcquireToken, AzureCli, ClientCredentials, ClientSecret, DeviceCodeFlow, FederatedCredential,
+    GraphClient, GraphError, GraphRequest, ManagedIdentity, TokenEndpointClient,
+};
+use azure_token_validator::token::{ApiRequirements, Claims, Timezone, TokenValidator};
+
+/// Resource tokens are acquired for when the spec names none
+const DEFAULT_RESOURCE: &str = "https://graph.microsoft.com";
+
+/// A pipeline read from a YAML spec
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CheckSpec {
+    pub acquire: AcquireSpec,
+    /// Audiences, scopes, roles and versions the token must have
+    #[serde(default)]
+    pub require: ApiRequirements,
+    /// Claims the token must have with exactly these values; array claims must contain them
+    #[serde(default)]
+    pub claims: BTreeMap<String, Value>,
+    /// Graph endpoints that must answer successfully with the token
+    #[serde(default)]
+    pub graph: Vec<String>,
+}
+
+/// How the token of a pipeline is acquired
+#[derive(Debug, Deserialize)]
+pub struct AcquireSpec {
+    #[serde(flatten)]
+    pub method: AcquireMethod,
+    /// Resource (App ID URI) to request a token for
+    #[serde(default)]
+    pub resource: Option<String>,
+    /// Tenant of the token endpoint, the --tenant option otherwise
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+/// Token provider of a pipeline, with the settings it needs
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", rename_all = "kebab-case")]
+pub enum AcquireMethod {
+    ManagedIdentity {
+        #[serde(default)]
+        client_id: Option<String>,
+    },
+    /// The secret is read from an environment variable, specs are often committed
+    ClientSecret {
+        client_id: String,
+        client_secret_env: String,
+    },
+    FederatedToken {
+        client_id: String,
+        token_file: PathBuf,
+    },
+    DeviceCode {
+        client_id: String,
+    },
+    AzureCli,
+}
+
+/// Outcome of one step of a pipeline
+#[derive(Debug, Clone, Serialize)]
+pub struct StepResult {
+    pub step: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// Outcome of a pipeline, one result per step run
+#[derive(Debug, Serialize)]
+pub struct CheckReport {
+    pub spec: PathBuf,
+    pub steps: Vec<StepResult>,
+}
+
+impl CheckReport {
+    /// Returns whether every step passed
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(|step| step.passed)
+    }
+
+    fn push(&mut self, step: impl Into<String>, passed: bool, detail: impl Into<String>) {
+        self.steps.push(StepResult {
+            step: step.into(),
+            passed,
+            detail: detail.into(),
+        });
+    }
+}
+
+/// Reads a pipeline spec from a YAML file
+pub fn read_spec(path: &Path) -> Result<CheckSpec> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read check spec {}", path.display()))?;
+    serde_yaml_ng::from_str(&content)
+        .with_context(|| format!("Invalid check spec {}", path.display()))
+}
+
+/// Runs the steps of a pipeline until one of acquisition or validation fails
+///
+/// `tenant` is used for the token endpoint when the spec names no tenant and
+/// `client` sends the requests to it and to Graph. Timestamps in step details
+/// are displayed in `timezone`.
+pub async fn run(
+    path: &Path,
+    spec: &CheckSpec,
+    validator: &TokenValidator,
+    tenant: &str,
+    client: &Client,
+    timezone: Timezone,
+) -> CheckReport {
+    let mut report = CheckReport {
+        spec: path.to_path_buf(),
+        steps: Vec::new(),
+    };
+
+    let resource = spec.acquire.resource.as_deref().unwrap_or(DEFAULT_RESOURCE);
+    let token = match provider(&spec.acquire, tenant, client) {
+        Ok(provider) => match provider.acquire_token(resource).await {
+            Ok(token) => {
+                report.push(
+                    "Acquire token",
+                    true,
+                    format!("{} for {}", provider, resource),
+                );
+                token.access_token
+            }
+            Err(e) => {
+                report.push("Acquire token", false, format!("{}: {:#}", provider, e));
+                return report;
+            }
+        },
+        Err(e) => {
+            report.push("Acquire token", false, format!("{:#}", e));
+            return report;
+        }
+    };
+
+    let claims = match validator.validate_token(&token).await {
+        Ok(claims) => {
+            let detail = format!(
+                "{} token for {}, expires {}",
+                claims.token_type(),
+                claims.audience_display(),
+                timezone.format_timestamp(claims.exp)
+            );
+            report.push("Validate token", true, detail);
+            claims
+        }
+        Err(e) => {
+            report.push("Validate token", false, format!("{:#}", e));
+            return report;
+        }
+    };
+
+    for check in spec.require.check(&claims) {
+        report.push(check.name, check.passed, check.detail);
+    }
+    for (name, expected) in &spec.claims {
+        let (passed, detail) = expect_claim(&claims, name, expected);
+        report.push(format!("Claim {}", name), passed, detail);
+    }
+
+    let graph = GraphClient::with_client(client.clone());
+    for endpoint in &spec.graph {
+        let step = format!("Graph {}", endpoint);
+        match graph.send(&token, &GraphRequest::new(endpoint)).await {
+            Ok(_) => report.push(step, true, "answered successfully"),
+            Err(e) => {
+                let detail = match e.downcast_ref::<GraphError>() {
+                    Some(GraphError {
+                        request_id: Some(request_id),
+                        ..
+                    }) => format!("{} (request-id {})", e, request_id),
+                    _ => e.to_string(),
+                };
+                report.push(step, false, detail);
+            }
+        }
+    }
+    report
+}
+
+/// Creates the token provider described by the spec
+fn provider(spec: &AcquireSpec, tenant: &str, client: &Client) -> Result<Box<dyn AcquireToken>> {
+    let tenant = spec.tenant.as_deref().unwrap_or(tenant);
+    let endpoint_client = || TokenEndpointClient::with_client(tenant, client.clone());
+    Ok(match &spec.method {
+        AcquireMethod::ManagedIdentity { client_id } => {
+            Box::new(ManagedIdentity::new(client_id.clone())?)
+        }
+        AcquireMethod::ClientSecret {
+            client_id,
+            client_secret_env,
+        } => {
+            let client_secret = std::env::var(client_secret_env)
+                .with_context(|| format!("{} is not set", client_secret_env))?;
+            let credentials = ClientCredentials {
+                client_id: client_id.clone(),
+                client_secret,
+            };
+            Box::new(ClientSecret::new(endpoint_client(), credentials))
+        }
+        AcquireMethod::FederatedToken {
+            client_id,
+            token_file,
+        } => Box::new(FederatedCredential::new(
+            endpoint_client(),
+            client_id,
+            token_file,
+        )),
+        AcquireMethod::DeviceCode { client_id } => {
+            Box::new(DeviceCodeFlow::new(endpoint_client(), client_id, |code| {
+                println!("{}", code.message)
+            }))
+        }
+        AcquireMethod::AzureCli => match &spec.tenant {
+            Some(tenant) => Box::new(AzureCli::with_tenant(tenant)),
+            None => Box::new(AzureCli::new()),
+        },
+    })
+}
+
+/// Compares a claim with its expected value; array claims pass if they contain it
+fn expect_claim(claims: &Claims, name: &str, expected: &Value) -> (bool, String) {
+    let claims = serde_json::to_value(claims).unwrap_or_default();
+    let actual = &claims[name];
+    let passed = match actual {
+        Value::Array(values) if !expected.is_array() => values.contains(expected),
+        actual => actual == expected,
+    };
+    let detail = match (passed, actual) {
+        (true, _) => actual.to_string(),
+        (false, Value::Null) => format!("missing, expected {}", expected),
+        (false, actual) => format!("{}, expected {}", actual, expected),
+    };
+    (passed, detail)
+}
@@ -0,0 +1,1390 @@
+use serde_json::Value;
+
+use std::time::Duration;
+
+use azure_token_validator::api::probe::{percentile, ProbeTiming};
+use azure_token_validator::api::{
+    AcquiredToken, AppMetadata, DirectoryIdentity, EndpointProbe, GraphDevice, GraphError,
+    GraphResource, TenantInfo, TokenEndpointError, TokenResponse,
+};
+use azure_token_validator::token::{
+    audience, audit_token, bound_certificate, conditional, decode_unverified, dictionary,
+    identity_chain, key_binding, AuditConfig, ClaimInfo, Claims, Clock, ConditionalAccess,
+    Identity, KeyStoreStats, RawInspection, RequirementCheck, RuleEvaluation, RuleStatus,
+    SamlAssertion, Severity, SystemClock, Timezone, TokenShape, TokenSize, Warning,
+};
+
+use crate::anonymize::Anonymized;
+use crate::batch::BatchRecord;
+use crate::check::CheckReport;
+use crate::doctor::{Section, Status};
+use crate::history::HistoryEntry;
+use crate::redact;
+use crate::scan::{format_remaining, Finding, ScanSummary};
+use crate::warnings::{render, Warnings};
+
+/// Number of array items shown before the rest is elided
+const MAX_ARRAY_ITEMS: usize = 20;
+
+/// Remaining lifetime from which a token found by `scan` is reported as long-lived
+const LONG_LIVED: i64 = 60 * 60;
+
+/// How much of a token is displayed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisplayMode {
+    /// One screen: who, which app and tenant, what the token grants and until when
+    Summary,
+    /// Well-known claims grouped by topic, followed by the others
+    #[default]
+    Standard,
+    /// Every claim grouped by topic, with its claim name, in full
+    Detailed,
+}
+
+/// Options controlling how claims are rendered
+#[derive(Debug, Clone, Default)]
+pub struct DisplayOptions {
+    /// Maximum length of a scalar value, `None` displays values in full
+    pub max_claim_length: Option<usize>,
+    /// Display emails, UPNs, `aio`/`uti` and signatures instead of masking them
+    pub show_sensitive: bool,
+    pub mode: DisplayMode,
+    /// Time relative times are described from, the system time if unset
+    pub now: Option<u64>,
+    /// Time zone timestamps are displayed in
+    pub timezone: Timezone,
+}
+
+/// Topic a claim is displayed under
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClaimGroup {
+    Identity,
+    Authorization,
+    Session,
+    Lifetime,
+    Protocol,
+}
+
+impl ClaimGroup {
+    const ALL: [ClaimGroup; 5] = [
+        ClaimGroup::Identity,
+        ClaimGroup::Authorization,
+        ClaimGroup::Session,
+        ClaimGroup::Lifetime,
+        ClaimGroup::Protocol,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            ClaimGroup::Identity => "Identity",
+            ClaimGroup::Authorization => "Authorization",
+            ClaimGroup::Session => "Session and Device",
+            ClaimGroup::Lifetime => "Lifetime",
+            ClaimGroup::Protocol => "Protocol",
+        }
+    }
+}
+
+/// A well-known claim, its label and topic, and whether only detailed output shows it
+struct KnownClaim {
+    name: &'static str,
+    label: &'static str,
+    group: ClaimGroup,
+    detailed_only: bool,
+}
+
+const fn known(name: &'static str, label: &'static str, group: ClaimGroup) -> KnownClaim {
+    KnownClaim {
+        name,
+        label,
+        group,
+        detailed_only: false,
+    }
+}
+
+/// Opaque claims that are only of interest when debugging Azure AD itself
+const fn internal(name: &'static str, label: &'static str, group: ClaimGroup) -> KnownClaim {
+    KnownClaim {
+        name,
+        label,
+        group,
+        detailed_only: true,
+    }
+}
+
+/// Well-known claims in display order
+const KNOWN_CLAIMS: &[KnownClaim] = &[
+    known("name", "Name", ClaimGroup::Identity),
+    known("given_name", "Given name", ClaimGroup::Identity),
+    known("family_name", "Family name", ClaimGroup::Identity),
+    known("email", "Email", ClaimGroup::Identity),
+    known("preferred_username", "Username", ClaimGroup::Identity),
+    known("upn", "UPN", ClaimGroup::Identity),
+    known("unique_name", "Unique name", ClaimGroup::Identity),
+    known("oid", "Object ID", ClaimGroup::Identity),
+    known("sub", "Subject", ClaimGroup::Identity),
+    known("tid", "Tenant ID", ClaimGroup::Identity),
+    known("idp", "Identity provider", ClaimGroup::Identity),
+    known("ipaddr", "IP address", ClaimGroup::Identity),
+    internal("puid", "Passport user ID", ClaimGroup::Identity),
+    internal("altsecid", "Alternate security ID", ClaimGroup::Identity),
+    internal("onprem_sid", "On-premises SID", ClaimGroup::Identity),
+    known("aud", "Audience", ClaimGroup::Authorization),
+    known("appid", "App ID", ClaimGroup::Authorization),
+    known("azp", "Authorized party", ClaimGroup::Authorization),
+    known("app_displayname", "App name", ClaimGroup::Authorization),
+    known(
+        "appidacr",
+        "Client authentication",
+        ClaimGroup::Authorization,
+    ),
+    known("azpacr", "Client authentication", ClaimGroup::Authorization),
+    known("idtyp", "Identity type", ClaimGroup::Authorization),
+    known("scp", "Scope", ClaimGroup::Authorization),
+    known("roles", "Roles", ClaimGroup::Authorization),
+    known("wids", "Directory roles", ClaimGroup::Authorization),
+    known("groups", "Groups", ClaimGroup::Authorization),
+    known("hasgroups", "Has groups", ClaimGroup::Authorization),
+    known("sid", "Session ID", ClaimGroup::Session),
+    known("deviceid", "Device ID", ClaimGroup::Session),
+    known("xms_ssm", "Session state", ClaimGroup::Session),
+    known("xms_pdl", "Preferred data location", ClaimGroup::Session),
+    known("nbf", "Not before", ClaimGroup::Lifetime),
+    known("iat", "Issued at", ClaimGroup::Lifetime),
+    known("auth_time", "Authenticated at", ClaimGroup::Lifetime),
+    known("exp", "Expiration", ClaimGroup::Lifetime),
+    known("iss", "Issuer", ClaimGroup::Protocol),
+    known("ver", "Version", ClaimGroup::Protocol),
+    known("amr", "Authentication methods", ClaimGroup::Protocol),
+    known("acr", "Authentication context class", ClaimGroup::Protocol),
+    known("acrs", "Authentication contexts", ClaimGroup::Protocol),
+    known("xms_cc", "Client capabilities", ClaimGroup::Protocol),
+    known("cnf", "Confirmation key", ClaimGroup::Protocol),
+    internal("nonce", "Nonce", ClaimGroup::Protocol),
+    internal("aio", "Internal session data", ClaimGroup::Protocol),
+    internal("uti", "Token ID", ClaimGroup::Protocol),
+    internal("rh", "Refresh handle", ClaimGroup::Protocol),
+];
+
+/// Claims holding seconds since the Unix epoch
+const TIMESTAMP_CLAIMS: &[&str] = &["nbf", "iat", "auth_time", "exp"];
+
+/// Displays token information grouped into identity, authorization, lifetime and protocol
+///
+/// Standard output omits opaque internal claims and lists unknown claims
+/// after the groups. Detailed output shows every claim with its name.
+pub fn display_token_info(claims: &Claims, options: &DisplayOptions, warnings: &Warnings) {
+    let claims = &redact::claims(claims, options.show_sensitive);
+    let detailed = options.mode == DisplayMode::Detailed;
+    let Ok(Value::Object(payload)) = serde_json::to_value(claims.as_ref()) else {
+        return;
+    };
+
+    println!("\n=== Token Information ===");
+    println!("Token type: {}", claims.token_type());
+
+    for group in ClaimGroup::ALL {
+        let lines: Vec<String> = KNOWN_CLAIMS
+            .iter()
+            .filter(|known| known.group == group && (detailed || !known.detailed_only))
+            .filter_map(|known| {
+                let value = payload.get(known.name)?;
+                let label = match detailed {
+                    true => format!("{} ({})", known.label, known.name),
+                    false => known.label.to_string(),
+                };
+                let line = render_known_claim(known.name, &label, value, options);
+                // Session and device claims are rarely understood, explain them inline
+                match (group, dictionary::lookup(known.name)) {
+                    (ClaimGroup::Session, Some(info)) => {
+                        Some(format!("{}\n   {}", line, info.description))
+                    }
+                    _ => Some(line),
+                }
+            })
+            .collect();
+        if !lines.is_empty() {
+            println!("\n=== {} ===", group.title());
+            lines.iter().for_each(|line| println!("{}", line));
+        }
+    }
+
+    if let Some(thumbprint) = bound_certificate(claims) {
+        println!(
+            "\nCertificate binding: bound to the client certificate with x5t#S256 {}, the token is only valid over mutual TLS with that certificate",
+            thumbprint
+        );
+    } else if let Some(binding) = key_binding(claims) {
+        let key = binding
+            .thumbprint
+            .as_deref()
+            .or(binding.kid.as_deref())
+            .unwrap_or("unknown key");
+        println!(
+            "\nProof of possession: bound to {} ({}), the token is only valid with a proof signed by that key",
+            key, binding.claim
+        );
+    }
+
+    if let Some(overage) = claims.group_overage() {
+        let mut message =
+            "The user is in too many groups (over 200 in JWTs) to list them in the token"
+                .to_string();
+        if let Some(endpoint) = &overage.endpoint {
+            message.push_str(&format!(
+                ", Azure AD refers to {} for the full list",
+                endpoint
+            ));
+        }
+        println!();
+        warnings.print(
+            Warning::new("groups-overage", Severity::Medium, message).remediation(
+                "Use --resolve-overage to fetch the group memberships from Microsoft Graph",
+            ),
+        );
+    }
+
+    let others: Vec<(&String, &Value)> = payload
+        .iter()
+        .filter(|(key, _)| KNOWN_CLAIMS.iter().all(|known| known.name != key.as_str()))
+        .collect();
+    if !others.is_empty() {
+        println!("\n=== Other Claims ===");
+        for (key, value) in others {
+            println!("{}", render_claim(key, value, options));
+        }
+    }
+}
+
+/// Renders a well-known claim, describing audiences and formatting timestamps
+fn render_known_claim(name: &str, label: &str, value: &Value, options: &DisplayOptions) -> String {
+    match (name, value) {
+        // Each audience of a multi-audience token on its own line, with the name of its API
+        ("aud", Value::Array(auds)) if auds.len() > 1 => {
+            let lines: Vec<String> = auds
+                .iter()
+                .map(|aud| {
+                    let aud = aud
+                        .as_str()
+                        .map_or_else(|| aud.to_string(), audience::describe);
+                    format!("\n  - {}", aud)
+                })
+                .collect();
+            format!("{}: ({} audiences){}", label, auds.len(), lines.concat())
+        }
+        ("aud", _) => format!("{}: {}", label, audience::describe_claim(value)),
+        (_, Value::Number(seconds)) if TIMESTAMP_CLAIMS.contains(&name) => {
+            let timestamp = seconds.as_u64().unwrap_or_default();
+            let now = options.now.unwrap_or_else(|| SystemClock.now());
+            format!(
+                "{}: {} ({})",
+                label,
+                options.timezone.format_timestamp(timestamp),
+                Claims::format_relative(timestamp, now)
+            )
+        }
+        ("xms_pdl", Value::String(code)) => match data_location(code) {
+            Some(location) => format!("{}: {} ({})", label, code, location),
+            None => format!("{}: {}", label, code),
+        },
+        ("amr", Value::Array(methods)) => {
+            let methods: Vec<&str> = methods.iter().filter_map(Value::as_str).collect();
+            format!("{}: {}", label, methods.join(", "))
+        }
+        _ => render_claim(label, value, options),
+    }
+}
+
+/// Names the geography of a multi-geo data location code (`xms_pdl`)
+fn data_location(code: &str) -> Option<&'static str> {
+    let location = match code.to_ascii_uppercase().as_str() {
+        "APC" => "Asia-Pacific",
+        "ARE" => "United Arab Emirates",
+        "AUS" => "Australia",
+        "BRA" => "Brazil",
+        "CAN" => "Canada",
+        "CHE" => "Switzerland",
+        "DEU" => "Germany",
+        "ESP" => "Spain",
+        "EUR" => "Europe",
+        "FRA" => "France",
+        "GBR" => "United Kingdom",
+        "IND" => "India",
+        "ISR" => "Israel",
+        "ITA" => "Italy",
+        "JPN" => "Japan",
+        "KOR" => "Korea",
+        "NAM" => "North America",
+        "NOR" => "Norway",
+        "POL" => "Poland",
+        "QAT" => "Qatar",
+        "SWE" => "Sweden",
+        "ZAF" => "South Africa",
+        _ => return None,
+    };
+    Some(location)
+}
+
+/// Displays who a token is for, which app and tenant, what it grants and until when
+pub fn display_token_summary(claims: &Claims, now: u64, options: &DisplayOptions) {
+    let claims = &redact::claims(claims, options.show_sensitive);
+    let chain = identity_chain(claims);
+
+    println!("\n=== Token Summary ===");
+    let version = claims
+        .version()
+        .map(|v| format!(" v{}", v))
+        .unwrap_or_default();
+    println!("Type: {}{}", claims.token_type(), version);
+    match &chain.user {
+        Some(user) => match &user.name {
+            Some(name) => println!("User: {} ({})", name, user.id),
+            None => println!("User: {}", user.id),
+        },
+        None => println!("User: none, app-only token"),
+    }
+    if let Some(client) = &chain.client {
+        match &client.name {
+            Some(name) => println!("App: {} ({})", name, client.id),
+            None => println!("App: {}", client.id),
+        }
+    }
+    if let Some(tenant) = claims.tenant_id() {
+        println!("Tenant: {}", tenant);
+    }
+    println!("Audience: {}", audience::describe_claim(&claims.aud));
+
+    let scopes = claims.scopes();
+    if !scopes.is_empty() {
+        println!("Scopes: {}", scopes.join(", "));
+    }
+    let roles = claims.roles();
+    if !roles.is_empty() {
+        println!("Roles: {}", roles.join(", "));
+    }
+    if claims.group_overage().is_some() {
+        println!("Groups: too many to list (overage)");
+    } else if !claims.groups().is_empty() {
+        println!("Groups: {}", claims.groups().len());
+    }
+
+    let remaining = claims.exp as i64 - now as i64;
+    match remaining > 0 {
+        true => println!(
+            "Valid until: {} ({} left)",
+            options.timezone.format_timestamp(claims.exp),
+            format_remaining(remaining)
+        ),
+        false => println!(
+            "Expired: {} ({} ago)",
+            options.timezone.format_timestamp(claims.exp),
+            format_remaining(remaining)
+        ),
+    }
+}
+
+/// Displays who a token identifies, which app acts and on whose behalf
+pub fn display_identity_chain(claims: &Claims, options: &DisplayOptions) {
+    let claims = &redact::claims(claims, options.show_sensitive);
+    let chain = identity_chain(claims);
+    let describe = |identity: &Identity| match &identity.name {
+        Some(name) => format!("{} ({})", name, identity.id),
+        None => identity.id.clone(),
+    };
+
+    println!("\n=== Identity Chain ===");
+    match &chain.user {
+        Some(user) => println!("👤 User: {}", describe(user)),
+        None => println!("🤖 App-only token, no signed-in user"),
+    }
+    // Earliest actor first, skipping the client repeated as the current actor
+    for actor in chain.actors.iter().rev() {
+        if chain
+            .client
+            .as_ref()
+            .is_some_and(|client| client.id == actor.id)
+        {
+            continue;
+        }
+        println!("   ↳ via {}", describe(actor));
+    }
+    if let Some(client) = &chain.client {
+        let authentication = chain
+            .client_authentication
+            .map(|auth| format!(", authenticated with {}", auth))
+            .unwrap_or_default();
+        match chain.is_delegated() {
+            true => println!(
+                "   ↳ App {} acting on the user's behalf{}",
+                describe(client),
+                authentication
+            ),
+            false => println!(
+                "   ↳ App {} acting as itself{}",
+                describe(client),
+                authentication
+            ),
+        }
+    }
+    println!("   ↳ Calling {}", chain.audience);
+}
+
+/// Displays the contents of a SAML assertion
+pub fn display_saml_assertion(assertion: &SamlAssertion, options: &DisplayOptions) {
+    let assertion = &redact::saml(assertion, options.show_sensitive);
+    println!("\n=== SAML Assertion ===");
+    println!("Assertion ID: {}", assertion.id);
+    println!("Issuer: {}", assertion.issuer);
+    println!("Audience: {}", assertion.audiences.join(", "));
+    println!("Issued at: {}", assertion.issue_instant);
+    if let Some(not_before) = &assertion.not_before {
+        println!("Not before: {}", not_before);
+    }
+    if let Some(not_on_or_after) = &assertion.not_on_or_after {
+        println!("Not on or after: {}", not_on_or_after);
+    }
+    if let Some(subject) = &assertion.subject {
+        println!("Subject: {}", subject);
+    }
+    if let Some(authn_context) = &assertion.authn_context {
+        println!("Authentication context: {}", authn_context);
+    }
+    println!("Signed: {}", if assertion.signed { "yes" } else { "no" });
+
+    if !assertion.attributes.is_empty() {
+        println!("\n=== Attributes ===");
+        for attribute in &assertion.attributes {
+            let value = match attribute.values.as_slice() {
+                [single] => Value::String(single.clone()),
+                values => Value::from(values.to_vec()),
+            };
+            println!("{}", render_claim(&attribute.name, &value, options));
+        }
+    }
+}
+
+/// Displays the Conditional Access claims of a token with their meaning
+pub fn display_conditional_access(claims: &Claims) {
+    let access = ConditionalAccess::from_claims(claims);
+    if access.is_empty() {
+        return;
+    }
+
+    println!("\n=== Conditional Access ===");
+    if let Some(acr) = &access.acr {
+        println!(
+            "Authentication class (acr): {} ({})",
+            acr,
+            conditional::acr_description(acr)
+        );
+    }
+    for method in &access.methods {
+        match conditional::method_description(method) {
+            Some(description) => println!("Method (amr): {} ({})", method, description),
+            None => println!("Method (amr): {}", method),
+        }
+    }
+    match access.auth_contexts.as_slice() {
+        [] => println!("Auth contexts (acrs): none satisfied"),
+        contexts => println!("Auth contexts (acrs): {} satisfied", contexts.join(", ")),
+    }
+    if access.is_cae_capable() {
+        println!(
+            "✅ Client is CAE-capable (xms_cc: {}): it handles claims challenges and can step up to an auth context",
+            access.client_capabilities.join(", ")
+        );
+    } else {
+        println!("ℹ️  Client did not declare the cp1 capability (xms_cc): it cannot handle claims challenges or continuous access evaluation");
+    }
+}
+
+/// Displays the findings of the security audit
+pub fn display_security_audit(
+    header: &Value,
+    claims: &Claims,
+    config: &AuditConfig,
+    warnings: &Warnings,
+) {
+    println!("\n=== Security Audit ===");
+
+    let findings = warnings.filter(audit_token(header, claims, config));
+    if findings.is_empty() {
+        println!("✅ No security issues found");
+        return;
+    }
+
+    for warning in &findings {
+        println!("{}", render(warning));
+    }
+}
+
+/// Displays the size of a token, its largest claims and the limits it approaches
+pub fn display_token_size(size: &TokenSize, warnings: &Warnings) {
+    println!("\n=== Token Size ===");
+    let segments: Vec<String> = size
+        .segments
+        .iter()
+        .map(|(name, bytes)| format!("{} {}", name, bytes))
+        .collect();
+    println!("Total: {} bytes ({})", size.total, segments.join(", "));
+    println!("Groups: {}, roles: {}", size.groups, size.roles);
+    let largest: Vec<String> = size
+        .largest_claims
+        .iter()
+        .map(|(name, bytes)| format!("{} {}", name, bytes))
+        .collect();
+    println!("Largest claims: {}", largest.join(", "));
+
+    let findings = warnings.filter(size.warnings());
+    if findings.is_empty() {
+        println!("✅ Well within the 4 KB cookie and 8 KB proxy header limits");
+        return;
+    }
+    for warning in &findings {
+        println!("{}", render(warning));
+    }
+}
+
+/// Displays the raw segments of a token
+pub fn display_raw_inspection(inspection: &RawInspection, options: &DisplayOptions) {
+    println!("\n=== Raw Token Segments ===");
+    for problem in &inspection.problems {
+        println!("❌ {}", problem);
+    }
+
+    for segment in &inspection.segments {
+        println!(
+            "\n--- {} ({} encoded bytes, {} decoded bytes) ---",
+            segment.name,
+            segment.encoded.len(),
+            segment.decoded.as_ref().map_or(0, Vec::len)
+        );
+
+        if segment.name == "signature" {
+            // Together with the decoded header and payload the signature would make the token replayable
+            if !options.show_sensitive {
+                println!("{}", redact::MASK);
+            } else if let Some(bytes) = &segment.decoded {
+                let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                println!("{}", hex);
+            }
+        } else if let Some(mut json) = segment.decoded_value() {
+            if !options.show_sensitive {
+                redact::redact_value(&mut json);
+            }
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json).unwrap_or_default()
+            );
+        } else if let Some(text) = segment.decoded_text() {
+            match options.show_sensitive {
+                true => println!("{}", text),
+                false => println!("{}", redact::redact_text(&text)),
+            }
+        }
+
+        for problem in &segment.problems {
+            println!("❌ {}", problem);
+        }
+    }
+
+    if inspection.is_well_formed() {
+        println!("\n✅ Token is well-formed");
+    }
+}
+
+/// Displays the envelope of a token endpoint response (without the raw tokens)
+pub fn display_token_response(response: &TokenResponse) {
+    println!("\n=== Token Response ===");
+    println!("Token type: {}", response.token_type);
+    if let Some(scope) = &response.scope {
+        println!("Scope: {}", scope);
+    }
+    println!("Expires in: {}s", response.expires_in);
+
+    let returned: Vec<&str> = [
+        ("access_token", response.access_token.is_some()),
+        ("refresh_token", response.refresh_token.is_some()),
+        ("id_token", response.id_token.is_some()),
+    ]
+    .iter()
+    .filter(|(_, present)| *present)
+    .map(|(name, _)| *name)
+    .collect();
+    println!("Returned tokens: {}", returned.join(", "));
+
+    for (key, value) in &response.extra {
+        println!("{}: {}", key, value);
+    }
+}
+
+/// Scopes of a token request that do not end up in the `scp` claim
+const OIDC_SCOPES: &[&str] = &["openid", "profile", "email", "offline_access"];
+
+/// Compares the expiry and scope of a token response with the claims of its access token
+///
+/// `expires_in` counts from when the response was issued, so it is shown
+/// next to the lifetime the claims give rather than the time remaining.
+pub fn display_response_claims(
+    response: &TokenResponse,
+    claims: &Claims,
+    now: u64,
+    timezone: Timezone,
+    warnings: &Warnings,
+) {
+    println!("\n=== Response vs Access Token ===");
+    println!(
+        "Expires in: {}s in the response, lifetime {}s from iat to exp",
+        response.expires_in,
+        claims.exp.saturating_sub(claims.iat)
+    );
+    println!(
+        "Expiration: {} ({})",
+        timezone.format_timestamp(claims.exp),
+        Claims::format_relative(claims.exp, now)
+    );
+
+    let Some(scope) = &response.scope else {
+        return;
+    };
+    println!("Scope (response): {}", scope);
+    let roles = claims.roles();
+    if claims.scopes().is_empty() && !roles.is_empty() {
+        println!("Roles (claims): {}", roles.join(" "));
+        return;
+    }
+    println!("Scope (claims): {}", claims.scp.as_deref().unwrap_or("-"));
+    // v2 responses name scopes with their resource, e.g. https://graph.microsoft.com/User.Read
+    let missing: Vec<&str> = scope
+        .split_whitespace()
+        .map(|scope| scope.rsplit('/').next().unwrap_or(scope))
+        .filter(|scope| !OIDC_SCOPES.contains(scope) && *scope != ".default")
+        .filter(|scope| !claims.has_scope(scope))
+        .collect();
+    if !missing.is_empty() {
+        warnings.print(Warning::new(
+            "scope-not-in-token",
+            Severity::Medium,
+            format!(
+                "Scopes in the response but not in the scp claim: {}",
+                missing.join(", ")
+            ),
+        ));
+    }
+}
+
+/// Displays the identity of the token holder, merged from the claims and Microsoft Graph
+///
+/// Values stated differently by the token and the directory are flagged,
+/// e.g. a user renamed since signing in.
+pub fn display_identity_summary(
+    identity: &DirectoryIdentity,
+    claims: &Claims,
+    show_sensitive: bool,
+    warnings: &Warnings,
+) {
+    let show = |value: &str| match show_sensitive {
+        true => value.to_string(),
+        false => redact::redact_text(value),
+    };
+    for fact in identity.facts(claims) {
+        match (&fact.token, &fact.directory) {
+            (Some(token), Some(directory)) if fact.is_mismatch() => warnings.print(
+                Warning::new(
+                    "identity-mismatch",
+                    Severity::Low,
+                    format!(
+                        "{}: '{}' in the token, '{}' in the directory",
+                        fact.name,
+                        show(token),
+                        show(directory)
+                    ),
+                )
+                .remediation(
+                    "The directory changed since sign-in, sign in again for current claims",
+                ),
+            ),
+            (Some(value), Some(_)) => println!("✅ {}: {}", fact.name, show(value)),
+            (Some(value), None) => println!("{}: {} (token only)", fact.name, show(value)),
+            (None, Some(value)) => println!("{}: {}", fact.name, show(value)),
+            (None, None) => {}
+        }
+    }
+    for error in &identity.errors {
+        println!("ℹ️  Not read from Graph, {}", error);
+    }
+}
+
+/// Displays checks, each marked as passed or failed
+pub fn display_checks(checks: &[RequirementCheck]) {
+    for check in checks {
+        let mark = if check.passed { "✅" } else { "❌" };
+        println!("{} {}: {}", mark, check.name, check.detail);
+    }
+}
+
+/// Displays every rule of a validation with its status and the values it compared
+pub fn display_validation_trace(rules: &[RuleEvaluation]) {
+    println!("\n=== Validation Trace ===");
+    for rule in rules {
+        let mark = match rule.status {
+            RuleStatus::Passed => "✅",
+            RuleStatus::Failed => "❌",
+            RuleStatus::Skipped => "ℹ️ ",
+        };
+        println!("{} {}: {}", mark, rule.rule, rule.detail);
+    }
+}
+
+/// Displays the steps of a smoke test pipeline and whether it passed
+pub fn display_check_report(report: &CheckReport) {
+    println!("\n=== Check: {} ===", report.spec.display());
+    for step in &report.steps {
+        let mark = if step.passed { "✅" } else { "❌" };
+        println!("{} {}: {}", mark, step.step, step.detail);
+    }
+    let passed = report.steps.iter().filter(|step| step.passed).count();
+    match report.passed() {
+        true => println!("\n✅ All {} steps passed", passed),
+        false => println!("\n❌ {} of {} steps passed", passed, report.steps.len()),
+    }
+}
+
+/// Displays what a token provider returned besides the access token
+pub fn display_acquired_token(token: &AcquiredToken, timezone: Timezone) {
+    println!("\n=== Acquired Token ===");
+    match token.expires_on {
+        Some(expires_on) => println!("Expires: {}", timezone.format_timestamp(expires_on)),
+        None => println!("Expires: not reported"),
+    }
+    if token.refresh_token.is_some() {
+        println!("Refresh token: returned");
+    }
+}
+
+/// Displays the claims of a federated token before it is exchanged with Azure AD
+///
+/// Azure AD only accepts the token if issuer, subject and audience match a
+/// federated identity credential on the app registration.
+pub fn display_federated_token(token: &str, timezone: Timezone, warnings: &Warnings) {
+    /// Audience Azure AD requires on federated tokens
+    const EXCHANGE_AUDIENCE: &str = "api://AzureADTokenExchange";
+
+    println!("\n=== Federated Token ===");
+    let claims = match decode_unverified(token) {
+        Ok((_, claims)) => claims,
+        Err(e) => {
+            println!("❌ Failed to decode federated token: {}", e);
+            return;
+        }
+    };
+
+    println!("Issuer: {}", claims.iss);
+    println!("Subject: {}", claims.sub);
+    println!("Audience: {}", claims.audience_display());
+    println!("Expiration: {}", timezone.format_timestamp(claims.exp));
+
+    if !claims
+        .audiences()
+        .iter()
+        .any(|aud| aud == EXCHANGE_AUDIENCE)
+    {
+        warnings.print(
+            Warning::new(
+                "exchange-audience",
+                Severity::High,
+                format!(
+                    "Audience does not include '{}', Azure AD will reject the exchange",
+                    EXCHANGE_AUDIENCE
+                ),
+            )
+            .remediation(format!(
+                "Request the token for the audience {}",
+                EXCHANGE_AUDIENCE
+            )),
+        );
+    }
+    println!("ℹ️  The federated identity credential must match this issuer and subject exactly");
+}
+
+/// Displays a failed token request, including the endpoint's error body verbatim
+pub fn display_token_error(error: &anyhow::Error) {
+    println!("\n=== Token Response ===");
+    match error.downcast_ref::<TokenEndpointError>() {
+        Some(endpoint_error) => {
+            println!("❌ {}", endpoint_error);
+            if let Ok(body) = serde_json::to_string_pretty(&endpoint_error.body) {
+                println!("{}", body);
+            }
+            if endpoint_error.error == "interaction_required" {
+                println!(
+                    "\n⚠️  The user must sign in interactively (e.g. for MFA, consent or Conditional Access) before new tokens can be issued."
+                );
+            }
+        }
+        None => println!("❌ Token request failed: {}", error),
+    }
+}
+
+/// Displays a failed Graph call with its request ID and the error body verbatim
+///
+/// `include_headers` adds the throttling and diagnostic headers of the response.
+pub fn display_graph_error(error: &anyhow::Error, include_headers: bool) {
+    println!("❌ Graph API test failed: {}", error);
+    let Some(graph_error) = error.downcast_ref::<GraphError>() else {
+        return;
+    };
+    if let Some(request_id) = &graph_error.request_id {
+        println!(
+            "   Request ID: {} (quote it in Microsoft support tickets)",
+            request_id
+        );
+    }
+    if let Some(date) = &graph_error.date {
+        println!("   Date: {}", date);
+    }
+    match &graph_error.body {
+        Value::Null => {}
+        Value::String(body) => println!("{}", body),
+        body => println!("{}", serde_json::to_string_pretty(body).unwrap_or_default()),
+    }
+    if include_headers {
+        println!("Headers:");
+        if graph_error.headers.is_empty() {
+            println!("  (no diagnostic headers)");
+        }
+        for (name, value) in &graph_error.headers {
+            println!("  {}: {}", name, value);
+        }
+    }
+}
+
+/// Displays the endpoints published for an app registration
+pub fn display_app_metadata(metadata: &AppMetadata) {
+    println!("Client ID: {}", metadata.client_id);
+    println!("v1 issuer: {}", metadata.v1.issuer);
+    println!("v2 issuer: {}", metadata.v2.issuer);
+    println!(
+        "Keys: {} ({} keys)",
+        metadata.v2.jwks_uri,
+        metadata.keys.keys.len()
+    );
+}
+
+/// Displays the public metadata of a tenant
+pub fn display_tenant_info(info: &TenantInfo) {
+    println!("\n=== Tenant ===");
+    println!("Tenant ID: {}", info.tenant_id().unwrap_or("-"));
+    println!("Issuer: {}", info.configuration.issuer);
+    if let Some(cloud_instance) = info.cloud_instance() {
+        println!("Cloud instance: {}", cloud_instance);
+    }
+    if let Some(region) = info.region() {
+        println!("Region: {}", region);
+    }
+    if let Some(sub_scope) = info.region_sub_scope() {
+        println!("Sovereign scope: {}", sub_scope);
+    }
+    println!("Token endpoint: {}", info.configuration.token_endpoint);
+
+    match &info.realm {
+        Some(realm) => {
+            println!("\n=== Domain ===");
+            println!(
+                "Domain: {}",
+                realm.domain_name.as_deref().unwrap_or(&info.tenant)
+            );
+            println!("Type: {}", realm.name_space_type);
+            if let Some(brand) = &realm.federation_brand_name {
+                println!("Brand name: {}", brand);
+            }
+            if let Some(auth_url) = &realm.auth_url {
+                println!("Federated sign-in: {}", auth_url);
+            }
+        }
+        None if !info.tenant.contains('.') => println!(
+            "\nℹ️  Look up a domain of the tenant for its brand name and whether it is federated"
+        ),
+        None => println!("\n⚠️  The user realm of {} could not be read", info.tenant),
+    }
+}
+
+/// Explains why a token that is not a signed JWT cannot be validated locally
+pub fn display_token_shape(shape: TokenShape, warnings: &Warnings) {
+    println!("\n=== Token Shape ===");
+    let kind = match shape {
+        TokenShape::Jwt => "Signed JWT",
+        TokenShape::EncryptedJwt => "Encrypted JWT (JWE)",
+        TokenShape::Opaque => "Opaque token",
+    };
+    println!("Kind: {}", kind);
+    if let Some(explanation) = shape.explanation() {
+        warnings.print(Warning::new(
+            "unverifiable-token",
+            Severity::Low,
+            format!("Signature validation is not possible. {}.", explanation),
+        ));
+    }
+}
+
+/// Displays latency percentiles per request phase for a probed endpoint
+pub fn display_endpoint_probe(probe: &EndpointProbe) {
+    println!("\n=== {} ===", probe.url);
+
+    let successes = probe.successes();
+    println!(
+        "Successful attempts: {}/{}",
+        successes.len(),
+        probe.attempts.len()
+    );
+    for failure in probe.failures() {
+        println!("❌ {}", failure);
+    }
+    if successes.is_empty() {
+        return;
+    }
+
+    let mut statuses: Vec<u16> = successes.iter().map(|t| t.status).collect();
+    statuses.sort();
+    statuses.dedup();
+    println!("HTTP status: {:?}", statuses);
+
+    println!("{:<8} {:>10} {:>10} {:>10}", "Phase", "p50", "p90", "max");
+    let phase = |get: fn(&ProbeTiming) -> Duration| successes.iter().map(get).collect::<Vec<_>>();
+    let phases = [
+        ("DNS", phase(|t| t.dns)),
+        ("TCP", phase(|t| t.connect)),
+        ("TLS", phase(|t| t.tls)),
+        ("TTFB", phase(|t| t.ttfb)),
+        ("Total", phase(|t| t.total)),
+    ];
+    for (name, values) in phases {
+        let format = |p: f64| {
+            percentile(&values, p).map_or("-".to_string(), |d| format!("{}ms", d.as_millis()))
+        };
+        println!(
+            "{:<8} {:>10} {:>10} {:>10}",
+            name,
+            format(50.0),
+            format(90.0),
+            format(100.0)
+        );
+    }
+}
+
+/// Displays an anonymized token and the key set to validate it with
+pub fn display_anonymized(anonymized: &Anonymized) {
+    println!("\n=== Anonymized Token ===");
+    println!("{}", anonymized.token);
+    match anonymized.replaced.as_slice() {
+        [] => {
+            println!("\nℹ️  The token has no identifying claims, only the signing key was replaced")
+        }
+        claims => println!("\nℹ️  Replaced: {}", claims.join(", ")),
+    }
+    println!("\n=== Signing Key (JWKS) ===");
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&anonymized.jwks).unwrap_or_default()
+    );
+    println!(
+        "\n⚠️  Tenant, application and group IDs are kept, review the token before sharing it"
+    );
+}
+
+/// Displays the tokens found by `scan`, live ones first
+pub fn display_scan_findings(summary: &ScanSummary) {
+    println!("\n=== Token Scan ===");
+    let mut findings: Vec<&Finding> = summary.findings.iter().collect();
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.remaining));
+
+    for finding in &findings {
+        let location = format!("{}:{}", finding.path.display(), finding.line);
+        if finding.remaining >= LONG_LIVED {
+            println!(
+                "❌ {}: live token for {} (sub {}), expires in {}",
+                location,
+                finding.audience,
+                finding.subject,
+                format_remaining(finding.remaining)
+            );
+        } else if finding.is_live() {
+            println!(
+                "⚠️  {}: live token for {} (sub {}), expires in {}",
+                location,
+                finding.audience,
+                finding.subject,
+                format_remaining(finding.remaining)
+            );
+        } else {
+            println!(
+                "ℹ️  {}: token for {} expired {} ago",
+                location,
+                finding.audience,
+                format_remaining(finding.remaining)
+            );
+        }
+    }
+
+    let live = findings.iter().filter(|finding| finding.is_live()).count();
+    println!(
+        "\n{} token(s) in {} file(s) scanned, {} still live",
+        findings.len(),
+        summary.files,
+        live
+    );
+    if summary.binary > 0 {
+        println!("ℹ️  {} binary file(s) skipped", summary.binary);
+    }
+}
+
+/// Displays failure reasons, validation latency and key cache use of a batch
+pub fn display_batch_stats(records: &[BatchRecord], keys: &KeyStoreStats) {
+    println!("\n=== Batch Statistics ===");
+
+    let mut reasons: Vec<(&str, usize)> = Vec::new();
+    for reason in records.iter().filter_map(|r| r.reason) {
+        match reasons.iter_mut().find(|(known, _)| *known == reason) {
+            Some((_, count)) => *count += 1,
+            None => reasons.push((reason, 1)),
+        }
+    }
+    reasons.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    if reasons.is_empty() {
+        println!("Failures: none");
+    } else {
+        println!("Failures by reason:");
+        for (reason, count) in reasons {
+            println!("  {:<24} {:>6}", reason, count);
+        }
+    }
+
+    let durations: Vec<Duration> = records
+        .iter()
+        .filter(|r| r.verdict != "undecodable")
+        .map(|r| r.elapsed)
+        .collect();
+    let format = |p: f64| {
+        percentile(&durations, p).map_or("-".to_string(), |d| format!("{}ms", d.as_millis()))
+    };
+    println!(
+        "Validation time: p50 {}, p95 {}, max {}",
+        format(50.0),
+        format(95.0),
+        format(100.0)
+    );
+
+    println!("JWKS cache: {} hits, {} misses", keys.hits, keys.misses);
+    if let Some(average) = keys.average_fetch_time() {
+        println!(
+            "JWKS fetches: {} ({} failed), {}ms on average",
+            keys.fetches,
+            keys.fetch_errors,
+            average.as_millis()
+        );
+    }
+}
+
+/// Displays a summary of a Graph API response
+///
+/// `tenant` is the tenant of the token used for the call, shown with users and
+/// groups since Graph does not return it for them.
+pub fn display_graph_response(resource: &GraphResource, tenant: Option<&str>) {
+    match resource {
+        GraphResource::User(user) => {
+            println!("User: {}", user.display_name.as_deref().unwrap_or("-"));
+            println!("  ID: {}", user.id);
+            if let Some(upn) = &user.user_principal_name {
+                println!("  UPN: {}", upn);
+            }
+            if let Some(mail) = &user.mail {
+                println!("  Mail: {}", mail);
+            }
+            if let Some(job_title) = &user.job_title {
+                println!("  Job title: {}", job_title);
+            }
+            if let Some(tenant) = tenant {
+                println!("  Tenant: {}", tenant);
+            }
+        }
+        GraphResource::Group(group) => {
+            println!("Group: {}", group.display_name.as_deref().unwrap_or("-"));
+            println!("  ID: {}", group.id);
+            let kind = if group.group_types.iter().any(|t| t == "Unified") {
+                "Microsoft 365"
+            } else if group.security_enabled == Some(true) {
+                "Security"
+            } else {
+                "Distribution"
+            };
+            println!("  Type: {}", kind);
+            if let Some(mail) = &group.mail {
+                println!("  Mail: {}", mail);
+            }
+            if let Some(tenant) = tenant {
+                println!("  Tenant: {}", tenant);
+            }
+        }
+        GraphResource::Organization(organization) => {
+            println!(
+                "Organization: {}",
+                organization.display_name.as_deref().unwrap_or("-")
+            );
+            println!("  Tenant: {}", organization.id);
+            if let Some(domain) = organization.default_domain() {
+                println!("  Default domain: {}", domain);
+            }
+            if let Some(tenant_type) = &organization.tenant_type {
+                println!("  Tenant type: {}", tenant_type);
+            }
+        }
+        GraphResource::Collection(items) => {
+            let noun = if items.len() == 1 { "item" } else { "items" };
+            println!("Collection: {} {}", items.len(), noun);
+            for item in items {
+                display_graph_response(item, tenant);
+            }
+        }
+        GraphResource::Other(value) => println!("Graph API response: {}", value),
+    }
+}
+
+/// Displays one line per saved validation run, numbered from the oldest
+pub fn display_history(entries: &[HistoryEntry], limit: usize) {
+    println!("\n=== Token History ===");
+    if entries.is_empty() {
+        println!("No saved validations, run with --save to record them");
+        return;
+    }
+
+    let skip = entries.len().saturating_sub(limit);
+    for (index, entry) in entries.iter().enumerate().skip(skip) {
+        let mark = if entry.verdict == "valid" {
+            "✅"
+        } else {
+            "❌"
+        };
+        println!(
+            "{:>4}  {}  {} {}  {}  {}",
+            index + 1,
+            entry.recorded_at,
+            mark,
+            &entry.token_sha256[..12.min(entry.token_sha256.len())],
+            entry.claims.sub,
+            entry.claims.aud
+        );
+    }
+    if skip > 0 {
+        println!("ℹ️  {} older entries not shown", skip);
+    }
+}
+
+/// Displays the full record of a saved validation run
+pub fn display_history_entry(number: usize, entry: &HistoryEntry, timezone: Timezone) {
+    let summary = &entry.claims;
+    println!("\n=== History Entry {} ===", number);
+    println!("Recorded at: {}", entry.recorded_at);
+    println!("Token SHA-256: {}", entry.token_sha256);
+    println!("Issuer: {}", summary.iss);
+    println!("Subject: {}", summary.sub);
+    println!("Audience: {}", summary.aud);
+    println!("Issued at: {}", timezone.format_timestamp(summary.iat));
+    println!("Expiration: {}", timezone.format_timestamp(summary.exp));
+    if let Some(tid) = &summary.tid {
+        println!("Tenant ID: {}", tid);
+    }
+    if let Some(oid) = &summary.oid {
+        println!("Object ID: {}", oid);
+    }
+    if let Some(client) = &summary.client {
+        println!("Client: {}", client);
+    }
+    if !summary.scopes.is_empty() {
+        println!("Scope: {}", summary.scopes.join(" "));
+    }
+    if !summary.roles.is_empty() {
+        println!("Roles: {}", summary.roles.join(", "));
+    }
+
+    println!("\n=== Validation Result ===");
+    match &entry.error {
+        None => println!("✅ Token signature is valid"),
+        Some(error) => println!("❌ Token validation failed: {}", error),
+    }
+}
+
+/// Renders a claim as `key: value`, expanding objects and arrays over indented lines
+pub fn render_claim(key: &str, value: &Value, options: &DisplayOptions) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push_str(&format!("{}:", key));
+            render_nested(value, 1, options, &mut out);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            let noun = if items.len() == 1 { "item" } else { "items" };
+            out.push_str(&format!("{}: ({} {})", key, items.len(), noun));
+            render_nested(value, 1, options, &mut out);
+        }
+        _ => out.push_str(&format!("{}: {}", key, render_scalar(value, options))),
+    }
+    out
+}
+
+fn render_nested(value: &Value, depth: usize, options: &DisplayOptions, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    match value {
+        Value::Object(map) => {
+            for (key, item) in map {
+                if is_complex(item) {
+                    out.push_str(&format!("\n{}{}:", indent, key));
+                    render_nested(item, depth + 1, options, out);
+                } else {
+                    out.push_str(&format!(
+                        "\n{}{}: {}",
+                        indent,
+                        key,
+                        render_scalar(item, options)
+                    ));
+                }
+            }
+        }
+        Value::Array(items) => {
+            let limit = match options.max_claim_length {
+                Some(_) => MAX_ARRAY_ITEMS,
+                None => items.len(),
+            };
+            for item in items.iter().take(limit) {
+                if is_complex(item) {
+                    out.push_str(&format!("\n{}-", indent));
+                    render_nested(item, depth + 1, options, out);
+                } else {
+                    out.push_str(&format!("\n{}- {}", indent, render_scalar(item, options)));
+                }
+            }
+            if items.len() > limit {
+                out.push_str(&format!(
+                    "\n{}... and {} more (use --full to show all)",
+                    indent,
+                    items.len() - limit
+                ));
+            }
+        }
+        _ => out.push_str(&format!(" {}", render_scalar(value, options))),
+    }
+}
+
+fn is_complex(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    }
+}
+
+fn render_scalar(value: &Value, options: &DisplayOptions) -> String {
+    let text = match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+
+    match options.max_claim_length {
+        Some(max) if text.chars().count() > max => {
+            let truncated: String = text.chars().take(max).collect();
+            format!(
+                "{}... ({} more chars)",
+                truncated,
+                text.chars().count() - max
+            )
+        }
+        _ => text,
+    }
+}
+
+/// Displays the device a token was issued on and its compliance state
+pub fn display_device(device: &GraphDevice, warnings: &Warnings) {
+    println!(
+        "Device: {} ({})",
+        device.display_name.as_deref().unwrap_or("-"),
+        device.device_id.as_deref().unwrap_or(&device.id)
+    );
+    if let Some(os) = &device.operating_system {
+        let version = device
+            .operating_system_version
+            .as_deref()
+            .unwrap_or_default();
+        println!("Operating system: {} {}", os, version);
+    }
+    let trust = match device.trust_type.as_deref() {
+        Some("Workplace") => "registered (Workplace)",
+        Some("AzureAd") => "Azure AD joined",
+        Some("ServerAd") => "hybrid Azure AD joined",
+        Some(other) => other,
+        None => "unknown",
+    };
+    println!("Join type: {}", trust);
+    if let Some(last) = &device.approximate_last_sign_in_date_time {
+        println!("Last sign-in: {}", last);
+    }
+    if device.account_enabled == Some(false) {
+        println!("❌ Device is disabled, its tokens fail device-based Conditional Access");
+    }
+    match (device.is_managed, device.is_compliant) {
+        (_, Some(true)) => println!("✅ Device is compliant"),
+        (_, Some(false)) => println!(
+            "❌ Device is not compliant, policies requiring a compliant device will block it"
+        ),
+        (Some(true), None) => warnings.print(Warning::new(
+            "device-compliance-pending",
+            Severity::Low,
+            "Device is managed but has no compliance state yet",
+        )),
+        _ => println!("ℹ️  Device is not managed by Intune, it has no compliance state"),
+    }
+}
+
+/// Displays the dictionary entry of a claim
+pub fn display_claim_info(info: &ClaimInfo) {
+    println!("\n=== {} ({}) ===", info.title, info.name);
+    println!("{}", info.description);
+    let versions: Vec<String> = info.versions.iter().map(|v| format!("v{}", v)).collect();
+    println!("\nToken versions: {}", versions.join(", "));
+    if info.optional {
+        println!("ℹ️  Optional claim, only issued when configured in the app registration");
+    }
+    println!("Typical values:");
+    for example in info.examples {
+        println!("  {}", example);
+    }
+}
+
+/// Displays the diagnoses of `doctor`, followed by the problems they likely cause
+pub fn display_diagnoses(sections: &[Section]) {
+    for section in sections {
+        println!("\n=== {} ===", section.title);
+        for diagnosis in &section.diagnoses {
+            println!(
+                "{} {}: {}",
+                diagnosis.status.mark(),
+                diagnosis.name,
+                diagnosis.detail
+            );
+        }
+    }
+
+    let problems: Vec<_> = sections
+        .iter()
+        .flat_map(|section| &section.diagnoses)
+        .filter(|diagnosis| diagnosis.status != Status::Ok)
+        .collect();
+    println!("\n=== Likely Causes ===");
+    if problems.is_empty() {
+        println!("✅ No problems found on this machine");
+        return;
+    }
+    for (number, diagnosis) in problems.iter().enumerate() {
+        if let Some(cause) = &diagnosis.cause {
+            println!("{}. {}: {}", number + 1, diagnosis.name, cause);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Diagnose the machine validation runs on
+//!
+//! Tokens that validate on one machine but not on another usually point at
+//! the machine: blocked endpoints, a proxy intercepting TLS, a drifting clock
+//! or a data directory that cannot be written. `doctor` checks each of them.
+
+use reqwest::{header, Client};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use url::Url;
+
+use azure_token_validator::api::HttpOptions;
+use azure_token_validator::token::verify::jwks_uri;
+use azure_token_validator::token::{skew_from_date_header, AzureTokenFormat, Clock, SystemClock};
+
+/// Maximum time a connectivity check may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Clock skew below this many seconds is within the precision of the measurement
+const NEGLIGIBLE_SKEW: u64 = 5;
+
+/// Proxy variables honored by the HTTP client, in the order it reads them
+const PROXY_VARIABLES: &[&str] = &[
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+];
+
+/// Outcome of one diagnosis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warning,
+    Failed,
+}
+
+impl Status {
+    /// Gets the mark the status is displayed with
+    pub fn mark(self) -> &'static str {
+        match self {
+            Status::Ok => "✅",
+            Status::Warning => "⚠️ ",
+            Status::Failed => "❌",
+        }
+    }
+}
+
+/// Result of checking one aspect of the environment
+#[derive(Debug)]
+pub struct Diagnosis {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+    /// The validation problem this likely causes, for anything but [`Status::Ok`]
+    pub cause: Option<String>,
+}
+
+impl Diagnosis {
+    fn ok(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Diagnosis {
+            name: name.into(),
+            status: Status::Ok,
+            detail: detail.into(),
+            cause: None,
+        }
+    }
+
+    fn problem(
+        name: impl Into<String>,
+        status: Status,
+        detail: impl Into<String>,
+        cause: impl Into<String>,
+    ) -> Self {
+        Diagnosis {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            cause: Some(cause.into()),
+        }
+    }
+}
+
+/// Diagnoses grouped by the aspect of the environment they check
+#[derive(Debug)]
+pub struct Section {
+    pub title: &'static str,
+    pub diagnoses: Vec<Diagnosis>,
+}
+
+/// Checks connectivity, proxy settings, the system clock and the data directory
+///
+/// `tenant` selects the sign-in endpoints, `leeway` is the clock skew tolerated
+/// by validation and `data_dir` is where the history is kept. Requests send
+/// the user agent and headers of `http`.
+pub async fn diagnose(
+    tenant: &str,
+    leeway: u64,
+    data_dir: &Path,
+    http: &HttpOptions,
+) -> Vec<Section> {
+    let client = http
+        .client_builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    let endpoints = [
+        (
+            "Sign-in metadata",
+            format!(
+                "https://login.microsoftonline.com/{}/v2.0/.well-known/openid-configuration",
+                tenant
+            ),
+        ),
+        ("Signing keys", jwks_uri(tenant, AzureTokenFormat::V2)),
+        (
+            "Microsoft Graph",
+            "https://graph.microsoft.com/v1.0/".to_string(),
+        ),
+    ];
+
+    let mut connectivity = Vec::new();
+    let mut server_date = None;
+    for (name, url) in &endpoints {
+        let (diagnosis, date) = check_endpoint(&client, name, url).await;
+        connectivity.push(diagnosis);
+        server_date = server_date.or(date);
+    }
+
+    vec![
+        Section {
+            title: "Connectivity",
+            diagnoses: connectivity,
+        },
+        Section {
+            title: "Proxy",
+            diagnoses: vec![check_proxy()],
+        },
+        Section {
+            title: "Clock",
+            diagnoses: vec![check_clock(server_date.as_deref(), leeway)],
+        },
+        Section {
+            title: "Data Directory",
+            diagnoses: vec![check_data_dir(data_dir)],
+        },
+    ]
+}
+
+/// Requests an endpoint, returning its `Date` header to measure the clock skew with
+async fn check_endpoint(client: &Client, name: &str, url: &str) -> (Diagnosis, Option<String>) {
+    match client.get(url).send().await {
+        Ok(response) => {
+            let date = response
+                .headers()
+                .get(header::DATE)
+                .and_then(|date| date.to_str().ok())
+                .map(str::to_string);
+            // Graph answers 401 without a token, any response means it is reachable
+            let diagnosis = Diagnosis::ok(
+                name,
+                format!("{} answered with HTTP {}", url, response.status()),
+            );
+            (diagnosis, date)
+        }
+        Err(e) => {
+            let error = format!("{:#}", anyhow::Error::from(e.without_url()));
+            let cause = if error.contains("certificate") {
+                "A TLS-inspecting proxy or firewall replaces the certificate: trust its root CA \
+                 or exempt Azure AD from inspection"
+            } else if error.contains("dns") || error.contains("resolve") {
+                "The host name does not resolve: check the DNS configuration"
+            } else {
+                "Keys and metadata cannot be fetched: allow outbound HTTPS to this host, \
+                 or configure HTTPS_PROXY"
+            };
+            let diagnosis = Diagnosis::problem(
+                name,
+                Status::Failed,
+                format!("{} is unreachable: {}", url, error),
+                cause,
+            );
+            (diagnosis, None)
+        }
+    }
+}
+
+/// Reports the proxy the HTTP client goes through, without its credentials
+fn check_proxy() -> Diagnosis {
+    let proxy = PROXY_VARIABLES
+        .iter()
+        .find_map(|name| Some((*name, std::env::var(name).ok()?)))
+        .filter(|(_, value)| !value.is_empty());
+    let Some((name, value)) = proxy else {
+        return Diagnosis::ok("Proxy", "none configured, connecting directly");
+    };
+
+    let shown = match Url::parse(&value) {
+        Ok(mut url) => {
+            let _ = url.set_password(None);
+            let _ = url.set_username("");
+            url.to_string()
+        }
+        Err(_) => value.clone(),
+    };
+    let no_proxy = std::env::var("NO_PROXY")
+        .or_else(|_| std::env::var("no_proxy"))
+        .unwrap_or_default();
+    let detail = match no_proxy.is_empty() {
+        true => format!("{}={}", name, shown),
+        false => format!("{}={} (NO_PROXY={})", name, shown, no_proxy),
+    };
+    Diagnosis::problem(
+        "Proxy",
+        Status::Warning,
+        detail,
+        "Requests go through a proxy: if it inspects TLS, its root CA must be trusted \
+         by this machine",
+    )
+}
+
+/// Compares the system clock with the `Date` header of an Azure AD response
+fn check_clock(server_date: Option<&str>, leeway: u64) -> Diagnosis {
+    let now = SystemClock.now();
+    let Some(skew) = server_date.and_then(|date| skew_from_date_header(date, now)) else {
+        return Diagnosis::problem(
+            "Clock skew",
+            Status::Warning,
+            "cannot be measured, no endpoint could be reached",
+            "Expiry checks may be off if the clock drifted, compare it with a time server",
+        );
+    };
+
+    let direction = if skew > 0 { "ahead of" } else { "behind" };
+    let detail = format!("{}s {} Azure AD", skew.unsigned_abs(), direction);
+    match skew.unsigned_abs() {
+        0..=NEGLIGIBLE_SKEW => Diagnosis::ok("Clock skew", detail),
+        seconds if seconds <= leeway => Diagnosis::problem(
+            "Clock skew",
+            Status::Warning,
+            format!("{}, within the {}s leeway", detail, leeway),
+            "The clock drifts: synchronize it before the skew exceeds the leeway",
+        ),
+        _ => Diagnosis::problem(
+            "Clock skew",
+            Status::Failed,
+            format!("{}, more than the {}s leeway", detail, leeway),
+            match skew > 0 {
+                true => "Fresh tokens are reported as expired: synchronize the clock",
+                false => "Tokens are reported as not yet valid: synchronize the clock",
+            },
+        ),
+    }
+}
+
+/// Checks that the data directory exists or can be created, and can be written
+fn check_data_dir(data_dir: &Path) -> Diagnosis {
+    let probe = data_dir.join(".doctor");
+    let result = fs::create_dir_all(data_dir)
+        .and_then(|_| fs::write(&probe, b"ok"))
+        .and_then(|_| fs::remove_file(&probe));
+    match result {
+        Ok(()) => Diagnosis::ok(
+            "Data directory",
+            format!("{} is writable", data_dir.display()),
+        ),
+        Err(e) => Diagnosis::problem(
+            "Data directory",
+            Status::Failed,
+            format!("{} is not writable: {}", data_dir.display(), e),
+            "Validations cannot be saved with --save: fix the permissions or use --history-file",
+        ),
+    }
+}
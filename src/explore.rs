@@ -0,0 +1,136 @@
+//! Interactive exploration of the claims of large tokens
+//!
+//! Reads commands from stdin: anything that is not a command searches claim
+//! names and values, long arrays such as `groups` stay collapsed until
+//! expanded, and values are copied to the clipboard with the OSC 52 escape
+//! sequence, which terminals support over SSH too.
+
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+
+use azure_token_validator::token::tree::COLLAPSE_AFTER;
+use azure_token_validator::token::ClaimTree;
+
+/// Number of search results listed
+const MAX_RESULTS: usize = 20;
+
+const HELP: &str = "\
+Commands:
+  <text>            search claim names and values (fuzzy), also /<text>
+  list              list the claims, long arrays collapsed
+  expand <claim>    list every value of an array claim
+  collapse <claim>  collapse an expanded array claim again
+  copy <path>       copy a value to the clipboard, e.g. copy oid or copy groups[3]
+  help              show this help
+  quit              leave";
+
+/// Explores a claim set until the user quits or stdin ends
+pub fn explore(tree: &ClaimTree) -> Result<()> {
+    let mut expanded = HashSet::new();
+    list(tree, &expanded);
+    println!("\nℹ️  Type a search, 'help' for commands or 'quit' to leave");
+
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        let line = line.trim();
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let argument = argument.trim();
+        match command {
+            "" => {}
+            "quit" | "exit" | "q" => return Ok(()),
+            "help" | "?" => println!("{}", HELP),
+            "list" | "ls" => list(tree, &expanded),
+            "expand" => match tree.root().get(argument) {
+                Some(Value::Array(_)) => {
+                    expanded.insert(argument.to_string());
+                    print_claim(argument, &tree.root()[argument], &expanded);
+                }
+                Some(_) => println!("ℹ️  {} is not an array", argument),
+                None => println!("❌ No claim named '{}'", argument),
+            },
+            "collapse" => {
+                expanded.remove(argument);
+                if let Some(value) = tree.root().get(argument) {
+                    print_claim(argument, value, &expanded);
+                }
+            }
+            "copy" => match tree.get(argument) {
+                Some(value) => {
+                    let text = match value {
+                        Value::String(text) => text.clone(),
+                        value => value.to_string(),
+                    };
+                    copy_to_clipboard(&text)?;
+                    println!("✅ Copied {} ({} characters)", argument, text.len());
+                }
+                None => println!("❌ No value at '{}'", argument),
+            },
+            _ => search(tree, line.strip_prefix('/').unwrap_or(line)),
+        }
+    }
+}
+
+/// Lists every claim, arrays longer than [`COLLAPSE_AFTER`] collapsed unless expanded
+fn list(tree: &ClaimTree, expanded: &HashSet<String>) {
+    println!("\n=== Claims ===");
+    if let Value::Object(claims) = tree.root() {
+        for (name, value) in claims {
+            print_claim(name, value, expanded);
+        }
+    }
+}
+
+fn print_claim(name: &str, value: &Value, expanded: &HashSet<String>) {
+    match value {
+        Value::Array(values) if values.len() > COLLAPSE_AFTER && !expanded.contains(name) => {
+            println!(
+                "{}: [{} values, 'expand {}' to list them]",
+                name,
+                values.len(),
+                name
+            )
+        }
+        Value::Array(values) => {
+            println!("{}:", name);
+            for (index, value) in values.iter().enumerate() {
+                println!("  [{}] {}", index, value);
+            }
+        }
+        value => println!("{}: {}", name, value),
+    }
+}
+
+/// Lists the values whose path or text match the query, best matches first
+fn search(tree: &ClaimTree, query: &str) {
+    let matches = tree.search(query);
+    if matches.is_empty() {
+        return println!("No claim matches '{}'", query);
+    }
+    for leaf in matches.iter().take(MAX_RESULTS) {
+        println!("  {} = {}", leaf.path, leaf.text());
+    }
+    if matches.len() > MAX_RESULTS {
+        println!(
+            "  ... {} more, refine the search",
+            matches.len() - MAX_RESULTS
+        );
+    }
+}
+
+/// Asks the terminal to put the text on the clipboard (OSC 52)
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()
+}
@@ -0,0 +1,115 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use azure_token_validator::token::Claims;
+
+/// Redacted summary of a validated token, without the token itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimsSummary {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: u64,
+    pub iat: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+}
+
+impl From<&Claims> for ClaimsSummary {
+    fn from(claims: &Claims) -> Self {
+        ClaimsSummary {
+            iss: claims.iss.clone(),
+            sub: claims.sub.clone(),
+            aud: claims.audience_display(),
+            exp: claims.exp,
+            iat: claims.iat,
+            tid: claims.tenant_id().map(str::to_string),
+            oid: claims.object_id().map(str::to_string),
+            client: claims.authorized_party().map(str::to_string),
+            scopes: claims.scopes().into_iter().map(str::to_string).collect(),
+            roles: claims.roles().into_iter().map(str::to_string).collect(),
+        }
+    }
+}
+
+/// One validation run recorded in the history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub recorded_at: String,
+    pub token_sha256: String,
+    pub claims: ClaimsSummary,
+    pub verdict: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    /// Creates an entry for a validation run, keeping only a hash of the token
+    pub fn new(token: &str, claims: &Claims, result: &Result<Claims>) -> Self {
+        HistoryEntry {
+            recorded_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            token_sha256: format!("{:x}", Sha256::digest(token.as_bytes())),
+            claims: claims.into(),
+            verdict: if result.is_ok() { "valid" } else { "invalid" }.to_string(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// Gets the default history location in the user's data directory
+///
+/// `$XDG_DATA_HOME` or `~/.local/share` on Unix, `%LOCALAPPDATA%` on Windows.
+pub fn default_path() -> PathBuf {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("azure-token-validator").join("history.jsonl")
+}
+
+/// Appends an entry to the JSONL history file, creating it if needed
+pub fn append(path: &Path, entry: &HistoryEntry) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+/// Loads all entries from the history file, oldest first
+pub fn load(path: &Path) -> Result<Vec<HistoryEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid history entry on line {}", index + 1))
+        })
+        .collect()
+}
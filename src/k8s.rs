@@ -0,0 +1,130 @@
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use tokio::process::Command;
+
+/// Keys tried, in order, when a secret holds several values and no key is given
+const TOKEN_KEYS: &[&str] = &["token", "access_token", "id_token"];
+
+/// Kubernetes secret holding a token, given as `namespace/name[:key]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretRef {
+    pub namespace: String,
+    pub name: String,
+    /// Key of the value holding the token, guessed from the secret if not given
+    pub key: Option<String>,
+}
+
+impl FromStr for SecretRef {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (path, key) = match value.split_once(':') {
+            Some((path, key)) => (path, Some(key.to_string())),
+            None => (value, None),
+        };
+        match path.split_once('/') {
+            Some((namespace, name))
+                if !namespace.is_empty() && !name.is_empty() && !name.contains('/') =>
+            {
+                Ok(SecretRef {
+                    namespace: namespace.to_string(),
+                    name: name.to_string(),
+                    key: key.filter(|key| !key.is_empty()),
+                })
+            }
+            _ => Err(format!(
+                "'{}' is not a secret of the form namespace/name[:key]",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.namespace, self.name)?;
+        match &self.key {
+            Some(key) => write!(f, ":{}", key),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The parts of a Secret object returned by `kubectl get secret -o json`
+#[derive(Debug, Deserialize)]
+struct Secret {
+    #[serde(default)]
+    data: BTreeMap<String, String>,
+}
+
+/// Reads a token from a Kubernetes secret
+///
+/// The secret is fetched with `kubectl`, so the local kubeconfig, its current
+/// context and any credential plugins (e.g. kubelogin for AKS) apply.
+pub async fn read_token(secret: &SecretRef) -> Result<String> {
+    let output = Command::new("kubectl")
+        .args([
+            "get",
+            "secret",
+            &secret.name,
+            "--namespace",
+            &secret.namespace,
+        ])
+        .args(["--output", "json"])
+        .output()
+        .await
+        .context("Failed to run kubectl, is it installed and on the PATH?")?;
+    if !output.status.success() {
+        bail!(
+            "kubectl could not get secret {}: {}",
+            secret,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let object: Secret =
+        serde_json::from_slice(&output.stdout).context("Unexpected output from kubectl")?;
+    let key = select_key(secret, &object.data)?;
+    let decoded = STANDARD
+        .decode(&object.data[key])
+        .with_context(|| format!("Value '{}' of secret {} is not base64", key, secret))?;
+    let token = String::from_utf8(decoded)
+        .with_context(|| format!("Value '{}' of secret {} is not text", key, secret))?;
+    Ok(token.trim().to_string())
+}
+
+/// Picks the value holding the token: the given key, the only one, or a well-known one
+fn select_key<'a>(secret: &SecretRef, data: &'a BTreeMap<String, String>) -> Result<&'a str> {
+    let keys: Vec<&str> = data.keys().map(String::as_str).collect();
+    if let Some(key) = &secret.key {
+        return match data.get_key_value(key) {
+            Some((key, _)) => Ok(key),
+            None => bail!(
+                "Secret {} has no key '{}', it has: {}",
+                secret,
+                key,
+                keys.join(", ")
+            ),
+        };
+    }
+    match keys.as_slice() {
+        [] => bail!("Secret {} has no data", secret),
+        [key] => Ok(key),
+        _ => TOKEN_KEYS
+            .iter()
+            .find_map(|known| keys.iter().find(|key| *key == known).copied())
+            .with_context(|| {
+                format!(
+                    "Secret {} has several keys ({}), name one as {}:<key>",
+                    secret,
+                    keys.join(", "),
+                    secret
+                )
+            }),
+    }
+}
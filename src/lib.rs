@@ -0,0 +1,25 @@
+//! Validation and inspection of Azure AD tokens
+//!
+//! The [`token`] module decodes and validates JWTs and SAML assertions
+//! against Azure AD signing keys, the [`api`] module talks to Azure AD and
+//! Microsoft Graph endpoints. Synchronous variants are available in the
+//! `blocking` module with the `blocking` feature. The `middleware` and
+//! `actix` features authenticate HTTP requests with a tower layer and an
+//! actix-web extractor respectively. The `test-fixtures` feature adds the
+//! `testing` module for deterministic tests of code using the validator.
+
+#[cfg(feature = "actix")]
+pub mod actix;
+#[cfg(any(feature = "net", feature = "blocking"))]
+pub mod api;
+#[cfg(any(feature = "middleware", feature = "actix"))]
+mod auth;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "middleware")]
+pub mod middleware;
+#[cfg(feature = "test-fixtures")]
+pub mod testing;
+pub mod token;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -1,12 +1,72 @@
-mod api;
-mod token;
+mod anonymize;
+mod batch;
+mod census;
+mod check;
+mod display;
+mod doctor;
+mod explore;
+mod history;
+mod k8s;
+mod monitor;
+mod otel;
+mod redact;
+mod report;
+mod scan;
+mod serve;
+mod update;
+mod warnings;
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use clap::{ArgGroup, Parser, Subcommand};
+use jsonwebtoken::Algorithm;
+use serde::Deserialize;
+use serde_json::Value;
 use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use url::Url;
 
-use api::GraphClient;
-use token::{Claims, TokenType, TokenValidator, ValidatorConfig};
+use azure_token_validator::api::{
+    probe_endpoint, AcquireToken, AzureCli, ClientCredentials, ClientSecret, DeviceCodeFlow,
+    FederatedCredential, GraphClient, GraphRequest, GraphResource, HttpOptions, ManagedIdentity,
+    MetadataClient, RateLimiter, ResourceApi, ResourceClient, ResourceTest, TokenEndpointClient,
+    TokenResponse,
+};
+use azure_token_validator::token::jwk::{Jwk, JwksResponse};
+use azure_token_validator::token::keystore::DEFAULT_TTL;
+use azure_token_validator::token::verify::jwks_uri;
+use azure_token_validator::token::{
+    audience, audit_token, certificate_binding, certificate_thumbprint, check_token_pair,
+    decode_saml_input, dictionary, extract_token, find_signing_key, inspect_raw, resolve_tenant,
+    signing_key_warnings, suggest_fixes, verify_dpop_proof, ApiRequirements, AppManifest,
+    AuditConfig, AzureTokenFormat, CertificateBinding, ClaimMapping, ClaimTree, Claims,
+    ClaimsSchema, Cloud, DiskCache, FixedClock, KeyCache, KeyStore, ProfileChain, RequirementCheck,
+    SamlAssertion, SchemaViolation, Severity, Timezone, TokenKind, TokenShape, TokenSize,
+    TokenType, TokenValidator, ValidationError, ValidatorBuilder, Warning, WarningPolicy,
+    AUTO_TENANT,
+};
+use batch::ExportFormat;
+use display::{
+    display_acquired_token, display_anonymized, display_app_metadata, display_batch_stats,
+    display_check_report, display_checks, display_claim_info, display_conditional_access,
+    display_device, display_diagnoses, display_endpoint_probe, display_federated_token,
+    display_graph_error, display_graph_response, display_history, display_history_entry,
+    display_identity_chain, display_identity_summary, display_raw_inspection,
+    display_response_claims, display_saml_assertion, display_scan_findings, display_security_audit,
+    display_tenant_info, display_token_error, display_token_info, display_token_response,
+    display_token_shape, display_token_size, display_token_summary, display_validation_trace,
+    DisplayMode, DisplayOptions,
+};
+use history::HistoryEntry;
+use k8s::SecretRef;
+use monitor::{MonitorOptions, TokenSource};
+use report::{Check, Report, ReportFormat};
+use scan::ScanOptions;
+use warnings::Warnings;
 
 /// Azure AD Token Validator CLI
 #[derive(Parser)]
@@ -18,66 +78,594 @@ use token::{Claims, TokenType, TokenValidator, ValidatorConfig};
     long_about = None
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// JWT token to validate (if not provided, will prompt for input)
     token: Option<String>,
 
-    /// Azure AD tenant ID (defaults to 'common')
-    #[arg(long, default_value = "common")]
+    /// Read lines from stdin as they arrive, validate every JWT found in them once
+    /// and print one NDJSON verdict per token
+    #[arg(long, conflicts_with = "token")]
+    stdin_stream: bool,
+
+    /// Read the token from a Kubernetes secret, given as namespace/name[:key], using
+    /// kubectl and the local kubeconfig
+    #[arg(long, value_name = "NS/NAME[:KEY]", conflicts_with_all = ["token", "stdin_stream"])]
+    from_k8s_secret: Option<SecretRef>,
+
+    /// Azure AD tenant ID, or 'auto' to use the tenant the token was issued by
+    /// ('common' when there is no token to take it from)
+    #[arg(long, global = true, default_value = AUTO_TENANT)]
     tenant: String,
 
     /// Skip token expiration check
-    #[arg(long)]
+    #[arg(long, global = true)]
     skip_expiration: bool,
 
+    /// Only verify the signature against the tenant's keys, without checking expiry, issuer,
+    /// audience or any other claim (e.g. for tokens of past incidents)
+    #[arg(long, global = true)]
+    signature_only: bool,
+
     /// Test Microsoft Graph API with the token
-    #[arg(long)]
+    #[arg(long, global = true)]
     test_graph: bool,
 
-    /// Custom Graph API endpoint to call (requires --test-graph)
-    #[arg(long)]
-    endpoint: Option<String>,
-}
+    /// Test the token against the API in its audience (Graph, Resource Manager, Key Vault or Storage)
+    #[arg(long, global = true)]
+    test_api: bool,
 
-/// Displays token information in a structured way
-fn display_token_info(claims: &Claims) {
-    println!("\n=== Token Information ===");
-    println!("Token type: {}", claims.token_type());
-    println!("Issuer: {}", claims.iss);
-    println!("Audience: {}", claims.audience_display());
+    /// Key Vault name or URL to test Key Vault tokens against (requires --test-api)
+    #[arg(long, global = true)]
+    vault: Option<String>,
 
-    // Display timestamps
-    println!("Not before: {}", Claims::format_timestamp(claims.nbf));
-    println!("Issued at: {}", Claims::format_timestamp(claims.iat));
-    println!("Expiration: {}", Claims::format_timestamp(claims.exp));
+    /// Storage account to test Storage tokens against (requires --test-api)
+    #[arg(long, global = true)]
+    storage_account: Option<String>,
 
-    // Display common claims if present
-    if let Some(name) = &claims.name {
-        println!("Name: {}", name);
-    }
+    /// Fetch the group memberships from Microsoft Graph when the token has a groups overage
+    #[arg(long, global = true)]
+    resolve_overage: bool,
 
-    if let Some(email) = &claims.email {
-        println!("Email: {}", email);
-    }
+    /// Custom Graph API endpoint to call (requires --test-graph), repeatable; several
+    /// endpoints are sent in a single $batch request
+    #[arg(long, global = true)]
+    endpoint: Vec<String>,
 
-    if let Some(username) = &claims.preferred_username {
-        println!("Username: {}", username);
-    }
+    /// Evaluate time-based claims as of this time (RFC 3339 or Unix seconds) instead of now
+    #[arg(long, global = true, value_parser = parse_timestamp)]
+    at: Option<u64>,
 
-    if let Some(appid) = &claims.appid {
-        println!("App ID: {}", appid);
-    }
+    /// Correct exp/nbf checks for the skew of the system clock to Azure AD, measured from the
+    /// Date header of the key fetch
+    #[arg(long, global = true, conflicts_with = "at")]
+    auto_skew: bool,
+
+    /// List every validation rule with its status and the values it compared
+    #[arg(long, global = true)]
+    explain: bool,
+
+    /// Signing algorithms to accept, comma-separated (HMAC algorithms are always rejected)
+    #[arg(long, global = true, value_delimiter = ',', default_value = "RS256", value_parser = parse_algorithm)]
+    allowed_algorithms: Vec<Algorithm>,
+
+    /// Key ID (kid) the token must be signed with
+    #[arg(long, global = true, value_name = "KID")]
+    pin_kid: Option<String>,
+
+    /// Certificate thumbprint of the key the token must be signed with (x5t, base64url or hex)
+    #[arg(long, global = true, value_name = "X5T")]
+    pin_thumbprint: Option<String>,
+
+    /// Client application IDs allowed to call the API, comma-separated (azp in v2, appid in v1 tokens)
+    #[arg(long, global = true, value_delimiter = ',', value_name = "APPID,...")]
+    allowed_clients: Vec<String>,
+
+    /// Also validate against each of these tenant IDs concurrently, comma-separated,
+    /// and report which of them issued the token
+    #[arg(long, global = true, value_delimiter = ',')]
+    try_tenants: Vec<String>,
+
+    /// Audience the token must be issued for, repeatable (not checked when omitted)
+    #[arg(long, global = true)]
+    audience: Vec<String>,
+
+    /// Issuer the token must come from (defaults to the token's own issuer)
+    #[arg(long, global = true)]
+    issuer: Option<String>,
+
+    /// DPoP proof JWT sent with the token, verified against the key the token is bound to
+    #[arg(long, global = true)]
+    dpop_proof: Option<String>,
+
+    /// TLS client certificate (PEM or DER) the token was presented with, checked against
+    /// the token's cnf.x5t#S256 certificate binding
+    #[arg(long, global = true, value_parser = parse_client_cert)]
+    client_cert: Option<String>,
+
+    /// ID token returned together with the token, checked to belong to the same sign-in
+    /// (tenant, user, client, at_hash and validity)
+    #[arg(long, global = true)]
+    id_token: Option<String>,
+
+    /// TOML file with an [api] block of accepted audiences, scopes, roles and versions
+    /// to check the token against, like the API's own middleware would
+    #[arg(long, global = true, value_parser = parse_api_config)]
+    api_config: Option<ApiRequirements>,
+
+    /// TOML file of [[profile]] blocks (a name plus the fields of an [api] block) checked
+    /// in order, reporting the first profile the token meets, like a gateway routing by it
+    #[arg(long, global = true, value_parser = parse_profiles)]
+    profiles: Option<ProfileChain>,
+
+    /// Authentication context ID the sign-in must have satisfied (acrs), e.g. c1, repeatable
+    #[arg(long, global = true)]
+    require_auth_context: Vec<String>,
+
+    /// Kind of token to check the claim rules of: access, id (also for an id_token_hint)
+    /// or logout (OpenID Connect back-channel logout token)
+    #[arg(long, global = true, value_name = "KIND")]
+    token_kind: Option<TokenKind>,
+
+    /// App registration manifest (JSON exported from the portal or Graph) to check the
+    /// token's audience, scopes and roles against
+    #[arg(long, global = true, value_parser = parse_manifest)]
+    manifest: Option<AppManifest>,
+
+    /// JSON Schema the decoded claims must conform to (types, required claims, enums,
+    /// patterns), codifying the token contract of an API
+    #[arg(long, global = true, value_parser = parse_claims_schema)]
+    claims_schema: Option<ClaimsSchema>,
+
+    /// YAML or TOML file mapping output fields to the claims they are taken from
+    /// (e.g. `user_id: oid`), to print the token as the application's own identity
+    #[arg(long, global = true, value_parser = parse_claim_mapping)]
+    map: Option<ClaimMapping>,
+
+    /// AD FS server (e.g. adfs.contoso.com) whose tokens are validated against its own
+    /// keys and federation metadata
+    #[arg(long, global = true, value_name = "HOST", value_parser = parse_adfs_host)]
+    adfs: Option<String>,
+
+    /// Token version the audit should expect (flags tokens of the other version)
+    #[arg(long, global = true, value_parser = ["1.0", "2.0"])]
+    expected_version: Option<String>,
+
+    /// Maximum length of a displayed claim value before it is truncated
+    #[arg(long, global = true, default_value_t = 120)]
+    max_claim_length: usize,
 
-    if let Some(scope) = &claims.scp {
-        println!("Scope: {}", scope);
+    /// Display claim values in full, without truncation
+    #[arg(long, global = true)]
+    full: bool,
+
+    /// Time zone to display timestamps in: utc, local or an IANA name such as Europe/Berlin
+    #[arg(long, global = true, default_value = "utc")]
+    timezone: Timezone,
+
+    /// Display a one-screen summary: user, app, tenant, scopes and roles, and validity
+    #[arg(long, global = true, conflicts_with = "detailed")]
+    summary: bool,
+
+    /// Display every claim with its name, in full, grouped into identity, authorization,
+    /// lifetime and protocol
+    #[arg(long, global = true)]
+    detailed: bool,
+
+    /// Print a shareable report of the token validation instead of the console output
+    #[arg(long, global = true, value_enum)]
+    report: Option<ReportFormat>,
+
+    /// Write the report to this file, replacing it atomically (JSON unless --report is given)
+    #[arg(long, global = true)]
+    output_file: Option<PathBuf>,
+
+    /// Post the report as JSON to this webhook, e.g. a Slack or Teams incoming webhook
+    #[arg(long, global = true)]
+    output_url: Option<Url>,
+
+    /// Append a redacted record of each validation (token hash, claims summary, verdict) to the history
+    #[arg(long, global = true)]
+    save: bool,
+
+    /// History file to use instead of the one in the user's data directory
+    #[arg(long, global = true, env = "AZURE_TOKEN_VALIDATOR_HISTORY")]
+    history_file: Option<PathBuf>,
+
+    /// Properties to return from the Graph API, comma-separated ($select)
+    #[arg(long, global = true, value_delimiter = ',')]
+    select: Vec<String>,
+
+    /// OData filter expression for Graph API collections ($filter)
+    #[arg(long, global = true)]
+    filter: Option<String>,
+
+    /// Maximum number of items to return from Graph API collections ($top)
+    #[arg(long, global = true)]
+    top: Option<u32>,
+
+    /// Extra header for all outbound requests (keys, discovery, Graph, token endpoint) as
+    /// NAME:VALUE, e.g. 'ConsistencyLevel: eventual' or 'client-request-id: <uuid>', repeatable
+    #[arg(long, global = true, value_parser = parse_header)]
+    header: Vec<(String, String)>,
+
+    /// User-Agent of all outbound requests
+    #[arg(long, global = true, env = "AZURE_TOKEN_VALIDATOR_USER_AGENT")]
+    user_agent: Option<String>,
+
+    /// Keeps fetched signing keys for later runs: a directory, or a redis:// URL if built
+    /// with the redis feature
+    #[arg(
+        long,
+        global = true,
+        env = "AZURE_TOKEN_VALIDATOR_KEY_CACHE",
+        value_parser = parse_key_cache
+    )]
+    key_cache: Option<Arc<dyn KeyCache>>,
+
+    /// Maximum number of Graph API requests per second (requests in a $batch count individually)
+    #[arg(long, global = true, default_value_t = 10.0)]
+    graph_rps: f64,
+
+    /// Show the throttling and diagnostic headers of failed Graph API responses
+    #[arg(long, global = true)]
+    include_headers: bool,
+
+    /// Print the Graph API response as raw JSON instead of a summary
+    #[arg(long, global = true)]
+    raw: bool,
+
+    /// Show emails, UPNs, `aio`/`uti` claims and token signatures instead of masking them
+    #[arg(long, global = true)]
+    show_sensitive: bool,
+
+    /// Log key fetches and the state of the JWKS circuit breaker to stderr
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Fail the run if a warning is reported that --allow does not suppress
+    #[arg(long, global = true)]
+    deny_warnings: bool,
+
+    /// Suppress the warnings with this code, e.g. 'nbf-missing', repeatable or comma-separated
+    #[arg(
+        long = "allow",
+        global = true,
+        value_name = "CODE",
+        value_delimiter = ','
+    )]
+    allow_warnings: Vec<String>,
+
+    /// Export spans of validation steps, key fetches and Graph calls to this OTLP/HTTP endpoint
+    #[arg(
+        long,
+        global = true,
+        value_name = "URL",
+        env = "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"
+    )]
+    otlp_endpoint: Option<Url>,
+}
+
+/// Prints log records of this crate to stderr
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with("azure_token_validator")
     }
 
-    // Display additional claims
-    if !claims.extra.is_empty() {
-        println!("\n=== Additional Claims ===");
-        for (key, value) in &claims.extra {
-            println!("{}: {}", key, value);
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
         }
     }
+
+    fn flush(&self) {}
+}
+
+/// Additional modes besides validating a token
+#[derive(Subcommand)]
+enum Command {
+    /// Shows the raw base64url-decoded segments of a token and any encoding problems
+    InspectRaw {
+        /// JWT token to inspect (if not provided, will prompt for input)
+        token: Option<String>,
+    },
+
+    /// Explores the claims of a token interactively: fuzzy search, expand long arrays
+    /// such as groups and copy values to the clipboard
+    Explore {
+        /// JWT token to explore (if not provided, will prompt for input)
+        token: Option<String>,
+    },
+
+    /// Replaces the user's identifying claims with fake values and re-signs the token with
+    /// a throwaway key, to attach a realistic token to bug reports
+    Anonymize {
+        /// JWT token to anonymize (if not provided, will prompt for input)
+        token: Option<String>,
+    },
+
+    /// Performs an on-behalf-of exchange and validates the resulting downstream token
+    Obo {
+        /// Incoming access token to exchange (if not provided, will prompt for input)
+        token: Option<String>,
+
+        /// Client ID of the middle-tier app registration
+        #[arg(long)]
+        client_id: String,
+
+        /// Client secret of the middle-tier app registration
+        #[arg(long, env = "AZURE_CLIENT_SECRET", hide_env_values = true)]
+        client_secret: String,
+
+        /// Space-separated scopes to request for the downstream API
+        #[arg(long, default_value = "https://graph.microsoft.com/.default")]
+        scope: String,
+    },
+
+    /// Redeems a refresh token and validates the returned tokens
+    Refresh {
+        /// Refresh token to redeem (if not provided, will prompt for input)
+        refresh_token: Option<String>,
+
+        /// Client ID of the app registration the refresh token was issued to
+        #[arg(long)]
+        client_id: String,
+
+        /// Client secret (only for confidential clients)
+        #[arg(long, env = "AZURE_CLIENT_SECRET", hide_env_values = true)]
+        client_secret: Option<String>,
+
+        /// Space-separated scopes to request
+        #[arg(long, default_value = "openid profile offline_access")]
+        scope: String,
+    },
+
+    /// Acquires a token from the current environment and validates it
+    #[command(group(
+        ArgGroup::new("method")
+            .required(true)
+            .args(["managed_identity", "federated_token_file", "client_credentials", "device_code", "azure_cli"])
+    ))]
+    Acquire {
+        /// Use the managed identity of this VM, AKS node, App Service or Function
+        #[arg(long)]
+        managed_identity: bool,
+
+        /// Exchange a federated token (e.g. the projected service account token of
+        /// AKS workload identity, $AZURE_FEDERATED_TOKEN_FILE) for an app token
+        #[arg(long)]
+        federated_token_file: Option<PathBuf>,
+
+        /// Request an app token with a client secret (client credentials flow), read from
+        /// AZURE_CLIENT_SECRET or --client-secret-file so it never shows up in the process list
+        #[arg(long)]
+        client_credentials: bool,
+
+        /// File holding the client secret, e.g. a mounted Kubernetes secret
+        #[arg(long, requires = "client_credentials")]
+        client_secret_file: Option<PathBuf>,
+
+        /// Sign in as a user on another device (device code flow, public clients only)
+        #[arg(long)]
+        device_code: bool,
+
+        /// Use the account signed in to the Azure CLI
+        #[arg(long)]
+        azure_cli: bool,
+
+        /// Resource (App ID URI) to request a token for
+        #[arg(long, default_value = "https://graph.microsoft.com")]
+        resource: String,
+
+        /// Client ID of a user-assigned managed identity, or of the app registration
+        /// trusting the federated credential
+        #[arg(long, env = "AZURE_CLIENT_ID")]
+        client_id: Option<String>,
+    },
+
+    /// Runs a smoke test pipeline from a YAML spec: acquire a token, validate it,
+    /// check its claims and call Graph endpoints with it
+    Check {
+        /// YAML spec of the pipeline
+        spec: PathBuf,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Validates many tokens from a file, one per line ('-' reads from stdin)
+    Batch {
+        /// File with one token per line, blank lines and '#' comments are skipped
+        file: PathBuf,
+
+        /// Write per-token results to a .csv, .ndjson or .jsonl file
+        #[arg(long)]
+        export: Option<PathBuf>,
+
+        /// Show failures by reason, validation latency and JWKS cache use
+        #[arg(long)]
+        stats: bool,
+    },
+
+    /// Validates tokens on a schedule and alerts when they fail or are about to expire;
+    /// alerts are posted to --output-url
+    #[command(group(
+        ArgGroup::new("sources")
+            .required(true)
+            .multiple(true)
+            .args(["env", "file", "managed_identity"])
+    ))]
+    Monitor {
+        /// Environment variable holding a token, repeatable
+        #[arg(long)]
+        env: Vec<String>,
+
+        /// File holding a token, read again for every check, repeatable
+        #[arg(long)]
+        file: Vec<PathBuf>,
+
+        /// Resource to acquire a managed identity token for, repeatable
+        #[arg(long, value_name = "RESOURCE")]
+        managed_identity: Vec<String>,
+
+        /// Client ID of a user-assigned managed identity
+        #[arg(long, env = "AZURE_CLIENT_ID")]
+        client_id: Option<String>,
+
+        /// Seconds between checks
+        #[arg(long, default_value_t = 300)]
+        interval: u64,
+
+        /// Alert when a token has fewer seconds than this left
+        #[arg(long, default_value_t = 600)]
+        min_remaining: u64,
+
+        /// Check once and exit with an error if any token needs attention (for cron)
+        #[arg(long)]
+        once: bool,
+    },
+
+    /// Searches a file or directory for embedded tokens and reports those still live
+    Scan {
+        /// File or directory to scan
+        path: PathBuf,
+
+        /// Number of files to scan in parallel (defaults to the number of CPUs)
+        #[arg(long)]
+        jobs: Option<usize>,
+
+        /// Also scan files excluded by .gitignore and .ignore files
+        #[arg(long)]
+        no_ignore: bool,
+    },
+
+    /// Lists validations saved with --save, or shows one of them again
+    History {
+        /// Number of the entry to show in full
+        #[arg(long)]
+        show: Option<usize>,
+
+        /// Number of most recent entries to list
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// Lists the signing keys of tenants with their certificate expiries as CSV,
+    /// of the --tenant tenant or of every tenant in --tenants
+    Keys {
+        /// File with one tenant ID or domain per line ('-' reads from stdin),
+        /// blank lines and '#' comments are skipped
+        #[arg(long)]
+        tenants: Option<PathBuf>,
+
+        /// Cloud the tenants are in (public, us_government or china)
+        #[arg(long, default_value = "public", value_parser = parse_cloud)]
+        cloud: Cloud,
+
+        /// Maximum number of key sets fetched per second, and at the same time
+        #[arg(long, default_value_t = 5.0)]
+        rps: f64,
+
+        /// Write the CSV to this file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Updates this binary to the newest release of the channel
+    SelfUpdate {
+        /// Releases to update to: stable, or beta to include pre-releases
+        #[arg(
+            long,
+            value_enum,
+            default_value = "stable",
+            env = "AZURE_TOKEN_VALIDATOR_CHANNEL"
+        )]
+        channel: update::Channel,
+
+        /// Release feed in the format of the GitHub releases API, e.g. an internal mirror
+        #[arg(long, default_value = update::DEFAULT_FEED, env = "AZURE_TOKEN_VALIDATOR_UPDATE_FEED")]
+        feed: String,
+
+        /// PEM public key the release binary must be signed with, e.g. of an internal
+        /// mirror (defaults to the project's release key)
+        #[arg(long, env = "AZURE_TOKEN_VALIDATOR_UPDATE_KEY")]
+        public_key: Option<PathBuf>,
+
+        /// Install a release without verifying its signature, trusting the feed alone
+        #[arg(long, conflicts_with = "public_key")]
+        insecure_skip_signature: bool,
+
+        /// Only report whether an update is available
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Measures latency to the tenant's discovery and JWKS endpoints
+    Probe {
+        /// Number of requests per endpoint
+        #[arg(long, default_value_t = 5)]
+        attempts: usize,
+    },
+
+    /// Checks connectivity, proxy settings, clock skew and the data directory of this machine
+    Doctor,
+
+    /// Looks up the public metadata of a tenant: issuer, region, cloud and brand name
+    TenantInfo {
+        /// Tenant ID or domain, e.g. the tid claim of a token or contoso.com
+        tenant: String,
+
+        /// Cloud to look the tenant up in (public, us_government or china)
+        #[arg(long, default_value = "public", value_parser = parse_cloud)]
+        cloud: Cloud,
+    },
+
+    /// Checks a token against the OpenID metadata and keys of an app registration
+    VerifyConfig {
+        /// JWT token issued for or to the app (if not provided, will prompt for input)
+        token: Option<String>,
+
+        /// Application (client) ID of the app registration
+        #[arg(long)]
+        client_id: String,
+
+        /// Cloud the app is registered in (public, us_government or china)
+        #[arg(long, default_value = "public", value_parser = parse_cloud)]
+        cloud: Cloud,
+    },
+
+    /// Explains what a claim means, which token versions carry it and its typical values
+    Explain {
+        /// Name of the claim, e.g. xms_tcdt
+        claim: String,
+    },
+
+    /// Runs an HTTP sidecar validating bearer tokens for a reverse proxy
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+        /// Tenant ID an X-Expected-Tenant header may name, repeatable; the header is ignored without it
+        #[arg(long = "header-tenant", value_name = "TENANT_ID")]
+        header_tenants: Vec<String>,
+    },
+}
+
+impl Command {
+    /// Whether the command runs without sending any HTTP request
+    fn is_offline(&self) -> bool {
+        matches!(
+            self,
+            Command::InspectRaw { .. }
+                | Command::Anonymize { .. }
+                | Command::History { .. }
+                | Command::Explain { .. }
+        )
+    }
 }
 
 /// Prompts the user to enter a token
@@ -91,60 +679,1814 @@ fn prompt_for_token() -> Result<String> {
     Ok(token.trim().to_string())
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args = Cli::parse();
+/// Extracts the token from input pasted from a redirect URL, saying where it was found
+///
+/// The note goes to stderr, so it does not end up in JSON reports.
+fn extract_input(input: &str) -> String {
+    let extracted = extract_token(input);
+    match (extracted.parameter, extracted.url_decoded) {
+        (Some(parameter), _) => eprintln!("ℹ️  Using the {} parameter of the URL", parameter),
+        (None, true) => eprintln!("ℹ️  URL-decoded the percent-encoded token"),
+        (None, false) => {}
+    }
+    extracted.token
+}
+
+/// Validates a token, or only its signature with `--signature-only`
+async fn validate(validator: &TokenValidator, token: &str, args: &Cli) -> Result<Claims> {
+    match args.signature_only {
+        true => validator.validate_signature(token).await,
+        false => validator.validate_token(token).await,
+    }
+}
+
+/// Validates every token of a token endpoint response, after the response itself
+///
+/// Refresh tokens are opaque to clients, only the token endpoint can check them.
+async fn validate_token_response(response: &TokenResponse, run: &Run) {
+    display_token_response(response);
+    if let Some(access_token) = &response.access_token {
+        let validator = validator(run);
+        if let Ok((_, claims)) = validator.decode_token(access_token) {
+            display_response_claims(
+                response,
+                &claims,
+                validator.config().clock.now(),
+                run.args.timezone,
+                &run.warnings,
+            );
+        }
+        println!("\n=== Access Token ===");
+        validate_and_display(access_token, run).await;
+    }
+    if let Some(id_token) = &response.id_token {
+        println!("\n=== ID Token ===");
+        validate_and_display(id_token, run).await;
+    }
+    if let Some(refresh_token) = &response.refresh_token {
+        println!("\n=== Refresh Token ===");
+        println!(
+            "ℹ️  Opaque, {} characters: check it by redeeming it with the refresh command",
+            refresh_token.len()
+        );
+    }
+}
+
+/// A run of the command line, collecting its warnings and failures
+struct Run {
+    args: Cli,
+    warnings: Warnings,
+    /// User agent, headers and client request ID of every HTTP client of the run
+    http: HttpOptions,
+    /// Graph client of the run, its one rate limiter pacing every Graph request
+    graph: GraphClient,
+    /// Signing keys of the run, shared by all of its validators
+    keys: KeyStore,
+    /// Tokens and assertions that failed to decode or validate
+    failures: AtomicUsize,
+}
+
+impl Run {
+    fn new(args: Cli) -> Self {
+        let policy = args
+            .allow_warnings
+            .iter()
+            .fold(WarningPolicy::new(), |policy, code| policy.allow(code))
+            .deny_warnings(args.deny_warnings);
+        let http = http_options(&args);
+        let graph = GraphClient::with_client(http.client())
+            .with_rate_limiter(RateLimiter::new(args.graph_rps));
+        let keys = key_store(&args, &http);
+        Self {
+            warnings: Warnings::new(policy),
+            http,
+            graph,
+            keys,
+            args,
+            failures: AtomicUsize::new(0),
+        }
+    }
+
+    /// Counts a token or assertion that failed to decode or validate
+    fn fail(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Fails if a token failed validation, or warnings were reported with --deny-warnings
+    fn finish(&self) -> Result<()> {
+        match self.failures.load(Ordering::Relaxed) {
+            0 => self.warnings.check(),
+            failures => bail!("{} token(s) failed validation", failures),
+        }
+    }
+}
+
+/// Configures a validator from the command line
+fn validator_builder(run: &Run) -> ValidatorBuilder {
+    let args = &run.args;
+    let mut builder = TokenValidator::builder()
+        .tenant(&args.tenant)
+        .validate_exp(!args.skip_expiration)
+        .allowed_algorithms(args.allowed_algorithms.clone())
+        .allowed_clients(args.allowed_clients.clone())
+        .auto_skew(args.auto_skew)
+        .http_client(run.http.client())
+        .key_store(run.keys.clone());
+    for audience in &args.audience {
+        builder = builder.audience(audience);
+    }
+    if let Some(issuer) = &args.issuer {
+        builder = builder.issuer(issuer);
+    }
+    if let Some(at) = args.at {
+        builder = builder.clock(FixedClock(at));
+    }
+    if let Some(host) = &args.adfs {
+        builder = builder.adfs(host);
+    }
+    if let Some(kid) = &args.pin_kid {
+        builder = builder.pin_kid(kid);
+    }
+    if let Some(thumbprint) = &args.pin_thumbprint {
+        builder = builder.pin_thumbprint(thumbprint);
+    }
+    builder
+}
+
+/// Creates a key store, keeping keys in the `--key-cache` if one is given
+fn key_store(args: &Cli, http: &HttpOptions) -> KeyStore {
+    match &args.key_cache {
+        Some(cache) => KeyStore::with_cache(http.client(), DEFAULT_TTL, cache.clone()),
+        None => KeyStore::new(http.client()),
+    }
+}
+
+/// Builds the validator configured on the command line
+fn validator(run: &Run) -> TokenValidator {
+    validator_builder(run).build()
+}
+
+/// Builds the user agent and headers of the run from the command line
+fn http_options(args: &Cli) -> HttpOptions {
+    let mut options = HttpOptions::new();
+    if let Some(user_agent) = &args.user_agent {
+        options = options.user_agent(user_agent);
+    }
+    for (name, value) in &args.header {
+        options = options
+            .header(name, value)
+            .expect("headers are checked by parse_header");
+    }
+    options
+}
+
+/// Gets the tenant for requests without a token to take it from, `common` for `auto`
+fn fixed_tenant(args: &Cli) -> &str {
+    match args.tenant.as_str() {
+        AUTO_TENANT => "common",
+        tenant => tenant,
+    }
+}
+
+/// Builds the display options from the command line
+fn display_options(args: &Cli) -> DisplayOptions {
+    let mode = match (args.summary, args.detailed) {
+        (true, _) => DisplayMode::Summary,
+        (_, true) => DisplayMode::Detailed,
+        _ => DisplayMode::Standard,
+    };
+    DisplayOptions {
+        max_claim_length: (!args.full && !args.detailed).then_some(args.max_claim_length),
+        show_sensitive: args.show_sensitive,
+        mode,
+        now: args.at,
+        timezone: args.timezone,
+    }
+}
+
+/// Parses a point in time given as RFC 3339 (`2024-07-01T12:00:00Z`) or Unix seconds
+fn parse_timestamp(value: &str) -> Result<u64, String> {
+    if let Ok(seconds) = value.parse() {
+        return Ok(seconds);
+    }
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .and_then(|time| u64::try_from(time.timestamp()).ok())
+        .ok_or_else(|| {
+            format!(
+                "'{}' is neither an RFC 3339 timestamp nor Unix seconds",
+                value
+            )
+        })
+}
+
+/// Parses a header given as `NAME:VALUE`
+fn parse_header(value: &str) -> Result<(String, String), String> {
+    let (name, value) = value
+        .split_once(':')
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .filter(|(name, _)| !name.is_empty())
+        .ok_or_else(|| format!("'{}' is not a header of the form NAME:VALUE", value))?;
+    HttpOptions::new()
+        .header(&name, &value)
+        .map_err(|e| e.to_string())?;
+    Ok((name, value))
+}
+
+/// Parses a key cache given as a directory or a Redis URL
+fn parse_key_cache(value: &str) -> Result<Arc<dyn KeyCache>, String> {
+    if value.starts_with("redis://") || value.starts_with("rediss://") {
+        #[cfg(feature = "redis")]
+        return azure_token_validator::token::RedisCache::new(value)
+            .map(|cache| Arc::new(cache) as Arc<dyn KeyCache>)
+            .map_err(|e| format!("invalid Redis URL '{}': {}", value, e));
+        #[cfg(not(feature = "redis"))]
+        return Err(format!(
+            "cannot use '{}': built without the redis feature",
+            value
+        ));
+    }
+    Ok(Arc::new(DiskCache::new(value)))
+}
+
+/// Parses a cloud name such as `public` or `us_government`
+fn parse_cloud(value: &str) -> Result<Cloud, String> {
+    serde_json::from_value(Value::String(value.to_ascii_lowercase())).map_err(|_| {
+        format!(
+            "'{}' is not a cloud (public, us_government or china)",
+            value
+        )
+    })
+}
+
+/// Gets the JWKS of a token and the key of it that verified the token
+///
+/// Tokens without `kid` are matched to a key by their signature.
+async fn verifying_key(
+    validator: &TokenValidator,
+    token: &str,
+    header: &Value,
+    claims: &Claims,
+) -> Option<(Arc<JwksResponse>, Jwk)> {
+    let uri = validator.jwks_uri_for(claims).ok()?;
+    let jwks = validator.get_jwks(&uri).await.ok()?;
+    let jwk = match header["kid"].as_str() {
+        Some(kid) => jwks.find_key(kid),
+        None => serde_json::from_value(header["alg"].clone())
+            .ok()
+            .and_then(|alg| find_signing_key(token, alg, &jwks).ok().flatten()),
+    }?
+    .clone();
+    Some((jwks, jwk))
+}
+
+/// Displays the pinned key a token was signed with, and the warnings about its key
+async fn display_signing_key(
+    validator: &TokenValidator,
+    token: &str,
+    header: &Value,
+    claims: &Claims,
+    run: &Run,
+) {
+    let Some((jwks, jwk)) = verifying_key(validator, token, header, claims).await else {
+        return;
+    };
+    let config = validator.config();
+    let warnings = signing_key_warnings(config, header, &jwks, &jwk);
+
+    if config.pinned_kid.is_some() || config.pinned_thumbprint.is_some() {
+        let thumbprint = jwk
+            .thumbprint()
+            .unwrap_or_else(|| "no certificate".to_string());
+        println!(
+            "✅ Signed with the pinned key '{}' ({})",
+            jwk.kid, thumbprint
+        );
+        let expiring = warnings
+            .iter()
+            .any(|warning| warning.code == "pinned-key-expiring");
+        if let Some(expires_at) = jwk.certificate_expiry().filter(|_| !expiring) {
+            println!(
+                "ℹ️  Its certificate expires {} ({})",
+                run.args.timezone.format_timestamp(expires_at),
+                Claims::format_relative(expires_at, config.clock.now())
+            );
+        }
+    }
+    for warning in warnings {
+        run.warnings.print(warning);
+    }
+}
+
+/// Reads the `[api]` requirements block of a TOML config file
+fn parse_api_config(path: &str) -> Result<ApiRequirements, String> {
+    #[derive(Deserialize)]
+    struct ConfigFile {
+        api: ApiRequirements,
+    }
+
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("cannot read '{}': {}", path, e))?;
+    toml::from_str::<ConfigFile>(&content)
+        .map(|config| config.api)
+        .map_err(|e| format!("invalid config '{}': {}", path, e))
+}
+
+/// Reads a client certificate and computes its `x5t#S256` thumbprint
+fn parse_client_cert(path: &str) -> Result<String, String> {
+    let certificate = std::fs::read(path).map_err(|e| format!("cannot read '{}': {}", path, e))?;
+    certificate_thumbprint(&certificate)
+        .map_err(|e| format!("invalid certificate '{}': {}", path, e))
+}
+
+/// Describes the verdict on the token's binding to the client certificate with `thumbprint`
+fn certificate_binding_check(binding: &CertificateBinding, thumbprint: &str) -> RequirementCheck {
+    let name = "Certificate binding";
+    match binding {
+        CertificateBinding::Bound => RequirementCheck {
+            name,
+            passed: true,
+            detail: format!("bound to the client certificate (x5t#S256 {})", thumbprint),
+        },
+        CertificateBinding::Unbound => RequirementCheck {
+            name,
+            passed: true,
+            detail: "unbound, the token has no cnf.x5t#S256 and is accepted with any certificate"
+                .to_string(),
+        },
+        CertificateBinding::Mismatch { bound } => RequirementCheck {
+            name,
+            passed: false,
+            detail: format!(
+                "mismatch, the token is bound to x5t#S256 {}, the client certificate is {}",
+                bound, thumbprint
+            ),
+        },
+    }
+}
+
+/// Reads the `[[profile]]` blocks of a TOML file
+fn parse_profiles(path: &str) -> Result<ProfileChain, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("cannot read '{}': {}", path, e))?;
+    toml::from_str(&content).map_err(|e| format!("invalid profiles '{}': {}", path, e))
+}
+
+/// Combines the `--api-config` requirements with those given as flags
+fn api_requirements(args: &Cli) -> Option<ApiRequirements> {
+    if args.api_config.is_none() && args.require_auth_context.is_empty() {
+        return None;
+    }
+    let mut requirements = args.api_config.clone().unwrap_or_default();
+    requirements
+        .auth_contexts
+        .extend(args.require_auth_context.iter().cloned());
+    Some(requirements)
+}
+
+/// Reads an app registration manifest exported as JSON
+fn parse_manifest(path: &str) -> Result<AppManifest, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("cannot read '{}': {}", path, e))?;
+    serde_json::from_str(&content).map_err(|e| format!("invalid manifest '{}': {}", path, e))
+}
+
+/// Describes a schema violation with the claim it was found at
+fn schema_violation(violation: &SchemaViolation) -> String {
+    match violation.path.as_str() {
+        "" => violation.message.clone(),
+        path => format!("{}: {}", path, violation.message),
+    }
+}
+
+/// Reads and compiles a JSON Schema for the claims
+fn parse_claims_schema(path: &str) -> Result<ClaimsSchema, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("cannot read '{}': {}", path, e))?;
+    let schema = serde_json::from_str(&content)
+        .map_err(|e| format!("invalid claims schema '{}': {}", path, e))?;
+    ClaimsSchema::new(&schema).map_err(|e| format!("invalid claims schema '{}': {}", path, e))
+}
+
+/// Reads a claim mapping, as YAML for `.yml` and `.yaml` files and as TOML otherwise
+fn parse_claim_mapping(path: &str) -> Result<ClaimMapping, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("cannot read '{}': {}", path, e))?;
+    let yaml = matches!(
+        std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str()),
+        Some("yml" | "yaml")
+    );
+    let mapping = match yaml {
+        true => serde_yaml_ng::from_str(&content).map_err(|e| e.to_string()),
+        false => toml::from_str(&content).map_err(|e| e.to_string()),
+    };
+    mapping.map_err(|e| format!("invalid claim mapping '{}': {}", path, e))
+}
+
+/// Parses an AD FS server given as a host name or URL, e.g. `https://adfs.contoso.com/adfs`
+fn parse_adfs_host(value: &str) -> Result<String, String> {
+    let host = value
+        .split_once("://")
+        .map_or(value, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    match host.is_empty() {
+        true => Err(format!("'{}' does not name an AD FS server", value)),
+        false => Ok(host.to_ascii_lowercase()),
+    }
+}
 
-    // Get token from args or prompt
-    let token = match args.token {
-        Some(t) => t,
-        None => prompt_for_token()?,
+/// Parses a JWS algorithm name such as `RS256`
+fn parse_algorithm(name: &str) -> Result<Algorithm, String> {
+    name.parse()
+        .map_err(|_| format!("unknown signing algorithm '{}'", name))
+}
+
+/// Notes that time-based checks are not evaluated against the current time
+fn print_evaluation_time(args: &Cli) {
+    if let Some(at) = args.at {
+        println!(
+            "ℹ️  Evaluating time-based claims as of {}",
+            args.timezone.format_timestamp(at)
+        );
+    }
+}
+
+/// Warns when the system clock is off by more than the leeway, or says it was corrected
+async fn print_clock_skew(
+    validator: &TokenValidator,
+    claims: &Claims,
+    result: &Result<Claims>,
+    run: &Run,
+) {
+    // Tokens failing the time checks are rejected before the keys are fetched
+    let time_failure = result.as_ref().err().is_some_and(|e| {
+        matches!(
+            e.downcast_ref::<ValidationError>(),
+            Some(ValidationError::Expired { .. } | ValidationError::NotYetValid { .. })
+        )
+    });
+    if time_failure && validator.clock_skew().is_none() {
+        if let Ok(uri) = validator.jwks_uri_for(claims) {
+            let _ = validator.get_jwks(&uri).await;
+        }
+    }
+    let Some(skew) = validator.clock_skew() else {
+        return;
     };
+    let leeway = validator.config().leeway;
+    let direction = if skew > 0 { "ahead of" } else { "behind" };
+    if run.args.auto_skew && skew != 0 {
+        println!(
+            "ℹ️  Corrected exp/nbf checks for a system clock {}s {} Azure AD",
+            skew.unsigned_abs(),
+            direction
+        );
+    } else if skew.unsigned_abs() > leeway {
+        run.warnings.print(
+            Warning::new(
+                "clock-skew",
+                Severity::High,
+                format!(
+                    "System clock is {}s {} Azure AD, more than the {}s leeway: expiry checks are off",
+                    skew.unsigned_abs(),
+                    direction,
+                    leeway
+                ),
+            )
+            .remediation("Synchronize the clock or use --auto-skew"),
+        );
+    }
+}
 
-    // Configure the validator
-    let config = ValidatorConfig {
-        tenant_id: args.tenant,
-        validate_exp: !args.skip_expiration,
-        validate_aud: false, // Always disable audience validation for this tool
-        validate_iss: true,
-        leeway: 300, // 5 minutes
+/// Parses and validates a SAML assertion, displaying the results
+async fn validate_and_display_saml(xml: &str, run: &Run) {
+    let args = &run.args;
+    if args.report.is_some() || args.output_file.is_some() || args.output_url.is_some() {
+        match build_saml_report(xml, run).await {
+            Ok(report) => {
+                if let Some(format) = args.report {
+                    println!("{}", report.render(format));
+                }
+                deliver_report(&report, run).await;
+            }
+            Err(e) => {
+                println!("❌ Failed to parse SAML assertion: {}", e);
+                run.fail();
+            }
+        }
+        return;
+    }
+
+    let assertion = match SamlAssertion::parse(xml) {
+        Ok(assertion) => assertion,
+        Err(e) => {
+            println!("❌ Failed to parse SAML assertion: {}", e);
+            run.fail();
+            return;
+        }
+    };
+
+    display_saml_assertion(&assertion, &display_options(args));
+
+    println!("\n=== Validation Result ===");
+    print_evaluation_time(args);
+    let validator = validator(run);
+    match validator.validate_saml(xml).await {
+        Ok(_) => println!("✅ Assertion signature, audience and issuer are valid"),
+        Err(e) => {
+            println!("❌ Assertion validation failed: {}", e);
+            run.fail();
+        }
+    }
+}
+
+/// Builds the security audit configuration from the command line
+fn audit_config(args: &Cli) -> AuditConfig {
+    let mut config = AuditConfig {
+        allowed_algorithms: args
+            .allowed_algorithms
+            .iter()
+            .map(|alg| format!("{:?}", alg))
+            .collect(),
+        expected_version: args.expected_version.clone(),
+        ..AuditConfig::default()
     };
+    config.trusted_issuer_hosts.extend(args.adfs.clone());
+    config
+}
 
-    let mut validator = TokenValidator::new(config);
+/// Gets the history file selected on the command line
+fn history_path(args: &Cli) -> PathBuf {
+    args.history_file
+        .clone()
+        .unwrap_or_else(history::default_path)
+}
+
+/// Appends a redacted record of a validation to the history when `--save` is set
+fn save_history(token: &str, claims: &Claims, result: &Result<Claims>, args: &Cli) {
+    if !args.save {
+        return;
+    }
+    let entry = HistoryEntry::new(token, claims, result);
+    if let Err(e) = history::append(&history_path(args), &entry) {
+        eprintln!("⚠️  Failed to save validation to history: {:#}", e);
+    }
+}
+
+/// Calls the Graph endpoints selected on the command line (`/me` by default)
+///
+/// Several endpoints are sent as one `$batch` call. Returns the result of each endpoint.
+async fn call_graph(token: &str, run: &Run) -> Result<Vec<(String, Result<Value>)>> {
+    let args = &run.args;
+    let endpoints = match args.endpoint.as_slice() {
+        [] => vec!["me".to_string()],
+        endpoints => endpoints.to_vec(),
+    };
+    let requests: Vec<GraphRequest> = endpoints
+        .iter()
+        .map(|endpoint| graph_request(endpoint, args))
+        .collect();
+
+    let graph = &run.graph;
+    let results = if let [request] = requests.as_slice() {
+        vec![graph.send(token, request).await]
+    } else {
+        graph
+            .batch(token, &requests)
+            .await?
+            .into_iter()
+            .map(|response| response.into_result())
+            .collect()
+    };
+    Ok(endpoints.into_iter().zip(results).collect())
+}
+
+/// Builds the Graph request for an endpoint with the query options from the command line
+fn graph_request(endpoint: &str, args: &Cli) -> GraphRequest {
+    let mut request = GraphRequest::new(endpoint);
+    if !args.select.is_empty() {
+        request = request.select(&args.select);
+    }
+    if let Some(filter) = &args.filter {
+        request = request.filter(filter);
+    }
+    if let Some(top) = args.top {
+        request = request.top(top);
+    }
+    for (name, value) in &args.header {
+        request = request.header(name, value);
+    }
+    request
+}
+
+/// Validates a token against the keys of each `--try-tenants` tenant concurrently
+///
+/// Each tenant requires its own issuer: Azure AD signing keys are shared by
+/// tenants, so only the issuer tells which tenant the token came from.
+/// Results are returned in the order the tenants were given.
+async fn try_tenants(token: &str, run: &Run) -> Vec<(String, Result<Claims>)> {
+    let args = &run.args;
+    let mut tasks = JoinSet::new();
+    for (index, tenant) in args.try_tenants.iter().enumerate() {
+        let validator = validator(run);
+        let token = token.to_string();
+        let tenant = tenant.clone();
+        tasks.spawn(async move {
+            let result = validator.validate_token_for_tenant(&token, &tenant).await;
+            (index, result)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok(result) => results.push(result),
+            Err(e) => eprintln!("⚠️  Tenant validation task failed: {}", e),
+        }
+    }
+    results.sort_by_key(|(index, _)| *index);
+    results
+        .into_iter()
+        .map(|(index, result)| (args.try_tenants[index].clone(), result))
+        .collect()
+}
+
+/// Decodes, audits and validates a token, displaying the results
+async fn validate_and_display(token: &str, run: &Run) {
+    let args = &run.args;
+    if args.report.is_some() || args.output_file.is_some() || args.output_url.is_some() {
+        match build_report(token, run).await {
+            Ok(report) => {
+                if let Some(format) = args.report {
+                    println!("{}", report.render(format));
+                }
+                deliver_report(&report, run).await;
+            }
+            Err(e) => {
+                println!("❌ Failed to decode token: {}", e);
+                run.fail();
+            }
+        }
+        return;
+    }
+
+    // Tokens that are not JWTs can only be checked by the API they were issued for
+    let shape = TokenShape::detect(token);
+    if shape != TokenShape::Jwt {
+        display_token_shape(shape, &run.warnings);
+        if args.test_graph {
+            test_graph(token, None, run).await;
+        } else {
+            println!(
+                "\nℹ️  Check whether Microsoft Graph accepts the token with: azure-token-validator --test-graph <TOKEN>"
+            );
+        }
+        return;
+    }
+
+    let validator = validator(run);
 
     // First decode without validation to display token info
-    match validator.decode_token(&token) {
-        Ok((_, claims)) => {
-            display_token_info(&claims);
+    match validator.decode_token(token) {
+        Ok((header, claims)) => {
+            let options = display_options(args);
+            if options.mode == DisplayMode::Summary {
+                display_token_summary(&claims, validator.config().clock.now(), &options);
+                display_security_audit(&header, &claims, &audit_config(args), &run.warnings);
+            } else {
+                display_token_info(&claims, &options, &run.warnings);
+                display_identity_chain(&claims, &options);
+                display_conditional_access(&claims);
+                display_security_audit(&header, &claims, &audit_config(args), &run.warnings);
+                display_token_size(&TokenSize::measure(token, &claims), &run.warnings);
+            }
 
             println!("\n=== Validation Result ===");
-            match validator.validate_token(&token).await {
-                Ok(_) => println!("✅ Token signature is valid"),
-                Err(e) => println!("❌ Token validation failed: {}", e),
+            print_evaluation_time(args);
+            let result = validate(&validator, token, args).await;
+            save_history(token, &claims, &result, args);
+            print_clock_skew(&validator, &claims, &result, run).await;
+            match result {
+                Ok(_) => {
+                    println!("✅ Token signature is valid");
+                    if args.signature_only {
+                        println!("ℹ️  Only the signature was verified, expiry, issuer, audience and other claims were not checked");
+                    }
+                    display_signing_key(&validator, token, &header, &claims, run).await;
+                }
+                Err(e) => {
+                    println!("❌ Token validation failed: {}", e);
+                    run.fail();
+                    let steps = suggest_fixes(&validator, token, &e).await;
+                    if !steps.is_empty() {
+                        println!("\n=== Suggested next steps ===");
+                        for (number, step) in steps.iter().enumerate() {
+                            println!("{}. {}", number + 1, step);
+                        }
+                    }
+                }
+            }
+
+            if args.explain {
+                display_validation_trace(&validator.explain(token).await);
+            }
+
+            if !args.try_tenants.is_empty() {
+                println!("\n=== Tenant Search ===");
+                let results = try_tenants(token, run).await;
+                for (tenant, result) in &results {
+                    match result {
+                        Ok(_) => println!("✅ {}: issued the token, signature verified", tenant),
+                        Err(e) => println!("❌ {}: {}", tenant, e),
+                    }
+                }
+                let verified: Vec<&str> = results
+                    .iter()
+                    .filter(|(_, result)| result.is_ok())
+                    .map(|(tenant, _)| tenant.as_str())
+                    .collect();
+                match verified.as_slice() {
+                    [] => println!("None of the tenants issued a valid token"),
+                    tenants => println!("Issued by {}", tenants.join(", ")),
+                }
+            }
+
+            if args.resolve_overage && claims.group_overage().is_some() {
+                println!("\n=== Group Memberships ===");
+                match resolve_overage(token, &claims, run).await {
+                    Ok(groups) => {
+                        println!("{} group(s) and directory role(s):", groups.len());
+                        for group in groups {
+                            println!("  - {}", group);
+                        }
+                    }
+                    Err(e) => println!("❌ Failed to resolve groups overage: {}", e),
+                }
+            }
+
+            if let Some(proof) = &args.dpop_proof {
+                println!("\n=== DPoP Proof ===");
+                let now = validator.config().clock.now();
+                display_checks(&verify_dpop_proof(proof, token, &claims, now));
+            }
+
+            if let Some(thumbprint) = &args.client_cert {
+                println!("\n=== Certificate Binding ===");
+                let binding = certificate_binding(&claims, thumbprint);
+                let mark = match binding {
+                    CertificateBinding::Bound => "✅",
+                    CertificateBinding::Unbound => "ℹ️ ",
+                    CertificateBinding::Mismatch { .. } => "❌",
+                };
+                let check = certificate_binding_check(&binding, thumbprint);
+                println!("{} {}: {}", mark, check.name, check.detail);
+            }
+
+            if let Some(id_token) = &args.id_token {
+                println!("\n=== Token Pair ===");
+                match validator.decode_token(id_token) {
+                    Ok((id_header, id_claims)) => {
+                        display_checks(&check_token_pair(&id_header, &id_claims, token, &claims))
+                    }
+                    Err(e) => println!("❌ Failed to decode the ID token: {}", e),
+                }
+            }
+
+            if let Some(requirements) = api_requirements(args) {
+                println!("\n=== API Requirements ===");
+                display_checks(&requirements.check(&claims));
+            }
+
+            if let Some(kind) = args.token_kind {
+                println!("\n=== Token Kind ({}) ===", kind);
+                display_checks(&kind.check(&claims));
+            }
+
+            if let Some(api) = audience::lookup_claim(&claims.aud) {
+                println!("\n=== {} Token ===", api.name);
+                display_checks(&api.check(&claims));
+                if let Some(quirk) = api.quirk {
+                    println!("ℹ️  {}", quirk);
+                }
+            }
+
+            if let Some(chain) = &args.profiles {
+                println!("\n=== Validation Profiles ===");
+                for result in chain.evaluate(&claims) {
+                    let failed: Vec<String> = result
+                        .checks
+                        .iter()
+                        .filter(|check| !check.passed)
+                        .map(|check| format!("{}: {}", check.name, check.detail))
+                        .collect();
+                    match failed.as_slice() {
+                        [] => println!("✅ {}", result.profile.name),
+                        failed => println!("❌ {}: {}", result.profile.name, failed.join("; ")),
+                    }
+                }
+                match chain.first_match(&claims) {
+                    Some(profile) => println!("Matched profile: {}", profile.name),
+                    None => println!("No profile matched"),
+                }
+            }
+
+            if let Some(manifest) = &args.manifest {
+                println!("\n=== App Manifest ===");
+                if let Some(name) = &manifest.display_name {
+                    println!("App: {} ({})", name, manifest.app_id);
+                }
+                display_checks(&manifest.check(&claims));
+            }
+
+            if let Some(schema) = &args.claims_schema {
+                println!("\n=== Claims Schema ===");
+                match schema.check(&claims).as_slice() {
+                    [] => println!("✅ Claims conform to the schema"),
+                    violations => {
+                        for violation in violations {
+                            println!("❌ {}", schema_violation(violation));
+                        }
+                    }
+                }
+            }
+
+            if let Some(mapping) = &args.map {
+                println!("\n=== Mapped Identity ===");
+                let claims = redact::claims(&claims, args.show_sensitive);
+                let mapped = mapping.apply(&claims);
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&mapped).unwrap_or_default()
+                );
             }
 
             // Run Graph API test if requested
             if args.test_graph && claims.token_type() == TokenType::Access {
-                println!("\n=== Graph API Test ===");
-                let graph_client = GraphClient::new();
+                let me = test_graph(token, claims.tenant_id(), run).await;
+                if let Some(me) = me {
+                    println!("\n=== Identity Summary ===");
+                    match run.graph.get_directory_identity(token, Some(me)).await {
+                        Ok(identity) => display_identity_summary(
+                            &identity,
+                            &claims,
+                            args.show_sensitive,
+                            &run.warnings,
+                        ),
+                        Err(e) => display_graph_error(&e, args.include_headers),
+                    }
+                }
+                if let Some(device_id) = claims.extra.get("deviceid").and_then(Value::as_str) {
+                    println!("\n=== Device ===");
+                    match run.graph.get_device(token, device_id).await {
+                        Ok(Some(device)) => display_device(&device, &run.warnings),
+                        Ok(None) => run.warnings.print(Warning::new(
+                            "device-not-found",
+                            Severity::Low,
+                            format!(
+                                "No device with ID {} in the tenant, it may have been deleted",
+                                device_id
+                            ),
+                        )),
+                        Err(e) => println!(
+                            "❌ Failed to look up device {} (needs Device.Read.All): {}",
+                            device_id, e
+                        ),
+                    }
+                }
+            } else if args.test_graph && claims.token_type() != TokenType::Access {
+                println!();
+                run.warnings.print(
+                    Warning::new(
+                        "graph-id-token",
+                        Severity::Medium,
+                        "Cannot test Graph API with an ID token",
+                    )
+                    .remediation("Test an access token issued for Microsoft Graph"),
+                );
+            }
 
-                if let Some(endpoint) = args.endpoint {
-                    match graph_client.call_endpoint(&token, &endpoint).await {
-                        Ok(response) => println!("Graph API response: {}", response),
-                        Err(e) => println!("❌ Graph API test failed: {}", e),
+            if !args.test_graph && !args.test_api {
+                let known = audience::lookup_claim(&claims.aud);
+                if let Some((name, options)) = known.and_then(|k| Some((k.name, k.test_options?))) {
+                    println!(
+                        "\nℹ️  Test this {} token against its API with: azure-token-validator {} <TOKEN>",
+                        name, options
+                    );
+                }
+            }
+
+            if args.test_api {
+                match ResourceApi::detect(&claims) {
+                    Some(api) => {
+                        println!("\n=== {} Test ===", api);
+                        match call_resource_api(token, api, run).await {
+                            Ok(test) => println!("✅ {} (GET {})", test.summary, test.url),
+                            Err(e) => println!("❌ {} test failed: {}", api, e),
+                        }
+                    }
+                    None => {
+                        println!();
+                        run.warnings.print(Warning::new(
+                            "unknown-api",
+                            Severity::Low,
+                            format!(
+                                "Audience {} is not a known Azure API that can be tested",
+                                claims.audience_display()
+                            ),
+                        ));
                     }
+                }
+            }
+        }
+        Err(e) => {
+            println!("❌ Failed to decode token: {}", e);
+            run.fail();
+        }
+    }
+}
+
+/// Calls the Graph endpoints from the command line with the token and displays the responses
+///
+/// Returns the `/me` response if `/me` was called successfully, unredacted.
+async fn test_graph(token: &str, tenant: Option<&str>, run: &Run) -> Option<Value> {
+    let args = &run.args;
+    println!("\n=== Graph API Test ===");
+    let results = match call_graph(token, run).await {
+        Ok(results) => results,
+        Err(e) => {
+            display_graph_error(&e, args.include_headers);
+            return None;
+        }
+    };
+
+    let mut me = None;
+    let batched = results.len() > 1;
+    for (endpoint, result) in results {
+        if batched {
+            println!("\n--- {} ---", endpoint);
+        }
+        match result {
+            Ok(mut response) => {
+                if endpoint.trim_matches('/') == "me" {
+                    me = Some(response.clone());
+                }
+                if !args.show_sensitive {
+                    redact::redact_value(&mut response);
+                }
+                if args.raw {
+                    println!("Graph API response: {}", response);
                 } else {
-                    match graph_client.get_me(&token).await {
-                        Ok(user_info) => println!("Graph API response: {}", user_info),
-                        Err(e) => println!("❌ Graph API test failed: {}", e),
+                    let resource = GraphResource::from_value(&response);
+                    display_graph_response(&resource, tenant);
+                }
+            }
+            Err(e) => display_graph_error(&e, args.include_headers),
+        }
+    }
+    me
+}
+
+/// Gets the tenant to request tokens from, falling back to $AZURE_TENANT_ID
+fn acquisition_tenant(args: &Cli) -> String {
+    match args.tenant.as_str() {
+        "common" | AUTO_TENANT => {
+            std::env::var("AZURE_TENANT_ID").unwrap_or_else(|_| args.tenant.clone())
+        }
+        tenant => tenant.to_string(),
+    }
+}
+
+/// Fetches the group memberships left out of a token with a groups overage
+async fn resolve_overage(token: &str, claims: &Claims, run: &Run) -> Result<Vec<String>> {
+    let user = claims
+        .object_id()
+        .context("Token has no oid claim to look up the user")?;
+    run.graph.get_member_objects(token, user).await
+}
+
+/// Calls a cheap read endpoint of the API a token was issued for
+async fn call_resource_api(token: &str, api: ResourceApi, run: &Run) -> Result<ResourceTest> {
+    let args = &run.args;
+    let client = ResourceClient::with_client(run.http.client());
+    match api {
+        ResourceApi::Graph => {
+            let me = run.graph.get_me(token).await?;
+            Ok(ResourceTest {
+                url: "https://graph.microsoft.com/v1.0/me".to_string(),
+                status: 200,
+                summary: format!("Signed in as {}", me["displayName"].as_str().unwrap_or("-")),
+            })
+        }
+        ResourceApi::ResourceManager => client.test_resource_manager(token).await,
+        ResourceApi::KeyVault => {
+            let vault = args
+                .vault
+                .as_deref()
+                .context("--vault is required to test Key Vault tokens")?;
+            client.test_key_vault(token, vault).await
+        }
+        ResourceApi::Storage => {
+            let account = args
+                .storage_account
+                .as_deref()
+                .context("--storage-account is required to test Storage tokens")?;
+            client.test_storage(token, account).await
+        }
+    }
+}
+
+/// Writes the report to the file and webhook given on the command line
+///
+/// Progress goes to stderr so a report printed with --report stays parseable.
+async fn deliver_report(report: &Report, run: &Run) {
+    let args = &run.args;
+    if let Some(path) = &args.output_file {
+        let format = args.report.unwrap_or(ReportFormat::Json);
+        match report::write_atomic(path, &report.render(format)) {
+            Ok(()) => eprintln!("ℹ️  Report written to {}", path.display()),
+            Err(e) => eprintln!("❌ {:#}", e),
+        }
+    }
+    if let Some(url) = &args.output_url {
+        match report::post_webhook(&run.http.client(), url, report).await {
+            Ok(()) => eprintln!(
+                "ℹ️  Report posted to {}",
+                url.host_str().unwrap_or_default()
+            ),
+            Err(e) => eprintln!("❌ Failed to post the report: {:#}", e),
+        }
+    }
+}
+
+/// Runs the same checks as [`validate_and_display`] and collects the results in a report
+async fn build_report(token: &str, run: &Run) -> Result<Report> {
+    let args = &run.args;
+    let validator = validator(run);
+    let (header, claims) = validator.decode_token(token)?;
+    let size = TokenSize::measure(token, &claims);
+    let mut findings = audit_token(&header, &claims, &audit_config(args));
+    findings.extend(size.warnings());
+
+    let mut checks = vec![Check {
+        name: "Token decoded",
+        passed: true,
+        detail: String::new(),
+    }];
+
+    let mut detail = String::new();
+    if let Some(at) = args.at {
+        detail = format!("evaluated as of {}", args.timezone.format_timestamp(at));
+    }
+    let result = validate(&validator, token, args).await;
+    save_history(token, &claims, &result, args);
+    let name = match args.signature_only {
+        true => "Signature valid (claims not checked)",
+        false => "Signature and claims valid",
+    };
+    match result {
+        Ok(_) => {
+            if let Some((jwks, jwk)) = verifying_key(&validator, token, &header, &claims).await {
+                findings.extend(signing_key_warnings(
+                    validator.config(),
+                    &header,
+                    &jwks,
+                    &jwk,
+                ));
+            }
+            checks.push(Check {
+                name,
+                passed: true,
+                detail,
+            });
+        }
+        Err(e) => {
+            run.fail();
+            checks.push(Check {
+                name,
+                passed: false,
+                detail: e.to_string(),
+            });
+        }
+    }
+    let findings = run.warnings.filter(findings);
+
+    if let Some(proof) = &args.dpop_proof {
+        checks.extend(
+            verify_dpop_proof(proof, token, &claims, validator.config().clock.now())
+                .into_iter()
+                .map(Check::from),
+        );
+    }
+
+    if let Some(thumbprint) = &args.client_cert {
+        let binding = certificate_binding(&claims, thumbprint);
+        checks.push(certificate_binding_check(&binding, thumbprint).into());
+    }
+
+    if let Some(id_token) = &args.id_token {
+        match validator.decode_token(id_token) {
+            Ok((id_header, id_claims)) => checks.extend(
+                check_token_pair(&id_header, &id_claims, token, &claims)
+                    .into_iter()
+                    .map(Check::from),
+            ),
+            Err(e) => checks.push(Check {
+                name: "ID token decoded",
+                passed: false,
+                detail: e.to_string(),
+            }),
+        }
+    }
+
+    if let Some(requirements) = api_requirements(args) {
+        checks.extend(requirements.check(&claims).into_iter().map(Check::from));
+    }
+
+    if let Some(kind) = args.token_kind {
+        checks.extend(kind.check(&claims).into_iter().map(Check::from));
+    }
+
+    if let Some(api) = audience::lookup_claim(&claims.aud) {
+        checks.extend(api.check(&claims).into_iter().map(Check::from));
+    }
+
+    if let Some(chain) = &args.profiles {
+        let matched = chain.first_match(&claims);
+        checks.push(Check {
+            name: "Validation profile matched",
+            passed: matched.is_some(),
+            detail: matched.map_or_else(String::new, |profile| profile.name.clone()),
+        });
+    }
+
+    if let Some(manifest) = &args.manifest {
+        checks.extend(manifest.check(&claims).into_iter().map(Check::from));
+    }
+
+    if let Some(schema) = &args.claims_schema {
+        let violations = schema.check(&claims);
+        checks.push(Check {
+            name: "Claims conform to the schema",
+            passed: violations.is_empty(),
+            detail: violations
+                .iter()
+                .map(schema_violation)
+                .collect::<Vec<_>>()
+                .join("; "),
+        });
+    }
+
+    let size_warnings = size.warnings();
+    checks.push(Check {
+        name: "Token size within limits",
+        passed: size_warnings.is_empty(),
+        detail: size_warnings
+            .iter()
+            .map(|warning| warning.message.as_str())
+            .collect::<Vec<_>>()
+            .join("; "),
+    });
+
+    checks.push(Check {
+        name: "No warnings",
+        passed: findings.is_empty(),
+        detail: match findings.len() {
+            0 => String::new(),
+            count => format!("{} finding(s)", count),
+        },
+    });
+
+    let mut graph_response = None;
+    if args.test_graph && claims.token_type() != TokenType::Access {
+        checks.push(Check {
+            name: "Graph API call succeeded",
+            passed: false,
+            detail: "Cannot test Graph API with an ID token".to_string(),
+        });
+    } else if args.test_graph {
+        match call_graph(token, run).await {
+            Ok(results) => {
+                let mut responses = serde_json::Map::new();
+                for (endpoint, result) in results {
+                    checks.push(match result {
+                        Ok(mut response) => {
+                            if !args.show_sensitive {
+                                redact::redact_value(&mut response);
+                            }
+                            responses.insert(endpoint.clone(), response);
+                            Check {
+                                name: "Graph API call succeeded",
+                                passed: true,
+                                detail: endpoint,
+                            }
+                        }
+                        Err(e) => Check {
+                            name: "Graph API call succeeded",
+                            passed: false,
+                            detail: format!("{}: {}", endpoint, e),
+                        },
+                    });
+                }
+                // A single response is included as is, several are keyed by endpoint
+                graph_response = match responses.len() {
+                    0 => None,
+                    1 if args.endpoint.len() <= 1 => responses.into_iter().next().map(|(_, v)| v),
+                    _ => Some(Value::Object(responses)),
+                };
+            }
+            Err(e) => checks.push(Check {
+                name: "Graph API call succeeded",
+                passed: false,
+                detail: e.to_string(),
+            }),
+        }
+    }
+
+    if args.test_api {
+        checks.push(match ResourceApi::detect(&claims) {
+            Some(api) => match call_resource_api(token, api, run).await {
+                Ok(test) => Check {
+                    name: "Resource API call succeeded",
+                    passed: true,
+                    detail: format!("{}: {}", api, test.summary),
+                },
+                Err(e) => Check {
+                    name: "Resource API call succeeded",
+                    passed: false,
+                    detail: format!("{}: {}", api, e),
+                },
+            },
+            None => Check {
+                name: "Resource API call succeeded",
+                passed: false,
+                detail: format!(
+                    "Audience {} is not a known Azure API",
+                    claims.audience_display()
+                ),
+            },
+        });
+    }
+
+    let trace = match args.explain {
+        true => Some(validator.explain(token).await),
+        false => None,
+    };
+
+    let redacted = redact::claims(&claims, args.show_sensitive);
+    Ok(Report {
+        generated_at: chrono::Utc::now()
+            .format("%Y-%m-%d %H:%M:%S UTC")
+            .to_string(),
+        tenant: resolve_tenant(&args.tenant, &claims).to_string(),
+        header,
+        mapped_identity: args.map.as_ref().map(|mapping| mapping.apply(&redacted)),
+        claims: redacted.into_owned(),
+        checks,
+        warnings: findings,
+        graph_response,
+        trace,
+        timezone: args.timezone,
+    })
+}
+
+/// Validates a SAML assertion and collects the results in a report like [`build_report`]
+///
+/// The assertion is reported as its claims, see [`SamlAssertion::to_claims`].
+async fn build_saml_report(xml: &str, run: &Run) -> Result<Report> {
+    let args = &run.args;
+    let assertion = SamlAssertion::parse(xml)?;
+    let claims = assertion.to_claims()?;
+    // The signature is only ever verified as RSA-SHA256
+    let header = match assertion.signed {
+        true => serde_json::json!({ "typ": "SAML2", "alg": "RS256" }),
+        false => serde_json::json!({ "typ": "SAML2" }),
+    };
+
+    let mut checks = vec![Check {
+        name: "Assertion parsed",
+        passed: true,
+        detail: String::new(),
+    }];
+    let mut detail = String::new();
+    if let Some(at) = args.at {
+        detail = format!("evaluated as of {}", args.timezone.format_timestamp(at));
+    }
+    let name = "Signature, conditions, audience and issuer valid";
+    checks.push(match validator(run).validate_saml(xml).await {
+        Ok(_) => Check {
+            name,
+            passed: true,
+            detail,
+        },
+        Err(e) => {
+            run.fail();
+            Check {
+                name,
+                passed: false,
+                detail: e.to_string(),
+            }
+        }
+    });
+
+    let redacted = redact::claims(&claims, args.show_sensitive);
+    Ok(Report {
+        generated_at: chrono::Utc::now()
+            .format("%Y-%m-%d %H:%M:%S UTC")
+            .to_string(),
+        tenant: resolve_tenant(&args.tenant, &claims).to_string(),
+        header,
+        mapped_identity: args.map.as_ref().map(|mapping| mapping.apply(&redacted)),
+        claims: redacted.into_owned(),
+        checks,
+        warnings: Vec::new(),
+        graph_response: None,
+        trace: None,
+        timezone: args.timezone,
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let run = Run::new(Cli::parse());
+    let args = &run.args;
+    // Azure AD and Graph log the ID, so it finds the requests of a run in their logs
+    if !args.command.as_ref().is_some_and(Command::is_offline) {
+        eprintln!("ℹ️  client-request-id: {}", run.http.client_request_id());
+    }
+    // Nothing else installs a logger, so this only fails if called twice
+    if args.verbose && log::set_logger(&StderrLogger).is_ok() {
+        log::set_max_level(log::LevelFilter::Debug);
+    }
+    // Flushes the remaining spans when main returns, also on errors
+    let _tracing = args.otlp_endpoint.as_ref().map(otel::install).transpose()?;
+
+    match &args.command {
+        Some(Command::InspectRaw { token }) => {
+            let token = match token {
+                Some(t) => t.clone(),
+                None => prompt_for_token()?,
+            };
+            display_raw_inspection(&inspect_raw(&token), &display_options(args));
+        }
+        Some(Command::Explore { token }) => {
+            let token = match token {
+                Some(t) => extract_input(t),
+                None => extract_input(&prompt_for_token()?),
+            };
+            let (_, claims) = validator(&run).decode_token(&token)?;
+            let claims = redact::claims(&claims, args.show_sensitive);
+            explore::explore(&ClaimTree::new(serde_json::to_value(&*claims)?))?;
+        }
+        Some(Command::Anonymize { token }) => {
+            let token = match token {
+                Some(t) => extract_input(t),
+                None => extract_input(&prompt_for_token()?),
+            };
+            display_anonymized(&anonymize::anonymize(&token)?);
+        }
+        Some(Command::Obo {
+            token,
+            client_id,
+            client_secret,
+            scope,
+        }) => {
+            let token = match token {
+                Some(t) => extract_input(t),
+                None => extract_input(&prompt_for_token()?),
+            };
+
+            println!("\n=== Incoming Token ===");
+            validate_and_display(&token, &run).await;
+
+            let credentials = ClientCredentials {
+                client_id: client_id.clone(),
+                client_secret: client_secret.clone(),
+            };
+            // The middle-tier app redeems the token in the tenant it was issued by
+            let tenant = match validator(&run).decode_token(&token) {
+                Ok((_, claims)) => resolve_tenant(&args.tenant, &claims).to_string(),
+                Err(_) => fixed_tenant(args).to_string(),
+            };
+            let endpoint_client = TokenEndpointClient::with_client(&tenant, run.http.client());
+            match endpoint_client
+                .on_behalf_of(&credentials, &token, scope)
+                .await
+            {
+                Ok(response) => {
+                    display_token_response(&response);
+                    if let Some(access_token) = &response.access_token {
+                        println!("\n=== Downstream Token ===");
+                        validate_and_display(access_token, &run).await;
                     }
                 }
-            } else if args.test_graph && claims.token_type() != TokenType::Access {
-                println!("\n⚠️  Warning: Cannot test Graph API with an ID token. You need an access token.");
+                Err(e) => display_token_error(&e),
+            }
+        }
+        Some(Command::Refresh {
+            refresh_token,
+            client_id,
+            client_secret,
+            scope,
+        }) => {
+            let refresh_token = match refresh_token {
+                Some(t) => t.clone(),
+                None => prompt_for_token()?,
+            };
+
+            let endpoint_client =
+                TokenEndpointClient::with_client(fixed_tenant(args), run.http.client());
+            match endpoint_client
+                .refresh(client_id, client_secret.as_deref(), &refresh_token, scope)
+                .await
+            {
+                Ok(response) => validate_token_response(&response, &run).await,
+                Err(e) => display_token_error(&e),
+            }
+        }
+        Some(Command::Acquire {
+            managed_identity,
+            federated_token_file,
+            client_credentials,
+            client_secret_file,
+            device_code,
+            azure_cli,
+            resource,
+            client_id,
+        }) => {
+            let app_id = || {
+                client_id
+                    .clone()
+                    .context("--client-id (or AZURE_CLIENT_ID) is required for this method")
+            };
+            let endpoint_client =
+                || TokenEndpointClient::with_client(acquisition_tenant(args), run.http.client());
+            let provider: Box<dyn AcquireToken> = if *managed_identity {
+                Box::new(ManagedIdentity::new(client_id.clone())?)
+            } else if let Some(path) = federated_token_file {
+                let assertion = std::fs::read_to_string(path).with_context(|| {
+                    format!("Failed to read federated token file {}", path.display())
+                })?;
+                display_federated_token(assertion.trim(), args.timezone, &run.warnings);
+                Box::new(FederatedCredential::new(endpoint_client(), app_id()?, path))
+            } else if *client_credentials {
+                let client_secret = match client_secret_file {
+                    Some(path) => std::fs::read_to_string(path)
+                        .with_context(|| {
+                            format!("Failed to read client secret file {}", path.display())
+                        })?
+                        .trim()
+                        .to_string(),
+                    None => std::env::var("AZURE_CLIENT_SECRET").context(
+                        "--client-credentials needs the secret in AZURE_CLIENT_SECRET or --client-secret-file",
+                    )?,
+                };
+                let credentials = ClientCredentials {
+                    client_id: app_id()?,
+                    client_secret,
+                };
+                Box::new(ClientSecret::new(endpoint_client(), credentials))
+            } else if *device_code {
+                Box::new(DeviceCodeFlow::new(endpoint_client(), app_id()?, |code| {
+                    println!("\n{}", code.message)
+                }))
+            } else if *azure_cli {
+                match args.tenant.as_str() {
+                    "common" | AUTO_TENANT => Box::new(AzureCli::new()),
+                    tenant => Box::new(AzureCli::with_tenant(tenant)),
+                }
+            } else {
+                unreachable!("clap requires an acquisition method")
+            };
+            println!("Requesting token from {}", provider);
+
+            match provider.acquire_token(resource).await {
+                Ok(token) => {
+                    display_acquired_token(&token, args.timezone);
+                    validate_and_display(&token.access_token, &run).await;
+                }
+                Err(e) => display_token_error(&e),
+            }
+        }
+        Some(Command::Check { spec: path, json }) => {
+            let spec = check::read_spec(path)?;
+            let report = check::run(
+                path,
+                &spec,
+                &validator(&run),
+                &acquisition_tenant(args),
+                &run.http.client(),
+                args.timezone,
+            )
+            .await;
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&report)?),
+                false => display_check_report(&report),
+            }
+            let failed = report.steps.iter().filter(|step| !step.passed).count();
+            if failed > 0 {
+                bail!("{} of {} check step(s) failed", failed, report.steps.len());
+            }
+        }
+        Some(Command::Batch {
+            file,
+            export,
+            stats,
+        }) => {
+            // Fail on an unknown export format before validating anything
+            let export = match export {
+                Some(path) => Some((path, ExportFormat::from_path(path)?)),
+                None => None,
+            };
+            let tokens = batch::read_tokens(file)?;
+            // Validators of a run share its key store, so each key set is fetched once
+            let validator = validator(&run);
+
+            println!("\n=== Batch Validation ===");
+            let mut records = Vec::with_capacity(tokens.len());
+            for (line, token) in &tokens {
+                let record = batch::validate_record(&validator, *line, token, args.timezone).await;
+                match &record.error {
+                    None => println!(
+                        "✅ Line {}: {} ({})",
+                        line,
+                        record.subject.as_deref().unwrap_or_default(),
+                        record.audience.as_deref().unwrap_or_default()
+                    ),
+                    Some(error) => println!("❌ Line {}: {}", line, error),
+                }
+                records.push(record);
+            }
+
+            let valid = records.iter().filter(|r| r.verdict == "valid").count();
+            println!("\n{} of {} tokens are valid", valid, records.len());
+            if *stats {
+                display_batch_stats(&records, &validator.key_store().stats());
+            }
+
+            if let Some((path, format)) = export {
+                batch::export(path, format, &records)?;
+                println!("ℹ️  Results written to {}", path.display());
+            }
+        }
+        Some(Command::Monitor {
+            env,
+            file,
+            managed_identity,
+            client_id,
+            interval,
+            min_remaining,
+            once,
+        }) => {
+            let sources = env
+                .iter()
+                .map(|name| TokenSource::Env(name.clone()))
+                .chain(file.iter().map(|path| TokenSource::File(path.clone())))
+                .chain(
+                    managed_identity
+                        .iter()
+                        .map(|resource| TokenSource::ManagedIdentity {
+                            resource: resource.clone(),
+                            client_id: client_id.clone(),
+                        }),
+                )
+                .collect();
+            let options = MonitorOptions {
+                sources,
+                interval: Duration::from_secs((*interval).max(1)),
+                min_remaining: Duration::from_secs(*min_remaining),
+                once: *once,
+                webhook: args.output_url.clone(),
+                client: run.http.client(),
+                timezone: args.timezone,
+            };
+            monitor::monitor(&validator(&run), &options).await?
+        }
+        Some(Command::Scan {
+            path,
+            jobs,
+            no_ignore,
+        }) => {
+            let options = ScanOptions {
+                jobs: jobs.unwrap_or_else(|| {
+                    std::thread::available_parallelism().map_or(1, |jobs| jobs.get())
+                }),
+                respect_ignores: !no_ignore,
+            };
+            let summary = scan::scan(path, &validator(&run), &options)?;
+            display_scan_findings(&summary);
+        }
+        Some(Command::History { show, limit }) => {
+            let entries = history::load(&history_path(args))?;
+            match show {
+                Some(number) => {
+                    let entry = number
+                        .checked_sub(1)
+                        .and_then(|index| entries.get(index))
+                        .with_context(|| {
+                            format!("No history entry {} ({} saved)", number, entries.len())
+                        })?;
+                    display_history_entry(*number, entry, args.timezone);
+                }
+                None => display_history(&entries, *limit),
+            }
+        }
+        Some(Command::VerifyConfig {
+            token,
+            client_id,
+            cloud,
+        }) => {
+            let token = match token {
+                Some(t) => extract_input(t),
+                None => extract_input(&prompt_for_token()?),
+            };
+            let (header, claims) = validator(&run).decode_token(&token)?;
+            let tenant = resolve_tenant(&args.tenant, &claims);
+
+            println!("\n=== App Registration ===");
+            let metadata = MetadataClient::with_client(run.http.client())
+                .app_metadata(*cloud, tenant, client_id)
+                .await?;
+            display_app_metadata(&metadata);
+
+            println!("\n=== Configuration Checks ===");
+            let checks = metadata.check(&header, &claims);
+            display_checks(&checks);
+            let failed = checks.iter().filter(|check| !check.passed).count();
+            match failed {
+                0 => println!("\nThe token matches the app registration"),
+                n => println!("\n{} of {} checks found a mismatch", n, checks.len()),
+            }
+        }
+        Some(Command::TenantInfo { tenant, cloud }) => {
+            let info = MetadataClient::with_client(run.http.client())
+                .tenant_info(*cloud, tenant)
+                .await?;
+            display_tenant_info(&info);
+        }
+        Some(Command::Keys {
+            tenants,
+            cloud,
+            rps,
+            output,
+        }) => {
+            let tenants = match tenants {
+                Some(path) => census::read_tenants(path)?,
+                None => vec![fixed_tenant(args).to_string()],
+            };
+            let records =
+                census::census(&tenants, *cloud, &run.keys, &RateLimiter::new(*rps)).await;
+
+            let now = validator(&run).config().clock.now();
+            let csv = census::to_csv(&records, now, args.timezone);
+            match output {
+                Some(path) => std::fs::write(path, csv)
+                    .with_context(|| format!("Failed to write {}", path.display()))?,
+                None => print!("{}", csv),
+            }
+            let failed = records
+                .iter()
+                .filter(|record| record.error.is_some())
+                .count();
+            eprintln!(
+                "ℹ️  {} key(s) of {} tenant(s), {} expiring within 30 days, {} tenant(s) failed",
+                records.len() - failed,
+                tenants.len(),
+                census::expiring(&records, now),
+                failed
+            );
+        }
+        Some(Command::SelfUpdate {
+            channel,
+            feed,
+            public_key,
+            insecure_skip_signature,
+            check,
+        }) => {
+            let current = update::current_version();
+            let client = run.http.client();
+            println!("=== Self Update ===");
+            let update = match update::check(&client, feed, *channel).await? {
+                Some(update) if update.version > current => update,
+                Some(update) => {
+                    println!(
+                        "✅ {} is up to date, the newest {} release is {}",
+                        current, channel, update.version
+                    );
+                    return Ok(());
+                }
+                None => {
+                    println!(
+                        "⚠️  No {} release provides {}",
+                        channel,
+                        update::asset_name()
+                    );
+                    return Ok(());
+                }
+            };
+            println!("ℹ️  Update available: {} → {}", current, update.version);
+            if *check {
+                return Ok(());
+            }
+
+            let public_key = match (public_key, insecure_skip_signature) {
+                (_, true) => None,
+                (Some(path), false) => Some(update::read_public_key(path)?),
+                (None, false) => Some(update::release_key()?),
+            };
+            let binary = update::download(&client, &update, public_key.as_ref()).await?;
+            println!("✅ Checksum verified");
+            match public_key {
+                Some(_) => println!("✅ Signature verified"),
+                None => println!(
+                    "⚠️  Signature not verified (--insecure-skip-signature), the binary is trusted as served by {}",
+                    feed
+                ),
+            }
+            let path = update::replace_executable(&binary)?;
+            println!("✅ Updated {} to {}", path.display(), update.version);
+        }
+        Some(Command::Probe { attempts }) => {
+            let tenant = fixed_tenant(args);
+            let endpoints = [
+                format!(
+                    "https://login.microsoftonline.com/{}/v2.0/.well-known/openid-configuration",
+                    tenant
+                ),
+                jwks_uri(tenant, AzureTokenFormat::V1),
+                jwks_uri(tenant, AzureTokenFormat::V2),
+            ];
+
+            for endpoint in &endpoints {
+                display_endpoint_probe(&probe_endpoint(endpoint, *attempts).await);
+            }
+        }
+        Some(Command::Doctor) => {
+            let data_dir = history_path(args)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            let leeway = validator(&run).config().leeway;
+            display_diagnoses(
+                &doctor::diagnose(fixed_tenant(args), leeway, &data_dir, &run.http).await,
+            );
+        }
+        Some(Command::Explain { claim }) => match dictionary::lookup(claim) {
+            Some(info) => display_claim_info(info),
+            None => {
+                let similar: Vec<&str> = dictionary::similar(claim)
+                    .iter()
+                    .map(|info| info.name)
+                    .collect();
+                match similar.as_slice() {
+                    [] => bail!("'{}' is not in the claims dictionary", claim),
+                    _ => bail!(
+                        "'{}' is not in the claims dictionary, did you mean {}?",
+                        claim,
+                        similar.join(", ")
+                    ),
+                }
+            }
+        },
+        Some(Command::Serve {
+            listen,
+            header_tenants,
+        }) => {
+            // Tokens name their tenant with --tenant auto, there is nothing to prefetch
+            let prefetch = match args.tenant.as_str() {
+                AUTO_TENANT => Vec::new(),
+                tenant => vec![
+                    jwks_uri(tenant, AzureTokenFormat::V1),
+                    jwks_uri(tenant, AzureTokenFormat::V2),
+                ],
+            };
+            serve::serve(*listen, validator(&run), &prefetch, header_tenants.clone()).await?
+        }
+        None if args.stdin_stream => batch::stream(&validator(&run), args.timezone).await?,
+        None => {
+            // Get token from args or prompt
+            let token = match (&args.token, &args.from_k8s_secret) {
+                (Some(t), _) => extract_input(t),
+                (None, Some(secret)) => k8s::read_token(secret).await?,
+                (None, None) => extract_input(&prompt_for_token()?),
+            };
+
+            // The JSON response of the token endpoint, as copied from a browser or curl
+            if token.starts_with('{') {
+                let response: TokenResponse = serde_json::from_str(&token)
+                    .context("JSON input is not a token endpoint response")?;
+                validate_token_response(&response, &run).await;
+                return run.finish();
+            }
+            match decode_saml_input(&token) {
+                Some(xml) => validate_and_display_saml(&xml, &run).await,
+                None => validate_and_display(&token, &run).await,
             }
         }
-        Err(e) => println!("❌ Failed to decode token: {}", e),
     }
 
-    Ok(())
+    run.finish()
 }
@@ -0,0 +1,139 @@
+//! Tower middleware that authenticates requests with Azure AD bearer tokens
+//!
+//! [`AuthLayer`] extracts the token from the `Authorization` header,
+//! validates it with a shared [`TokenValidator`] and inserts the verified
+//! [`Claims`] into the request extensions. Works with any tower-based
+//! framework, e.g. axum handlers can take `Extension<Claims>`.
+
+use http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use http::{HeaderValue, Request, Response, StatusCode};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::token::{bearer_token, Claims, TokenValidator};
+
+pub use crate::auth::AuthError;
+
+type FailureHandler<B> = Arc<dyn Fn(AuthError) -> Response<B> + Send + Sync>;
+
+/// Layer that validates bearer tokens before passing requests on
+pub struct AuthLayer<B> {
+    validator: Arc<TokenValidator>,
+    on_failure: FailureHandler<B>,
+}
+
+impl<B: Default + 'static> AuthLayer<B> {
+    /// Creates a layer that rejects unauthenticated requests with `401 Unauthorized`
+    pub fn new(validator: Arc<TokenValidator>) -> Self {
+        AuthLayer {
+            validator,
+            on_failure: Arc::new(unauthorized),
+        }
+    }
+}
+
+impl<B> AuthLayer<B> {
+    /// Sets the function building the response for rejected requests
+    pub fn on_failure<F>(mut self, on_failure: F) -> Self
+    where
+        F: Fn(AuthError) -> Response<B> + Send + Sync + 'static,
+    {
+        self.on_failure = Arc::new(on_failure);
+        self
+    }
+}
+
+impl<B> Clone for AuthLayer<B> {
+    fn clone(&self) -> Self {
+        AuthLayer {
+            validator: self.validator.clone(),
+            on_failure: self.on_failure.clone(),
+        }
+    }
+}
+
+impl<S, B> Layer<S> for AuthLayer<B> {
+    type Service = AuthService<S, B>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            validator: self.validator.clone(),
+            on_failure: self.on_failure.clone(),
+        }
+    }
+}
+
+/// Service created by [`AuthLayer`]
+pub struct AuthService<S, B> {
+    inner: S,
+    validator: Arc<TokenValidator>,
+    on_failure: FailureHandler<B>,
+}
+
+impl<S: Clone, B> Clone for AuthService<S, B> {
+    fn clone(&self) -> Self {
+        AuthService {
+            inner: self.inner.clone(),
+            validator: self.validator.clone(),
+            on_failure: self.on_failure.clone(),
+        }
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for AuthService<S, ResBody>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        // Use the service that was driven to readiness and leave a fresh clone behind
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let validator = self.validator.clone();
+        let on_failure = self.on_failure.clone();
+
+        Box::pin(async move {
+            let header = request
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok());
+            let Some(token) = header.and_then(bearer_token).map(str::to_string) else {
+                return Ok(on_failure(AuthError::MissingToken));
+            };
+
+            match validator.validate_token(&token).await {
+                Ok(claims) => {
+                    request.extensions_mut().insert::<Claims>(claims);
+                    inner.call(request).await
+                }
+                Err(e) => Ok(on_failure(AuthError::InvalidToken(e))),
+            }
+        })
+    }
+}
+
+/// Default failure response as described in RFC 6750
+fn unauthorized<B: Default>(error: AuthError) -> Response<B> {
+    let mut response = Response::new(B::default());
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response.headers_mut().insert(
+        WWW_AUTHENTICATE,
+        HeaderValue::from_static(error.challenge()),
+    );
+    response
+}
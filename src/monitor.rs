@@ -0,0 +1,187 @@
+//! Periodic health checks of the tokens a pipeline depends on
+//!
+//! Tokens are given by reference and loaded again for every check, so a
+//! rotated file or a freshly acquired managed identity token is picked up.
+//! An alert is raised when a token fails validation or has less than the
+//! configured lifetime left, and again once it recovers.
+
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use serde_json::json;
+use std::fmt;
+use std::mem;
+use std::path::PathBuf;
+use std::time::Duration;
+use url::Url;
+
+use azure_token_validator::api::{AcquireToken, ManagedIdentity};
+use azure_token_validator::token::{failure_code, Timezone, TokenValidator};
+
+use crate::report;
+use crate::scan::format_remaining;
+
+/// Where a monitored token is loaded from
+#[derive(Debug, Clone)]
+pub enum TokenSource {
+    /// Environment variable holding the token
+    Env(String),
+    /// File holding the token, e.g. a projected service account token
+    File(PathBuf),
+    /// Token acquired from the managed identity of the host for a resource
+    ManagedIdentity {
+        resource: String,
+        client_id: Option<String>,
+    },
+}
+
+impl fmt::Display for TokenSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenSource::Env(name) => write!(f, "env:{}", name),
+            TokenSource::File(path) => write!(f, "file:{}", path.display()),
+            TokenSource::ManagedIdentity { resource, .. } => {
+                write!(f, "managed-identity:{}", resource)
+            }
+        }
+    }
+}
+
+impl TokenSource {
+    async fn load(&self) -> Result<String> {
+        match self {
+            TokenSource::Env(name) => {
+                std::env::var(name).with_context(|| format!("{} is not set", name))
+            }
+            TokenSource::File(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display())),
+            TokenSource::ManagedIdentity {
+                resource,
+                client_id,
+            } => {
+                let provider = ManagedIdentity::new(client_id.clone())?;
+                Ok(provider.acquire_token(resource).await?.access_token)
+            }
+        }
+        .map(|token| token.trim().to_string())
+    }
+}
+
+/// Outcome of checking one token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Health {
+    /// Valid with enough lifetime left, in seconds
+    Healthy(i64),
+    /// Valid, but with less than the threshold left, in seconds
+    Expiring(i64),
+    /// Could not be loaded or failed validation
+    Failing(String),
+}
+
+impl Health {
+    /// Returns whether the token needs attention
+    pub fn is_alert(&self) -> bool {
+        !matches!(self, Health::Healthy(_))
+    }
+}
+
+impl fmt::Display for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Health::Healthy(remaining) => write!(f, "valid, {} left", format_remaining(*remaining)),
+            Health::Expiring(remaining) => {
+                write!(f, "valid, but only {} left", format_remaining(*remaining))
+            }
+            Health::Failing(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Settings of a monitoring run
+#[derive(Debug, Clone)]
+pub struct MonitorOptions {
+    pub sources: Vec<TokenSource>,
+    /// Time between checks
+    pub interval: Duration,
+    /// Remaining lifetime below which a token is reported as expiring
+    pub min_remaining: Duration,
+    /// Check once and fail if any token needs attention, e.g. when run from cron
+    pub once: bool,
+    /// Webhook alerts and recoveries are posted to
+    pub webhook: Option<Url>,
+    /// Client the webhook is called with
+    pub client: Client,
+    /// Time zone the time of each check is displayed in
+    pub timezone: Timezone,
+}
+
+/// Checks the tokens on a schedule until stopped, or once with `options.once`
+pub async fn monitor(validator: &TokenValidator, options: &MonitorOptions) -> Result<()> {
+    let client = &options.client;
+    let mut previous: Vec<Option<Health>> = vec![None; options.sources.len()];
+    let mut ticker = tokio::time::interval(options.interval);
+
+    loop {
+        ticker.tick().await;
+        let checked_at = options
+            .timezone
+            .format_timestamp(validator.config().clock.now());
+        for (source, previous) in options.sources.iter().zip(previous.iter_mut()) {
+            let health = check(source, validator, options.min_remaining).await;
+            let mark = match &health {
+                Health::Healthy(_) => "✅",
+                Health::Expiring(_) => "⚠️ ",
+                Health::Failing(_) => "❌",
+            };
+            println!("[{}] {} {}: {}", checked_at, mark, source, health);
+
+            // Alert when the state of a token changes, not on every check
+            let changed = match previous {
+                Some(previous) => mem::discriminant(previous) != mem::discriminant(&health),
+                None => health.is_alert(),
+            };
+            if changed {
+                if let Some(url) = &options.webhook {
+                    let text = match health.is_alert() {
+                        true => format!("{} {}: {}", mark, source, health),
+                        false => format!("✅ {} recovered: {}", source, health),
+                    };
+                    let payload = json!({ "text": text, "source": source.to_string() });
+                    if let Err(e) = report::post_json(client, url, &payload).await {
+                        eprintln!("❌ Failed to post the alert: {:#}", e);
+                    }
+                }
+            }
+            *previous = Some(health);
+        }
+
+        if options.once {
+            let alerts = previous.iter().flatten().filter(|h| h.is_alert()).count();
+            if alerts > 0 {
+                bail!("{} of {} token(s) need attention", alerts, previous.len());
+            }
+            return Ok(());
+        }
+    }
+}
+
+async fn check(
+    source: &TokenSource,
+    validator: &TokenValidator,
+    min_remaining: Duration,
+) -> Health {
+    let token = match source.load().await {
+        Ok(token) => token,
+        Err(e) => return Health::Failing(format!("{:#}", e)),
+    };
+    match validator.validate_token(&token).await {
+        Ok(claims) => {
+            let remaining = claims.exp as i64 - validator.config().clock.now() as i64;
+            if remaining < min_remaining.as_secs() as i64 {
+                Health::Expiring(remaining)
+            } else {
+                Health::Healthy(remaining)
+            }
+        }
+        Err(e) => Health::Failing(format!("{} ({})", e, failure_code(&e))),
+    }
+}
@@ -0,0 +1,42 @@
+use anyhow::{Context, Result};
+use azure_token_validator::token::spans::TRACER_NAME;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use url::Url;
+
+/// Exports the spans of this run until dropped, then flushes the remaining ones
+pub struct Tracing {
+    provider: SdkTracerProvider,
+}
+
+/// Installs a global tracer provider exporting spans over OTLP/HTTP to `endpoint`
+///
+/// The endpoint is the full traces URL of the collector, e.g.
+/// `http://localhost:4318/v1/traces`.
+pub fn install(endpoint: &Url) -> Result<Tracing> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint.as_str())
+        .build()
+        .context("Failed to create the OTLP span exporter")?;
+    let provider = SdkTracerProvider::builder()
+        .with_resource(
+            Resource::builder()
+                .with_service_name(env!("CARGO_PKG_NAME"))
+                .build(),
+        )
+        .with_batch_exporter(exporter)
+        .build();
+    opentelemetry::global::set_tracer_provider(provider.clone());
+    log::debug!("Exporting {} spans to {}", TRACER_NAME, endpoint);
+    Ok(Tracing { provider })
+}
+
+impl Drop for Tracing {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("⚠️  Could not export all spans: {}", e);
+        }
+    }
+}
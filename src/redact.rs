@@ -0,0 +1,103 @@
+use serde_json::Value;
+use std::borrow::Cow;
+
+use azure_token_validator::token::{Claims, SamlAssertion};
+
+/// Opaque claims that are masked entirely
+const OPAQUE_CLAIMS: &[&str] = &["aio", "uti"];
+
+/// Claims identifying the user, masked like emails or entirely
+const IDENTITY_CLAIMS: &[&str] = &["email", "upn", "unique_name", "preferred_username"];
+
+/// Replacement for masked values
+pub const MASK: &str = "[REDACTED]";
+
+/// Masks personal data and replayable values in the claims unless `show_sensitive` is set
+pub fn claims(claims: &Claims, show_sensitive: bool) -> Cow<'_, Claims> {
+    if show_sensitive {
+        return Cow::Borrowed(claims);
+    }
+
+    let Ok(mut value) = serde_json::to_value(claims) else {
+        return Cow::Borrowed(claims);
+    };
+    redact_value(&mut value);
+    serde_json::from_value(value).map_or(Cow::Borrowed(claims), Cow::Owned)
+}
+
+/// Masks the subject and attribute values of a SAML assertion unless `show_sensitive` is set
+pub fn saml(assertion: &SamlAssertion, show_sensitive: bool) -> Cow<'_, SamlAssertion> {
+    if show_sensitive {
+        return Cow::Borrowed(assertion);
+    }
+
+    let mut assertion = assertion.clone();
+    assertion.subject = assertion.subject.as_deref().map(redact_text);
+    for attribute in &mut assertion.attributes {
+        for value in &mut attribute.values {
+            *value = redact_text(value);
+        }
+    }
+    Cow::Owned(assertion)
+}
+
+/// Masks sensitive claims, email addresses and JWTs anywhere in a JSON value
+pub fn redact_value(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.as_str();
+                match value {
+                    Value::String(text) if OPAQUE_CLAIMS.contains(&key) => *text = MASK.to_string(),
+                    Value::String(text) if IDENTITY_CLAIMS.contains(&key) => {
+                        *text = mask_email(text).unwrap_or_else(|| MASK.to_string())
+                    }
+                    _ => redact_value(value),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_value),
+        Value::String(text) => *text = redact_text(text),
+        _ => {}
+    }
+}
+
+/// Masks email addresses and JWTs in free text
+///
+/// Emails keep their first character and domain (`j***@contoso.com`) so the
+/// tenant of an account can still be told apart.
+pub fn redact_text(text: &str) -> String {
+    text.split_inclusive(char::is_whitespace)
+        .map(|word| {
+            let trimmed = word.trim_end();
+            let core = trimmed.trim_matches(|c: char| "\"'()<>[],;".contains(c));
+            if is_jwt(core) {
+                word.replacen(core, MASK, 1)
+            } else if let Some(masked) = mask_email(core) {
+                word.replacen(core, &masked, 1)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect()
+}
+
+fn mask_email(text: &str) -> Option<String> {
+    let (local, domain) = text.rsplit_once('@')?;
+    let first = local.chars().next()?;
+    if !domain.contains('.') || domain.starts_with('.') || domain.ends_with('.') {
+        return None;
+    }
+    Some(format!("{}***@{}", first, domain))
+}
+
+fn is_jwt(text: &str) -> bool {
+    let segments: Vec<&str> = text.split('.').collect();
+    segments.len() == 3
+        && text.starts_with("eyJ")
+        && segments.iter().all(|segment| {
+            segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
@@ -0,0 +1,338 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use url::Url;
+
+use azure_token_validator::token::{Claims, RequirementCheck, RuleEvaluation, Timezone, Warning};
+
+/// Claims holding Unix timestamps, rendered as dates in reports
+const TIMESTAMP_CLAIMS: &[&str] = &["exp", "iat", "nbf", "auth_time"];
+
+/// Formats of shareable validation reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+    Json,
+}
+
+/// Outcome of one check of the validation checklist
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl From<RequirementCheck> for Check {
+    fn from(check: RequirementCheck) -> Self {
+        Check {
+            name: check.name,
+            passed: check.passed,
+            detail: check.detail,
+        }
+    }
+}
+
+/// Everything known about one validated token, ready to be rendered
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub generated_at: String,
+    pub tenant: String,
+    pub header: Value,
+    pub claims: Claims,
+    pub checks: Vec<Check>,
+    pub warnings: Vec<Warning>,
+    /// Claims translated by a `--map` file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mapped_identity: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graph_response: Option<Value>,
+    /// Every validation rule evaluated, with `--explain`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Vec<RuleEvaluation>>,
+    /// Time zone timestamp claims are displayed in
+    #[serde(skip)]
+    pub timezone: Timezone,
+}
+
+impl Report {
+    /// Renders the report in the given format
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.markdown(),
+            ReportFormat::Html => self.html(),
+            ReportFormat::Json => serde_json::to_string_pretty(self)
+                .unwrap_or_else(|e| json!(e.to_string()).to_string()),
+        }
+    }
+
+    /// Summarizes the outcome in one line, e.g. for chat notifications
+    pub fn summary(&self) -> String {
+        let failed: Vec<&str> = self
+            .checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| check.name)
+            .collect();
+        let token = format!(
+            "{} token for {} in tenant {}",
+            self.token_type(),
+            self.claims.audience_display(),
+            self.tenant
+        );
+        match failed.as_slice() {
+            [] => format!("✅ {} passed all {} checks", token, self.checks.len()),
+            failed => format!("❌ {} failed: {}", token, failed.join(", ")),
+        }
+    }
+
+    /// Gets the kind of token reported, SAML assertions are marked by their `typ`
+    fn token_type(&self) -> String {
+        match self.header["typ"].as_str() {
+            Some("SAML2") => "SAML 2.0 assertion".to_string(),
+            _ => self.claims.token_type().to_string(),
+        }
+    }
+
+    /// Gets the claims as (name, display value) rows
+    fn claim_rows(&self) -> Vec<(String, String)> {
+        let Ok(Value::Object(claims)) = serde_json::to_value(&self.claims) else {
+            return Vec::new();
+        };
+
+        claims
+            .into_iter()
+            .map(|(key, value)| {
+                let display = match (&value, value.as_u64()) {
+                    (_, Some(timestamp)) if TIMESTAMP_CLAIMS.contains(&key.as_str()) => {
+                        format!(
+                            "{} ({})",
+                            self.timezone.format_timestamp(timestamp),
+                            timestamp
+                        )
+                    }
+                    (Value::String(text), _) => text.clone(),
+                    _ => value.to_string(),
+                };
+                (key, display)
+            })
+            .collect()
+    }
+
+    fn markdown(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# Token Validation Report\n\n");
+        out.push_str(&format!("- **Generated:** {}\n", self.generated_at));
+        out.push_str(&format!(
+            "- **Tenant:** {}\n",
+            markdown_escape(&self.tenant)
+        ));
+        out.push_str(&format!("- **Token type:** {}\n", self.token_type()));
+        out.push_str(&format!(
+            "- **Algorithm:** {}\n",
+            self.header["alg"].as_str().unwrap_or("-")
+        ));
+
+        out.push_str("\n## Validation Checklist\n\n");
+        for check in &self.checks {
+            let mark = if check.passed { "x" } else { " " };
+            out.push_str(&format!("- [{}] **{}**", mark, check.name));
+            if !check.detail.is_empty() {
+                out.push_str(&format!(": {}", markdown_escape(&check.detail)));
+            }
+            out.push('\n');
+        }
+
+        out.push_str("\n## Warnings\n\n");
+        if self.warnings.is_empty() {
+            out.push_str("No warnings.\n");
+        } else {
+            out.push_str("| Severity | Code | Finding | Remediation |\n|---|---|---|---|\n");
+            for warning in &self.warnings {
+                out.push_str(&format!(
+                    "| {} | `{}` | {} | {} |\n",
+                    warning.severity,
+                    warning.code,
+                    markdown_escape(&warning.message),
+                    markdown_escape(warning.remediation.as_deref().unwrap_or_default())
+                ));
+            }
+        }
+
+        out.push_str("\n## Claims\n\n| Claim | Value |\n|---|---|\n");
+        for (key, value) in self.claim_rows() {
+            out.push_str(&format!(
+                "| `{}` | {} |\n",
+                markdown_escape(&key),
+                markdown_escape(&value)
+            ));
+        }
+
+        if let Some(mapped) = &self.mapped_identity {
+            out.push_str("\n## Mapped Identity\n\n```json\n");
+            out.push_str(&serde_json::to_string_pretty(mapped).unwrap_or_default());
+            out.push_str("\n```\n");
+        }
+
+        if let Some(response) = &self.graph_response {
+            out.push_str("\n## Graph API Response\n\n```json\n");
+            out.push_str(&serde_json::to_string_pretty(response).unwrap_or_default());
+            out.push_str("\n```\n");
+        }
+        out
+    }
+
+    fn html(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        out.push_str("<title>Token Validation Report</title>\n<style>\n");
+        out.push_str("body { font-family: sans-serif; margin: 2em; }\n");
+        out.push_str("table { border-collapse: collapse; }\n");
+        out.push_str("th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }\n");
+        out.push_str("td { font-family: monospace; word-break: break-all; }\n");
+        out.push_str(".pass { color: #1a7f37; } .fail { color: #cf222e; }\n");
+        out.push_str("</style>\n</head>\n<body>\n<h1>Token Validation Report</h1>\n<ul>\n");
+        out.push_str(&format!(
+            "<li><b>Generated:</b> {}</li>\n",
+            html_escape(&self.generated_at)
+        ));
+        out.push_str(&format!(
+            "<li><b>Tenant:</b> {}</li>\n",
+            html_escape(&self.tenant)
+        ));
+        out.push_str(&format!(
+            "<li><b>Token type:</b> {}</li>\n",
+            self.token_type()
+        ));
+        out.push_str(&format!(
+            "<li><b>Algorithm:</b> {}</li>\n</ul>\n",
+            html_escape(self.header["alg"].as_str().unwrap_or("-"))
+        ));
+
+        out.push_str("<h2>Validation Checklist</h2>\n<ul>\n");
+        for check in &self.checks {
+            let (class, mark) = if check.passed {
+                ("pass", "&#10004;")
+            } else {
+                ("fail", "&#10008;")
+            };
+            out.push_str(&format!(
+                "<li class=\"{}\">{} <b>{}</b>",
+                class, mark, check.name
+            ));
+            if !check.detail.is_empty() {
+                out.push_str(&format!(": {}", html_escape(&check.detail)));
+            }
+            out.push_str("</li>\n");
+        }
+        out.push_str("</ul>\n");
+
+        out.push_str("<h2>Warnings</h2>\n");
+        if self.warnings.is_empty() {
+            out.push_str("<p>No warnings.</p>\n");
+        } else {
+            out.push_str(
+                "<table>\n<tr><th>Severity</th><th>Code</th><th>Finding</th><th>Remediation</th></tr>\n",
+            );
+            for warning in &self.warnings {
+                out.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    warning.severity,
+                    warning.code,
+                    html_escape(&warning.message),
+                    html_escape(warning.remediation.as_deref().unwrap_or_default())
+                ));
+            }
+            out.push_str("</table>\n");
+        }
+
+        out.push_str("<h2>Claims</h2>\n<table>\n<tr><th>Claim</th><th>Value</th></tr>\n");
+        for (key, value) in self.claim_rows() {
+            out.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td></tr>\n",
+                html_escape(&key),
+                html_escape(&value)
+            ));
+        }
+        out.push_str("</table>\n");
+
+        if let Some(mapped) = &self.mapped_identity {
+            out.push_str("<h2>Mapped Identity</h2>\n<pre>");
+            out.push_str(&html_escape(
+                &serde_json::to_string_pretty(mapped).unwrap_or_default(),
+            ));
+            out.push_str("</pre>\n");
+        }
+
+        if let Some(response) = &self.graph_response {
+            out.push_str("<h2>Graph API Response</h2>\n<pre>");
+            out.push_str(&html_escape(
+                &serde_json::to_string_pretty(response).unwrap_or_default(),
+            ));
+            out.push_str("</pre>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+/// Escapes characters that would break a Markdown table cell
+fn markdown_escape(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Writes a rendered report to `path`, replacing it atomically
+///
+/// The report is written to a temporary file next to `path` first, so
+/// readers never see a partially written report.
+pub fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let name = path
+        .file_name()
+        .with_context(|| format!("{} is not a file path", path.display()))?;
+    let temporary = path.with_file_name(format!(".{}.tmp", name.to_string_lossy()));
+    fs::write(&temporary, content)
+        .with_context(|| format!("Failed to write {}", temporary.display()))?;
+    fs::rename(&temporary, path).with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+/// Posts the report as JSON to a webhook
+///
+/// The summary is sent as `text`, which Slack and Teams incoming webhooks
+/// display; other receivers get the full report alongside it.
+pub async fn post_webhook(client: &Client, url: &Url, report: &Report) -> Result<()> {
+    let mut payload = serde_json::to_value(report)?;
+    payload["text"] = Value::from(report.summary());
+    post_json(client, url, &payload).await
+}
+
+/// Posts a JSON payload to a webhook
+///
+/// Errors leave out the URL, whose path is the webhook's secret.
+pub async fn post_json(client: &Client, url: &Url, payload: &Value) -> Result<()> {
+    let response = client
+        .post(url.clone())
+        .json(payload)
+        .send()
+        .await
+        .map_err(reqwest::Error::without_url)?;
+    if !response.status().is_success() {
+        bail!(
+            "Webhook at {} answered {}",
+            url.host_str().unwrap_or_default(),
+            response.status()
+        );
+    }
+    Ok(())
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,151 @@
+//! Search files for embedded tokens
+//!
+//! Tokens pasted into configuration files, scripts or logs remain usable by
+//! anyone who reads them until they expire. `scan` finds them and reports
+//! which are still live.
+
+use anyhow::{Context, Result};
+use ignore::{WalkBuilder, WalkState};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use azure_token_validator::token::TokenValidator;
+
+use crate::batch::find_tokens;
+
+/// A token found in a file
+#[derive(Debug)]
+pub struct Finding {
+    pub path: PathBuf,
+    /// 1-based line number
+    pub line: usize,
+    pub subject: String,
+    pub audience: String,
+    /// Seconds until the token expires, negative once it has expired
+    pub remaining: i64,
+}
+
+impl Finding {
+    /// Returns whether the token has not expired yet
+    pub fn is_live(&self) -> bool {
+        self.remaining > 0
+    }
+}
+
+/// Bytes looked at to tell binary files from text, as git does
+const BINARY_PROBE: usize = 8000;
+
+/// Options of a scan
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Number of files scanned in parallel
+    pub jobs: usize,
+    /// Skip files excluded by `.gitignore`, `.ignore` and the global git excludes
+    pub respect_ignores: bool,
+}
+
+/// Results of a scan
+#[derive(Debug, Default)]
+pub struct ScanSummary {
+    pub findings: Vec<Finding>,
+    /// Number of text files scanned
+    pub files: usize,
+    /// Number of binary files skipped
+    pub binary: usize,
+}
+
+/// Scans a file, or every file below a directory, for tokens that decode
+///
+/// Strings that look like JWTs but do not decode are ignored. Hidden files
+/// such as `.env` are scanned, the `.git` directory is not.
+pub fn scan(root: &Path, validator: &TokenValidator, options: &ScanOptions) -> Result<ScanSummary> {
+    fs::metadata(root).with_context(|| format!("Failed to read {}", root.display()))?;
+
+    let now = validator.config().clock.now() as i64;
+    let summary = Mutex::new(ScanSummary::default());
+    WalkBuilder::new(root)
+        .threads(options.jobs)
+        .hidden(false)
+        .git_ignore(options.respect_ignores)
+        .git_global(options.respect_ignores)
+        .git_exclude(options.respect_ignores)
+        .ignore(options.respect_ignores)
+        // Apply .gitignore files outside of git repositories too
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build_parallel()
+        .run(|| {
+            Box::new(|entry| {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        log::warn!("Skipping entry: {}", e);
+                        return WalkState::Continue;
+                    }
+                };
+                if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+                    return WalkState::Continue;
+                }
+
+                let path = entry.path();
+                let bytes = match fs::read(path) {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        log::warn!("Skipping {}: {}", path.display(), e);
+                        return WalkState::Continue;
+                    }
+                };
+                if bytes[..bytes.len().min(BINARY_PROBE)].contains(&0) {
+                    summary
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .binary += 1;
+                    return WalkState::Continue;
+                }
+
+                let findings = scan_text(path, &String::from_utf8_lossy(&bytes), validator, now);
+                let mut summary = summary.lock().unwrap_or_else(PoisonError::into_inner);
+                summary.files += 1;
+                summary.findings.extend(findings);
+                WalkState::Continue
+            })
+        });
+
+    let mut summary = summary.into_inner().unwrap_or_else(PoisonError::into_inner);
+    // Files finish in any order across threads
+    summary
+        .findings
+        .sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+    Ok(summary)
+}
+
+fn scan_text(path: &Path, content: &str, validator: &TokenValidator, now: i64) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        for token in find_tokens(line) {
+            let Ok((_, claims)) = validator.decode_token(token) else {
+                continue;
+            };
+            findings.push(Finding {
+                path: path.to_path_buf(),
+                line: index + 1,
+                subject: claims.sub.clone(),
+                audience: claims.audience_display(),
+                remaining: claims.exp as i64 - now,
+            });
+        }
+    }
+    findings
+}
+
+/// Formats a number of seconds as e.g. `2d 3h`, `5h 12m` or `42m`
+pub fn format_remaining(seconds: i64) -> String {
+    let seconds = seconds.unsigned_abs();
+    let (days, hours, minutes) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60);
+    match (days, hours) {
+        (0, 0) => format!("{}m", minutes),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
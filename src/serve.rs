@@ -0,0 +1,361 @@
+//! HTTP sidecar validating bearer tokens on behalf of a reverse proxy
+//!
+//! `/validate` answers the subrequests of nginx's `auth_request` or
+//! Traefik's ForwardAuth: `200 OK` with the verified claims when the
+//! `Authorization` header carries a valid token, `401 Unauthorized`
+//! otherwise. For proxies in front of multi-tenant APIs, an
+//! `X-Expected-Tenant` header can require the token to be issued by one of
+//! the tenants allowed with `--header-tenant`; it is ignored otherwise.
+//! `/metrics` exposes the validation and JWKS metrics in the Prometheus
+//! exposition format. `/events` is a WebSocket streaming the outcome of
+//! every validation, redacted, to watch traffic while load testing.
+
+use anyhow::{bail, Context, Result};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use serde::Serialize;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use azure_token_validator::token::{
+    bearer_token, failure_code, telemetry, Claims, TokenValidator, ValidatorConfig, AUTO_TENANT,
+};
+
+use crate::redact;
+
+/// Header naming the tenant a request's token must be issued by
+const EXPECTED_TENANT: &str = "x-expected-tenant";
+
+/// HTTP requests handled by the sidecar by `path` and `status`
+const HTTP_REQUESTS: &str = "azure_token_validator_http_requests_total";
+
+/// How often histograms of the Prometheus recorder are compacted
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long before their expiry cached signing keys are fetched again
+const REFRESH_AHEAD: Duration = Duration::from_secs(60 * 60);
+
+/// Events buffered for each `/events` subscriber; slower subscribers skip the oldest
+const EVENT_BUFFER: usize = 1024;
+
+struct AppState {
+    validator: TokenValidator,
+    /// Tenant whose issuer tokens must carry when no issuer is configured
+    tenant: Option<String>,
+    /// Tenants an `X-Expected-Tenant` header may name, the header is ignored if empty
+    header_tenants: Vec<String>,
+    metrics: PrometheusHandle,
+    /// Validation events as JSON, redacted
+    events: broadcast::Sender<String>,
+}
+
+/// Outcome of one validation as streamed to `/events`
+#[derive(Debug, Serialize)]
+struct ValidationEvent {
+    /// Unix seconds the token was validated at
+    time: u64,
+    valid: bool,
+    /// Failure code, e.g. `expired`, or `missing_token` without a bearer token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+    /// Taken from the unverified claims when validation failed
+    tenant: Option<String>,
+    audience: Option<String>,
+    client: Option<String>,
+    /// Masked to the first character and the domain
+    upn: Option<String>,
+    duration_ms: u128,
+}
+
+impl ValidationEvent {
+    fn new(
+        time: u64,
+        reason: Option<&'static str>,
+        claims: Option<&Claims>,
+        started: Instant,
+    ) -> Self {
+        ValidationEvent {
+            time,
+            valid: reason.is_none(),
+            reason,
+            tenant: claims.and_then(|claims| claims.tenant_id().map(str::to_string)),
+            audience: claims.map(Claims::audience_display),
+            client: claims.and_then(|claims| claims.authorized_party().map(str::to_string)),
+            upn: claims.and_then(|claims| {
+                claims
+                    .user_principal_name()
+                    .or(claims.preferred_username.as_deref())
+                    .map(str::to_string)
+            }),
+            duration_ms: started.elapsed().as_millis(),
+        }
+    }
+}
+
+/// Refuses configurations under which the sidecar would let anyone through
+///
+/// Without an audience any API's tokens pass, and without a fixed tenant or
+/// an issuer each token's own issuer is accepted. Returns the tenant whose
+/// issuer tokens must carry when no issuer is configured.
+fn required_tenant(config: &ValidatorConfig) -> Result<Option<String>> {
+    if !config.validate_aud || config.audiences.is_empty() {
+        bail!("The sidecar needs an --audience, otherwise it accepts tokens issued for any API");
+    }
+    if !config.validate_iss {
+        bail!("The sidecar needs issuer validation, otherwise it accepts tokens of any tenant");
+    }
+    match (&config.issuer, config.tenant_id.as_str()) {
+        (Some(_), _) => Ok(None),
+        (None, tenant) if tenant != AUTO_TENANT && uuid::Uuid::parse_str(tenant).is_ok() => {
+            Ok(Some(tenant.to_string()))
+        }
+        (None, _) => bail!(
+            "The sidecar needs a fixed --tenant ID or an --issuer, otherwise it accepts tokens of any tenant"
+        ),
+    }
+}
+
+/// Checks the tenants an `X-Expected-Tenant` header may name
+///
+/// Each must be a tenant ID. A configured issuer is never replaced by the
+/// issuer of a tenant a request names.
+fn check_header_tenants(config: &ValidatorConfig, tenants: &[String]) -> Result<()> {
+    if tenants.is_empty() {
+        return Ok(());
+    }
+    if config.issuer.is_some() {
+        bail!("--header-tenant cannot be combined with --issuer, the configured issuer always applies");
+    }
+    if let Some(tenant) = tenants
+        .iter()
+        .find(|tenant| uuid::Uuid::parse_str(tenant).is_err())
+    {
+        bail!("--header-tenant needs tenant IDs, '{}' is not one", tenant);
+    }
+    Ok(())
+}
+
+/// Serves the sidecar endpoints on `addr` until the process is stopped
+///
+/// Fails before listening if the configuration would accept tokens of any
+/// tenant or for any API, see [`required_tenant`]. An `X-Expected-Tenant`
+/// header is only honored for the tenants of `header_tenants`. The keys of
+/// `prefetch` are fetched before listening, and all cached keys are refreshed
+/// ahead of their expiry so no request waits for a fetch.
+pub async fn serve(
+    addr: SocketAddr,
+    validator: TokenValidator,
+    prefetch: &[String],
+    header_tenants: Vec<String>,
+) -> Result<()> {
+    let tenant = required_tenant(validator.config())?;
+    check_header_tenants(validator.config(), &header_tenants)?;
+    let metrics = PrometheusBuilder::new()
+        .install_recorder()
+        .context("Failed to install the Prometheus recorder")?;
+    telemetry::describe();
+    metrics::describe_counter!(HTTP_REQUESTS, "HTTP requests handled by the sidecar");
+
+    let upkeep = metrics.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(UPKEEP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+
+    for uri in prefetch {
+        match validator.get_jwks(uri).await {
+            Ok(_) => println!("✅ Fetched signing keys from {}", uri),
+            Err(e) => println!("⚠️  Could not prefetch signing keys from {}: {}", uri, e),
+        }
+    }
+    let _refresh = validator.key_store().spawn_refresh_ahead(REFRESH_AHEAD);
+
+    let (events, _) = broadcast::channel(EVENT_BUFFER);
+    let state = Arc::new(AppState {
+        validator,
+        tenant,
+        header_tenants,
+        metrics,
+        events,
+    });
+    let app = Router::new()
+        .route("/validate", get(validate).post(validate))
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/metrics", get(render_metrics))
+        .route("/events", get(stream_events))
+        .layer(middleware::from_fn(count_requests))
+        .with_state(state);
+
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen on {}", addr))?;
+    println!("ℹ️  Listening on http://{}", listener.local_addr()?);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+/// Validates the bearer token of the request
+async fn validate(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    let started = Instant::now();
+    let now = state.validator.config().clock.now();
+    let header = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    let Some(token) = header.and_then(bearer_token) else {
+        publish(
+            &state,
+            ValidationEvent::new(now, Some("missing_token"), None, started),
+        );
+        return unauthorized("Bearer", json!({ "error": "Missing bearer token" }));
+    };
+
+    // Callers can set the header themselves, so it is only honored for allowed tenants
+    let requested_tenant = headers
+        .get(EXPECTED_TENANT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|tenant| !tenant.is_empty() && !state.header_tenants.is_empty());
+    let expected_tenant = match requested_tenant {
+        Some(tenant)
+            if state
+                .header_tenants
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(tenant)) =>
+        {
+            Some(tenant)
+        }
+        Some(tenant) => {
+            let claims = state
+                .validator
+                .decode_token(token)
+                .ok()
+                .map(|(_, claims)| claims);
+            let event =
+                ValidationEvent::new(now, Some("tenant_not_allowed"), claims.as_ref(), started);
+            publish(&state, event);
+            return unauthorized(
+                "Bearer error=\"invalid_token\"",
+                json!({
+                    "error": format!("Tenant '{}' is not allowed with --header-tenant", tenant),
+                    "reason": "tenant_not_allowed",
+                }),
+            );
+        }
+        None => state.tenant.as_deref(),
+    };
+    let result = match expected_tenant {
+        Some(tenant) => {
+            state
+                .validator
+                .validate_token_for_tenant(token, tenant)
+                .await
+        }
+        None => state.validator.validate_token(token).await,
+    };
+    match result {
+        Ok(claims) => {
+            publish(
+                &state,
+                ValidationEvent::new(now, None, Some(&claims), started),
+            );
+            Json(claims).into_response()
+        }
+        Err(e) => {
+            let claims = state
+                .validator
+                .decode_token(token)
+                .ok()
+                .map(|(_, claims)| claims);
+            let event = ValidationEvent::new(now, Some(failure_code(&e)), claims.as_ref(), started);
+            publish(&state, event);
+            unauthorized(
+                "Bearer error=\"invalid_token\"",
+                json!({ "error": format!("{:#}", e), "reason": failure_code(&e) }),
+            )
+        }
+    }
+}
+
+/// Sends an event to the `/events` subscribers, if there are any
+fn publish(state: &AppState, event: ValidationEvent) {
+    if state.events.receiver_count() == 0 {
+        return;
+    }
+    let Ok(mut value) = serde_json::to_value(&event) else {
+        return;
+    };
+    redact::redact_value(&mut value);
+    // Fails only when the last subscriber has just left
+    let _ = state.events.send(value.to_string());
+}
+
+/// Upgrades to a WebSocket streaming validation events
+async fn stream_events(State(state): State<Arc<AppState>>, upgrade: WebSocketUpgrade) -> Response {
+    let events = state.events.subscribe();
+    upgrade.on_upgrade(move |socket| send_events(socket, events))
+}
+
+/// Sends each event as a text message until the client disconnects
+async fn send_events(mut socket: WebSocket, mut events: broadcast::Receiver<String>) {
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let message = match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(skipped)) => json!({ "skipped": skipped }).to_string(),
+                    Err(RecvError::Closed) => return,
+                };
+                if socket.send(Message::Text(message.into())).await.is_err() {
+                    return;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+                // Pings are answered by the WebSocket implementation
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn render_metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [("content-type", "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
+
+/// Counts requests by matched route and response status
+async fn count_requests(request: Request, next: Next) -> Response {
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched".to_string(), |path| path.as_str().to_string());
+    let response = next.run(request).await;
+    let status = response.status().as_u16().to_string();
+    metrics::counter!(HTTP_REQUESTS, "path" => path, "status" => status).increment(1);
+    response
+}
+
+fn unauthorized(challenge: &'static str, body: serde_json::Value) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(WWW_AUTHENTICATE, challenge)],
+        Json(body),
+    )
+        .into_response()
+}
@@ -0,0 +1,283 @@
+//! Fixture keys, tokens and a mock key provider for tests without network access
+//!
+//! The fixture tokens are issued at [`ISSUED_AT`] and signed with the
+//! [`TestKey::primary`] key, so a validator using [`clock`] and a
+//! [`MockKeyProvider`] validates them deterministically. The private keys
+//! are public, never trust them outside of tests.
+
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+#[cfg(feature = "net")]
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(feature = "net")]
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::token::jwk::{Jwk, JwksResponse};
+#[cfg(feature = "net")]
+use crate::token::provider::KeysFuture;
+use crate::token::{Claims, ClaimsBuilder, FixedClock};
+#[cfg(feature = "net")]
+use crate::token::{KeyProvider, TokenValidator};
+
+/// Tenant the fixture tokens are issued by
+pub const TENANT_ID: &str = "72f988bf-86f1-41af-91ab-2d7cd011db47";
+/// Name of the Azure AD B2C tenant of the B2C fixture token
+pub const B2C_TENANT: &str = "contoso";
+/// Client ID of the application the v2 and B2C fixture tokens are issued for
+pub const CLIENT_ID: &str = "6e74172b-be56-4843-9ff4-e66a39bb12e3";
+/// Object ID of the user the fixture tokens are issued to
+pub const USER_ID: &str = "9f4880d8-80ba-4c40-97bc-f7a23c703084";
+/// Issue time of the fixture tokens, in seconds since the Unix epoch
+pub const ISSUED_AT: u64 = 1_700_000_000;
+/// Lifetime of the fixture tokens in seconds
+pub const LIFETIME: u64 = 3600;
+
+/// An RSA key pair with a public JWK, for signing test tokens
+#[derive(Debug, Clone, Copy)]
+pub struct TestKey {
+    private_key: &'static str,
+    jwks: &'static str,
+}
+
+impl TestKey {
+    /// Gets the key the fixture tokens are signed with, ID `test-key`
+    pub fn primary() -> Self {
+        TestKey {
+            private_key: include_str!("../tests/fixtures/rsa-private.pem"),
+            jwks: include_str!("../tests/fixtures/jwks.json"),
+        }
+    }
+
+    /// Gets a second key, ID `rotated-key`, e.g. to test key rotation
+    pub fn rotated() -> Self {
+        TestKey {
+            private_key: include_str!("../tests/fixtures/rsa-rotated.pem"),
+            jwks: include_str!("../tests/fixtures/jwks-rotated.json"),
+        }
+    }
+
+    /// Gets the public key as published in a JWKS
+    pub fn jwk(&self) -> Jwk {
+        self.jwks().keys.remove(0)
+    }
+
+    /// Gets a key set containing only this key
+    pub fn jwks(&self) -> JwksResponse {
+        serde_json::from_str(self.jwks).expect("fixture JWKS is valid")
+    }
+
+    /// Gets the ID of the key, set as `kid` in the tokens it signs
+    pub fn kid(&self) -> String {
+        self.jwk().kid
+    }
+
+    /// Gets the private key in PEM format
+    pub fn private_key_pem(&self) -> &'static str {
+        self.private_key
+    }
+
+    /// Signs the claims using RS256, naming this key in the header
+    pub fn sign(&self, claims: &Claims) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(self.kid());
+        self.sign_with_header(&header, claims)
+    }
+
+    /// Signs the claims with a custom header, e.g. one without `kid`
+    pub fn sign_with_header(&self, header: &Header, claims: &Claims) -> String {
+        let key = EncodingKey::from_rsa_pem(self.private_key.as_bytes())
+            .expect("fixture private key is valid");
+        encode(header, claims, &key).expect("claims serialize to JSON")
+    }
+}
+
+/// Gets a key set with both fixture keys
+pub fn jwks() -> JwksResponse {
+    JwksResponse {
+        keys: vec![TestKey::primary().jwk(), TestKey::rotated().jwk()],
+    }
+}
+
+/// Gets a clock frozen one minute after the fixture tokens were issued
+pub fn clock() -> FixedClock {
+    FixedClock(ISSUED_AT + 60)
+}
+
+/// Creates the claims of a v1 Microsoft Graph access token issued at [`ISSUED_AT`]
+pub fn v1_claims() -> ClaimsBuilder {
+    issued_claims()
+        .iss(&format!("https://sts.windows.net/{}/", TENANT_ID))
+        .aud("https://graph.microsoft.com")
+        .appid(CLIENT_ID)
+        .upn("adele.vance@contoso.com")
+        .scopes(&["User.Read"])
+        .ver("1.0")
+}
+
+/// Creates the claims of a v2 access token for [`CLIENT_ID`] issued at [`ISSUED_AT`]
+pub fn v2_claims() -> ClaimsBuilder {
+    issued_claims()
+        .iss(&format!(
+            "https://login.microsoftonline.com/{}/v2.0",
+            TENANT_ID
+        ))
+        .aud(CLIENT_ID)
+        .azp(CLIENT_ID)
+        .preferred_username("adele.vance@contoso.com")
+        .scopes(&["access_as_user"])
+        .ver("2.0")
+}
+
+/// Creates the claims of an Azure AD B2C ID token for [`CLIENT_ID`] issued at [`ISSUED_AT`]
+pub fn b2c_claims() -> ClaimsBuilder {
+    issued_claims()
+        .iss(&format!(
+            "https://{}.b2clogin.com/{}/v2.0/",
+            B2C_TENANT, TENANT_ID
+        ))
+        .aud(CLIENT_ID)
+        .name("Adele Vance")
+        .claim("emails", vec!["adele.vance@contoso.com"])
+        .claim("tfp", "B2C_1_signupsignin")
+        .ver("1.0")
+}
+
+/// Gets a v1 Microsoft Graph access token signed with the primary key
+pub fn v1_token() -> String {
+    sign(v1_claims())
+}
+
+/// Gets a v2 access token signed with the primary key
+pub fn v2_token() -> String {
+    sign(v2_claims())
+}
+
+/// Gets an Azure AD B2C ID token signed with the primary key
+pub fn b2c_token() -> String {
+    sign(b2c_claims())
+}
+
+fn issued_claims() -> ClaimsBuilder {
+    Claims::builder()
+        .sub(USER_ID)
+        .oid(USER_ID)
+        .tid(TENANT_ID)
+        .iat(ISSUED_AT)
+        .nbf(ISSUED_AT)
+        .exp(ISSUED_AT + LIFETIME)
+}
+
+fn sign(claims: ClaimsBuilder) -> String {
+    let claims = claims.build().expect("fixture claims are valid");
+    TestKey::primary().sign(&claims)
+}
+
+/// Creates a validator for the fixture tokens that never touches the network
+///
+/// Keys are served by a [`MockKeyProvider`] with both fixture keys and the
+/// time is frozen by [`clock`].
+#[cfg(feature = "net")]
+pub fn validator() -> TokenValidator {
+    TokenValidator::builder()
+        .key_provider(MockKeyProvider::default())
+        .clock(clock())
+        .build()
+}
+
+/// Key provider serving a key set that tests can swap, fail and inspect
+///
+/// Clones share their state, so a test can keep a clone to rotate keys or
+/// count fetches after handing the provider to a validator.
+#[cfg(feature = "net")]
+#[derive(Debug, Clone)]
+pub struct MockKeyProvider {
+    state: Arc<MockState>,
+}
+
+#[cfg(feature = "net")]
+#[derive(Debug)]
+struct MockState {
+    jwks: Mutex<Arc<JwksResponse>>,
+    failing: AtomicBool,
+    requests: Mutex<Vec<String>>,
+    refetches: AtomicUsize,
+}
+
+#[cfg(feature = "net")]
+impl Default for MockKeyProvider {
+    fn default() -> Self {
+        Self::new(jwks())
+    }
+}
+
+#[cfg(feature = "net")]
+impl MockKeyProvider {
+    /// Creates a provider serving `jwks` for every JWKS URI
+    pub fn new(jwks: JwksResponse) -> Self {
+        MockKeyProvider {
+            state: Arc::new(MockState {
+                jwks: Mutex::new(Arc::new(jwks)),
+                failing: AtomicBool::new(false),
+                requests: Mutex::new(Vec::new()),
+                refetches: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Replaces the key set served from now on, e.g. to simulate a key rotation
+    pub fn set_keys(&self, jwks: JwksResponse) {
+        *self
+            .state
+            .jwks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Arc::new(jwks);
+    }
+
+    /// Makes every following request fail, or succeed again, like an unreachable endpoint
+    pub fn set_failing(&self, failing: bool) {
+        self.state.failing.store(failing, Ordering::SeqCst);
+    }
+
+    /// Gets the JWKS URIs requested so far, in order
+    pub fn requests(&self) -> Vec<String> {
+        self.state
+            .requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Gets how often the keys were refetched because a token named an unknown key
+    pub fn refetches(&self) -> usize {
+        self.state.refetches.load(Ordering::SeqCst)
+    }
+
+    fn serve(&self, uri: &str) -> KeysFuture<'static> {
+        self.state
+            .requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(uri.to_string());
+        let result = match self.state.failing.load(Ordering::SeqCst) {
+            true => Err(anyhow::anyhow!("Mock key provider failed to fetch {}", uri)),
+            false => Ok(self
+                .state
+                .jwks
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()),
+        };
+        Box::pin(async move { result })
+    }
+}
+
+#[cfg(feature = "net")]
+impl KeyProvider for MockKeyProvider {
+    fn get_keys<'a>(&'a self, uri: &'a str) -> KeysFuture<'a> {
+        self.serve(uri)
+    }
+
+    fn refetch_keys<'a>(&'a self, uri: &'a str) -> KeysFuture<'a> {
+        self.state.refetches.fetch_add(1, Ordering::SeqCst);
+        self.serve(uri)
+    }
+}
@@ -0,0 +1,239 @@
+use serde_json::Value;
+
+use crate::token::requirements::RequirementCheck;
+use crate::token::verify::{token_format, AzureTokenFormat};
+use crate::token::Claims;
+
+/// First-party Microsoft API that tokens are commonly issued for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownAudience {
+    /// Friendly name of the API
+    pub name: &'static str,
+    /// Application ID of the API's service principal
+    pub app_id: &'static str,
+    /// App ID URIs accepted as audience, `*.` matches any subdomain
+    pub uris: &'static [&'static str],
+    /// Command line options testing a token for this API, if supported
+    pub test_options: Option<&'static str>,
+    /// Token version the API is issued, whatever the client requested
+    pub token_version: &'static str,
+    /// Quirk of the API's tokens worth knowing when debugging them, if any
+    pub quirk: Option<&'static str>,
+}
+
+/// Well-known first-party APIs
+pub const KNOWN_AUDIENCES: &[KnownAudience] = &[
+    KnownAudience {
+        name: "Microsoft Graph",
+        app_id: "00000003-0000-0000-c000-000000000000",
+        uris: &["https://graph.microsoft.com"],
+        test_options: Some("--test-graph"),
+        token_version: "1.0",
+        quirk: Some(
+            "Graph signs its tokens over a hashed nonce in the header, only Graph itself \
+             can validate their signature",
+        ),
+    },
+    KnownAudience {
+        name: "Azure AD Graph (retired)",
+        app_id: "00000002-0000-0000-c000-000000000000",
+        uris: &["https://graph.windows.net"],
+        test_options: None,
+        token_version: "1.0",
+        quirk: Some("Azure AD Graph was retired, new tokens for it are rejected"),
+    },
+    KnownAudience {
+        name: "Azure Resource Manager",
+        app_id: "797f4846-ba00-4fd7-ba43-dac1f8f63013",
+        uris: &[
+            "https://management.azure.com",
+            "https://management.core.windows.net",
+        ],
+        test_options: Some("--test-api"),
+        token_version: "1.0",
+        quirk: Some(
+            "ARM accepts management.azure.com and management.core.windows.net alike, \
+             with or without a trailing slash",
+        ),
+    },
+    KnownAudience {
+        name: "Azure Key Vault",
+        app_id: "cfa8b339-82a2-471a-a3c9-0fc0be7a4093",
+        uris: &["https://vault.azure.net"],
+        test_options: Some("--test-api --vault <VAULT>"),
+        token_version: "1.0",
+        quirk: None,
+    },
+    KnownAudience {
+        name: "Azure Storage",
+        app_id: "e406a681-f3d4-42a8-90b6-c2b029497af1",
+        uris: &["https://storage.azure.com"],
+        test_options: Some("--test-api --storage-account <ACCOUNT>"),
+        token_version: "1.0",
+        quirk: None,
+    },
+    KnownAudience {
+        name: "SharePoint Online",
+        app_id: "00000003-0000-0ff1-ce00-000000000000",
+        uris: &["https://*.sharepoint.com"],
+        test_options: None,
+        token_version: "1.0",
+        quirk: None,
+    },
+    KnownAudience {
+        name: "Exchange Online",
+        app_id: "00000002-0000-0ff1-ce00-000000000000",
+        uris: &[
+            "https://outlook.office365.com",
+            "https://outlook.office.com",
+        ],
+        test_options: None,
+        token_version: "1.0",
+        quirk: None,
+    },
+    KnownAudience {
+        name: "Azure DevOps",
+        app_id: "499b84ac-1321-427f-aa17-267ca6975798",
+        uris: &["https://app.vssps.visualstudio.com"],
+        test_options: None,
+        token_version: "1.0",
+        quirk: Some("Azure DevOps tokens name the app ID as audience and may lack the ver claim"),
+    },
+];
+
+impl KnownAudience {
+    /// Returns whether an audience value is this API's app ID or one of its URIs
+    pub fn matches(&self, aud: &str) -> bool {
+        let aud = aud.trim_end_matches('/');
+        aud.eq_ignore_ascii_case(self.app_id)
+            || self.uris.iter().any(|uri| match uri.split_once("*.") {
+                Some((scheme, domain)) => aud
+                    .to_ascii_lowercase()
+                    .strip_prefix(scheme)
+                    .is_some_and(|host| host.ends_with(&format!(".{}", domain))),
+                None => aud.eq_ignore_ascii_case(uri),
+            })
+    }
+
+    /// Gets the values an API accepts as its audience, the app ID and the URIs
+    ///
+    /// First-party APIs accept tokens naming any of them, with or without a
+    /// trailing slash. Wildcard URIs are left out.
+    pub fn aliases(&self) -> Vec<String> {
+        let uris = self.uris.iter().filter(|uri| !uri.contains('*'));
+        std::iter::once(self.app_id.to_string())
+            .chain(uris.flat_map(|uri| [uri.to_string(), format!("{}/", uri)]))
+            .collect()
+    }
+
+    /// Checks a token against what this API expects of its tokens
+    ///
+    /// Explains the API's quirks, e.g. a missing `ver` claim, so they are not
+    /// mistaken for a broken token.
+    pub fn check(&self, claims: &Claims) -> Vec<RequirementCheck> {
+        let mut checks = Vec::new();
+
+        let aud = lookup_value(&claims.aud).unwrap_or_default();
+        checks.push(RequirementCheck {
+            name: "Audience form",
+            passed: true,
+            detail: match aud.eq_ignore_ascii_case(self.app_id) {
+                true => format!(
+                    "aud is the app ID of {}, which accepts it and {} alike",
+                    self.name,
+                    self.uris.join(", ")
+                ),
+                false => format!(
+                    "aud is {}, {} also accepts its app ID {}",
+                    aud, self.name, self.app_id
+                ),
+            },
+        });
+
+        checks.push(match claims.version() {
+            Some(version) => RequirementCheck {
+                name: "Token version",
+                passed: version == self.token_version,
+                detail: format!(
+                    "version {}, {} is always issued v{} tokens",
+                    version, self.name, self.token_version
+                ),
+            },
+            None => RequirementCheck {
+                name: "Token version",
+                passed: true,
+                detail: format!(
+                    "no ver claim, {} tokens are v{} regardless",
+                    self.name, self.token_version
+                ),
+            },
+        });
+
+        if self.token_version == "1.0" {
+            let v1 = matches!(token_format(claims), AzureTokenFormat::V1);
+            checks.push(RequirementCheck {
+                name: "Issuer matches version",
+                passed: v1,
+                detail: match v1 {
+                    true => format!("issued by {}", claims.iss),
+                    false => format!(
+                        "issuer {} is not the v1.0 issuer sts.windows.net of {} tokens",
+                        claims.iss, self.name
+                    ),
+                },
+            });
+        }
+
+        checks
+    }
+}
+
+/// Gets the first audience value that names a first-party API, or the first at all
+fn lookup_value(aud: &Value) -> Option<&str> {
+    match aud {
+        Value::String(aud) => Some(aud),
+        Value::Array(auds) => {
+            let mut values = auds.iter().filter_map(Value::as_str);
+            values
+                .clone()
+                .find(|aud| lookup(aud).is_some())
+                .or_else(|| values.next())
+        }
+        _ => None,
+    }
+}
+
+/// Looks up the first-party API an audience value belongs to
+pub fn lookup(aud: &str) -> Option<&'static KnownAudience> {
+    KNOWN_AUDIENCES.iter().find(|known| known.matches(aud))
+}
+
+/// Looks up the first-party API of an `aud` claim, the first known one for multiple audiences
+pub fn lookup_claim(aud: &Value) -> Option<&'static KnownAudience> {
+    match aud {
+        Value::String(aud) => lookup(aud),
+        Value::Array(auds) => auds.iter().filter_map(Value::as_str).find_map(lookup),
+        _ => None,
+    }
+}
+
+/// Formats an audience value with the name of its API, e.g. `Microsoft Graph (00000003-...)`
+pub fn describe(aud: &str) -> String {
+    match lookup(aud) {
+        Some(known) => format!("{} ({})", known.name, aud),
+        None => aud.to_string(),
+    }
+}
+
+/// Formats an `aud` claim with the names of the APIs of its values
+pub fn describe_claim(aud: &Value) -> String {
+    match aud {
+        Value::String(aud) => describe(aud),
+        Value::Array(auds) => auds
+            .iter()
+            .map(|aud| aud.as_str().map_or_else(|| aud.to_string(), describe))
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,214 @@
+use serde_json::Value;
+use url::Url;
+
+use crate::token::audience;
+use crate::token::claims::Claims;
+use crate::token::cloud::Cloud;
+use crate::token::warning::Warning;
+
+pub use crate::token::warning::Severity;
+
+/// Authentication methods (`amr` values) that indicate multi-factor authentication
+const MFA_METHODS: &[&str] = &["mfa", "ngcmfa", "fido"];
+
+/// Security audit configuration
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    pub max_lifetime: u64, // in seconds
+    pub allowed_algorithms: Vec<String>,
+    pub expected_version: Option<String>,
+    pub trusted_issuer_hosts: Vec<String>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            max_lifetime: 24 * 60 * 60, // 24 hours
+            allowed_algorithms: vec!["RS256".to_string()],
+            expected_version: None,
+            // The issuers of every cloud, sovereign clouds included
+            trusted_issuer_hosts: Cloud::ALL
+                .iter()
+                .flat_map(|cloud| cloud.issuer_hosts())
+                .map(|host| host.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Audits a decoded token for security-relevant properties
+///
+/// Findings are returned ordered from most to least severe.
+pub fn audit_token(header: &Value, claims: &Claims, config: &AuditConfig) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    // Signing algorithm
+    match header["alg"].as_str() {
+        Some(alg) if !config.allowed_algorithms.iter().any(|a| a == alg) => {
+            warnings.push(
+                Warning::new(
+                    "alg-not-allowed",
+                    Severity::High,
+                    format!(
+                        "Signing algorithm '{}' is not in the allowlist ({})",
+                        alg,
+                        config.allowed_algorithms.join(", ")
+                    ),
+                )
+                .remediation("Azure AD signs with RS256, check that the token was issued by it"),
+            );
+        }
+        None => warnings.push(
+            Warning::new(
+                "alg-missing",
+                Severity::High,
+                "Token header has no 'alg' parameter",
+            )
+            .remediation("The token was not issued by Azure AD, do not accept it"),
+        ),
+        _ => {}
+    }
+    if header["kid"].as_str().is_none() {
+        warnings.push(
+            Warning::new(
+                "kid-missing",
+                Severity::Low,
+                "Token header has no 'kid': the signing key is found by trying every key in the JWKS, \
+                 which Azure AD never requires",
+            )
+            .remediation("Check the issuer, tokens of Azure AD always name their signing key"),
+        );
+    }
+
+    // Lifetime
+    let lifetime = claims.exp.saturating_sub(claims.nbf.unwrap_or(claims.iat));
+    if lifetime > config.max_lifetime {
+        warnings.push(
+            Warning::new(
+                "long-lifetime",
+                Severity::Medium,
+                format!(
+                    "Token lifetime of {}s exceeds the maximum of {}s",
+                    lifetime, config.max_lifetime
+                ),
+            )
+            .remediation("Shorten the lifetime with a token lifetime policy"),
+        );
+    }
+
+    if claims.nbf.is_none() {
+        warnings.push(
+            Warning::new(
+                "nbf-missing",
+                Severity::Low,
+                "Token has no 'nbf' claim, it is valid from any point in time before expiry",
+            )
+            .remediation("Reject tokens whose iat lies in the future"),
+        );
+    }
+
+    // Audience
+    let audiences = claims.audiences();
+    for aud in audiences.iter().filter(|aud| aud.contains('*')) {
+        warnings.push(
+            Warning::new(
+                "wildcard-audience",
+                Severity::High,
+                format!("Audience '{}' contains a wildcard", aud),
+            )
+            .remediation("Accept only the exact application ID URI or client ID of the API"),
+        );
+    }
+
+    // Token version, first-party APIs get their own version whatever the app expects
+    if let Some(expected) = &config.expected_version {
+        if let Some(version) = claims.version() {
+            if version != expected && audience::lookup_claim(&claims.aud).is_none() {
+                warnings.push(
+                    Warning::new(
+                        "unexpected-version",
+                        Severity::Medium,
+                        format!("Token version is {} but {} was expected", version, expected),
+                    )
+                    .remediation(
+                        "Set requestedAccessTokenVersion in the manifest of the API's app registration",
+                    ),
+                );
+            }
+        }
+    }
+
+    // Authentication strength (only present on delegated tokens)
+    if let Some(amr) = &claims.amr {
+        let has_mfa = amr
+            .iter()
+            .any(|method| MFA_METHODS.contains(&method.as_str()));
+        if !has_mfa {
+            warnings.push(
+                Warning::new(
+                    "no-mfa",
+                    Severity::Medium,
+                    format!(
+                        "Authentication methods {} do not include multi-factor authentication",
+                        Value::from(amr.clone())
+                    ),
+                )
+                .remediation("Require MFA for the app with a Conditional Access policy"),
+            );
+        }
+    }
+    if claims.extra.get("acr").and_then(Value::as_str) == Some("0") {
+        warnings.push(
+            Warning::new(
+                "acr-zero",
+                Severity::Medium,
+                "Authentication context class is '0', the user did not meet ISO/IEC 29115 requirements",
+            )
+            .remediation("Require a stronger sign-in with a Conditional Access policy"),
+        );
+    }
+
+    // Issuing cloud
+    match Url::parse(&claims.iss)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+    {
+        Some(host)
+            if !config
+                .trusted_issuer_hosts
+                .iter()
+                .any(|trusted| host_matches(trusted, &host)) =>
+        {
+            warnings.push(
+                Warning::new(
+                    "unexpected-cloud",
+                    Severity::High,
+                    format!("Token was issued by an unexpected authority '{}'", host),
+                )
+                .remediation("Accept tokens of the cloud of your tenant only"),
+            );
+        }
+        None => warnings.push(
+            Warning::new(
+                "invalid-issuer",
+                Severity::High,
+                format!("Issuer '{}' is not a valid URL", claims.iss),
+            )
+            .remediation("The token was not issued by Azure AD, do not accept it"),
+        ),
+        _ => {}
+    }
+
+    warnings.sort_by_key(|warning| std::cmp::Reverse(warning.severity));
+    warnings
+}
+
+/// Matches a host against a trusted host, where `*.` matches any subdomain
+fn host_matches(trusted: &str, host: &str) -> bool {
+    match trusted.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.ends_with('.')),
+        None => trusted == host,
+    }
+}
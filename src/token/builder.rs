@@ -0,0 +1,159 @@
+use jsonwebtoken::Algorithm;
+use reqwest::Client;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::token::clock::Clock;
+use crate::token::cloud::Cloud;
+use crate::token::keystore::KeyStore;
+use crate::token::provider::KeyProvider;
+use crate::token::validator::TokenValidator;
+use crate::token::verify::ValidatorConfig;
+
+/// Fluent builder for a [`TokenValidator`]
+///
+/// Starts from [`ValidatorConfig::default`], so only options that differ
+/// need to be set:
+///
+/// ```no_run
+/// # use azure_token_validator::token::TokenValidator;
+/// let validator = TokenValidator::builder()
+///     .tenant("72f988bf-86f1-41af-91ab-2d7cd011db47")
+///     .audience("api://my-api")
+///     .build();
+/// ```
+#[derive(Default)]
+pub struct ValidatorBuilder {
+    config: ValidatorConfig,
+    client: Option<Client>,
+    key_store: Option<KeyStore>,
+    key_provider: Option<Arc<dyn KeyProvider>>,
+}
+
+impl ValidatorBuilder {
+    /// Creates a builder with the default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the whole configuration, e.g. one loaded from a file
+    pub fn config(mut self, config: ValidatorConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets the tenant whose keys verify tokens, `auto` for each token's own tenant
+    pub fn tenant(mut self, tenant_id: impl Into<String>) -> Self {
+        self.config.tenant_id = tenant_id.into();
+        self
+    }
+
+    /// Accepts tokens for this audience, enabling audience validation
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.config.audiences.push(audience.into());
+        self.config.validate_aud = true;
+        self
+    }
+
+    /// Requires tokens to come from this issuer instead of accepting their own `iss`
+    pub fn issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.config.issuer = Some(issuer.into());
+        self
+    }
+
+    /// Validates tokens of an AD FS server, e.g. `adfs.contoso.com`, against its own keys
+    pub fn adfs(mut self, host: impl Into<String>) -> Self {
+        self.config.adfs_host = Some(host.into());
+        self
+    }
+
+    /// Accepts tokens issued in these clouds only
+    pub fn clouds(mut self, clouds: impl IntoIterator<Item = Cloud>) -> Self {
+        self.config.clouds = clouds.into_iter().collect();
+        self
+    }
+
+    /// Sets the clock skew tolerated when checking `exp` and `nbf`
+    pub fn leeway(mut self, leeway: Duration) -> Self {
+        self.config.leeway = leeway.as_secs();
+        self
+    }
+
+    /// Sets the signing algorithms to accept, HMAC algorithms are always rejected
+    pub fn allowed_algorithms(mut self, algorithms: impl IntoIterator<Item = Algorithm>) -> Self {
+        self.config.allowed_algorithms = algorithms.into_iter().collect();
+        self
+    }
+
+    /// Accepts tokens requested by these client applications only (`azp` or `appid`)
+    pub fn allowed_clients<S: Into<String>>(
+        mut self,
+        clients: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.config.allowed_clients = clients.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Accepts tokens signed with the key of this ID only
+    pub fn pin_kid(mut self, kid: impl Into<String>) -> Self {
+        self.config.pinned_kid = Some(kid.into());
+        self
+    }
+
+    /// Accepts tokens signed with the key whose certificate has this thumbprint only
+    pub fn pin_thumbprint(mut self, thumbprint: impl Into<String>) -> Self {
+        self.config.pinned_thumbprint = Some(thumbprint.into());
+        self
+    }
+
+    /// Corrects the clock by its skew to Azure AD, measured from the `Date` header of key fetches
+    pub fn auto_skew(mut self, enabled: bool) -> Self {
+        self.config.auto_skew = enabled;
+        self
+    }
+
+    /// Enables or disables the expiration check
+    pub fn validate_exp(mut self, validate: bool) -> Self {
+        self.config.validate_exp = validate;
+        self
+    }
+
+    /// Enables or disables the not-before check
+    pub fn validate_nbf(mut self, validate: bool) -> Self {
+        self.config.validate_nbf = validate;
+        self
+    }
+
+    /// Sets the clock used for time-based claim checks, e.g. to freeze time in tests
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.config.clock = Arc::new(clock);
+        self
+    }
+
+    /// Shares the keys cached in `key_store` with other validators
+    pub fn key_store(mut self, key_store: KeyStore) -> Self {
+        self.key_store = Some(key_store);
+        self
+    }
+
+    /// Serves signing keys from a custom source instead of fetching them from Azure AD
+    pub fn key_provider(mut self, key_provider: impl KeyProvider + 'static) -> Self {
+        self.key_provider = Some(Arc::new(key_provider));
+        self
+    }
+
+    /// Sets the HTTP client used to fetch keys and federation metadata, e.g. one with a proxy
+    pub fn http_client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Builds the validator
+    pub fn build(self) -> TokenValidator {
+        let client = self.client.unwrap_or_default();
+        let key_store = self
+            .key_store
+            .unwrap_or_else(|| KeyStore::new(client.clone()));
+        TokenValidator::from_parts(self.config, client, key_store, self.key_provider)
+    }
+}
@@ -0,0 +1,129 @@
+use roxmltree::{Node, NodeId};
+use std::collections::BTreeMap;
+
+/// Serializes an element subtree with Exclusive XML Canonicalization (without comments)
+///
+/// This is the `http://www.w3.org/2001/10/xml-exc-c14n#` transform used by
+/// Azure AD to sign SAML assertions. The element `excluded`, if given, is
+/// omitted from the output, which implements the enveloped-signature transform.
+pub fn canonicalize(element: Node, excluded: Option<NodeId>) -> String {
+    let mut out = String::new();
+    write_element(element, excluded, &BTreeMap::new(), &mut out);
+    out
+}
+
+fn write_element(
+    node: Node,
+    excluded: Option<NodeId>,
+    rendered: &BTreeMap<String, String>,
+    out: &mut String,
+) {
+    let qname = element_qname(node);
+    let mut rendered = rendered.clone();
+
+    // Namespaces are only declared where they are visibly utilized, i.e. by the
+    // element itself or one of its attributes, and not yet rendered above
+    let mut used_prefixes = vec![prefix_of(qname).to_string()];
+    for attr in node.attributes() {
+        let attr_prefix = prefix_of(attr_qname(node, &attr));
+        if !attr_prefix.is_empty() && attr_prefix != "xml" {
+            used_prefixes.push(attr_prefix.to_string());
+        }
+    }
+    used_prefixes.sort();
+    used_prefixes.dedup();
+
+    out.push('<');
+    out.push_str(qname);
+
+    for prefix in used_prefixes {
+        let lookup = if prefix.is_empty() {
+            None
+        } else {
+            Some(prefix.as_str())
+        };
+        let uri = node.lookup_namespace_uri(lookup).unwrap_or_default();
+        let previous = rendered.get(&prefix).map(String::as_str);
+        // An empty default namespace only needs declaring to undo a non-empty one
+        let needs_declaration = match (prefix.is_empty(), uri.is_empty()) {
+            (true, true) => previous.is_some_and(|p| !p.is_empty()),
+            _ => previous != Some(uri),
+        };
+        if needs_declaration {
+            if prefix.is_empty() {
+                out.push_str(" xmlns=\"");
+            } else {
+                out.push_str(&format!(" xmlns:{}=\"", prefix));
+            }
+            out.push_str(&escape_attribute(uri));
+            out.push('"');
+            rendered.insert(prefix, uri.to_string());
+        }
+    }
+
+    let mut attributes: Vec<_> = node
+        .attributes()
+        .map(|attr| {
+            (
+                attr.namespace().unwrap_or_default(),
+                attr.name(),
+                attr_qname(node, &attr),
+                attr.value(),
+            )
+        })
+        .collect();
+    attributes.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+    for (_, _, qname, value) in attributes {
+        out.push_str(&format!(" {}=\"{}\"", qname, escape_attribute(value)));
+    }
+    out.push('>');
+
+    for child in node.children() {
+        if Some(child.id()) == excluded {
+            continue;
+        }
+        if child.is_element() {
+            write_element(child, excluded, &rendered, out);
+        } else if let Some(text) = child.text().filter(|_| child.is_text()) {
+            out.push_str(&escape_text(text));
+        }
+    }
+
+    out.push_str(&format!("</{}>", qname));
+}
+
+/// Gets the element name as written in the source document (including prefix)
+fn element_qname<'a>(node: Node<'a, '_>) -> &'a str {
+    let input = node.document().input_text();
+    let start = node.range().start + 1;
+    let end = input[start..]
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .map_or(input.len(), |offset| start + offset);
+    &input[start..end]
+}
+
+/// Gets the attribute name as written in the source document (including prefix)
+fn attr_qname<'a>(node: Node<'a, '_>, attr: &roxmltree::Attribute<'a, '_>) -> &'a str {
+    &node.document().input_text()[attr.range_qname()]
+}
+
+fn prefix_of(qname: &str) -> &str {
+    qname.split_once(':').map_or("", |(prefix, _)| prefix)
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\r', "&#xD;")
+}
+
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('"', "&quot;")
+        .replace('\t', "&#x9;")
+        .replace('\n', "&#xA;")
+        .replace('\r', "&#xD;")
+}
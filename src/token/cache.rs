@@ -0,0 +1,286 @@
+//! Storage of fetched key sets, shared between processes if need be
+//!
+//! A [`KeyStore`](crate::token::KeyStore) always keeps the keys it uses in
+//! memory. Given a [`KeyCache`], it also stores every fetched key set there
+//! and looks keys up in it before fetching them, so instances of a service
+//! share one fetch and a restarted process starts with the keys it had.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+pub use crate::token::jwk::CachedKeys;
+use crate::token::jwk::JwksResponse;
+
+/// Future returned by [`KeyCache`] methods
+pub type CacheFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Storage of key sets by JWKS URI
+///
+/// Caches hold whatever was stored last; the key store decides whether the
+/// keys are fresh enough from when they were fetched.
+pub trait KeyCache: Send + Sync + std::fmt::Debug {
+    /// Gets the key set last stored for `uri`
+    fn load<'a>(&'a self, uri: &'a str) -> CacheFuture<'a, Option<CachedKeys>>;
+
+    /// Stores the key set of `uri`, replacing the previous one
+    fn store<'a>(&'a self, uri: &'a str, keys: CachedKeys) -> CacheFuture<'a, ()>;
+}
+
+/// Most key sets a [`MemoryCache`] keeps unless created with another capacity
+pub const MAX_KEY_SETS: usize = 256;
+
+/// Key sets kept in the memory of this process
+///
+/// Shared between the key stores it is given to, e.g. stores using
+/// different HTTP clients. Once full, storing another key set evicts the
+/// one fetched longest ago.
+#[derive(Debug)]
+pub struct MemoryCache {
+    entries: RwLock<HashMap<String, CachedKeys>>,
+    capacity: usize,
+}
+
+impl Default for MemoryCache {
+    fn default() -> Self {
+        Self::with_capacity(MAX_KEY_SETS)
+    }
+}
+
+impl MemoryCache {
+    /// Creates an empty cache holding up to [`MAX_KEY_SETS`] key sets
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty cache holding up to `capacity` key sets
+    pub fn with_capacity(capacity: usize) -> Self {
+        MemoryCache {
+            entries: RwLock::new(HashMap::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Gets the cached URIs with when their keys were fetched
+    pub async fn fetched(&self) -> Vec<(String, SystemTime)> {
+        self.entries
+            .read()
+            .await
+            .iter()
+            .map(|(uri, keys)| (uri.clone(), keys.fetched_at))
+            .collect()
+    }
+
+    pub(crate) async fn get(&self, uri: &str) -> Option<CachedKeys> {
+        self.entries.read().await.get(uri).cloned()
+    }
+
+    pub(crate) async fn insert(&self, uri: &str, keys: CachedKeys) {
+        let mut entries = self.entries.write().await;
+        if entries.len() >= self.capacity && !entries.contains_key(uri) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, keys)| keys.fetched_at)
+                .map(|(uri, _)| uri.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(uri.to_string(), keys);
+    }
+}
+
+impl KeyCache for MemoryCache {
+    fn load<'a>(&'a self, uri: &'a str) -> CacheFuture<'a, Option<CachedKeys>> {
+        Box::pin(async move { Ok(self.get(uri).await) })
+    }
+
+    fn store<'a>(&'a self, uri: &'a str, keys: CachedKeys) -> CacheFuture<'a, ()> {
+        Box::pin(async move {
+            self.insert(uri, keys).await;
+            Ok(())
+        })
+    }
+}
+
+/// A key set as written to disk or Redis
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredKeys {
+    uri: String,
+    /// Seconds since the Unix epoch
+    fetched_at: u64,
+    jwks: JwksResponse,
+}
+
+impl StoredKeys {
+    fn to_json(uri: &str, keys: &CachedKeys) -> Result<Vec<u8>> {
+        let stored = StoredKeys {
+            uri: uri.to_string(),
+            fetched_at: keys
+                .fetched_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            jwks: keys.jwks.as_ref().clone(),
+        };
+        Ok(serde_json::to_vec(&stored)?)
+    }
+
+    fn from_json(json: &[u8]) -> Result<CachedKeys> {
+        let stored: StoredKeys = serde_json::from_slice(json)?;
+        Ok(CachedKeys {
+            jwks: Arc::new(stored.jwks),
+            fetched_at: UNIX_EPOCH + Duration::from_secs(stored.fetched_at),
+        })
+    }
+}
+
+/// Key sets kept as JSON files in a directory, one per JWKS URI
+///
+/// Keys survive restarts, and processes on one host sharing the directory
+/// share the keys. Files are replaced atomically, so readers never see a
+/// partly written key set.
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    /// Creates a cache in `dir`, created on the first store if missing
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        DiskCache { dir: dir.into() }
+    }
+
+    /// Gets the file holding the keys of `uri`, named after its SHA-256 hash
+    pub fn path(&self, uri: &str) -> PathBuf {
+        let hash: String = Sha256::digest(uri.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        self.dir.join(format!("{}.json", hash))
+    }
+}
+
+impl KeyCache for DiskCache {
+    fn load<'a>(&'a self, uri: &'a str) -> CacheFuture<'a, Option<CachedKeys>> {
+        Box::pin(async move {
+            let path = self.path(uri);
+            let json = match tokio::fs::read(&path).await {
+                Ok(json) => json,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to read {}", path.display()))
+                }
+            };
+            StoredKeys::from_json(&json)
+                .map(Some)
+                .with_context(|| format!("Invalid cached keys in {}", path.display()))
+        })
+    }
+
+    fn store<'a>(&'a self, uri: &'a str, keys: CachedKeys) -> CacheFuture<'a, ()> {
+        Box::pin(async move {
+            let json = StoredKeys::to_json(uri, &keys)?;
+            let path = self.path(uri);
+            // Written beside the target and renamed over it, so the file is never partial
+            let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+            tokio::fs::create_dir_all(&self.dir)
+                .await
+                .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+            tokio::fs::write(&partial, json)
+                .await
+                .with_context(|| format!("Failed to write {}", partial.display()))?;
+            tokio::fs::rename(&partial, &path)
+                .await
+                .with_context(|| format!("Failed to write {}", path.display()))
+        })
+    }
+}
+
+/// Key sets kept in Redis, shared by every instance of a service
+///
+/// Each key set is a JSON string under the prefix followed by its JWKS URI.
+/// The connection is opened on first use and reconnects after failures.
+#[cfg(feature = "redis")]
+pub struct RedisCache {
+    client: redis::Client,
+    prefix: String,
+    connection: tokio::sync::OnceCell<redis::aio::ConnectionManager>,
+}
+
+#[cfg(feature = "redis")]
+impl RedisCache {
+    /// Prefix of the Redis keys unless another one is set
+    pub const DEFAULT_PREFIX: &'static str = "azure-token-validator:jwks:";
+
+    /// Creates a cache in the Redis server at `url`, e.g. `redis://cache:6379/0`
+    ///
+    /// Fails only if the URL is invalid; the server is connected to on first use.
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(RedisCache {
+            client: redis::Client::open(url)?,
+            prefix: Self::DEFAULT_PREFIX.to_string(),
+            connection: tokio::sync::OnceCell::new(),
+        })
+    }
+
+    /// Stores keys under `prefix`, e.g. to keep the keys of environments apart
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    async fn connection(&self) -> Result<redis::aio::ConnectionManager> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| redis::aio::ConnectionManager::new(self.client.clone()))
+            .await?;
+        Ok(connection.clone())
+    }
+}
+
+#[cfg(feature = "redis")]
+impl std::fmt::Debug for RedisCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisCache")
+            .field("server", &self.client.get_connection_info().addr)
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+#[cfg(feature = "redis")]
+impl KeyCache for RedisCache {
+    fn load<'a>(&'a self, uri: &'a str) -> CacheFuture<'a, Option<CachedKeys>> {
+        Box::pin(async move {
+            let mut connection = self.connection().await?;
+            let json: Option<Vec<u8>> =
+                redis::AsyncCommands::get(&mut connection, format!("{}{}", self.prefix, uri))
+                    .await?;
+            json.map(|json| StoredKeys::from_json(&json))
+                .transpose()
+                .context("Invalid cached keys in Redis")
+        })
+    }
+
+    fn store<'a>(&'a self, uri: &'a str, keys: CachedKeys) -> CacheFuture<'a, ()> {
+        Box::pin(async move {
+            let json = StoredKeys::to_json(uri, &keys)?;
+            let mut connection = self.connection().await?;
+            redis::AsyncCommands::set::<_, _, ()>(
+                &mut connection,
+                format!("{}{}", self.prefix, uri),
+                json,
+            )
+            .await?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,118 @@
+use serde_json::Value;
+
+use crate::token::{audience, Claims};
+
+/// User or application taking part in a token's identity chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// Object ID of a user, application ID of an app
+    pub id: String,
+    /// Display name, UPN or app display name, if the token carries one
+    pub name: Option<String>,
+}
+
+/// Who a token identifies, which app is acting and on whose behalf
+///
+/// In delegated tokens, including those a middle-tier API obtained with the
+/// on-behalf-of flow, the subject is the signed-in user and the client is the
+/// app calling the audience on the user's behalf. App-only tokens have no user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityChain {
+    /// Signed-in user, `None` for app-only tokens
+    pub user: Option<Identity>,
+    /// App the token was issued to, `azp` in v2 tokens and `appid` in v1
+    pub client: Option<Identity>,
+    /// How the client authenticated, from `azpacr` (v2) or `appidacr` (v1)
+    pub client_authentication: Option<&'static str>,
+    /// Earlier parties from nested `act` claims (RFC 8693), most recent first
+    pub actors: Vec<Identity>,
+    /// Audience the chain ends at, with the name of known first-party APIs
+    pub audience: String,
+}
+
+impl IdentityChain {
+    /// Returns whether the token acts on behalf of a user
+    pub fn is_delegated(&self) -> bool {
+        self.user.is_some()
+    }
+}
+
+/// Reconstructs the identity chain of a token from its claims
+pub fn identity_chain(claims: &Claims) -> IdentityChain {
+    let client = claims.authorized_party().map(|id| Identity {
+        id: id.to_string(),
+        name: claims
+            .extra
+            .get("app_displayname")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+    });
+
+    // v2 tokens carry azpacr, v1 tokens appidacr
+    let acr = match claims.version() {
+        Some("2.0") => claims.extra.get("azpacr"),
+        _ => claims.extra.get("appidacr"),
+    };
+    let client_authentication = match acr.and_then(Value::as_str) {
+        Some("0") => Some("public client"),
+        Some("1") => Some("client secret"),
+        Some("2") => Some("certificate"),
+        _ => None,
+    };
+
+    let user = is_delegated(claims).then(|| Identity {
+        // sub is pairwise per app, oid identifies the user across apps
+        id: claims.object_id().unwrap_or(&claims.sub).to_string(),
+        name: claims
+            .name
+            .as_deref()
+            .or(claims.user_principal_name())
+            .or(claims.preferred_username.as_deref())
+            .map(str::to_string),
+    });
+
+    let mut actors = Vec::new();
+    let mut act = claims.extra.get("act");
+    while let Some(actor @ Value::Object(_)) = act {
+        if let Some(id) = ["azp", "appid", "oid", "sub"]
+            .iter()
+            .find_map(|claim| actor[*claim].as_str())
+        {
+            actors.push(Identity {
+                id: id.to_string(),
+                name: ["app_displayname", "name"]
+                    .iter()
+                    .find_map(|claim| actor[*claim].as_str())
+                    .map(str::to_string),
+            });
+        }
+        act = actor.get("act");
+    }
+
+    IdentityChain {
+        user,
+        client,
+        client_authentication,
+        actors,
+        audience: audience::describe_claim(&claims.aud),
+    }
+}
+
+/// Decides whether a token was issued for a user rather than the app itself
+fn is_delegated(claims: &Claims) -> bool {
+    // idtyp is an optional claim naming the kind of principal
+    match claims.extra.get("idtyp").and_then(Value::as_str) {
+        Some("user") => return true,
+        Some("app") => return false,
+        _ => {}
+    }
+    if claims.scp.is_some()
+        || claims.user_principal_name().is_some()
+        || claims.preferred_username.is_some()
+        || claims.name.is_some()
+    {
+        return true;
+    }
+    // In app-only tokens the subject is the service principal itself
+    claims.object_id() != Some(claims.sub.as_str()) && claims.roles().is_empty()
+}
@@ -1,17 +1,30 @@
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 use std::fmt;
 
+use crate::token::audience;
+use crate::token::clock::Timezone;
+use crate::token::verify::unix_now;
+
+/// Reference to the group memberships left out of a token because there are too many
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupOverage {
+    /// Endpoint named in `_claim_sources` to fetch the groups from, if any
+    pub endpoint: Option<String>,
+}
+
 /// Represents the claims in an Azure AD JWT token
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Claims {
     pub iss: String,
     pub sub: String,
     pub aud: Value,
     pub exp: u64,
     pub iat: u64,
-    pub nbf: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -22,45 +35,326 @@ pub struct Claims {
     pub appid: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unique_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amr: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ver: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub azp: Option<String>,
     // Additional fields that might be present
     #[serde(flatten)]
     pub extra: HashMap<String, Value>,
 }
 
 impl Claims {
-    /// Determines if this token is an access token or ID token based on the audience
-    pub fn token_type(&self) -> TokenType {
-        // Microsoft Graph API ID
-        const GRAPH_API_ID: &str = "00000003-0000-0000-c000-000000000000";
+    /// Creates a builder issuing claims now that expire in one hour
+    pub fn builder() -> ClaimsBuilder {
+        ClaimsBuilder::new()
+    }
 
-        if let Value::String(aud) = &self.aud {
-            if aud == GRAPH_API_ID {
-                return TokenType::Access;
-            }
+    /// Determines if this token is an access token or ID token
+    ///
+    /// Tokens for a first-party API (Graph, ARM, Azure DevOps, ...) and
+    /// tokens granting delegated scopes are access tokens.
+    pub fn token_type(&self) -> TokenType {
+        if self.scp.is_some() || audience::lookup_claim(&self.aud).is_some() {
+            return TokenType::Access;
         }
         TokenType::Id
     }
 
-    /// Gets a formatted display of the audience claim
+    /// Gets the values of the audience claim, a single one unless `aud` is an array
+    pub fn audiences(&self) -> Vec<String> {
+        match &self.aud {
+            Value::String(aud) => vec![aud.clone()],
+            Value::Array(auds) => auds
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Gets a formatted display of the audience claim, multiple audiences separated by commas
     pub fn audience_display(&self) -> String {
         match &self.aud {
-            Value::String(aud) => aud.clone(),
-            Value::Array(auds) => {
-                serde_json::to_string(auds).unwrap_or_else(|_| "Error formatting".to_string())
-            }
+            Value::String(_) | Value::Array(_) => self.audiences().join(", "),
             _ => "Unknown format".to_string(),
         }
     }
 
-    /// Formats a timestamp as human-readable date/time
+    /// Gets the immutable identifier of the user or service principal (`oid`)
+    pub fn object_id(&self) -> Option<&str> {
+        self.oid.as_deref()
+    }
+
+    /// Gets the tenant the token was issued for (`tid`)
+    pub fn tenant_id(&self) -> Option<&str> {
+        self.tid.as_deref()
+    }
+
+    /// Gets the user principal name from `upn`, falling back to `unique_name` in v1 tokens
+    pub fn user_principal_name(&self) -> Option<&str> {
+        self.upn.as_deref().or(self.unique_name.as_deref())
+    }
+
+    /// Gets the client application that requested the token (`azp` in v2, `appid` in v1)
+    pub fn authorized_party(&self) -> Option<&str> {
+        self.azp.as_deref().or(self.appid.as_deref())
+    }
+
+    /// Gets the token version (`ver`), e.g. `1.0` or `2.0`
+    pub fn version(&self) -> Option<&str> {
+        self.ver.as_deref()
+    }
+
+    /// Gets the object IDs of the groups the user is a member of
+    pub fn groups(&self) -> &[String] {
+        self.groups.as_deref().unwrap_or_default()
+    }
+
+    /// Detects a groups overage, where the user is in too many groups for the `groups` claim
+    ///
+    /// Azure AD then replaces `groups` with a reference in `_claim_names` and
+    /// `_claim_sources`, or sets `hasgroups` in tokens from the implicit flow.
+    pub fn group_overage(&self) -> Option<GroupOverage> {
+        if let Some(source) = self
+            .extra
+            .get("_claim_names")
+            .and_then(|n| n["groups"].as_str())
+        {
+            let endpoint = self
+                .extra
+                .get("_claim_sources")
+                .and_then(|sources| sources[source]["endpoint"].as_str())
+                .map(str::to_string);
+            return Some(GroupOverage { endpoint });
+        }
+
+        match self.extra.get("hasgroups") {
+            Some(Value::Bool(true)) => Some(GroupOverage { endpoint: None }),
+            _ => None,
+        }
+    }
+
+    /// Gets the methods the user authenticated with (`amr`), e.g. `pwd` or `mfa`
+    pub fn authentication_methods(&self) -> &[String] {
+        self.amr.as_deref().unwrap_or_default()
+    }
+
+    /// Gets the delegated permissions from the space-delimited `scp` claim
+    pub fn scopes(&self) -> Vec<&str> {
+        self.scp
+            .as_deref()
+            .map(|scp| scp.split_whitespace().collect())
+            .unwrap_or_default()
+    }
+
+    /// Gets the application or app roles from the `roles` claim
+    pub fn roles(&self) -> Vec<&str> {
+        match self.extra.get("roles") {
+            Some(Value::Array(roles)) => roles.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Checks if the token grants the given delegated permission, e.g. `User.Read`
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes().contains(&scope)
+    }
+
+    /// Checks if the token contains the given role, e.g. `Admin`
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles().contains(&role)
+    }
+
+    /// Formats a timestamp as human-readable date/time in UTC
+    ///
+    /// Use [`Timezone::format_timestamp`] to display it in another time zone.
     pub fn format_timestamp(timestamp: u64) -> String {
-        match chrono::DateTime::from_timestamp(timestamp as i64, 0) {
-            Some(dt) => dt.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
-            None => format!("{} (invalid timestamp)", timestamp),
+        Timezone::Utc.format_timestamp(timestamp)
+    }
+
+    /// Describes how far a timestamp is from now, e.g. `in 58 minutes` or `3 days ago`
+    pub fn format_relative(timestamp: u64, now: u64) -> String {
+        let delta = timestamp as i64 - now as i64;
+        let seconds = delta.unsigned_abs();
+        let (amount, unit) = match seconds {
+            0..=59 => return "just now".to_string(),
+            60..=3569 => ((seconds + 30) / 60, "minute"),
+            3570..=129_599 => ((seconds + 1800) / 3600, "hour"),
+            _ => ((seconds + 43_200) / 86_400, "day"),
+        };
+        let plural = if amount == 1 { "" } else { "s" };
+        match delta > 0 {
+            true => format!("in {} {}{}", amount, unit, plural),
+            false => format!("{} {}{} ago", amount, unit, plural),
         }
     }
 }
 
+/// Builder for [`Claims`], e.g. to construct realistic tokens in tests
+///
+/// Every setter stores the raw claim value, so well-known claims passed to
+/// [`ClaimsBuilder::claim`] end up in their typed field, exactly as if the
+/// token had been decoded.
+#[derive(Debug, Clone)]
+pub struct ClaimsBuilder {
+    claims: Map<String, Value>,
+}
+
+impl Default for ClaimsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClaimsBuilder {
+    /// Creates a builder issuing claims now that expire in one hour
+    pub fn new() -> Self {
+        let now = unix_now().unwrap_or_default();
+        let mut claims = Map::new();
+        claims.insert("iss".to_string(), Value::from(""));
+        claims.insert("sub".to_string(), Value::from(""));
+        claims.insert("aud".to_string(), Value::from(""));
+        claims.insert("iat".to_string(), Value::from(now));
+        claims.insert("nbf".to_string(), Value::from(now));
+        claims.insert("exp".to_string(), Value::from(now + 3600));
+        ClaimsBuilder { claims }
+    }
+
+    /// Sets an arbitrary claim
+    pub fn claim(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.claims.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Removes a claim, e.g. to build tokens without `nbf`
+    pub fn without(mut self, key: &str) -> Self {
+        self.claims.remove(key);
+        self
+    }
+
+    /// Sets the issuer (`iss`)
+    pub fn iss(self, iss: &str) -> Self {
+        self.claim("iss", iss)
+    }
+
+    /// Sets the subject (`sub`)
+    // Named after the claim, like all other setters
+    #[allow(clippy::should_implement_trait)]
+    pub fn sub(self, sub: &str) -> Self {
+        self.claim("sub", sub)
+    }
+
+    /// Sets the audience, either a single string or an array of strings
+    pub fn aud(self, aud: impl Into<Value>) -> Self {
+        self.claim("aud", aud)
+    }
+
+    /// Sets the issue time (`iat`) in seconds since the Unix epoch
+    pub fn iat(self, iat: u64) -> Self {
+        self.claim("iat", iat)
+    }
+
+    /// Sets the start of the validity period (`nbf`) in seconds since the Unix epoch
+    pub fn nbf(self, nbf: u64) -> Self {
+        self.claim("nbf", nbf)
+    }
+
+    /// Sets the expiration (`exp`) in seconds since the Unix epoch
+    pub fn exp(self, exp: u64) -> Self {
+        self.claim("exp", exp)
+    }
+
+    /// Sets the expiration relative to the issue time, negative values yield expired claims
+    pub fn exp_in(self, seconds: i64) -> Self {
+        let iat = self.claims.get("iat").and_then(Value::as_u64).unwrap_or(0);
+        self.exp(iat.saturating_add_signed(seconds))
+    }
+
+    /// Sets the display name (`name`)
+    pub fn name(self, name: &str) -> Self {
+        self.claim("name", name)
+    }
+
+    /// Sets the email address (`email`)
+    pub fn email(self, email: &str) -> Self {
+        self.claim("email", email)
+    }
+
+    /// Sets the preferred username (`preferred_username`)
+    pub fn preferred_username(self, preferred_username: &str) -> Self {
+        self.claim("preferred_username", preferred_username)
+    }
+
+    /// Sets the client application ID of v1 tokens (`appid`)
+    pub fn appid(self, appid: &str) -> Self {
+        self.claim("appid", appid)
+    }
+
+    /// Sets the delegated permissions, joined into the space-delimited `scp` claim
+    pub fn scopes(self, scopes: &[&str]) -> Self {
+        self.claim("scp", scopes.join(" "))
+    }
+
+    /// Sets the app roles (`roles`)
+    pub fn roles(self, roles: &[&str]) -> Self {
+        self.claim("roles", roles.to_vec())
+    }
+
+    /// Sets the object ID (`oid`)
+    pub fn oid(self, oid: &str) -> Self {
+        self.claim("oid", oid)
+    }
+
+    /// Sets the tenant ID (`tid`)
+    pub fn tid(self, tid: &str) -> Self {
+        self.claim("tid", tid)
+    }
+
+    /// Sets the user principal name (`upn`)
+    pub fn upn(self, upn: &str) -> Self {
+        self.claim("upn", upn)
+    }
+
+    /// Sets the group object IDs (`groups`)
+    pub fn groups(self, groups: &[&str]) -> Self {
+        self.claim("groups", groups.to_vec())
+    }
+
+    /// Sets the authentication methods (`amr`)
+    pub fn amr(self, amr: &[&str]) -> Self {
+        self.claim("amr", amr.to_vec())
+    }
+
+    /// Sets the token version (`ver`)
+    pub fn ver(self, ver: &str) -> Self {
+        self.claim("ver", ver)
+    }
+
+    /// Sets the client application ID of v2 tokens (`azp`)
+    pub fn azp(self, azp: &str) -> Self {
+        self.claim("azp", azp)
+    }
+
+    /// Builds the claims, failing if a well-known claim has the wrong type
+    pub fn build(self) -> Result<Claims> {
+        serde_json::from_value(Value::Object(self.claims)).context("Invalid claims")
+    }
+}
+
 /// Represents the type of token
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TokenType {
@@ -0,0 +1,153 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::token::verify::unix_now;
+
+/// Source of the current time for time-based claim checks
+///
+/// Validation never reads the system time directly, so replacing the clock
+/// evaluates `exp` and friends as of any other point in time.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Gets the current time as seconds since the Unix epoch
+    fn now(&self) -> u64;
+}
+
+/// Clock reading the system time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        unix_now().unwrap_or_default()
+    }
+}
+
+/// Clock frozen at a fixed point in time, e.g. to reproduce a past rejection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Clock that only moves when told to, for deterministic tests
+///
+/// Clones share the same time, so a test can keep a handle to advance the
+/// clock after passing it to a validator.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// Creates a clock set to the given Unix time
+    pub fn new(now: u64) -> Self {
+        ManualClock {
+            now: Arc::new(AtomicU64::new(now)),
+        }
+    }
+
+    /// Sets the clock to the given Unix time
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    /// Moves the clock forward by the given number of seconds
+    pub fn advance(&self, seconds: u64) {
+        self.now.fetch_add(seconds, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+/// Clock corrected by a measured skew, e.g. a VM clock that drifted from Azure AD
+///
+/// `skew` is how far the wrapped clock is ahead, as measured by
+/// [`skew_from_date_header`], and is subtracted from its time.
+#[derive(Debug, Clone)]
+pub struct SkewedClock {
+    clock: Arc<dyn Clock>,
+    skew: i64,
+}
+
+impl SkewedClock {
+    /// Creates a clock correcting `clock` by `skew` seconds
+    pub fn new(clock: Arc<dyn Clock>, skew: i64) -> Self {
+        SkewedClock { clock, skew }
+    }
+}
+
+impl Clock for SkewedClock {
+    fn now(&self) -> u64 {
+        (self.clock.now() as i64).saturating_sub(self.skew).max(0) as u64
+    }
+}
+
+/// Measures how far a clock reading `now` is ahead of a server, from the server's HTTP `Date` header
+///
+/// Negative when the clock is behind. The header only has a resolution of one
+/// second, and the request's latency adds to the measured skew.
+pub fn skew_from_date_header(date: &str, now: u64) -> Option<i64> {
+    let server_time = chrono::DateTime::parse_from_rfc2822(date).ok()?.timestamp();
+    Some(now as i64 - server_time)
+}
+
+/// Time zone timestamps are displayed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Timezone {
+    #[default]
+    Utc,
+    /// Time zone of this machine
+    Local,
+    /// IANA time zone, e.g. `Europe/Berlin`
+    Named(chrono_tz::Tz),
+}
+
+impl Timezone {
+    /// Formats a Unix timestamp as date and time in this time zone
+    pub fn format(&self, timestamp: u64) -> Option<String> {
+        let utc = chrono::DateTime::from_timestamp(i64::try_from(timestamp).ok()?, 0)?;
+        Some(match self {
+            Timezone::Utc => utc.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            Timezone::Local => utc
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S %:z")
+                .to_string(),
+            Timezone::Named(tz) => utc
+                .with_timezone(tz)
+                .format("%Y-%m-%d %H:%M:%S %Z")
+                .to_string(),
+        })
+    }
+
+    /// Formats a Unix timestamp like [`Timezone::format`], falling back to the raw number if it is out of range
+    pub fn format_timestamp(&self, timestamp: u64) -> String {
+        self.format(timestamp)
+            .unwrap_or_else(|| format!("{} (invalid timestamp)", timestamp))
+    }
+}
+
+impl FromStr for Timezone {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "utc" | "z" => Ok(Timezone::Utc),
+            "local" => Ok(Timezone::Local),
+            _ => value.parse().map(Timezone::Named).map_err(|_| {
+                format!(
+                    "'{}' is not 'utc', 'local' or an IANA time zone such as Europe/Berlin",
+                    value
+                )
+            }),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Azure cloud a token can be issued in, each with its own Azure AD authority
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cloud {
+    /// Azure public cloud, including External ID tenants
+    Public,
+    /// Azure US Government
+    UsGovernment,
+    /// Azure operated by 21Vianet in China
+    China,
+}
+
+impl Cloud {
+    /// All clouds, accepted by default
+    pub const ALL: &'static [Cloud] = &[Cloud::Public, Cloud::UsGovernment, Cloud::China];
+
+    /// Host of the cloud's Azure AD authority, which publishes the signing keys
+    pub fn authority_host(self) -> &'static str {
+        match self {
+            Cloud::Public => "login.microsoftonline.com",
+            Cloud::UsGovernment => "login.microsoftonline.us",
+            Cloud::China => "login.chinacloudapi.cn",
+        }
+    }
+
+    /// Hosts appearing in the issuer of tokens from this cloud, `*.` matches any subdomain
+    pub fn issuer_hosts(self) -> &'static [&'static str] {
+        match self {
+            Cloud::Public => &[
+                "login.microsoftonline.com",
+                "sts.windows.net",
+                "*.ciamlogin.com",
+            ],
+            Cloud::UsGovernment => &["login.microsoftonline.us"],
+            Cloud::China => &["login.chinacloudapi.cn", "sts.chinacloudapi.cn"],
+        }
+    }
+
+    /// Determines the cloud that issued a token from its issuer URL
+    ///
+    /// v1 tokens of US Government tenants are issued by `sts.windows.net` like
+    /// those of the public cloud, and are attributed to the public cloud.
+    pub fn of_issuer(issuer: &str) -> Option<Cloud> {
+        let host = issuer.split_once("://")?.1.split('/').next()?;
+        Cloud::ALL.iter().copied().find(|cloud| {
+            cloud
+                .issuer_hosts()
+                .iter()
+                .any(|pattern| match pattern.strip_prefix("*.") {
+                    Some(domain) => host
+                        .strip_suffix(domain)
+                        .is_some_and(|subdomain| subdomain.ends_with('.')),
+                    None => host.eq_ignore_ascii_case(pattern),
+                })
+        })
+    }
+}
+
+impl fmt::Display for Cloud {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cloud::Public => write!(f, "Azure public cloud"),
+            Cloud::UsGovernment => write!(f, "Azure US Government"),
+            Cloud::China => write!(f, "Azure China"),
+        }
+    }
+}
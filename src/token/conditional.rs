@@ -0,0 +1,106 @@
+use serde_json::Value;
+
+use crate::token::Claims;
+
+/// Client capability of apps that handle claims challenges, required for continuous access evaluation
+const CAE_CAPABILITY: &str = "cp1";
+
+/// Conditional Access state recorded in a token
+///
+/// Built from `acr`, `amr`, `acrs` (authentication context IDs satisfied
+/// when the token was issued) and `xms_cc` (capabilities the client declared).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConditionalAccess {
+    /// Authentication context class reference (`acr`)
+    pub acr: Option<String>,
+    /// Authentication methods (`amr`)
+    pub methods: Vec<String>,
+    /// Authentication context IDs satisfied by the sign-in (`acrs`), e.g. `c1`
+    pub auth_contexts: Vec<String>,
+    /// Client capabilities (`xms_cc`), e.g. `cp1`
+    pub client_capabilities: Vec<String>,
+}
+
+impl ConditionalAccess {
+    /// Reads the Conditional Access claims of a token
+    pub fn from_claims(claims: &Claims) -> Self {
+        let strings = |name: &str| match claims.extra.get(name) {
+            Some(Value::Array(values)) => values
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            Some(Value::String(value)) => vec![value.clone()],
+            _ => Vec::new(),
+        };
+
+        ConditionalAccess {
+            acr: claims
+                .extra
+                .get("acr")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            methods: claims.authentication_methods().to_vec(),
+            auth_contexts: strings("acrs"),
+            client_capabilities: strings("xms_cc"),
+        }
+    }
+
+    /// Returns whether none of the Conditional Access claims are present
+    pub fn is_empty(&self) -> bool {
+        self == &ConditionalAccess::default()
+    }
+
+    /// Returns whether the sign-in satisfied the authentication context `id`
+    pub fn satisfies(&self, id: &str) -> bool {
+        self.auth_contexts
+            .iter()
+            .any(|context| context.eq_ignore_ascii_case(id))
+    }
+
+    /// Returns whether the client declared it handles claims challenges (`xms_cc` contains `cp1`)
+    ///
+    /// Only such clients receive long-lived tokens under continuous access
+    /// evaluation and can step up to an authentication context on demand.
+    pub fn is_cae_capable(&self) -> bool {
+        self.client_capabilities
+            .iter()
+            .any(|capability| capability == CAE_CAPABILITY)
+    }
+}
+
+/// Describes an authentication method (`amr` value) as defined by RFC 8176 and Azure AD
+pub fn method_description(method: &str) -> Option<&'static str> {
+    Some(match method {
+        "pwd" => "password",
+        "rsa" => "RSA key, e.g. Microsoft Authenticator or Windows Hello",
+        "otp" => "one-time passcode",
+        "fed" => "federated identity provider",
+        "wia" => "Windows integrated authentication",
+        "mfa" => "multi-factor authentication",
+        "ngcmfa" => "multi-factor authentication for Windows Hello provisioning",
+        "wiaormfa" => "Windows integrated or multi-factor authentication",
+        "fido" => "FIDO2 security key",
+        "hwk" => "hardware-secured key",
+        "swk" => "software-secured key",
+        "sc" => "smart card",
+        "sms" => "SMS confirmation",
+        "tel" => "phone call confirmation",
+        "face" => "facial recognition",
+        "fpt" => "fingerprint",
+        "pin" => "PIN",
+        "kba" => "knowledge-based authentication",
+        "mca" => "multiple-channel authentication",
+        _ => return None,
+    })
+}
+
+/// Describes an authentication context class reference (`acr`)
+pub fn acr_description(acr: &str) -> &'static str {
+    match acr {
+        "0" => "did not meet ISO/IEC 29115 requirements",
+        "1" => "authenticated",
+        // Azure AD B2C puts the user flow or custom policy here
+        _ => "user flow or policy",
+    }
+}
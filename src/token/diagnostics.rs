@@ -0,0 +1,219 @@
+use jsonwebtoken::errors::ErrorKind;
+use url::Url;
+
+use crate::token::claims::Claims;
+use crate::token::error::ValidationError;
+use crate::token::validator::TokenValidator;
+use crate::token::verify::{
+    adfs_host, issuer_for, jwks_uri, resolve_tenant, token_format, AzureTokenFormat,
+};
+
+/// Looks into why a token failed validation and suggests how to proceed
+///
+/// Signing keys missing from the key set used are searched for in the v1 and
+/// v2 key sets of the token's own tenant and of its application, so this may
+/// fetch several key sets.
+pub async fn suggest_fixes(
+    validator: &TokenValidator,
+    token: &str,
+    error: &anyhow::Error,
+) -> Vec<String> {
+    let (header, claims) = match validator.decode_token(token) {
+        Ok(decoded) => decoded,
+        Err(_) => {
+            return vec![
+                "Check that the whole token was copied: it must consist of three dot-separated segments"
+                    .to_string(),
+            ]
+        }
+    };
+    let config = validator.config();
+    let used_tenant = resolve_tenant(&config.tenant_id, &claims);
+    let tenant = claims.tenant_id().unwrap_or(used_tenant);
+
+    if error.chain().any(|cause| cause.is::<reqwest::Error>()) {
+        return vec![format!(
+            "Check the network connection to login.microsoftonline.com, the keys of tenant {} could not be fetched",
+            used_tenant
+        )];
+    }
+    let Some(error) = error.downcast_ref::<ValidationError>() else {
+        return Vec::new();
+    };
+
+    // Keys of an AD FS server are only trusted once it is configured
+    if let Some(host) = adfs_host(&claims.iss).filter(|_| config.adfs_host.is_none()) {
+        let key_mismatch = match error {
+            ValidationError::KeyNotFound { .. } | ValidationError::NoMatchingKey { .. } => true,
+            ValidationError::Jwt(e) => *e.kind() == ErrorKind::InvalidSignature,
+            _ => false,
+        };
+        if key_mismatch {
+            return vec![format!(
+                "The token was issued by the AD FS server {0}: use --adfs {0} to validate it against the server's keys",
+                host
+            )];
+        }
+    }
+
+    match error {
+        ValidationError::KeyNotFound { kid } => find_key(validator, &claims, kid).await,
+        ValidationError::NoMatchingKey { .. } => {
+            let mut steps = Vec::new();
+            if tenant != used_tenant {
+                steps.push(format!(
+                    "Use --tenant {} to try the key set of the token's tenant",
+                    tenant
+                ));
+            }
+            steps.push(
+                "Tokens from emulators and local tooling are signed with their own keys, not with those Azure AD publishes"
+                    .to_string(),
+            );
+            steps
+        }
+        ValidationError::KeysUnavailable { uri, retry_in, .. } => vec![format!(
+            "Fetching {} keeps failing and is paused for {}s: check the network connection and the Azure AD service health",
+            uri, retry_in
+        )],
+        ValidationError::InvalidTenant(_) => vec![
+            "The token names a tenant that cannot be part of an Azure AD URL: it was not issued by Azure AD, \
+             or use --tenant with the GUID of the expected tenant"
+                .to_string(),
+        ],
+        ValidationError::Expired { .. } => vec![
+            "Request a new token, or use --skip-expiration to validate the signature anyway".to_string(),
+            format!(
+                "Use --at {} to check the token as of when it was issued",
+                claims.iat
+            ),
+        ],
+        ValidationError::NotYetValid { .. } => vec![
+            "Check the clock of this machine against the issuer's, or wait until the token becomes valid"
+                .to_string(),
+        ],
+        ValidationError::AlgorithmNotAllowed { alg, .. } => vec![format!(
+            "Use --allowed-algorithms {:?} if this issuer is expected to sign with {:?}",
+            alg, alg
+        )],
+        ValidationError::CloudNotAllowed { cloud, .. } => vec![format!(
+            "Add the {} to the accepted clouds if its tokens are expected, its keys are published at {}",
+            cloud,
+            cloud.authority_host()
+        )],
+        ValidationError::KeyNotPinned { kid, .. } => vec![format!(
+            "If Azure AD rotated its keys, check that '{}' is listed in the JWKS and pin it instead",
+            kid
+        )],
+        ValidationError::ClientNotAllowed {
+            client: Some(client),
+            ..
+        } => vec![format!(
+            "Add {} to --allowed-clients if this application is expected to call the API",
+            client
+        )],
+        ValidationError::ClientNotAllowed { client: None, .. } => vec![
+            "The token names no client: app-only tokens of some first-party services carry \
+             neither azp nor appid"
+                .to_string(),
+        ],
+        // Tokens that cannot be decoded were handled above
+        ValidationError::Malformed(_) | ValidationError::NotAJwt(_) => Vec::new(),
+        ValidationError::AlgorithmDowngrade { .. } | ValidationError::UnsupportedAlgorithm(_) => {
+            vec!["Do not trust this token: Azure AD only signs tokens with RSA keys".to_string()]
+        }
+        ValidationError::Jwt(e) => match e.kind() {
+            ErrorKind::InvalidIssuer => {
+                let mut steps = vec![format!(
+                    "The token's issuer is {}, expected {}",
+                    claims.iss,
+                    config.issuer.as_deref().unwrap_or("-")
+                )];
+                let expected = issuer_for(tenant, token_format(&claims));
+                if expected != claims.iss {
+                    steps.push(format!(
+                        "Azure AD issues {} for tenant {}, the token may come from another cloud or issuer",
+                        expected, tenant
+                    ));
+                }
+                steps.push(format!("Use --issuer {} to accept it", claims.iss));
+                steps
+            }
+            ErrorKind::InvalidAudience => {
+                let mut steps = vec![format!(
+                    "The token was issued for {}, not for {}",
+                    claims.audience_display(),
+                    config.audiences.join(", ")
+                )];
+                steps.extend(claims.audiences().into_iter().map(|aud| {
+                    format!("Use --audience {} to accept tokens for this audience", aud)
+                }));
+                steps
+            }
+            ErrorKind::InvalidSignature => {
+                let mut steps = Vec::new();
+                if header.get("nonce").is_some() {
+                    steps.push(
+                        "The header has a nonce: Microsoft Graph tokens are signed over a hashed nonce \
+                         and can only be validated by Graph itself"
+                            .to_string(),
+                    );
+                }
+                if tenant != used_tenant {
+                    steps.push(format!("Use --tenant {} to check against the key set of the token's tenant", tenant));
+                }
+                steps.push(
+                    "Check that the token was not modified, e.g. by re-encoding or line wrapping".to_string(),
+                );
+                steps
+            }
+            _ => Vec::new(),
+        },
+    }
+}
+
+/// Searches the key sets of the token's tenant and application for a signing key
+async fn find_key(validator: &TokenValidator, claims: &Claims, kid: &str) -> Vec<String> {
+    let config = validator.config();
+    let tenant = claims
+        .tenant_id()
+        .unwrap_or(resolve_tenant(&config.tenant_id, claims));
+
+    let mut candidates = vec![
+        jwks_uri(tenant, AzureTokenFormat::V1),
+        jwks_uri(tenant, AzureTokenFormat::V2),
+    ];
+    // Apps with custom signing keys publish them in an app-specific key set
+    if let Some(aud) = claims.audiences().first() {
+        let app = aud.trim_start_matches("api://");
+        if let Ok(mut uri) = Url::parse(&jwks_uri(tenant, AzureTokenFormat::V2)) {
+            uri.query_pairs_mut().append_pair("appid", app);
+            candidates.push(uri.to_string());
+        }
+    }
+
+    for uri in &candidates {
+        let Ok(jwks) = validator.get_jwks(uri).await else {
+            continue;
+        };
+        if jwks.find_key(kid).is_some() {
+            return vec![
+                format!("Key '{}' is published at {}", kid, uri),
+                match uri.contains("?appid=") {
+                    true => "The application uses a custom signing key: validate the token against this key set"
+                        .to_string(),
+                    false => format!("Use --tenant {} to validate against this key set", tenant),
+                },
+            ];
+        }
+    }
+
+    vec![
+        format!(
+            "Key '{}' is not published by tenant {}: the token was issued by another issuer ({}), \
+             or the key was rotated out",
+            kid, tenant, claims.iss
+        ),
+        "Request a new token if it was issued a while ago".to_string(),
+    ]
+}
@@ -0,0 +1,551 @@
+/// Reference entry describing a claim Azure AD issues
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClaimInfo {
+    /// Name of the claim in the token payload
+    pub name: &'static str,
+    /// Short human-readable title
+    pub title: &'static str,
+    /// What the claim means and how applications should use it
+    pub description: &'static str,
+    /// Token versions carrying the claim (`1.0`, `2.0`)
+    pub versions: &'static [&'static str],
+    /// Whether the claim is only issued when configured as an optional claim
+    pub optional: bool,
+    /// Typical values, for recognizing the claim in a token
+    pub examples: &'static [&'static str],
+}
+
+const BOTH: &[&str] = &["1.0", "2.0"];
+const V1: &[&str] = &["1.0"];
+const V2: &[&str] = &["2.0"];
+
+/// Claims found in Azure AD access and ID tokens, in alphabetical order
+pub const CLAIMS: &[ClaimInfo] = &[
+    ClaimInfo {
+        name: "acct",
+        title: "Account status",
+        description: "Status of the user's account in the tenant: 0 for a member, 1 for a guest.",
+        versions: BOTH,
+        optional: true,
+        examples: &["0", "1"],
+    },
+    ClaimInfo {
+        name: "acr",
+        title: "Authentication context class",
+        description: "Authentication strength of the sign-in. \"0\" means the user did not \
+                      meet ISO/IEC 29115 requirements, \"1\" is a regular sign-in.",
+        versions: V1,
+        optional: false,
+        examples: &["0", "1"],
+    },
+    ClaimInfo {
+        name: "acrs",
+        title: "Authentication contexts",
+        description: "Conditional Access authentication contexts the sign-in satisfied. \
+                      Check it before allowing sensitive operations protected by an \
+                      authentication context.",
+        versions: BOTH,
+        optional: false,
+        examples: &["[\"c1\"]", "[\"c1\", \"c3\"]"],
+    },
+    ClaimInfo {
+        name: "aio",
+        title: "Internal session data",
+        description: "Opaque value Azure AD uses to reuse tokens. Applications must not \
+                      rely on its content.",
+        versions: BOTH,
+        optional: false,
+        examples: &["E2ZgYDj8...AAA="],
+    },
+    ClaimInfo {
+        name: "altsecid",
+        title: "Alternate security ID",
+        description: "Identifier of a user from another identity provider, e.g. a \
+                      Microsoft account signed in to a tenant.",
+        versions: V1,
+        optional: false,
+        examples: &["5::10033FFF8F9B6B36"],
+    },
+    ClaimInfo {
+        name: "amr",
+        title: "Authentication methods",
+        description: "How the user authenticated, e.g. with a password, MFA or a \
+                      certificate. Only reliable in ID tokens and v1.0 access tokens.",
+        versions: V1,
+        optional: false,
+        examples: &["[\"pwd\"]", "[\"pwd\", \"mfa\"]", "[\"rsa\", \"mfa\"]"],
+    },
+    ClaimInfo {
+        name: "app_displayname",
+        title: "App name",
+        description: "Display name of the client application the token was requested by. \
+                      Informational only, never authorize based on it.",
+        versions: V1,
+        optional: false,
+        examples: &["Graph Explorer"],
+    },
+    ClaimInfo {
+        name: "appid",
+        title: "App ID",
+        description: "Application (client) ID of the client that requested the token. \
+                      Replaced by azp in v2.0 tokens.",
+        versions: V1,
+        optional: false,
+        examples: &["de8bc8b5-d9f9-48b1-a8ad-b748da725064"],
+    },
+    ClaimInfo {
+        name: "appidacr",
+        title: "Client authentication",
+        description: "How the client authenticated: 0 for a public client, 1 with a \
+                      client secret, 2 with a certificate. Replaced by azpacr in v2.0 tokens.",
+        versions: V1,
+        optional: false,
+        examples: &["0", "1", "2"],
+    },
+    ClaimInfo {
+        name: "aud",
+        title: "Audience",
+        description: "Intended recipient of the token. An API must reject tokens whose \
+                      audience is not its own application ID or App ID URI.",
+        versions: BOTH,
+        optional: false,
+        examples: &[
+            "https://graph.microsoft.com",
+            "api://orders",
+            "6e74172b-be56-4843-9ff4-e66a39bb12e3",
+        ],
+    },
+    ClaimInfo {
+        name: "auth_time",
+        title: "Authenticated at",
+        description: "Time the user last entered credentials, in seconds since the Unix \
+                      epoch. Differs from iat when a session was reused.",
+        versions: BOTH,
+        optional: true,
+        examples: &["1700000000"],
+    },
+    ClaimInfo {
+        name: "azp",
+        title: "Authorized party",
+        description: "Application (client) ID of the client that requested the token. \
+                      Replaces appid in v2.0 tokens.",
+        versions: V2,
+        optional: false,
+        examples: &["de8bc8b5-d9f9-48b1-a8ad-b748da725064"],
+    },
+    ClaimInfo {
+        name: "azpacr",
+        title: "Client authentication",
+        description: "How the client authenticated: 0 for a public client, 1 with a \
+                      client secret, 2 with a certificate. Replaces appidacr in v2.0 tokens.",
+        versions: V2,
+        optional: false,
+        examples: &["0", "1", "2"],
+    },
+    ClaimInfo {
+        name: "cnf",
+        title: "Confirmation key",
+        description: "Key the token is bound to (proof of possession). The token is only \
+                      valid together with a proof signed by that key.",
+        versions: BOTH,
+        optional: false,
+        examples: &["{\"jkt\": \"0ZcOCORZNYy-DWpqq30jZyJGHTN0d2HglBV3uiguA4I\"}"],
+    },
+    ClaimInfo {
+        name: "ctry",
+        title: "Country",
+        description: "Two-letter country or region of the user's tenant.",
+        versions: BOTH,
+        optional: true,
+        examples: &["US", "DE"],
+    },
+    ClaimInfo {
+        name: "deviceid",
+        title: "Device ID",
+        description: "ID of the Azure AD device object the user signed in from, present for \
+                      registered and joined devices. Use --test-graph to look up its \
+                      compliance state.",
+        versions: BOTH,
+        optional: false,
+        examples: &["3c8e4a3b-7a1e-4f0e-9b6f-2d5c1e8a9f10"],
+    },
+    ClaimInfo {
+        name: "email",
+        title: "Email",
+        description: "Email address of the user, if the account has one. Can be changed \
+                      by the user or an admin, never use it as an identifier.",
+        versions: BOTH,
+        optional: true,
+        examples: &["adele.vance@contoso.com"],
+    },
+    ClaimInfo {
+        name: "exp",
+        title: "Expiration",
+        description: "Time the token expires, in seconds since the Unix epoch. Access \
+                      tokens typically live 60 to 90 minutes.",
+        versions: BOTH,
+        optional: false,
+        examples: &["1700003600"],
+    },
+    ClaimInfo {
+        name: "family_name",
+        title: "Family name",
+        description: "Last name of the user as set in the directory.",
+        versions: BOTH,
+        optional: true,
+        examples: &["Vance"],
+    },
+    ClaimInfo {
+        name: "given_name",
+        title: "Given name",
+        description: "First name of the user as set in the directory.",
+        versions: BOTH,
+        optional: true,
+        examples: &["Adele"],
+    },
+    ClaimInfo {
+        name: "groups",
+        title: "Groups",
+        description: "Object IDs of the groups the user is a member of, when the app is \
+                      configured to emit group claims. Replaced by an overage reference \
+                      when the user is in too many groups.",
+        versions: BOTH,
+        optional: true,
+        examples: &["[\"f2a8f57e-5d28-4c2b-a9fc-8ecbc7b96b1a\"]"],
+    },
+    ClaimInfo {
+        name: "hasgroups",
+        title: "Has groups",
+        description: "Set instead of groups in implicit flow tokens when the user is in \
+                      groups, which then have to be fetched from Microsoft Graph.",
+        versions: BOTH,
+        optional: false,
+        examples: &["true"],
+    },
+    ClaimInfo {
+        name: "iat",
+        title: "Issued at",
+        description: "Time the token was issued, in seconds since the Unix epoch.",
+        versions: BOTH,
+        optional: false,
+        examples: &["1700000000"],
+    },
+    ClaimInfo {
+        name: "idp",
+        title: "Identity provider",
+        description: "Identity provider that authenticated the user, when it differs from \
+                      the issuer, e.g. for guests and Microsoft accounts.",
+        versions: BOTH,
+        optional: false,
+        examples: &[
+            "live.com",
+            "https://sts.windows.net/9188040d-6c67-4c5b-b112-36a304b66dad/",
+        ],
+    },
+    ClaimInfo {
+        name: "idtyp",
+        title: "Identity type",
+        description: "Whether the token was issued to an application (app) or on behalf \
+                      of a user (user). Emitted when configured as an optional claim.",
+        versions: BOTH,
+        optional: true,
+        examples: &["app", "user"],
+    },
+    ClaimInfo {
+        name: "ipaddr",
+        title: "IP address",
+        description: "IP address the user authenticated from.",
+        versions: V1,
+        optional: false,
+        examples: &["203.0.113.42"],
+    },
+    ClaimInfo {
+        name: "iss",
+        title: "Issuer",
+        description: "Security token service that issued the token, naming the tenant. \
+                      v1.0 tokens come from sts.windows.net, v2.0 tokens from \
+                      login.microsoftonline.com.",
+        versions: BOTH,
+        optional: false,
+        examples: &[
+            "https://sts.windows.net/72f988bf-86f1-41af-91ab-2d7cd011db47/",
+            "https://login.microsoftonline.com/72f988bf-86f1-41af-91ab-2d7cd011db47/v2.0",
+        ],
+    },
+    ClaimInfo {
+        name: "login_hint",
+        title: "Login hint",
+        description: "Opaque value that can be passed as login_hint to sign the same user \
+                      in again without prompting for the account.",
+        versions: BOTH,
+        optional: true,
+        examples: &["O.CiQ5ZjQ4ODBkOC04..."],
+    },
+    ClaimInfo {
+        name: "name",
+        title: "Name",
+        description: "Display name of the user. Can be changed, never use it as an \
+                      identifier.",
+        versions: BOTH,
+        optional: false,
+        examples: &["Adele Vance"],
+    },
+    ClaimInfo {
+        name: "nbf",
+        title: "Not before",
+        description: "Time before which the token must not be accepted, in seconds since \
+                      the Unix epoch. Usually equal to iat.",
+        versions: BOTH,
+        optional: false,
+        examples: &["1700000000"],
+    },
+    ClaimInfo {
+        name: "nonce",
+        title: "Nonce",
+        description: "Value the client sent in the sign-in request, checked by the client \
+                      to prevent replay of ID tokens.",
+        versions: BOTH,
+        optional: false,
+        examples: &["638349...NzA0"],
+    },
+    ClaimInfo {
+        name: "oid",
+        title: "Object ID",
+        description: "Immutable ID of the user or service principal in the tenant. Use it \
+                      with tid to identify a principal across applications.",
+        versions: BOTH,
+        optional: false,
+        examples: &["9f4880d8-80ba-4c40-97bc-f7a23c703084"],
+    },
+    ClaimInfo {
+        name: "onprem_sid",
+        title: "On-premises SID",
+        description: "Security identifier of a user synchronized from on-premises Active \
+                      Directory, for legacy authorization.",
+        versions: BOTH,
+        optional: true,
+        examples: &["S-1-5-21-1180699209-877415012-3182924384-1004"],
+    },
+    ClaimInfo {
+        name: "preferred_username",
+        title: "Username",
+        description: "Username of the user, an email address, phone number or UPN. \
+                      Mutable, only for display.",
+        versions: V2,
+        optional: false,
+        examples: &["adele.vance@contoso.com"],
+    },
+    ClaimInfo {
+        name: "puid",
+        title: "Passport user ID",
+        description: "Internal ID of the user, not documented for use by applications.",
+        versions: V1,
+        optional: false,
+        examples: &["10033FFF8F9B6B36"],
+    },
+    ClaimInfo {
+        name: "rh",
+        title: "Refresh handle",
+        description: "Opaque value Azure AD uses to revalidate tokens. Applications must \
+                      not rely on its content.",
+        versions: BOTH,
+        optional: false,
+        examples: &["0.AQEAv4j5cvGGr0GRqy180BHbR..."],
+    },
+    ClaimInfo {
+        name: "roles",
+        title: "Roles",
+        description: "App roles assigned to the user or application. Application \
+                      permissions of app-only tokens appear here.",
+        versions: BOTH,
+        optional: false,
+        examples: &["[\"Orders.Read.All\"]", "[\"Admin\"]"],
+    },
+    ClaimInfo {
+        name: "scp",
+        title: "Scope",
+        description: "Space-separated delegated permissions the user consented to for the \
+                      client. Only present in tokens issued on behalf of a user.",
+        versions: BOTH,
+        optional: false,
+        examples: &["User.Read", "openid profile email User.Read"],
+    },
+    ClaimInfo {
+        name: "sid",
+        title: "Session ID",
+        description: "ID of the user's sign-in session, shared by all tokens of that \
+                      session and used for front- and back-channel logout.",
+        versions: BOTH,
+        optional: true,
+        examples: &["0048c8f7-6a45-4bd0-a0d6-1cd1d1e0fa21"],
+    },
+    ClaimInfo {
+        name: "sub",
+        title: "Subject",
+        description: "Pairwise ID of the user, unique per application. Use oid instead to \
+                      identify a user across applications.",
+        versions: BOTH,
+        optional: false,
+        examples: &["AAAAAAAAAAAAAAAAAAAAAIkzqFVrSaSaFHy782bbtaQ"],
+    },
+    ClaimInfo {
+        name: "tenant_ctry",
+        title: "Tenant country",
+        description: "Two-letter country or region an admin configured for the tenant.",
+        versions: BOTH,
+        optional: true,
+        examples: &["US", "DE"],
+    },
+    ClaimInfo {
+        name: "tenant_region_scope",
+        title: "Tenant region",
+        description: "Region of the tenant the user belongs to.",
+        versions: BOTH,
+        optional: true,
+        examples: &["NA", "EU", "AS"],
+    },
+    ClaimInfo {
+        name: "tid",
+        title: "Tenant ID",
+        description: "Directory (tenant) ID the user signed in to. Microsoft accounts sign \
+                      in to the consumer tenant 9188040d-6c67-4c5b-b112-36a304b66dad.",
+        versions: BOTH,
+        optional: false,
+        examples: &["72f988bf-86f1-41af-91ab-2d7cd011db47"],
+    },
+    ClaimInfo {
+        name: "unique_name",
+        title: "Unique name",
+        description: "Human-readable name of the user, usually the UPN. Mutable, only for \
+                      display.",
+        versions: V1,
+        optional: false,
+        examples: &["adele.vance@contoso.com"],
+    },
+    ClaimInfo {
+        name: "upn",
+        title: "UPN",
+        description: "User principal name. Mutable and not present for guests in every \
+                      case, never use it as an identifier.",
+        versions: V1,
+        optional: true,
+        examples: &["adele.vance@contoso.com"],
+    },
+    ClaimInfo {
+        name: "uti",
+        title: "Token ID",
+        description: "Unique identifier of the token, used by Azure AD internally. Not \
+                      suitable for replay detection.",
+        versions: BOTH,
+        optional: false,
+        examples: &["p0yYG4gyJ0OvdXUy4ygxAA"],
+    },
+    ClaimInfo {
+        name: "ver",
+        title: "Version",
+        description: "Version of the access token, set by the accessTokenAcceptedVersion \
+                      of the API's app registration.",
+        versions: BOTH,
+        optional: false,
+        examples: &["1.0", "2.0"],
+    },
+    ClaimInfo {
+        name: "verified_primary_email",
+        title: "Verified primary email",
+        description: "Primary email address of the user from their directory profile, \
+                      verified by the tenant.",
+        versions: BOTH,
+        optional: true,
+        examples: &["[\"adele.vance@contoso.com\"]"],
+    },
+    ClaimInfo {
+        name: "wids",
+        title: "Directory roles",
+        description: "Template IDs of the Azure AD directory roles assigned to the user, \
+                      e.g. 62e90394-69f5-4237-9190-012177145e10 for Global Administrator.",
+        versions: BOTH,
+        optional: false,
+        examples: &["[\"b79fbf4d-3ef9-4689-8143-76b194e85509\"]"],
+    },
+    ClaimInfo {
+        name: "xms_cc",
+        title: "Client capabilities",
+        description: "Capabilities the client declared. cp1 means it handles claims \
+                      challenges, required for continuous access evaluation.",
+        versions: BOTH,
+        optional: false,
+        examples: &["[\"cp1\"]"],
+    },
+    ClaimInfo {
+        name: "xms_edov",
+        title: "Email domain owner verified",
+        description: "Whether the domain of the email claim is verified by the tenant \
+                      that owns it. Check it before trusting email for authorization.",
+        versions: BOTH,
+        optional: true,
+        examples: &["true", "1"],
+    },
+    ClaimInfo {
+        name: "xms_pdl",
+        title: "Preferred data location",
+        description: "Geographic location the user's data is stored in, for multi-geo \
+                      tenants. Apps should keep the user's data in the same geography.",
+        versions: BOTH,
+        optional: true,
+        examples: &["EUR", "NAM", "APC"],
+    },
+    ClaimInfo {
+        name: "xms_pl",
+        title: "User preferred language",
+        description: "Preferred language of the user, if set.",
+        versions: BOTH,
+        optional: true,
+        examples: &["en-US", "de-DE"],
+    },
+    ClaimInfo {
+        name: "xms_ssm",
+        title: "Session state",
+        description: "Internal marker of how Azure AD manages the sign-in session. Not \
+                      documented for use by applications.",
+        versions: BOTH,
+        optional: false,
+        examples: &["1"],
+    },
+    ClaimInfo {
+        name: "xms_tcdt",
+        title: "Tenant creation time",
+        description: "Time the user's tenant was created, in seconds since the Unix epoch.",
+        versions: BOTH,
+        optional: true,
+        examples: &["1490900000"],
+    },
+    ClaimInfo {
+        name: "xms_tpl",
+        title: "Tenant preferred language",
+        description: "Preferred language of the resource tenant, if set.",
+        versions: BOTH,
+        optional: true,
+        examples: &["en", "de"],
+    },
+];
+
+/// Looks up a claim by name, ignoring case
+pub fn lookup(name: &str) -> Option<&'static ClaimInfo> {
+    CLAIMS
+        .iter()
+        .find(|info| info.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Finds claims whose name contains, or is contained in, the given name
+///
+/// Used to suggest claims for a misspelled or partial name, e.g. `xms`.
+pub fn similar(name: &str) -> Vec<&'static ClaimInfo> {
+    let name = name.trim().to_ascii_lowercase();
+    if name.is_empty() {
+        return Vec::new();
+    }
+    CLAIMS
+        .iter()
+        .filter(|info| info.name.contains(name.as_str()) || name.contains(info.name))
+        .collect()
+}
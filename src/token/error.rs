@@ -0,0 +1,194 @@
+use jsonwebtoken::errors::{Error as JwtError, ErrorKind};
+use jsonwebtoken::Algorithm;
+use thiserror::Error;
+
+use crate::token::claims::Claims;
+use crate::token::cloud::Cloud;
+use crate::token::malformed::MalformedToken;
+use crate::token::raw::TokenShape;
+
+/// Validation failures callers may want to handle specifically
+///
+/// These are returned wrapped in `anyhow::Error` and can be recovered with
+/// `error.downcast_ref::<ValidationError>()`.
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("Signing algorithm {alg:?} is not allowed (allowed: {allowed:?})")]
+    AlgorithmNotAllowed {
+        alg: Algorithm,
+        allowed: Vec<Algorithm>,
+    },
+    #[error(
+        "Algorithm downgrade rejected: {alg:?} would use the public signing key as an HMAC secret"
+    )]
+    AlgorithmDowngrade { alg: Algorithm },
+    #[error("Unsupported signing algorithm '{0}'")]
+    UnsupportedAlgorithm(String),
+    #[error(
+        "Token expired {}s ago at {} (leeway {leeway}s)",
+        .now - .expires_at,
+        Claims::format_timestamp(*.expires_at)
+    )]
+    Expired {
+        expires_at: u64,
+        now: u64,
+        leeway: u64,
+    },
+    #[error(
+        "Token is not valid until {}, {}s from now (leeway {leeway}s)",
+        Claims::format_timestamp(*.not_before),
+        .not_before - .now
+    )]
+    NotYetValid {
+        not_before: u64,
+        now: u64,
+        leeway: u64,
+    },
+    #[error("Token was issued in the {cloud}, which is not accepted (accepted: {allowed:?})")]
+    CloudNotAllowed { cloud: Cloud, allowed: Vec<Cloud> },
+    #[error(
+        "Token was requested by {}, which is not an allowed client (allowed: {})",
+        .client.as_deref().unwrap_or("no named client (azp or appid)"),
+        .allowed.join(", ")
+    )]
+    ClientNotAllowed {
+        client: Option<String>,
+        allowed: Vec<String>,
+    },
+    #[error("Token was signed with key '{kid}', not with the pinned key {pinned}")]
+    KeyNotPinned { kid: String, pinned: String },
+    #[error(
+        "Signing key '{kid}' not found in the JWKS: the token was likely issued by a different tenant \
+         or cloud, or the key has rotated; try --tenant <tid>"
+    )]
+    KeyNotFound { kid: String },
+    #[error(
+        "Token has no 'kid' and none of the {tried} key(s) tried from the JWKS verifies its signature: \
+         the token was likely issued by a different tenant or signed by an emulator; try --tenant <tid>"
+    )]
+    NoMatchingKey { tried: usize },
+    #[error(
+        "Signing keys at {uri} are unavailable after {failures} failed fetch(es), retrying in {retry_in}s: {reason}"
+    )]
+    KeysUnavailable {
+        uri: String,
+        failures: u32,
+        retry_in: u64,
+        reason: String,
+    },
+    #[error(
+        "Tenant '{0}' is neither a GUID nor a single DNS label, no signing keys are fetched for it"
+    )]
+    InvalidTenant(String),
+    #[error("Token is malformed: {0}")]
+    Malformed(MalformedToken),
+    #[error("{}", .0.explanation().unwrap_or("Token is not a JWT"))]
+    NotAJwt(TokenShape),
+    /// A decoding or verification failure reported by `jsonwebtoken`
+    #[error("{}", explain(.0.kind()))]
+    Jwt(#[from] JwtError),
+}
+
+impl ValidationError {
+    /// Gets a short, stable identifier of the failure, e.g. `expired` or `invalid_signature`
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValidationError::AlgorithmNotAllowed { .. } => "algorithm_not_allowed",
+            ValidationError::AlgorithmDowngrade { .. } => "algorithm_downgrade",
+            ValidationError::UnsupportedAlgorithm(_) => "unsupported_algorithm",
+            ValidationError::Expired { .. } => "expired",
+            ValidationError::NotYetValid { .. } => "not_yet_valid",
+            ValidationError::CloudNotAllowed { .. } => "cloud_not_allowed",
+            ValidationError::ClientNotAllowed { .. } => "client_not_allowed",
+            ValidationError::KeyNotFound { .. } | ValidationError::NoMatchingKey { .. } => {
+                "key_not_found"
+            }
+            ValidationError::KeyNotPinned { .. } => "key_not_pinned",
+            ValidationError::KeysUnavailable { .. } => "keys_unavailable",
+            ValidationError::InvalidTenant(_) => "invalid_tenant",
+            ValidationError::Malformed(_) => "malformed",
+            ValidationError::NotAJwt(_) => "not_a_jwt",
+            ValidationError::Jwt(e) => match e.kind() {
+                ErrorKind::InvalidSignature => "invalid_signature",
+                ErrorKind::InvalidIssuer => "invalid_issuer",
+                ErrorKind::InvalidAudience => "invalid_audience",
+                ErrorKind::ExpiredSignature => "expired",
+                ErrorKind::ImmatureSignature => "not_yet_valid",
+                ErrorKind::MissingRequiredClaim(_) => "missing_claim",
+                ErrorKind::InvalidRsaKey(_)
+                | ErrorKind::InvalidEcdsaKey
+                | ErrorKind::InvalidKeyFormat => "invalid_key",
+                _ => "malformed",
+            },
+        }
+    }
+}
+
+/// Gets the failure code of any validation error, `keys_unavailable` if keys could not be fetched
+pub fn failure_code(error: &anyhow::Error) -> &'static str {
+    if let Some(error) = error.downcast_ref::<ValidationError>() {
+        return error.code();
+    }
+    #[cfg(feature = "net")]
+    if error.chain().any(|cause| cause.is::<reqwest::Error>()) {
+        return "keys_unavailable";
+    }
+    "other"
+}
+
+/// Turns a `jsonwebtoken` error kind into a message saying what likely went wrong
+fn explain(kind: &ErrorKind) -> String {
+    match kind {
+        ErrorKind::InvalidToken => {
+            "Token is malformed: expected three base64url segments separated by dots (header.payload.signature)".to_string()
+        }
+        ErrorKind::InvalidSignature => {
+            "Signature invalid: the token was likely issued by a different tenant or the signing key has rotated; try --tenant <tid>".to_string()
+        }
+        ErrorKind::InvalidEcdsaKey | ErrorKind::InvalidKeyFormat | ErrorKind::RsaFailedSigning => {
+            "Signing key from the JWKS is unusable: the key set may be corrupt or use an unsupported key type".to_string()
+        }
+        ErrorKind::InvalidRsaKey(reason) => format!(
+            "RSA signing key from the JWKS is invalid ({}): the key set may be corrupt",
+            reason
+        ),
+        ErrorKind::InvalidAlgorithmName => {
+            "Unknown signing algorithm in the token header".to_string()
+        }
+        ErrorKind::MissingRequiredClaim(claim) => {
+            format!("Token has no '{}' claim, which is required", claim)
+        }
+        ErrorKind::ExpiredSignature => {
+            "Token has expired; use --skip-expiration to inspect it anyway".to_string()
+        }
+        ErrorKind::InvalidIssuer => {
+            "Issuer does not match: the token was issued by another tenant or cloud".to_string()
+        }
+        ErrorKind::InvalidAudience => {
+            "Audience does not match: the token was issued for a different API or app".to_string()
+        }
+        ErrorKind::InvalidSubject => "Subject does not match the expected value".to_string(),
+        ErrorKind::ImmatureSignature => {
+            "Token is not valid yet: check the clock of this machine".to_string()
+        }
+        ErrorKind::InvalidAlgorithm | ErrorKind::MissingAlgorithm => {
+            "Token algorithm does not match the signing key: the key set may belong to another issuer".to_string()
+        }
+        ErrorKind::Base64(e) => format!(
+            "Token is not valid base64url ({}): it may have been truncated or wrapped when copying",
+            e
+        ),
+        ErrorKind::Json(e) => format!(
+            "Token header or payload is not valid JSON, or a claim has an unexpected type ({})",
+            e
+        ),
+        ErrorKind::Utf8(e) => format!(
+            "Token is not valid UTF-8 ({}): it may have been truncated when copying",
+            e
+        ),
+        ErrorKind::Crypto(_) => {
+            "Signature could not be verified: the key does not fit the token's algorithm".to_string()
+        }
+        other => format!("{:?}", other),
+    }
+}
@@ -0,0 +1,336 @@
+//! Trace of every rule a validation evaluates, with the values it compares
+//!
+//! A failed validation reports the first rule a token breaks. The trace
+//! evaluates each rule on its own instead, so it also shows the rules behind
+//! the first failure and why the others passed or were skipped.
+
+use jsonwebtoken::Algorithm;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::token::claims::Claims;
+use crate::token::cloud::Cloud;
+use crate::token::jwk::{Jwk, JwksResponse};
+use crate::token::verify::{
+    accepted_audiences, check_algorithm, check_client, check_pinned_key, decode_unverified,
+    find_signing_key, issuer_tenant, resolve_tenant, ValidatorConfig, AUTO_TENANT,
+};
+
+/// Tenants accepting tokens of any tenant
+const MULTI_TENANT: &[&str] = &["common", "organizations", "consumers", AUTO_TENANT];
+
+/// Outcome of one rule of a validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleStatus {
+    Passed,
+    Failed,
+    /// Not configured, or not evaluable because of another rule
+    Skipped,
+}
+
+/// One rule of a validation with the values it compared
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleEvaluation {
+    /// Short name of the rule, e.g. `signature`, `exp` or `aud`
+    pub rule: &'static str,
+    pub status: RuleStatus,
+    pub detail: String,
+}
+
+impl RuleEvaluation {
+    fn new(rule: &'static str, status: RuleStatus, detail: impl Into<String>) -> Self {
+        RuleEvaluation {
+            rule,
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    fn check(rule: &'static str, passed: bool, detail: impl Into<String>) -> Self {
+        let status = match passed {
+            true => RuleStatus::Passed,
+            false => RuleStatus::Failed,
+        };
+        Self::new(rule, status, detail)
+    }
+
+    fn skipped(rule: &'static str, detail: impl Into<String>) -> Self {
+        Self::new(rule, RuleStatus::Skipped, detail)
+    }
+}
+
+/// Evaluates every rule of a validation against a token
+///
+/// `jwks` is the key set the signature is verified with; without it the
+/// signature and key pinning rules are skipped. A token that cannot be
+/// decoded yields a single failed `decode` rule.
+pub fn explain_validation(
+    config: &ValidatorConfig,
+    token: &str,
+    jwks: Option<&JwksResponse>,
+) -> Vec<RuleEvaluation> {
+    let (header, claims) = match decode_unverified(token) {
+        Ok(decoded) => decoded,
+        Err(e) => return vec![RuleEvaluation::check("decode", false, e.to_string())],
+    };
+
+    let mut rules = Vec::new();
+    let alg = check_algorithm(config, &header);
+    rules.push(match &alg {
+        Ok(alg) => RuleEvaluation::check(
+            "alg",
+            true,
+            format!(
+                "{:?}, allowed {}",
+                alg,
+                algorithms(&config.allowed_algorithms)
+            ),
+        ),
+        Err(e) => RuleEvaluation::check("alg", false, e.to_string()),
+    });
+
+    let (signature, jwk) = match (alg, jwks) {
+        (Err(_), _) => (
+            RuleEvaluation::skipped("signature", "the algorithm is not accepted"),
+            None,
+        ),
+        (_, None) => (
+            RuleEvaluation::skipped("signature", "the signing keys are unavailable"),
+            None,
+        ),
+        (Ok(alg), Some(jwks)) => explain_signature(token, &header, alg, jwks),
+    };
+    rules.push(signature);
+    rules.push(explain_pinned_key(config, jwk));
+
+    rules.push(RuleEvaluation::check(
+        "required_claims",
+        true,
+        "iss, sub, aud, exp and iat are present",
+    ));
+    rules.extend(explain_time_claims(config, &claims));
+    rules.push(explain_issuer(config, &claims));
+    rules.push(explain_audience(config, &claims));
+    rules.push(explain_tenant(config, &claims));
+    rules.push(match Cloud::of_issuer(&claims.iss) {
+        Some(cloud) => RuleEvaluation::check(
+            "cloud",
+            config.clouds.contains(&cloud),
+            format!("{}, allowed {}", cloud, clouds(&config.clouds)),
+        ),
+        None => RuleEvaluation::skipped("cloud", "the issuer is not of a known cloud"),
+    });
+    rules.push(match config.allowed_clients.is_empty() {
+        true => RuleEvaluation::skipped("client", "any client is accepted"),
+        false => RuleEvaluation::check(
+            "client",
+            check_client(config, &claims).is_ok(),
+            format!(
+                "{}, allowed {}",
+                claims.authorized_party().unwrap_or("no azp or appid claim"),
+                config.allowed_clients.join(", ")
+            ),
+        ),
+    });
+    rules
+}
+
+/// Verifies only the signature, returning the key it was verified with
+fn explain_signature<'a>(
+    token: &str,
+    header: &Value,
+    alg: Algorithm,
+    jwks: &'a JwksResponse,
+) -> (RuleEvaluation, Option<&'a Jwk>) {
+    let Some(kid) = header["kid"].as_str() else {
+        return match find_signing_key(token, alg, jwks) {
+            Ok(Some(jwk)) => (
+                RuleEvaluation::check(
+                    "signature",
+                    true,
+                    format!("no kid, verified with key '{}' of the key set", jwk.kid),
+                ),
+                Some(jwk),
+            ),
+            _ => (
+                RuleEvaluation::check(
+                    "signature",
+                    false,
+                    format!(
+                        "no kid, no key of the {} in the key set matches",
+                        jwks.keys.len()
+                    ),
+                ),
+                None,
+            ),
+        };
+    };
+
+    let Some(jwk) = jwks.find_key(kid) else {
+        let detail = format!(
+            "key '{}' is not one of the {} keys of the key set",
+            kid,
+            jwks.keys.len()
+        );
+        return (RuleEvaluation::check("signature", false, detail), None);
+    };
+    let verified = token.rsplit_once('.').is_some_and(|(message, signature)| {
+        jwk.to_decoding_key().is_ok_and(|key| {
+            jsonwebtoken::crypto::verify(signature, message.as_bytes(), &key, alg).unwrap_or(false)
+        })
+    });
+    let detail = match verified {
+        true => format!("{:?} signature verified with key '{}'", alg, kid),
+        false => format!("{:?} signature does not match key '{}'", alg, kid),
+    };
+    (
+        RuleEvaluation::check("signature", verified, detail),
+        Some(jwk),
+    )
+}
+
+fn explain_pinned_key(config: &ValidatorConfig, jwk: Option<&Jwk>) -> RuleEvaluation {
+    let pinned = config
+        .pinned_kid
+        .iter()
+        .chain(&config.pinned_thumbprint)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    match jwk {
+        _ if pinned.is_empty() => RuleEvaluation::skipped("pinned_key", "no key is pinned"),
+        None => RuleEvaluation::skipped("pinned_key", "the signing key was not found"),
+        Some(jwk) => RuleEvaluation::check(
+            "pinned_key",
+            check_pinned_key(config, jwk).is_ok(),
+            format!("key '{}', pinned {}", jwk.kid, pinned),
+        ),
+    }
+}
+
+fn explain_time_claims(config: &ValidatorConfig, claims: &Claims) -> [RuleEvaluation; 2] {
+    let now = config.clock.now();
+    let leeway = config.leeway;
+    let time = |timestamp: u64| format!("{} ({})", timestamp, Claims::format_timestamp(timestamp));
+
+    let exp = match config.validate_exp {
+        false => RuleEvaluation::skipped("exp", "expiry validation is disabled"),
+        true => {
+            let passed = now <= claims.exp.saturating_add(leeway);
+            RuleEvaluation::check(
+                "exp",
+                passed,
+                format!(
+                    "exp {} + {}s leeway {} now {}",
+                    time(claims.exp),
+                    leeway,
+                    if passed { ">=" } else { "<" },
+                    time(now)
+                ),
+            )
+        }
+    };
+    let nbf = match (config.validate_nbf, claims.nbf) {
+        (false, _) => RuleEvaluation::skipped("nbf", "not-before validation is disabled"),
+        (true, None) => RuleEvaluation::skipped("nbf", "the token has no nbf claim"),
+        (true, Some(nbf)) => {
+            let passed = nbf <= now.saturating_add(leeway);
+            RuleEvaluation::check(
+                "nbf",
+                passed,
+                format!(
+                    "nbf {} - {}s leeway {} now {}",
+                    time(nbf),
+                    leeway,
+                    if passed { "<=" } else { ">" },
+                    time(now)
+                ),
+            )
+        }
+    };
+    [exp, nbf]
+}
+
+fn explain_issuer(config: &ValidatorConfig, claims: &Claims) -> RuleEvaluation {
+    match (config.validate_iss, &config.issuer) {
+        (false, _) => RuleEvaluation::skipped("iss", "issuer validation is disabled"),
+        (true, None) => RuleEvaluation::check(
+            "iss",
+            true,
+            format!(
+                "'{}', no issuer is configured so the token's own is accepted",
+                claims.iss
+            ),
+        ),
+        (true, Some(issuer)) => RuleEvaluation::check(
+            "iss",
+            claims.iss == *issuer,
+            format!("'{}', expected '{}'", claims.iss, issuer),
+        ),
+    }
+}
+
+fn explain_audience(config: &ValidatorConfig, claims: &Claims) -> RuleEvaluation {
+    if !config.validate_aud {
+        return RuleEvaluation::skipped("aud", "audience validation is disabled");
+    }
+    let accepted = accepted_audiences(config);
+    let audiences = claims.audiences();
+    match audiences.iter().find(|aud| accepted.contains(aud)) {
+        Some(aud) => RuleEvaluation::check("aud", true, format!("'{}' is accepted", aud)),
+        None => RuleEvaluation::check(
+            "aud",
+            false,
+            format!(
+                "'{}' is not one of {}",
+                claims.audience_display(),
+                accepted.join(", ")
+            ),
+        ),
+    }
+}
+
+/// The tenant selects the signing keys, tokens of other tenants are only rejected by `iss`
+fn explain_tenant(config: &ValidatorConfig, claims: &Claims) -> RuleEvaluation {
+    let keys_of = resolve_tenant(&config.tenant_id, claims);
+    let token_tenant = claims.tenant_id().or_else(|| issuer_tenant(&claims.iss));
+    match token_tenant {
+        _ if MULTI_TENANT.contains(&config.tenant_id.as_str()) => RuleEvaluation::skipped(
+            "tenant",
+            format!(
+                "any tenant is accepted with '{}', keys of '{}' are used",
+                config.tenant_id, keys_of
+            ),
+        ),
+        Some(tenant) if tenant.eq_ignore_ascii_case(&config.tenant_id) => RuleEvaluation::check(
+            "tenant",
+            true,
+            format!("'{}' is the configured tenant", tenant),
+        ),
+        tenant => RuleEvaluation::skipped(
+            "tenant",
+            format!(
+                "'{}' is not the configured tenant '{}', only an expected issuer rejects it",
+                tenant.unwrap_or("unknown"),
+                config.tenant_id
+            ),
+        ),
+    }
+}
+
+fn algorithms(algorithms: &[Algorithm]) -> String {
+    algorithms
+        .iter()
+        .map(|alg| format!("{:?}", alg))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn clouds(clouds: &[Cloud]) -> String {
+    clouds
+        .iter()
+        .map(Cloud::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
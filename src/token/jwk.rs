@@ -1,29 +1,116 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use jsonwebtoken::DecodingKey;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use simple_asn1::ASN1Block;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::token::error::ValidationError;
+use crate::token::saml::certificate_pem;
 
 /// Represents a JSON Web Key from Azure AD
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[allow(dead_code)]
 pub struct Jwk {
     pub kid: String,
     pub kty: String,
     #[serde(rename = "use")]
     pub usage: Option<String>,
-    pub n: String,
-    pub e: String,
+    /// RSA modulus, absent from some AD FS and B2C keys that only carry `x5c`
+    pub n: Option<String>,
+    /// RSA public exponent, absent along with `n`
+    pub e: Option<String>,
+    /// Certificate chain as base64-encoded DER, the first certifying the key
+    #[serde(default)]
+    pub x5c: Vec<String>,
+    /// Base64url-encoded SHA-1 thumbprint of the certificate
+    pub x5t: Option<String>,
 }
 
 impl Jwk {
     /// Converts a JWK to a DecodingKey for token validation
+    ///
+    /// The key is taken from the modulus and exponent, or from the first
+    /// `x5c` certificate if they are missing.
     pub fn to_decoding_key(&self) -> Result<DecodingKey> {
-        // jsonwebtoken's from_rsa_components expects the raw base64 strings from the JWK
-        Ok(DecodingKey::from_rsa_components(&self.n, &self.e)?)
+        let key = match (&self.n, &self.e, self.x5c.first()) {
+            // jsonwebtoken's from_rsa_components expects the raw base64 strings from the JWK
+            (Some(n), Some(e), _) => DecodingKey::from_rsa_components(n, e),
+            (_, _, Some(certificate)) => DecodingKey::from_rsa_pem(
+                certificate_pem(&certificate.split_whitespace().collect::<String>()).as_bytes(),
+            ),
+            _ => {
+                return Err(anyhow!(
+                    "Key '{}' has neither a modulus and exponent (n, e) nor a certificate (x5c)",
+                    self.kid
+                ))
+            }
+        };
+        Ok(key.map_err(ValidationError::from)?)
+    }
+
+    /// Gets the base64url SHA-1 thumbprint of the key's certificate (`x5t`)
+    ///
+    /// Computed from the first `x5c` certificate if the key does not carry it.
+    pub fn thumbprint(&self) -> Option<String> {
+        if let Some(x5t) = &self.x5t {
+            return Some(x5t.clone());
+        }
+        let der = self.certificate_der()?;
+        Some(base64_url::encode(&Sha1::digest(&der)))
+    }
+
+    /// Checks a thumbprint given as base64url (`x5t`) or as hex, as shown in the Azure portal
+    pub fn has_thumbprint(&self, thumbprint: &str) -> bool {
+        let Some(own) = self.thumbprint() else {
+            return false;
+        };
+        let hex: String = base64_url::decode(&own)
+            .unwrap_or_default()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        own == thumbprint
+            || (!hex.is_empty() && hex.eq_ignore_ascii_case(&thumbprint.replace(':', "")))
+    }
+
+    /// Gets when the key's certificate expires (`notAfter`) as seconds since the Unix epoch
+    pub fn certificate_expiry(&self) -> Option<u64> {
+        let blocks = simple_asn1::from_der(&self.certificate_der()?).ok()?;
+        let ASN1Block::Sequence(_, certificate) = blocks.first()? else {
+            return None;
+        };
+        let ASN1Block::Sequence(_, tbs) = certificate.first()? else {
+            return None;
+        };
+        // The version is absent from v1 certificates
+        let fields = match tbs.first()? {
+            ASN1Block::Explicit(..) => &tbs[1..],
+            _ => &tbs[..],
+        };
+        // Serial number, signature algorithm, issuer, then the validity period
+        let ASN1Block::Sequence(_, validity) = fields.get(3)? else {
+            return None;
+        };
+        match validity.get(1)? {
+            ASN1Block::UTCTime(_, time) | ASN1Block::GeneralizedTime(_, time) => {
+                Some(time.assume_utc().unix_timestamp().max(0) as u64)
+            }
+            _ => None,
+        }
+    }
+
+    fn certificate_der(&self) -> Option<Vec<u8>> {
+        let certificate: String = self.x5c.first()?.split_whitespace().collect();
+        STANDARD.decode(certificate).ok()
     }
 }
 
 /// Represents a response from a JWKS endpoint
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct JwksResponse {
     pub keys: Vec<Jwk>,
 }
@@ -34,3 +121,32 @@ impl JwksResponse {
         self.keys.iter().find(|key| key.kid == kid)
     }
 }
+
+/// How long fetched keys are used before they are fetched again
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Minimum age of cached keys before a token naming an unknown key causes a refetch
+#[cfg(any(feature = "net", feature = "blocking"))]
+pub(crate) const MIN_REFETCH_AGE: Duration = Duration::from_secs(30);
+
+/// A key set and when it was fetched
+#[derive(Debug, Clone)]
+pub struct CachedKeys {
+    pub jwks: Arc<JwksResponse>,
+    pub fetched_at: SystemTime,
+}
+
+impl CachedKeys {
+    /// Wraps a key set fetched just now
+    pub fn new(jwks: Arc<JwksResponse>) -> Self {
+        CachedKeys {
+            jwks,
+            fetched_at: SystemTime::now(),
+        }
+    }
+
+    /// Gets how long ago the keys were fetched, zero if by a clock ahead of ours
+    pub fn age(&self) -> Duration {
+        self.fetched_at.elapsed().unwrap_or_default()
+    }
+}
@@ -0,0 +1,512 @@
+use anyhow::{anyhow, Result};
+use reqwest::{header, Client};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+use crate::token::cache::{CachedKeys, KeyCache, MemoryCache, MAX_KEY_SETS};
+use crate::token::clock::{skew_from_date_header, Clock, SystemClock};
+use crate::token::error::ValidationError;
+use crate::token::jwk::JwksResponse;
+use crate::token::spans::Span;
+use crate::token::telemetry;
+
+pub use crate::token::jwk::DEFAULT_TTL;
+use crate::token::jwk::MIN_REFETCH_AGE;
+
+/// How long a failed fetch is remembered, failing lookups fast instead of retrying
+const NEGATIVE_TTL: Duration = Duration::from_secs(5);
+
+/// Consecutive failed fetches of a key set that open its circuit
+const FAILURE_THRESHOLD: u32 = 3;
+
+/// How long an open circuit fails lookups before letting a probe fetch through
+const OPEN_DURATION: Duration = Duration::from_secs(30);
+
+/// Consecutive failed fetches of a JWKS URI
+#[derive(Debug, Clone)]
+struct FetchFailures {
+    count: u32,
+    last_at: Instant,
+    reason: String,
+}
+
+impl FetchFailures {
+    /// How long after the last failure lookups fail without fetching
+    fn backoff(&self) -> Duration {
+        match self.count >= FAILURE_THRESHOLD {
+            true => OPEN_DURATION,
+            false => NEGATIVE_TTL,
+        }
+    }
+}
+
+/// State of the circuit breaker guarding the fetches of a key set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Fetches go through
+    Closed,
+    /// Fetches failed repeatedly, lookups fail fast until `retry_in` has passed
+    Open { retry_in: Duration },
+    /// The next fetch is a probe, closing the circuit if it succeeds
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct KeyStoreInner {
+    client: Client,
+    ttl: Duration,
+    entries: MemoryCache,
+    /// Cache shared with other key stores or processes, consulted before fetching
+    shared: Option<Arc<dyn KeyCache>>,
+    // One lock per JWKS URI being fetched so concurrent misses result in a single fetch
+    fetch_locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    /// Failures of up to [`MAX_KEY_SETS`] URIs, the least recent are forgotten beyond it
+    failures: Mutex<HashMap<String, FetchFailures>>,
+    counters: Counters,
+    /// Skew of the system clock measured at the last fetch
+    clock_skew: Mutex<Option<i64>>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    fetches: AtomicU64,
+    fetch_errors: AtomicU64,
+    fetch_micros: AtomicU64,
+}
+
+/// Cache and fetch counters of a [`KeyStore`] since it was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyStoreStats {
+    /// Lookups served from the cache
+    pub hits: u64,
+    /// Lookups that found no fresh key set and fetched it
+    pub misses: u64,
+    /// JWKS fetches, including refreshes
+    pub fetches: u64,
+    /// Fetches that failed
+    pub fetch_errors: u64,
+    /// Total time spent fetching
+    pub fetch_time: Duration,
+}
+
+impl KeyStoreStats {
+    /// Average time of a fetch, if any were made
+    pub fn average_fetch_time(&self) -> Option<Duration> {
+        (self.fetches > 0).then(|| self.fetch_time / self.fetches as u32)
+    }
+}
+
+/// Shared, thread-safe JWKS cache
+///
+/// Cloning a `KeyStore` is cheap and yields a handle to the same cache, so
+/// one store can back any number of validators. Concurrent requests for the
+/// same URI are coalesced into a single HTTP fetch.
+///
+/// A failed fetch is remembered for 5 seconds, during which lookups of the
+/// key set fail without a request. After 3 consecutive failures its circuit
+/// opens for 30 seconds, after which a single probe fetch decides whether it
+/// closes again. Transitions are logged under the `azure_token_validator`
+/// target of the `log` facade.
+///
+/// Keys are kept in memory, up to [`MAX_KEY_SETS`] key sets. A store created with [`KeyStore::with_cache`]
+/// also keeps them in a [`KeyCache`], e.g. on disk or in Redis, and uses
+/// keys found there while they are younger than its TTL.
+#[derive(Debug, Clone)]
+pub struct KeyStore {
+    inner: Arc<KeyStoreInner>,
+}
+
+impl Default for KeyStore {
+    fn default() -> Self {
+        Self::new(Client::new())
+    }
+}
+
+impl KeyStore {
+    /// Creates a new key store using the given HTTP client
+    pub fn new(client: Client) -> Self {
+        Self::with_ttl(client, DEFAULT_TTL)
+    }
+
+    /// Creates a new key store that refetches keys older than `ttl`
+    pub fn with_ttl(client: Client, ttl: Duration) -> Self {
+        Self::with_parts(client, ttl, None)
+    }
+
+    /// Creates a new key store sharing the keys it fetches through `cache`
+    ///
+    /// Keys are looked up in the cache before they are fetched and stored in
+    /// it after every fetch. Errors of the cache are logged and otherwise
+    /// ignored, so an unavailable cache only costs fetches.
+    pub fn with_cache(client: Client, ttl: Duration, cache: Arc<dyn KeyCache>) -> Self {
+        Self::with_parts(client, ttl, Some(cache))
+    }
+
+    fn with_parts(client: Client, ttl: Duration, shared: Option<Arc<dyn KeyCache>>) -> Self {
+        KeyStore {
+            inner: Arc::new(KeyStoreInner {
+                client,
+                ttl,
+                entries: MemoryCache::new(),
+                shared,
+                fetch_locks: Mutex::new(HashMap::new()),
+                failures: Mutex::new(HashMap::new()),
+                counters: Counters::default(),
+                clock_skew: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Gets JWKS from the cache, fetching it if missing or expired
+    pub async fn get(&self, uri: &str) -> Result<Arc<JwksResponse>> {
+        if let Some(jwks) = self.cached(uri).await {
+            self.record_lookup(true);
+            return Ok(jwks);
+        }
+
+        self.locked(uri, async {
+            // Another task may have completed the fetch while we were waiting
+            if let Some(jwks) = self.cached(uri).await {
+                self.record_lookup(true);
+                return Ok(jwks);
+            }
+
+            self.record_lookup(false);
+            self.fetch(uri).await
+        })
+        .await
+    }
+
+    /// Fetches JWKS from the given URI, bypassing and updating the cache
+    pub async fn refresh(&self, uri: &str) -> Result<Arc<JwksResponse>> {
+        let result = self.locked(uri, self.fetch(uri)).await;
+        telemetry::record_jwks_refresh(result.is_ok());
+        result
+    }
+
+    /// Fetches JWKS again unless the cached keys were fetched moments ago
+    ///
+    /// Used when a token names a key missing from the cached set, so keys
+    /// published by a rotation are picked up at once while tokens with
+    /// made-up key IDs cannot make the store refetch more than every 30 seconds.
+    pub async fn refetch(&self, uri: &str) -> Result<Arc<JwksResponse>> {
+        self.locked(uri, async {
+            // Keys fetched while we were waiting, or just before, are as fresh as it gets
+            if let Some(jwks) = self.lookup(uri, MIN_REFETCH_AGE).await {
+                return Ok(jwks);
+            }
+
+            let result = self.fetch(uri).await;
+            telemetry::record_jwks_refresh(result.is_ok());
+            result
+        })
+        .await
+    }
+
+    /// Caches the given key set for `uri` as if it had just been fetched
+    ///
+    /// Useful to validate against known keys without network access.
+    pub async fn insert(&self, uri: &str, jwks: JwksResponse) {
+        self.store(uri, CachedKeys::new(Arc::new(jwks))).await;
+    }
+
+    /// Refreshes all cached JWKS documents every `interval` in a background task
+    ///
+    /// Errors are ignored, keeping the previously cached keys in use until
+    /// the next successful refresh. The task runs until the handle is aborted.
+    pub fn spawn_background_refresh(&self, interval: Duration) -> JoinHandle<()> {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await; // The first tick completes immediately
+            loop {
+                ticker.tick().await;
+                for (uri, _) in store.inner.entries.fetched().await {
+                    let _ = store.refresh(&uri).await;
+                }
+            }
+        })
+    }
+
+    /// Cache hits, misses and fetch times so far
+    pub fn stats(&self) -> KeyStoreStats {
+        let counters = &self.inner.counters;
+        KeyStoreStats {
+            hits: counters.hits.load(Ordering::Relaxed),
+            misses: counters.misses.load(Ordering::Relaxed),
+            fetches: counters.fetches.load(Ordering::Relaxed),
+            fetch_errors: counters.fetch_errors.load(Ordering::Relaxed),
+            fetch_time: Duration::from_micros(counters.fetch_micros.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Gets how far the system clock was ahead of Azure AD at the last fetch, in seconds
+    ///
+    /// Measured from the `Date` header of the JWKS response, negative when the
+    /// clock is behind. `None` until a key set was fetched from a server sending it.
+    pub fn clock_skew(&self) -> Option<i64> {
+        *self
+            .inner
+            .clock_skew
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Gets the state of the circuit breaker guarding fetches from `uri`
+    pub fn circuit_state(&self, uri: &str) -> CircuitState {
+        match self.failures(uri) {
+            Some(failures) if failures.count >= FAILURE_THRESHOLD => {
+                match OPEN_DURATION.checked_sub(failures.last_at.elapsed()) {
+                    Some(retry_in) if !retry_in.is_zero() => CircuitState::Open { retry_in },
+                    _ => CircuitState::HalfOpen,
+                }
+            }
+            _ => CircuitState::Closed,
+        }
+    }
+
+    fn failures(&self, uri: &str) -> Option<FetchFailures> {
+        self.inner
+            .failures
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(uri)
+            .cloned()
+    }
+
+    /// Updates the failures of `uri` with the outcome of a fetch
+    fn record_outcome(&self, uri: &str, result: &Result<Arc<JwksResponse>>) {
+        let mut failures = self
+            .inner
+            .failures
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let error = match result {
+            Ok(_) => {
+                if let Some(previous) = failures.remove(uri) {
+                    if previous.count >= FAILURE_THRESHOLD {
+                        log::info!("JWKS circuit for {} closed: fetch succeeded", uri);
+                    }
+                }
+                return;
+            }
+            Err(e) => e,
+        };
+
+        if failures.len() >= MAX_KEY_SETS && !failures.contains_key(uri) {
+            let oldest = failures
+                .iter()
+                .min_by_key(|(_, failure)| failure.last_at)
+                .map(|(uri, _)| uri.clone());
+            if let Some(oldest) = oldest {
+                failures.remove(&oldest);
+            }
+        }
+        let entry = failures.entry(uri.to_string()).or_insert(FetchFailures {
+            count: 0,
+            last_at: Instant::now(),
+            reason: String::new(),
+        });
+        entry.count += 1;
+        entry.last_at = Instant::now();
+        entry.reason = format!("{:#}", error);
+        match entry.count.cmp(&FAILURE_THRESHOLD) {
+            std::cmp::Ordering::Less => log::debug!(
+                "Fetching JWKS from {} failed ({} in a row), failing lookups for {}s: {}",
+                uri,
+                entry.count,
+                NEGATIVE_TTL.as_secs(),
+                entry.reason
+            ),
+            std::cmp::Ordering::Equal => log::warn!(
+                "JWKS circuit for {} opened after {} failed fetches, failing lookups for {}s: {}",
+                uri,
+                entry.count,
+                OPEN_DURATION.as_secs(),
+                entry.reason
+            ),
+            std::cmp::Ordering::Greater => log::warn!(
+                "JWKS circuit for {} reopened: probe fetch failed: {}",
+                uri,
+                entry.reason
+            ),
+        }
+    }
+
+    fn record_lookup(&self, hit: bool) {
+        let counter = match hit {
+            true => &self.inner.counters.hits,
+            false => &self.inner.counters.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        telemetry::record_jwks_lookup(hit);
+    }
+
+    /// Refreshes cached JWKS documents `ahead` of their expiry in a background task
+    ///
+    /// Unlike [`KeyStore::spawn_background_refresh`], each key set is only
+    /// fetched again shortly before the TTL would make a validation wait for
+    /// it. Failed refreshes keep the cached keys and are retried on the next
+    /// check. The task runs until the handle is aborted.
+    pub fn spawn_refresh_ahead(&self, ahead: Duration) -> JoinHandle<()> {
+        let store = self.clone();
+        let check_every = (ahead / 4).clamp(Duration::from_secs(1), Duration::from_secs(60));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(check_every);
+            loop {
+                ticker.tick().await;
+                let refresh_after = store.inner.ttl.saturating_sub(ahead);
+                let due =
+                    store
+                        .inner
+                        .entries
+                        .fetched()
+                        .await
+                        .into_iter()
+                        .filter(|(_, fetched_at)| {
+                            fetched_at.elapsed().unwrap_or_default() >= refresh_after
+                        });
+                for (uri, _) in due {
+                    let _ = store.refresh(&uri).await;
+                }
+            }
+        })
+    }
+
+    async fn cached(&self, uri: &str) -> Option<Arc<JwksResponse>> {
+        self.lookup(uri, self.inner.ttl).await
+    }
+
+    /// Gets keys fetched less than `max_age` ago, from memory or the shared cache
+    async fn lookup(&self, uri: &str, max_age: Duration) -> Option<Arc<JwksResponse>> {
+        if let Some(keys) = self.inner.entries.get(uri).await {
+            if keys.age() < max_age {
+                return Some(keys.jwks);
+            }
+        }
+
+        let shared = self.inner.shared.as_ref()?;
+        let keys = match shared.load(uri).await {
+            Ok(keys) => keys?,
+            Err(e) => {
+                log::warn!("Reading cached JWKS of {} failed: {:#}", uri, e);
+                return None;
+            }
+        };
+        if keys.age() >= max_age {
+            return None;
+        }
+        log::debug!("Using JWKS of {} from the shared cache", uri);
+        let jwks = keys.jwks.clone();
+        self.inner.entries.insert(uri, keys).await;
+        Some(jwks)
+    }
+
+    /// Keeps keys in memory and in the shared cache
+    async fn store(&self, uri: &str, keys: CachedKeys) {
+        self.inner.entries.insert(uri, keys.clone()).await;
+        if let Some(shared) = &self.inner.shared {
+            if let Err(e) = shared.store(uri, keys).await {
+                log::warn!("Caching JWKS of {} failed: {:#}", uri, e);
+            }
+        }
+    }
+
+    /// Runs `task` holding the fetch lock of `uri`, removed again once no other task holds it
+    async fn locked<T>(&self, uri: &str, task: impl Future<Output = T>) -> T {
+        let lock = self
+            .inner
+            .fetch_locks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(uri.to_string())
+            .or_default()
+            .clone();
+        let result = {
+            let _guard = lock.lock().await;
+            task.await
+        };
+
+        let mut locks = self
+            .inner
+            .fetch_locks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Held by the map and this task only, so no other task is waiting for it
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(uri);
+        }
+        result
+    }
+
+    async fn fetch(&self, uri: &str) -> Result<Arc<JwksResponse>> {
+        if let Some(failures) = self.failures(uri) {
+            let since = failures.last_at.elapsed();
+            if since < failures.backoff() {
+                log::debug!("JWKS lookup of {} failed fast: recent fetches failed", uri);
+                return Err(ValidationError::KeysUnavailable {
+                    uri: uri.to_string(),
+                    failures: failures.count,
+                    retry_in: (failures.backoff() - since).as_secs().max(1),
+                    reason: failures.reason,
+                }
+                .into());
+            }
+            if failures.count >= FAILURE_THRESHOLD {
+                log::info!("JWKS circuit for {} half-open: probing", uri);
+            }
+        }
+
+        let started = Instant::now();
+        let span = Span::start("fetch_jwks");
+        span.attribute("uri", uri);
+        let result = self.download(uri).await;
+        span.outcome(&result);
+        let elapsed = started.elapsed();
+        self.record_outcome(uri, &result);
+
+        let counters = &self.inner.counters;
+        counters.fetches.fetch_add(1, Ordering::Relaxed);
+        counters
+            .fetch_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        if result.is_err() {
+            counters.fetch_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        telemetry::record_jwks_fetch(result.is_ok(), elapsed);
+        if result.is_ok() {
+            log::debug!("Fetched JWKS from {} in {}ms", uri, elapsed.as_millis());
+        }
+        result
+    }
+
+    async fn download(&self, uri: &str) -> Result<Arc<JwksResponse>> {
+        let response = self.inner.client.get(uri).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Failed to fetch JWKS: {}", response.status()));
+        }
+        let skew = response
+            .headers()
+            .get(header::DATE)
+            .and_then(|date| date.to_str().ok())
+            .and_then(|date| skew_from_date_header(date, SystemClock.now()));
+        if let Some(skew) = skew {
+            log::debug!("System clock is {}s ahead of {}", skew, uri);
+            *self
+                .inner
+                .clock_skew
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(skew);
+        }
+
+        let jwks = Arc::new(response.json::<JwksResponse>().await?);
+        self.store(uri, CachedKeys::new(jwks.clone())).await;
+        Ok(jwks)
+    }
+}
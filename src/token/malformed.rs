@@ -0,0 +1,181 @@
+//! Diagnostics for tokens that cannot be decoded
+//!
+//! Tokens are mostly broken in transit: cut off by a terminal, wrapped by an
+//! editor or pasted with a stray character. Each failure names the segment
+//! and the byte it was found at, so the damage can be located in the token.
+
+use thiserror::Error;
+
+/// Longest token decoded, far above the 8 KB most proxies accept in a header
+pub const MAX_TOKEN_LENGTH: usize = 64 * 1024;
+
+/// Dot-separated segment of a compact JWT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSegment {
+    Header,
+    Payload,
+    Signature,
+}
+
+impl TokenSegment {
+    /// Gets the position of the segment in the token, from 0
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl std::fmt::Display for TokenSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenSegment::Header => write!(f, "header"),
+            TokenSegment::Payload => write!(f, "payload"),
+            TokenSegment::Signature => write!(f, "signature"),
+        }
+    }
+}
+
+/// Why a token could not be decoded
+///
+/// Base64url failures give the offset of the offending byte in the token as
+/// given; JSON failures the offset in the decoded segment.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MalformedToken {
+    #[error("the token is {length} bytes long, more than the {max} bytes accepted")]
+    TooLarge { length: usize, max: usize },
+    #[error(
+        "expected three base64url segments separated by dots (header.payload.signature), found {found}"
+    )]
+    SegmentCount { found: usize },
+    #[error("the {segment} is empty")]
+    EmptySegment { segment: TokenSegment },
+    #[error(
+        "the {segment} is not valid base64url: byte {offset} ({}) is not a base64url character, \
+         the token may have been wrapped or altered when copying",
+        describe_byte(*.byte)
+    )]
+    InvalidCharacter {
+        segment: TokenSegment,
+        offset: usize,
+        byte: u8,
+    },
+    #[error(
+        "the {segment} is not valid base64url: it ends in the middle of a character at byte {offset}, \
+         the token may have been truncated when copying"
+    )]
+    Truncated {
+        segment: TokenSegment,
+        offset: usize,
+    },
+    #[error(
+        "the {segment} is not valid JSON: it ends unexpectedly after {offset} bytes, \
+         the token may have been truncated when copying"
+    )]
+    TruncatedJson {
+        segment: TokenSegment,
+        offset: usize,
+    },
+    #[error("the {segment} is not valid JSON at byte {offset} ({reason})")]
+    InvalidJson {
+        segment: TokenSegment,
+        offset: usize,
+        reason: String,
+    },
+    #[error("the {segment} is not a JSON object")]
+    NotAnObject { segment: TokenSegment },
+    #[error("the payload does not hold valid claims ({reason})")]
+    InvalidClaims { reason: String },
+}
+
+impl MalformedToken {
+    /// Gets the segment the failure was found in, if it is in one
+    pub fn segment(&self) -> Option<TokenSegment> {
+        match self {
+            MalformedToken::TooLarge { .. } | MalformedToken::SegmentCount { .. } => None,
+            MalformedToken::InvalidClaims { .. } => Some(TokenSegment::Payload),
+            MalformedToken::EmptySegment { segment }
+            | MalformedToken::InvalidCharacter { segment, .. }
+            | MalformedToken::Truncated { segment, .. }
+            | MalformedToken::TruncatedJson { segment, .. }
+            | MalformedToken::InvalidJson { segment, .. }
+            | MalformedToken::NotAnObject { segment } => Some(*segment),
+        }
+    }
+
+    /// Gets the byte offset of the failure, in the token or in the decoded segment
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            MalformedToken::InvalidCharacter { offset, .. }
+            | MalformedToken::Truncated { offset, .. }
+            | MalformedToken::TruncatedJson { offset, .. }
+            | MalformedToken::InvalidJson { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// Maps a base64url failure of a segment starting at `start` in the token
+    pub(crate) fn from_base64(
+        segment: TokenSegment,
+        encoded: &str,
+        start: usize,
+        error: base64::DecodeError,
+    ) -> Self {
+        match error {
+            base64::DecodeError::InvalidByte(index, byte) => MalformedToken::InvalidCharacter {
+                segment,
+                offset: start + index,
+                byte,
+            },
+            base64::DecodeError::InvalidPadding => {
+                let index = encoded.find('=').unwrap_or(encoded.len());
+                MalformedToken::InvalidCharacter {
+                    segment,
+                    offset: start + index,
+                    byte: b'=',
+                }
+            }
+            base64::DecodeError::InvalidLastSymbol(index, _) => MalformedToken::Truncated {
+                segment,
+                offset: start + index,
+            },
+            base64::DecodeError::InvalidLength(length) => MalformedToken::Truncated {
+                segment,
+                offset: start + length,
+            },
+        }
+    }
+
+    /// Maps a JSON failure of a decoded segment
+    pub(crate) fn from_json(segment: TokenSegment, json: &[u8], error: serde_json::Error) -> Self {
+        match error.is_eof() {
+            true => MalformedToken::TruncatedJson {
+                segment,
+                offset: json.len(),
+            },
+            false => MalformedToken::InvalidJson {
+                segment,
+                offset: json_offset(json, error.line(), error.column()),
+                reason: error.to_string(),
+            },
+        }
+    }
+}
+
+/// Turns the 1-based line and column of a JSON error into a byte offset
+fn json_offset(json: &[u8], line: usize, column: usize) -> usize {
+    let line_start: usize = json
+        .split_inclusive(|byte| *byte == b'\n')
+        .take(line.saturating_sub(1))
+        .map(<[u8]>::len)
+        .sum();
+    (line_start + column.saturating_sub(1)).min(json.len())
+}
+
+fn describe_byte(byte: u8) -> String {
+    match byte {
+        b' ' => "a space".to_string(),
+        b'\n' | b'\r' => "a line break".to_string(),
+        b'\t' => "a tab".to_string(),
+        byte if byte.is_ascii_graphic() => format!("'{}'", byte as char),
+        byte => format!("0x{:02x}", byte),
+    }
+}
@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+
+use crate::token::requirements::RequirementCheck;
+use crate::token::Claims;
+
+/// App registration manifest as exported from the Azure portal or Microsoft Graph
+///
+/// Only the fields describing what tokens for the app may contain are read.
+/// Manifests in the legacy Azure AD Graph format keep their scopes in a
+/// top-level `oauth2Permissions` list instead of `api.oauth2PermissionScopes`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AppManifest {
+    /// Application (client) ID
+    pub app_id: String,
+    /// Display name of the app registration
+    pub display_name: Option<String>,
+    /// App ID URIs, e.g. `api://my-app-id`
+    pub identifier_uris: Vec<String>,
+    pub api: ManifestApi,
+    /// Delegated scopes of a legacy Azure AD Graph manifest
+    pub oauth2_permissions: Vec<PermissionScope>,
+    pub app_roles: Vec<AppRole>,
+}
+
+/// The `api` block of an app registration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ManifestApi {
+    pub oauth2_permission_scopes: Vec<PermissionScope>,
+}
+
+/// Delegated permission (scope) exposed by an app registration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionScope {
+    pub value: String,
+    #[serde(default = "enabled")]
+    pub is_enabled: bool,
+}
+
+/// App role defined by an app registration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppRole {
+    pub value: String,
+    /// `User` and/or `Application`
+    #[serde(default)]
+    pub allowed_member_types: Vec<String>,
+    #[serde(default = "enabled")]
+    pub is_enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl AppManifest {
+    /// Gets the delegated scopes of the manifest, in either manifest format
+    pub fn scopes(&self) -> impl Iterator<Item = &PermissionScope> {
+        self.api
+            .oauth2_permission_scopes
+            .iter()
+            .chain(&self.oauth2_permissions)
+    }
+
+    /// Checks that the audience, scopes and roles of a token are defined by this app registration
+    ///
+    /// Scopes and roles are only checked when the token carries them.
+    pub fn check(&self, claims: &Claims) -> Vec<RequirementCheck> {
+        let mut checks = vec![self.check_audience(claims)];
+
+        let scopes = claims.scopes();
+        if !scopes.is_empty() {
+            let (passed, detail) = check_values(
+                &scopes,
+                &self
+                    .scopes()
+                    .map(|scope| (scope.value.as_str(), scope.is_enabled))
+                    .collect::<Vec<_>>(),
+                "api.oauth2PermissionScopes",
+            );
+            checks.push(RequirementCheck {
+                name: "Scopes in manifest",
+                passed,
+                detail,
+            });
+        }
+
+        let roles = claims.roles();
+        if !roles.is_empty() {
+            let (passed, detail) = check_values(
+                &roles,
+                &self
+                    .app_roles
+                    .iter()
+                    .map(|role| (role.value.as_str(), role.is_enabled))
+                    .collect::<Vec<_>>(),
+                "appRoles",
+            );
+            checks.push(RequirementCheck {
+                name: "Roles in manifest",
+                passed,
+                detail,
+            });
+        }
+
+        checks
+    }
+
+    fn check_audience(&self, claims: &Claims) -> RequirementCheck {
+        let audiences = claims.audiences();
+        let accepted: Vec<&str> = std::iter::once(self.app_id.as_str())
+            .chain(self.identifier_uris.iter().map(String::as_str))
+            .filter(|value| !value.is_empty())
+            .collect();
+        let matched = audiences.iter().find(|aud| {
+            accepted.iter().any(|value| {
+                value
+                    .trim_end_matches('/')
+                    .eq_ignore_ascii_case(aud.trim_end_matches('/'))
+            })
+        });
+
+        RequirementCheck {
+            name: "Audience in manifest",
+            passed: matched.is_some(),
+            detail: match matched {
+                Some(aud) => format!("{} is an identifier of the app", aud),
+                None => format!(
+                    "{} is neither the appId nor one of the identifierUris ({})",
+                    claims.audience_display(),
+                    accepted.join(", ")
+                ),
+            },
+        }
+    }
+}
+
+/// Compares the values granted in a token with those defined in `field`
+fn check_values(granted: &[&str], defined: &[(&str, bool)], field: &str) -> (bool, String) {
+    let mut missing = Vec::new();
+    let mut disabled = Vec::new();
+    for value in granted {
+        match defined.iter().find(|(defined, _)| defined == value) {
+            None => missing.push(*value),
+            Some((_, false)) => disabled.push(*value),
+            Some((_, true)) => {}
+        }
+    }
+
+    if missing.is_empty() && disabled.is_empty() {
+        return (true, format!("{} defined in {}", granted.join(", "), field));
+    }
+    let mut problems = Vec::new();
+    if !missing.is_empty() {
+        problems.push(format!("{} not defined in {}", missing.join(", "), field));
+    }
+    if !disabled.is_empty() {
+        problems.push(format!("{} disabled in {}", disabled.join(", "), field));
+    }
+    (false, problems.join("; "))
+}
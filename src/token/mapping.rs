@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use crate::token::Claims;
+
+/// Translation of token claims into an application's own identity model
+///
+/// Each field of the output names the claim it is taken from:
+///
+/// ```yaml
+/// user_id: oid
+/// username: [preferred_username, upn, unique_name]
+/// permissions: { claim: scp, split: " " }
+/// tenant: { claim: tid, default: unknown }
+/// ```
+///
+/// A list takes the first claim present. Nested claims are named with a
+/// dotted path, e.g. `act.sub`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ClaimMapping {
+    pub fields: BTreeMap<String, MappingRule>,
+}
+
+/// How one output field is computed from the claims
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MappingRule {
+    /// Value of a single claim
+    Claim(String),
+    /// Value of the first of these claims present in the token
+    FirstOf(Vec<String>),
+    /// Value of a claim, optionally split into a list and with a default
+    Detailed(DetailedRule),
+}
+
+/// A mapping rule with options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DetailedRule {
+    /// Claim, or claims of which the first present is used
+    pub claim: ClaimNames,
+    /// Separator to split a string claim at, e.g. `" "` for `scp`
+    #[serde(default)]
+    pub split: Option<String>,
+    /// Value used when none of the claims is present
+    #[serde(default)]
+    pub default: Option<Value>,
+}
+
+/// One claim name or several, in order of preference
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ClaimNames {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl ClaimNames {
+    fn as_slice(&self) -> &[String] {
+        match self {
+            ClaimNames::One(name) => std::slice::from_ref(name),
+            ClaimNames::Many(names) => names,
+        }
+    }
+}
+
+impl ClaimMapping {
+    /// Computes the mapped identity from the claims of a token
+    ///
+    /// Fields whose claims are all missing, and which have no default, are `null`.
+    pub fn apply(&self, claims: &Claims) -> Value {
+        let payload = serde_json::to_value(claims).unwrap_or_default();
+        let mapped: Map<String, Value> = self
+            .fields
+            .iter()
+            .map(|(field, rule)| (field.clone(), rule.apply(&payload)))
+            .collect();
+        Value::Object(mapped)
+    }
+}
+
+impl MappingRule {
+    fn apply(&self, payload: &Value) -> Value {
+        let (names, split, default) = match self {
+            MappingRule::Claim(name) => (std::slice::from_ref(name), None, None),
+            MappingRule::FirstOf(names) => (names.as_slice(), None, None),
+            MappingRule::Detailed(rule) => (
+                rule.claim.as_slice(),
+                rule.split.as_deref(),
+                rule.default.as_ref(),
+            ),
+        };
+
+        let value = names.iter().find_map(|name| lookup(payload, name));
+        match (value, split) {
+            (Some(Value::String(text)), Some(separator)) => text
+                .split(separator)
+                .filter(|part| !part.is_empty())
+                .map(Value::from)
+                .collect(),
+            (Some(value), _) => value.clone(),
+            (None, _) => default.cloned().unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// Looks up a claim by name, or by a dotted path into nested claims
+fn lookup<'a>(payload: &'a Value, name: &str) -> Option<&'a Value> {
+    if let Some(value) = payload.get(name) {
+        return Some(value).filter(|value| !value.is_null());
+    }
+    name.split('.')
+        .try_fold(payload, |value, key| value.get(key))
+        .filter(|value| !value.is_null())
+}
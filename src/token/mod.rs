@@ -1,7 +1,95 @@
+pub mod audience;
+pub mod audit;
+#[cfg(feature = "net")]
+pub mod builder;
+mod c14n;
+#[cfg(feature = "net")]
+pub mod cache;
+pub mod chain;
 pub mod claims;
+pub mod clock;
+pub mod cloud;
+pub mod conditional;
+#[cfg(feature = "net")]
+pub mod diagnostics;
+pub mod dictionary;
+pub mod error;
+pub mod explain;
 pub mod jwk;
+#[cfg(feature = "net")]
+pub mod keystore;
+pub mod malformed;
+pub mod manifest;
+pub mod mapping;
+pub mod pair;
+pub mod pop;
+#[cfg(feature = "net")]
+pub mod provider;
+pub mod raw;
+pub mod requirements;
+pub mod saml;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod size;
+pub mod spans;
+#[cfg(any(feature = "net", feature = "blocking"))]
+pub mod telemetry;
+pub mod tree;
+#[cfg(feature = "net")]
 pub mod validator;
+pub mod verify;
+pub mod warning;
 
 // Re-export commonly used items for easier imports
-pub use claims::{Claims, TokenType};
-pub use validator::{TokenValidator, ValidatorConfig};
+pub use audience::KnownAudience;
+pub use audit::{audit_token, AuditConfig};
+#[cfg(feature = "net")]
+pub use builder::ValidatorBuilder;
+#[cfg(feature = "redis")]
+pub use cache::RedisCache;
+#[cfg(feature = "net")]
+pub use cache::{CachedKeys, DiskCache, KeyCache, MemoryCache, MAX_KEY_SETS};
+pub use chain::{identity_chain, Identity, IdentityChain};
+pub use claims::{Claims, ClaimsBuilder, GroupOverage, TokenType};
+pub use clock::{
+    skew_from_date_header, Clock, FixedClock, ManualClock, SkewedClock, SystemClock, Timezone,
+};
+pub use cloud::Cloud;
+pub use conditional::ConditionalAccess;
+#[cfg(feature = "net")]
+pub use diagnostics::suggest_fixes;
+pub use dictionary::ClaimInfo;
+pub use error::{failure_code, ValidationError};
+pub use explain::{explain_validation, RuleEvaluation, RuleStatus};
+#[cfg(feature = "net")]
+pub use keystore::{CircuitState, KeyStore, KeyStoreStats};
+pub use malformed::{MalformedToken, TokenSegment, MAX_TOKEN_LENGTH};
+pub use manifest::AppManifest;
+pub use mapping::ClaimMapping;
+pub use pair::check_token_pair;
+pub use pop::{
+    bound_certificate, certificate_binding, certificate_thumbprint, key_binding, verify_dpop_proof,
+    CertificateBinding, KeyBinding,
+};
+#[cfg(feature = "net")]
+pub use provider::KeyProvider;
+pub use raw::{
+    bearer_token, extract_token, inspect_raw, ExtractedToken, RawInspection, TokenShape,
+};
+pub use requirements::{
+    ApiRequirements, ProfileChain, ProfileResult, RequirementCheck, TokenKind, ValidationProfile,
+};
+pub use saml::{decode_saml_input, SamlAssertion};
+#[cfg(feature = "schema")]
+pub use schema::{ClaimsSchema, SchemaViolation};
+pub use size::{SizeLimit, TokenSize};
+pub use tree::{ClaimLeaf, ClaimTree};
+#[cfg(feature = "net")]
+pub use validator::TokenValidator;
+pub use verify::{
+    adfs_host, ciam_tenant, cloud_issuer_for, decode_unverified, find_signing_key, is_tenant_label,
+    issuer_for, resolve_tenant, signing_key_warnings, validate_signature_with_jwks,
+    validate_with_jwks, AzureTokenFormat, ValidatorConfig, AUTO_TENANT, MAX_KIDLESS_KEYS,
+    PIN_EXPIRY_WARNING,
+};
+pub use warning::{Severity, Warning, WarningPolicy};
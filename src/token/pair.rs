@@ -0,0 +1,163 @@
+use serde_json::Value;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::token::requirements::RequirementCheck;
+use crate::token::Claims;
+
+/// Checks that an ID token and an access token belong to the same sign-in
+///
+/// Both must come from the same tenant for the same user, the ID token's
+/// `at_hash` must match the access token, the ID token must be issued to the
+/// client that requested the access token, and their validity must overlap.
+/// A mismatch usually means a client mixed up the tokens of two responses.
+pub fn check_token_pair(
+    id_header: &Value,
+    id_claims: &Claims,
+    access_token: &str,
+    access_claims: &Claims,
+) -> Vec<RequirementCheck> {
+    let mut checks = Vec::new();
+
+    checks.push(match (id_claims.tenant_id(), access_claims.tenant_id()) {
+        (Some(id_tid), Some(access_tid)) => RequirementCheck {
+            name: "Same tenant",
+            passed: id_tid == access_tid,
+            detail: match id_tid == access_tid {
+                true => format!("tid {}", id_tid),
+                false => format!("ID token tid {}, access token tid {}", id_tid, access_tid),
+            },
+        },
+        _ => RequirementCheck {
+            name: "Same tenant",
+            passed: false,
+            detail: "tid is missing from one of the tokens".to_string(),
+        },
+    });
+
+    checks.push(same_user(id_claims, access_claims));
+
+    let id_audiences = id_claims.audiences();
+    checks.push(match access_claims.authorized_party() {
+        Some(client) => {
+            let same_client = id_audiences.iter().any(|aud| aud == client);
+            RequirementCheck {
+                name: "Same client",
+                passed: same_client,
+                detail: match same_client {
+                    true => format!("both issued to {}", client),
+                    false => format!(
+                        "ID token issued to {}, access token requested by {}",
+                        id_claims.audience_display(),
+                        client
+                    ),
+                },
+            }
+        }
+        None => RequirementCheck {
+            name: "Same client",
+            passed: false,
+            detail: "access token names no client (azp or appid)".to_string(),
+        },
+    });
+
+    let alg = id_header["alg"].as_str().unwrap_or_default();
+    let at_hash = id_claims.extra.get("at_hash").and_then(Value::as_str);
+    checks.push(match (at_hash, access_token_hash(alg, access_token)) {
+        (Some(at_hash), Some(expected)) => RequirementCheck {
+            name: "at_hash matches",
+            passed: at_hash == expected,
+            detail: match at_hash == expected {
+                true => "at_hash matches the access token".to_string(),
+                false => format!(
+                    "at_hash {} does not match the access token hash {}, the ID token \
+                     was issued with a different access token",
+                    at_hash, expected
+                ),
+            },
+        },
+        (Some(_), None) => RequirementCheck {
+            name: "at_hash matches",
+            passed: false,
+            detail: format!(
+                "cannot compute at_hash for the ID token algorithm '{}'",
+                alg
+            ),
+        },
+        (None, _) => RequirementCheck {
+            name: "at_hash matches",
+            passed: false,
+            detail: "ID token has no at_hash, it was not issued in the same response \
+                     as an access token"
+                .to_string(),
+        },
+    });
+
+    let start = |claims: &Claims| claims.nbf.unwrap_or(claims.iat);
+    let overlap_start = start(id_claims).max(start(access_claims));
+    let overlap_end = id_claims.exp.min(access_claims.exp);
+    checks.push(RequirementCheck {
+        name: "Validity overlaps",
+        passed: overlap_start < overlap_end,
+        detail: match overlap_start < overlap_end {
+            true => format!(
+                "both valid from {} to {}",
+                Claims::format_timestamp(overlap_start),
+                Claims::format_timestamp(overlap_end)
+            ),
+            false => format!(
+                "ID token valid until {}, access token until {}: they were never valid \
+                 at the same time",
+                Claims::format_timestamp(id_claims.exp),
+                Claims::format_timestamp(access_claims.exp)
+            ),
+        },
+    });
+
+    checks
+}
+
+/// Compares the user of both tokens by `oid`, or by `sub` when issued for the same app
+///
+/// `sub` is pairwise, unique per application, so it only matches when the
+/// access token is for the client itself.
+fn same_user(id_claims: &Claims, access_claims: &Claims) -> RequirementCheck {
+    let (passed, detail) = match (id_claims.object_id(), access_claims.object_id()) {
+        (Some(id_oid), Some(access_oid)) => (
+            id_oid == access_oid,
+            match id_oid == access_oid {
+                true => format!("oid {}", id_oid),
+                false => format!("ID token oid {}, access token oid {}", id_oid, access_oid),
+            },
+        ),
+        _ if id_claims.aud == access_claims.aud => (
+            id_claims.sub == access_claims.sub,
+            format!(
+                "no oid, ID token sub {}, access token sub {}",
+                id_claims.sub, access_claims.sub
+            ),
+        ),
+        _ => (
+            false,
+            "no oid to compare, and sub differs per application".to_string(),
+        ),
+    };
+    RequirementCheck {
+        name: "Same user",
+        passed,
+        detail,
+    }
+}
+
+/// Computes the `at_hash` of an access token for an ID token signed with `alg`
+///
+/// The left half of the token's hash, using the hash function of the
+/// signing algorithm, base64url-encoded (OpenID Connect Core, 3.1.3.6).
+pub fn access_token_hash(alg: &str, access_token: &str) -> Option<String> {
+    let digest = match alg.get(alg.len().saturating_sub(3)..) {
+        Some("256") => Sha256::digest(access_token.as_bytes()).to_vec(),
+        Some("384") => Sha384::digest(access_token.as_bytes()).to_vec(),
+        Some("512") => Sha512::digest(access_token.as_bytes()).to_vec(),
+        _ => return None,
+    };
+    Some(base64_url::encode(&digest[..digest.len() / 2]))
+}
@@ -0,0 +1,309 @@
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use jsonwebtoken::jwk::Jwk;
+use jsonwebtoken::{decode, decode_header, DecodingKey, Validation};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+use crate::token::requirements::RequirementCheck;
+use crate::token::Claims;
+
+/// Maximum age of a DPoP proof, in seconds
+const MAX_PROOF_AGE: u64 = 300;
+
+/// Key a proof-of-possession token is bound to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    /// Claim the binding was found in, `cnf` or `req_cnf`
+    pub claim: &'static str,
+    /// JWK SHA-256 thumbprint (RFC 7638) of the bound key, if given or computable
+    pub thumbprint: Option<String>,
+    /// Key ID of the bound key; Azure AD uses the key's thumbprint as its ID
+    pub kid: Option<String>,
+}
+
+impl KeyBinding {
+    /// Returns whether a key with the given thumbprint is the bound key
+    pub fn matches(&self, thumbprint: &str) -> bool {
+        self.thumbprint.as_deref() == Some(thumbprint) || self.kid.as_deref() == Some(thumbprint)
+    }
+}
+
+/// Whether a token is bound to the TLS client certificate it was presented with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CertificateBinding {
+    /// The token's `cnf.x5t#S256` is the certificate's thumbprint
+    Bound,
+    /// The token has no `cnf.x5t#S256`, it is accepted with any certificate
+    Unbound,
+    /// The token is bound to the certificate with this `x5t#S256` thumbprint
+    Mismatch { bound: String },
+}
+
+/// Detects the key a token is bound to from its `cnf` or `req_cnf` claim
+///
+/// `cnf` may hold the thumbprint (`jkt`), the public key itself (`jwk`) or a
+/// key ID (`kid`). `req_cnf` is the base64url-encoded confirmation requested
+/// by the client.
+pub fn key_binding(claims: &Claims) -> Option<KeyBinding> {
+    if let Some(cnf) = claims.extra.get("cnf") {
+        return Some(binding_from_confirmation("cnf", cnf));
+    }
+
+    let req_cnf = claims.extra.get("req_cnf")?;
+    let confirmation = match req_cnf {
+        Value::String(encoded) => base64_url::decode(encoded)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .unwrap_or(Value::Null),
+        other => other.clone(),
+    };
+    Some(binding_from_confirmation("req_cnf", &confirmation))
+}
+
+fn binding_from_confirmation(claim: &'static str, confirmation: &Value) -> KeyBinding {
+    let thumbprint = match (&confirmation["jkt"], &confirmation["jwk"]) {
+        (Value::String(jkt), _) => Some(jkt.clone()),
+        (_, jwk @ Value::Object(_)) => jwk_thumbprint(jwk).ok(),
+        _ => None,
+    };
+    KeyBinding {
+        claim,
+        thumbprint,
+        kid: confirmation["kid"].as_str().map(str::to_string),
+    }
+}
+
+/// Gets the `x5t#S256` thumbprint of the certificate a token is bound to (RFC 8705)
+pub fn bound_certificate(claims: &Claims) -> Option<&str> {
+    claims.extra.get("cnf")?["x5t#S256"].as_str()
+}
+
+/// Checks a certificate-bound token against the client certificate's `x5t#S256` thumbprint
+pub fn certificate_binding(claims: &Claims, thumbprint: &str) -> CertificateBinding {
+    match bound_certificate(claims) {
+        Some(bound) if bound.trim_end_matches('=') == thumbprint.trim_end_matches('=') => {
+            CertificateBinding::Bound
+        }
+        Some(bound) => CertificateBinding::Mismatch {
+            bound: bound.to_string(),
+        },
+        None => CertificateBinding::Unbound,
+    }
+}
+
+/// Computes the `x5t#S256` thumbprint of a certificate, PEM or DER encoded
+///
+/// The thumbprint is the base64url-encoded SHA-256 digest of the DER
+/// encoding. Of a PEM file with a chain, the first certificate is the
+/// client's own.
+pub fn certificate_thumbprint(certificate: &[u8]) -> Result<String> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+    let der = match std::str::from_utf8(certificate)
+        .ok()
+        .and_then(|pem| pem.split_once(BEGIN))
+    {
+        Some((_, rest)) => {
+            let (body, _) = rest
+                .split_once(END)
+                .context("PEM certificate has no END line")?;
+            let body: String = body.split_whitespace().collect();
+            STANDARD
+                .decode(body)
+                .context("PEM certificate is not valid base64")?
+        }
+        None => certificate.to_vec(),
+    };
+    // A DER certificate is an ASN.1 SEQUENCE
+    if der.first() != Some(&0x30) {
+        bail!("Not a PEM or DER encoded certificate");
+    }
+    Ok(base64_url::encode(&Sha256::digest(&der)))
+}
+
+/// Computes the JWK SHA-256 thumbprint of a public key (RFC 7638)
+pub fn jwk_thumbprint(jwk: &Value) -> Result<String> {
+    let kty = jwk["kty"].as_str().context("JWK has no kty")?;
+    // Required members of each key type, in lexicographic order
+    let members: &[&str] = match kty {
+        "RSA" => &["e", "kty", "n"],
+        "EC" => &["crv", "kty", "x", "y"],
+        "OKP" => &["crv", "kty", "x"],
+        other => bail!("Unsupported JWK key type '{}'", other),
+    };
+
+    let fields = members
+        .iter()
+        .map(|member| {
+            let value = jwk[*member]
+                .as_str()
+                .with_context(|| format!("JWK has no '{}' member", member))?;
+            Ok(format!("\"{}\":{}", member, serde_json::to_string(value)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let digest = Sha256::digest(format!("{{{}}}", fields.join(",")).as_bytes());
+    Ok(base64_url::encode(&digest))
+}
+
+/// Verifies a DPoP proof (RFC 9449) presented with an access token
+///
+/// Checks that the proof is signed with the key embedded in its header, that
+/// this key is the one the token is bound to, that the proof covers this
+/// access token (`ath`) and that it was issued within five minutes of
+/// `now`, in seconds since the Unix epoch, e.g. the validator clock's time.
+pub fn verify_dpop_proof(
+    proof: &str,
+    access_token: &str,
+    claims: &Claims,
+    now: u64,
+) -> Vec<RequirementCheck> {
+    let mut checks = Vec::new();
+
+    let header: Value = match proof
+        .split('.')
+        .next()
+        .and_then(|segment| base64_url::decode(segment).ok())
+        .and_then(|json| serde_json::from_slice(&json).ok())
+    {
+        Some(header) => header,
+        None => {
+            checks.push(RequirementCheck {
+                name: "Proof decoded",
+                passed: false,
+                detail: "DPoP proof header is not valid base64url-encoded JSON".to_string(),
+            });
+            return checks;
+        }
+    };
+
+    let typ = header["typ"].as_str().unwrap_or_default();
+    checks.push(RequirementCheck {
+        name: "Proof type",
+        passed: typ.eq_ignore_ascii_case("dpop+jwt"),
+        detail: format!("typ is '{}', expected 'dpop+jwt'", typ),
+    });
+
+    let payload = match verify_signature(proof) {
+        Ok(payload) => {
+            checks.push(RequirementCheck {
+                name: "Proof signature",
+                passed: true,
+                detail: format!(
+                    "signed with the embedded {} key",
+                    header["alg"].as_str().unwrap_or("-")
+                ),
+            });
+            payload
+        }
+        Err(e) => {
+            checks.push(RequirementCheck {
+                name: "Proof signature",
+                passed: false,
+                detail: e.to_string(),
+            });
+            return checks;
+        }
+    };
+
+    let thumbprint = jwk_thumbprint(&header["jwk"]).unwrap_or_default();
+    checks.push(match key_binding(claims) {
+        Some(binding) => RequirementCheck {
+            name: "Proof key bound to token",
+            passed: binding.matches(&thumbprint),
+            detail: format!(
+                "proof key thumbprint {}, token bound to {}",
+                thumbprint,
+                binding
+                    .thumbprint
+                    .as_deref()
+                    .or(binding.kid.as_deref())
+                    .unwrap_or("an unknown key")
+            ),
+        },
+        None => RequirementCheck {
+            name: "Proof key bound to token",
+            passed: false,
+            detail: "token has no cnf claim".to_string(),
+        },
+    });
+
+    let expected_ath = base64_url::encode(&Sha256::digest(access_token.as_bytes()));
+    checks.push(match payload["ath"].as_str() {
+        Some(ath) => RequirementCheck {
+            name: "Proof covers access token",
+            passed: ath == expected_ath,
+            detail: match ath == expected_ath {
+                true => "ath matches the token hash".to_string(),
+                false => format!("ath {} does not match the token hash {}", ath, expected_ath),
+            },
+        },
+        None => RequirementCheck {
+            name: "Proof covers access token",
+            passed: false,
+            detail: "proof has no ath claim".to_string(),
+        },
+    });
+
+    checks.push(match payload["iat"].as_u64() {
+        Some(iat) => RequirementCheck {
+            name: "Proof is recent",
+            passed: iat.abs_diff(now) <= MAX_PROOF_AGE,
+            detail: format!(
+                "issued at {}, accepted within {}s",
+                Claims::format_timestamp(iat),
+                MAX_PROOF_AGE
+            ),
+        },
+        None => RequirementCheck {
+            name: "Proof is recent",
+            passed: false,
+            detail: "proof has no iat claim".to_string(),
+        },
+    });
+
+    checks.push(RequirementCheck {
+        name: "Proof request",
+        passed: payload["htm"].is_string()
+            && payload["htu"].is_string()
+            && payload["jti"].is_string(),
+        detail: format!(
+            "{} {}",
+            payload["htm"].as_str().unwrap_or("<no htm>"),
+            payload["htu"].as_str().unwrap_or("<no htu>")
+        ),
+    });
+
+    checks
+}
+
+/// Verifies the signature of a DPoP proof with the JWK in its header
+fn verify_signature(proof: &str) -> Result<Value> {
+    let header = decode_header(proof)?;
+    let jwk: &Jwk = header
+        .jwk
+        .as_ref()
+        .context("DPoP proof header has no jwk")?;
+    if matches!(
+        header.alg,
+        jsonwebtoken::Algorithm::HS256
+            | jsonwebtoken::Algorithm::HS384
+            | jsonwebtoken::Algorithm::HS512
+    ) {
+        bail!(
+            "DPoP proofs must use an asymmetric algorithm, not {:?}",
+            header.alg
+        );
+    }
+
+    let mut validation = Validation::new(header.alg);
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    validation.required_spec_claims = HashSet::new();
+
+    let key = DecodingKey::from_jwk(jwk)?;
+    Ok(decode::<Value>(proof, &key, &validation)?.claims)
+}
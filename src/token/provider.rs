@@ -0,0 +1,56 @@
+use anyhow::Result;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::token::jwk::JwksResponse;
+use crate::token::keystore::KeyStore;
+
+/// Future returned by [`KeyProvider`] methods
+pub type KeysFuture<'a> = Pin<Box<dyn Future<Output = Result<Arc<JwksResponse>>> + Send + 'a>>;
+
+/// Source of the signing keys a [`crate::token::TokenValidator`] verifies tokens with
+///
+/// [`KeyStore`] fetches and caches the keys Azure AD publishes. Implement
+/// this to serve keys from elsewhere, e.g. a configuration store or a
+/// pinned key set in an air-gapped deployment.
+pub trait KeyProvider: Send + Sync {
+    /// Gets the key set published at the JWKS `uri`, from a cache if available
+    fn get_keys<'a>(&'a self, uri: &'a str) -> KeysFuture<'a>;
+
+    /// Gets the key set published at the JWKS `uri`, bypassing any cache
+    fn refresh_keys<'a>(&'a self, uri: &'a str) -> KeysFuture<'a> {
+        self.get_keys(uri)
+    }
+
+    /// Gets the key set again after a token named a key missing from it
+    ///
+    /// Called at most once per validation so rotated keys are found without
+    /// waiting for the cache to expire. Providers fetching over the network
+    /// should limit how often this refetches.
+    fn refetch_keys<'a>(&'a self, uri: &'a str) -> KeysFuture<'a> {
+        self.refresh_keys(uri)
+    }
+}
+
+impl KeyProvider for KeyStore {
+    fn get_keys<'a>(&'a self, uri: &'a str) -> KeysFuture<'a> {
+        Box::pin(self.get(uri))
+    }
+
+    fn refresh_keys<'a>(&'a self, uri: &'a str) -> KeysFuture<'a> {
+        Box::pin(self.refresh(uri))
+    }
+
+    fn refetch_keys<'a>(&'a self, uri: &'a str) -> KeysFuture<'a> {
+        Box::pin(self.refetch(uri))
+    }
+}
+
+/// A fixed key set is used for every JWKS URI
+impl KeyProvider for JwksResponse {
+    fn get_keys<'a>(&'a self, _uri: &'a str) -> KeysFuture<'a> {
+        let jwks = Arc::new(self.clone());
+        Box::pin(async move { Ok(jwks) })
+    }
+}
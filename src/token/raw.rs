@@ -0,0 +1,260 @@
+use serde_json::Value;
+use url::form_urlencoded;
+
+/// Names of the segments of a compact JWS token
+const SEGMENT_NAMES: [&str; 3] = ["header", "payload", "signature"];
+
+/// Parameters of redirect URLs carrying tokens, in order of preference
+const TOKEN_PARAMETERS: [&str; 2] = ["access_token", "id_token"];
+
+/// What kind of token a string is, as far as can be told without any keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenShape {
+    /// Signed JWT, whose signature can be validated against the issuer's keys
+    Jwt,
+    /// Encrypted JWT (JWE), which only its recipient can decrypt
+    EncryptedJwt,
+    /// Not a JWT at all, e.g. a Microsoft account (MSA) access token or a reference token
+    Opaque,
+}
+
+impl TokenShape {
+    /// Detects the shape of a token from its encoding
+    ///
+    /// JWTs start with a base64url-encoded JSON header, i.e. with `eyJ`;
+    /// anything else is opaque. A `Bearer ` prefix is ignored.
+    pub fn detect(token: &str) -> TokenShape {
+        let token = token.trim();
+        let token = token.strip_prefix("Bearer ").unwrap_or(token);
+        if !token.starts_with("eyJ") {
+            return TokenShape::Opaque;
+        }
+        match token.split('.').count() {
+            5 => TokenShape::EncryptedJwt,
+            _ => TokenShape::Jwt,
+        }
+    }
+
+    /// Explains why the signature of a token of this shape cannot be validated
+    pub fn explanation(self) -> Option<&'static str> {
+        match self {
+            TokenShape::Jwt => None,
+            TokenShape::EncryptedJwt => Some(
+                "The token is an encrypted JWT (JWE): its claims and signature can only be read by the app it was encrypted for",
+            ),
+            TokenShape::Opaque => Some(
+                "The token is opaque, not a JWT: it has no claims or signature that can be checked locally. \
+                 Tokens of personal Microsoft accounts and some first-party resources are issued this way, \
+                 only the API they were issued for can validate them",
+            ),
+        }
+    }
+}
+
+/// Token found in pasted input, with where it was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedToken {
+    pub token: String,
+    /// URL parameter the token was taken from, e.g. `id_token`
+    pub parameter: Option<&'static str>,
+    /// Whether the token was percent-encoded
+    pub url_decoded: bool,
+}
+
+/// Extracts the token from input copied from a redirect URL
+///
+/// Tokens of the implicit and hybrid flows arrive in the fragment or query
+/// of a redirect URL, e.g. `#id_token=eyJ...&state=...`, and are sometimes
+/// percent-encoded. Both are decoded; the `access_token` parameter is
+/// preferred over `id_token`. Other input is returned trimmed.
+pub fn extract_token(input: &str) -> ExtractedToken {
+    let input = input.trim();
+    // Fragment parameters first, SPAs with hash routing put them after a '?' in the fragment
+    let (url, fragment) = input.split_once('#').unwrap_or((input, ""));
+    let pairs: Vec<(String, String)> = [after_query(fragment), after_query(url)]
+        .iter()
+        .flat_map(|parameters| form_urlencoded::parse(parameters.as_bytes()).into_owned())
+        .collect();
+    for parameter in TOKEN_PARAMETERS {
+        if let Some((_, token)) = pairs.iter().find(|(name, _)| name == parameter) {
+            return ExtractedToken {
+                token: token.clone(),
+                parameter: Some(parameter),
+                url_decoded: true,
+            };
+        }
+    }
+
+    // A bare percent-encoded token, only decoded if it then is a JWT
+    if input.contains('%') {
+        if let [(decoded, value)] = pairs.as_slice() {
+            if value.is_empty() && TokenShape::detect(decoded) != TokenShape::Opaque {
+                return ExtractedToken {
+                    token: decoded.clone(),
+                    parameter: None,
+                    url_decoded: true,
+                };
+            }
+        }
+    }
+    ExtractedToken {
+        token: input.to_string(),
+        parameter: None,
+        url_decoded: false,
+    }
+}
+
+/// Extracts the token from an `Authorization: Bearer <token>` header value
+pub fn bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+/// Gets the query of a URL or fragment, or all of it if it has none
+fn after_query(part: &str) -> &str {
+    part.split_once('?').map_or(part, |(_, query)| query)
+}
+
+/// Raw inspection result of a single token segment
+#[derive(Debug)]
+pub struct RawSegment {
+    pub name: &'static str,
+    pub encoded: String,
+    pub decoded: Option<Vec<u8>>,
+    pub problems: Vec<String>,
+}
+
+impl RawSegment {
+    /// Returns the decoded bytes as text, replacing invalid UTF-8 sequences
+    pub fn decoded_text(&self) -> Option<String> {
+        self.decoded
+            .as_ref()
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// Returns the decoded bytes parsed as JSON, if they are valid JSON
+    pub fn decoded_value(&self) -> Option<Value> {
+        serde_json::from_slice(self.decoded.as_ref()?).ok()
+    }
+
+    /// Returns the decoded bytes pretty-printed as JSON, if they are valid JSON
+    pub fn decoded_json(&self) -> Option<String> {
+        serde_json::to_string_pretty(&self.decoded_value()?).ok()
+    }
+}
+
+/// Raw inspection result of a compact JWT
+#[derive(Debug)]
+pub struct RawInspection {
+    pub segments: Vec<RawSegment>,
+    pub problems: Vec<String>,
+}
+
+impl RawInspection {
+    /// Returns true if no encoding problems were found in the token or any segment
+    pub fn is_well_formed(&self) -> bool {
+        self.problems.is_empty() && self.segments.iter().all(|s| s.problems.is_empty())
+    }
+}
+
+/// Splits a compact JWT and decodes each segment without interpreting it
+///
+/// Every problem that would make a JWT library reject the token as malformed
+/// is reported rather than stopping at the first one.
+pub fn inspect_raw(token: &str) -> RawInspection {
+    let mut problems = Vec::new();
+
+    let trimmed = token.trim();
+    if trimmed.len() != token.len() {
+        problems.push("Token has leading or trailing whitespace".to_string());
+    }
+    let token = match trimmed.strip_prefix("Bearer ") {
+        Some(stripped) => {
+            problems.push(
+                "Token starts with the 'Bearer ' prefix of an Authorization header".to_string(),
+            );
+            stripped
+        }
+        None => trimmed,
+    };
+
+    let parts: Vec<&str> = token.split('.').collect();
+    match parts.len() {
+        3 => {}
+        5 => problems.push(
+            "Token has 5 segments, it is an encrypted JWE and cannot be inspected".to_string(),
+        ),
+        n => problems.push(format!("Token has {} segments, expected 3", n)),
+    }
+
+    let segments = parts
+        .iter()
+        .enumerate()
+        .map(|(index, part)| {
+            let name = SEGMENT_NAMES.get(index).copied().unwrap_or("extra");
+            inspect_segment(name, part)
+        })
+        .collect();
+
+    RawInspection { segments, problems }
+}
+
+fn inspect_segment(name: &'static str, encoded: &str) -> RawSegment {
+    let mut problems = Vec::new();
+
+    if encoded.is_empty() {
+        problems.push("Segment is empty".to_string());
+    }
+    if encoded.ends_with('=') {
+        problems.push("Segment has '=' padding, which base64url in JWTs must omit".to_string());
+    }
+    if let Some((offset, c)) = encoded
+        .trim_end_matches('=')
+        .char_indices()
+        .find(|(_, c)| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+    {
+        let hint = match c {
+            '+' | '/' => " (standard base64 instead of base64url)",
+            c if c.is_whitespace() => " (whitespace, possibly from line wrapping)",
+            _ => "",
+        };
+        problems.push(format!(
+            "Invalid base64url character {:?} at offset {}{}",
+            c, offset, hint
+        ));
+    }
+    if encoded.trim_end_matches('=').len() % 4 == 1 {
+        problems.push("Segment length is not a valid base64url length (truncated?)".to_string());
+    }
+
+    let decoded = match base64_url::decode(encoded.trim_end_matches('=')) {
+        Ok(bytes) => Some(bytes),
+        Err(e) => {
+            problems.push(format!("Base64url decoding failed: {}", e));
+            None
+        }
+    };
+
+    // Header and payload must be UTF-8 JSON; the signature is opaque bytes
+    if let Some(bytes) = decoded.as_ref().filter(|_| name != "signature") {
+        match std::str::from_utf8(bytes) {
+            Err(e) => problems.push(format!(
+                "Decoded bytes are not valid UTF-8 (first invalid byte at offset {})",
+                e.valid_up_to()
+            )),
+            Ok(text) => {
+                if let Err(e) = serde_json::from_str::<Value>(text) {
+                    problems.push(format!("Decoded text is not valid JSON: {}", e));
+                }
+            }
+        }
+    }
+
+    RawSegment {
+        name,
+        encoded: encoded.to_string(),
+        decoded,
+        problems,
+    }
+}
@@ -0,0 +1,335 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::token::conditional::ConditionalAccess;
+use crate::token::Claims;
+
+/// Requirements an API places on incoming tokens, like its authorization middleware
+///
+/// Empty lists are not checked. A token needs one of the `scopes` (delegated
+/// tokens) or one of the `roles` (application tokens), not all of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiRequirements {
+    /// Accepted audiences, e.g. the App ID URI `api://my-app-id` and the client ID
+    pub audiences: Vec<String>,
+    /// Accepted delegated scopes (`scp`)
+    pub scopes: Vec<String>,
+    /// Accepted app roles (`roles`)
+    pub roles: Vec<String>,
+    /// Accepted token versions, `1.0` and/or `2.0`
+    pub versions: Vec<String>,
+    /// Authentication context IDs the sign-in must have satisfied (`acrs`), all of them
+    pub auth_contexts: Vec<String>,
+}
+
+/// Named set of requirements, e.g. for one of the resources behind an API gateway
+///
+/// Deserialized from the `name` and the fields of [`ApiRequirements`] side by
+/// side, rejecting unknown fields like `ApiRequirements` does.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ValidationProfile {
+    pub name: String,
+    #[serde(flatten)]
+    pub requirements: ApiRequirements,
+}
+
+// serde cannot reject unknown fields of a flattened struct, so the name is split off by hand
+impl<'de> Deserialize<'de> for ValidationProfile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut fields = Map::deserialize(deserializer)?;
+        let name = match fields.remove("name") {
+            Some(Value::String(name)) => name,
+            Some(_) => return Err(D::Error::custom("profile name must be a string")),
+            None => return Err(D::Error::missing_field("name")),
+        };
+        let requirements =
+            serde_json::from_value(Value::Object(fields)).map_err(D::Error::custom)?;
+        Ok(ValidationProfile { name, requirements })
+    }
+}
+
+/// Profiles a token is checked against in order, the first one it meets matches
+///
+/// Lets a gateway fronting several resources accept e.g. "Graph tokens, or
+/// tokens for api://mine with scope X" and route by the matching profile.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileChain {
+    #[serde(rename = "profile")]
+    pub profiles: Vec<ValidationProfile>,
+}
+
+/// Outcome of checking a token against one profile of a chain
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProfileResult<'a> {
+    pub profile: &'a ValidationProfile,
+    pub checks: Vec<RequirementCheck>,
+}
+
+impl ProfileResult<'_> {
+    /// Returns whether the token meets every requirement of the profile
+    pub fn matched(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+}
+
+/// Outcome of checking a token against one requirement
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RequirementCheck {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl ApiRequirements {
+    /// Checks the claims of a token against each configured requirement
+    pub fn check(&self, claims: &Claims) -> Vec<RequirementCheck> {
+        let mut checks = Vec::new();
+
+        if !self.audiences.is_empty() {
+            let audiences = claims.audiences();
+            let accepted = audiences.iter().find(|aud| {
+                self.audiences
+                    .iter()
+                    .any(|expected| expected.trim_end_matches('/') == aud.trim_end_matches('/'))
+            });
+            checks.push(RequirementCheck {
+                name: "Audience accepted",
+                passed: accepted.is_some(),
+                detail: match accepted {
+                    Some(aud) => aud.to_string(),
+                    None => format!(
+                        "{} is not one of {}",
+                        claims.audience_display(),
+                        self.audiences.join(", ")
+                    ),
+                },
+            });
+        }
+
+        if !self.versions.is_empty() {
+            let version = claims.version().unwrap_or("unknown");
+            checks.push(RequirementCheck {
+                name: "Token version accepted",
+                passed: self.versions.iter().any(|v| v == version),
+                detail: format!("version {}, accepted {}", version, self.versions.join(", ")),
+            });
+        }
+
+        if !self.scopes.is_empty() || !self.roles.is_empty() {
+            let scope = self.scopes.iter().find(|scope| claims.has_scope(scope));
+            let role = self.roles.iter().find(|role| claims.has_role(role));
+            checks.push(RequirementCheck {
+                name: "Scope or role granted",
+                passed: scope.is_some() || role.is_some(),
+                detail: match (scope, role) {
+                    (Some(scope), _) => format!("scope {}", scope),
+                    (None, Some(role)) => format!("role {}", role),
+                    (None, None) => format!(
+                        "token has scopes [{}] and roles [{}], none of which are accepted",
+                        claims.scopes().join(" "),
+                        claims.roles().join(", ")
+                    ),
+                },
+            });
+        }
+
+        if !self.auth_contexts.is_empty() {
+            let access = ConditionalAccess::from_claims(claims);
+            let missing: Vec<&str> = self
+                .auth_contexts
+                .iter()
+                .filter(|id| !access.satisfies(id))
+                .map(String::as_str)
+                .collect();
+            checks.push(RequirementCheck {
+                name: "Auth context satisfied",
+                passed: missing.is_empty(),
+                detail: if missing.is_empty() {
+                    format!("acrs includes {}", self.auth_contexts.join(", "))
+                } else if access.is_cae_capable() {
+                    format!(
+                        "{} not in acrs [{}]: the API should answer with a claims challenge \
+                         (insufficient_claims) so the client signs in again",
+                        missing.join(", "),
+                        access.auth_contexts.join(", ")
+                    )
+                } else {
+                    format!(
+                        "{} not in acrs [{}], and the client did not declare the cp1 capability \
+                         (xms_cc) needed to handle a claims challenge",
+                        missing.join(", "),
+                        access.auth_contexts.join(", ")
+                    )
+                },
+            });
+        }
+
+        checks
+    }
+}
+
+/// Event member marking a JWT as an OpenID Connect back-channel logout token
+pub const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+/// Kind of token a client says it is holding, with the claim rules of that kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Access token for an API, granting scopes or roles
+    Access,
+    /// ID token, e.g. one sent back as `id_token_hint` when signing out
+    Id,
+    /// OpenID Connect back-channel logout token posted to an app's logout URL
+    Logout,
+}
+
+impl TokenKind {
+    /// Checks the claims of a token against the rules of this kind of token
+    pub fn check(&self, claims: &Claims) -> Vec<RequirementCheck> {
+        match self {
+            TokenKind::Access => {
+                let granted = !claims.scopes().is_empty() || !claims.roles().is_empty();
+                vec![RequirementCheck {
+                    name: "Scope or role present",
+                    passed: granted,
+                    detail: match granted {
+                        true => format!(
+                            "scopes [{}], roles [{}]",
+                            claims.scopes().join(" "),
+                            claims.roles().join(", ")
+                        ),
+                        false => {
+                            "neither scp nor roles, an API cannot authorize this token".to_string()
+                        }
+                    },
+                }]
+            }
+            TokenKind::Id => vec![
+                RequirementCheck {
+                    name: "Subject present",
+                    passed: !claims.sub.is_empty(),
+                    detail: format!("sub {}", claims.sub),
+                },
+                RequirementCheck {
+                    name: "No delegated scopes",
+                    passed: claims.scp.is_none(),
+                    detail: match &claims.scp {
+                        None => "no scp claim".to_string(),
+                        Some(scp) => format!("scp {}: this is an access token", scp),
+                    },
+                },
+            ],
+            TokenKind::Logout => logout_checks(claims),
+        }
+    }
+}
+
+/// Applies the rules of OpenID Connect Back-Channel Logout 1.0, section 2.4
+fn logout_checks(claims: &Claims) -> Vec<RequirementCheck> {
+    let events = claims.extra.get("events");
+    let event = events.and_then(|events| events.get(BACKCHANNEL_LOGOUT_EVENT));
+    let sid = claims.extra.get("sid").and_then(Value::as_str);
+    let nonce = claims.extra.get("nonce");
+    let jti = claims.extra.get("jti").and_then(Value::as_str);
+
+    vec![
+        RequirementCheck {
+            name: "Logout event present",
+            passed: event.is_some_and(Value::is_object),
+            detail: match (events, event) {
+                (_, Some(Value::Object(_))) => format!("events names {}", BACKCHANNEL_LOGOUT_EVENT),
+                (_, Some(other)) => {
+                    format!("the logout event must be a JSON object, not {}", other)
+                }
+                (Some(events), None) => {
+                    format!("events {} lacks {}", events, BACKCHANNEL_LOGOUT_EVENT)
+                }
+                (None, None) => "no events claim, this is not a logout token".to_string(),
+            },
+        },
+        RequirementCheck {
+            name: "No nonce",
+            passed: nonce.is_none(),
+            detail: match nonce {
+                None => "no nonce claim".to_string(),
+                Some(_) => "logout tokens must not contain a nonce, \
+                            it would let an ID token be replayed as a logout token"
+                    .to_string(),
+            },
+        },
+        RequirementCheck {
+            name: "Session identified",
+            passed: sid.is_some() || !claims.sub.is_empty(),
+            detail: match sid {
+                Some(sid) => format!("sid {}", sid),
+                None if !claims.sub.is_empty() => format!(
+                    "no sid, only sub {}: every session of the user is ended",
+                    claims.sub
+                ),
+                None => "neither sid nor sub names the session to end".to_string(),
+            },
+        },
+        RequirementCheck {
+            name: "Token ID present",
+            passed: jti.is_some(),
+            detail: match jti {
+                Some(jti) => format!("jti {}, reject logout tokens seen before", jti),
+                None => "no jti claim, replayed logout tokens cannot be detected".to_string(),
+            },
+        },
+    ]
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Access => write!(f, "access"),
+            TokenKind::Id => write!(f, "id"),
+            TokenKind::Logout => write!(f, "logout"),
+        }
+    }
+}
+
+impl FromStr for TokenKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "access" | "access_token" => Ok(TokenKind::Access),
+            "id" | "id_token" | "id_token_hint" => Ok(TokenKind::Id),
+            "logout" | "logout_token" => Ok(TokenKind::Logout),
+            _ => Err(format!(
+                "'{}' is not a token kind, use access, id or logout",
+                value
+            )),
+        }
+    }
+}
+
+impl ProfileChain {
+    /// Creates a chain checking the profiles in the given order
+    pub fn new(profiles: Vec<ValidationProfile>) -> Self {
+        ProfileChain { profiles }
+    }
+
+    /// Gets the first profile whose requirements the token meets
+    pub fn first_match(&self, claims: &Claims) -> Option<&ValidationProfile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.requirements.check(claims).iter().all(|c| c.passed))
+    }
+
+    /// Checks the token against every profile, e.g. to explain why none matched
+    pub fn evaluate(&self, claims: &Claims) -> Vec<ProfileResult<'_>> {
+        self.profiles
+            .iter()
+            .map(|profile| ProfileResult {
+                profile,
+                checks: profile.requirements.check(claims),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,283 @@
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use jsonwebtoken::{crypto, Algorithm, DecodingKey};
+use roxmltree::{Document, Node};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::token::c14n::canonicalize;
+use crate::token::claims::Claims;
+
+const SAML_ASSERTION_NS: &str = "urn:oasis:names:tc:SAML:2.0:assertion";
+const XMLDSIG_NS: &str = "http://www.w3.org/2000/09/xmldsig#";
+const RSA_SHA256: &str = "http://www.w3.org/2001/04/xmldsig-more#rsa-sha256";
+const SHA256: &str = "http://www.w3.org/2001/04/xmlenc#sha256";
+
+/// A SAML attribute with all of its values
+#[derive(Debug, Clone)]
+pub struct SamlAttribute {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+/// Represents the parts of a SAML 2.0 assertion relevant for inspection
+#[derive(Debug, Clone)]
+pub struct SamlAssertion {
+    pub id: String,
+    pub issuer: String,
+    pub issue_instant: String,
+    pub subject: Option<String>,
+    pub not_before: Option<String>,
+    pub not_on_or_after: Option<String>,
+    pub audiences: Vec<String>,
+    pub authn_context: Option<String>,
+    pub attributes: Vec<SamlAttribute>,
+    pub signed: bool,
+}
+
+/// Detects SAML input and returns the assertion XML
+///
+/// Accepts raw XML as well as base64-encoded XML (as posted by the SAML
+/// HTTP-POST binding). Returns `None` for anything else, e.g. JWTs.
+pub fn decode_saml_input(input: &str) -> Option<String> {
+    let input = input.trim();
+    if input.starts_with('<') {
+        return Some(input.to_string());
+    }
+    if input.contains('.') {
+        return None;
+    }
+
+    let compact: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+    let bytes = STANDARD.decode(compact).ok()?;
+    let xml = String::from_utf8(bytes).ok()?;
+    let xml = xml.trim_start_matches('\u{feff}').trim();
+    xml.starts_with('<').then(|| xml.to_string())
+}
+
+impl SamlAssertion {
+    /// Parses the first SAML 2.0 assertion found in the document
+    pub fn parse(xml: &str) -> Result<Self> {
+        let doc = Document::parse(xml).context("Invalid XML")?;
+        let assertion = find_assertion(&doc)?;
+
+        let child_text = |parent: Node, name: &str| -> Option<String> {
+            child(parent, SAML_ASSERTION_NS, name)
+                .and_then(|n| n.text())
+                .map(|t| t.trim().to_string())
+        };
+
+        let subject = child(assertion, SAML_ASSERTION_NS, "Subject")
+            .and_then(|subject| child_text(subject, "NameID"));
+
+        let conditions = child(assertion, SAML_ASSERTION_NS, "Conditions");
+        let audiences = conditions
+            .map(|conditions| {
+                conditions
+                    .descendants()
+                    .filter(|n| n.has_tag_name((SAML_ASSERTION_NS, "Audience")))
+                    .filter_map(|n| n.text())
+                    .map(|t| t.trim().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let authn_context = assertion
+            .descendants()
+            .find(|n| n.has_tag_name((SAML_ASSERTION_NS, "AuthnContextClassRef")))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string());
+
+        let attributes = assertion
+            .descendants()
+            .filter(|n| n.has_tag_name((SAML_ASSERTION_NS, "Attribute")))
+            .map(|attr| SamlAttribute {
+                name: attr.attribute("Name").unwrap_or_default().to_string(),
+                values: attr
+                    .children()
+                    .filter(|n| n.has_tag_name((SAML_ASSERTION_NS, "AttributeValue")))
+                    .map(|n| n.text().unwrap_or_default().trim().to_string())
+                    .collect(),
+            })
+            .collect();
+
+        Ok(SamlAssertion {
+            id: assertion.attribute("ID").unwrap_or_default().to_string(),
+            issuer: child_text(assertion, "Issuer").context("Assertion has no Issuer")?,
+            issue_instant: assertion
+                .attribute("IssueInstant")
+                .unwrap_or_default()
+                .to_string(),
+            subject,
+            not_before: conditions
+                .and_then(|c| c.attribute("NotBefore"))
+                .map(str::to_string),
+            not_on_or_after: conditions
+                .and_then(|c| c.attribute("NotOnOrAfter"))
+                .map(str::to_string),
+            audiences,
+            authn_context,
+            attributes,
+            signed: child(assertion, XMLDSIG_NS, "Signature").is_some(),
+        })
+    }
+
+    /// Gets the assertion as claims, e.g. to report it like a JWT
+    ///
+    /// Issuer, subject and audiences become `iss`, `sub` and `aud`, the issue
+    /// instant and conditions `iat`, `nbf` and `exp`. Attributes are kept
+    /// under their full name, as an array if they have several values.
+    pub fn to_claims(&self) -> Result<Claims> {
+        let timestamp = |value: &str| {
+            chrono::DateTime::parse_from_rfc3339(value)
+                .map(|time| time.timestamp().max(0) as u64)
+                .ok()
+        };
+        let single_or_array = |values: &[String]| match values {
+            [value] => Value::from(value.as_str()),
+            values => Value::from(values.to_vec()),
+        };
+
+        let mut builder = Claims::builder()
+            .iss(&self.issuer)
+            .sub(self.subject.as_deref().unwrap_or_default())
+            .aud(single_or_array(&self.audiences))
+            .iat(timestamp(&self.issue_instant).unwrap_or_default())
+            .exp(
+                self.not_on_or_after
+                    .as_deref()
+                    .and_then(timestamp)
+                    .unwrap_or_default(),
+            );
+        builder = match self.not_before.as_deref().and_then(timestamp) {
+            Some(nbf) => builder.nbf(nbf),
+            None => builder.without("nbf"),
+        };
+        for attribute in &self.attributes {
+            builder = builder.claim(&attribute.name, single_or_array(&attribute.values));
+        }
+        builder.build()
+    }
+}
+
+/// Verifies the enveloped XML signature of the assertion against trusted certificates
+///
+/// `certificates` are base64-encoded DER certificates, e.g. from the tenant's
+/// federation metadata. Returns the index of the certificate that verified
+/// the signature. The certificate embedded in the assertion is never trusted.
+pub fn verify_saml_signature(xml: &str, certificates: &[String]) -> Result<usize> {
+    let doc = Document::parse(xml).context("Invalid XML")?;
+    let assertion = find_assertion(&doc)?;
+    let signature = child(assertion, XMLDSIG_NS, "Signature").context("Assertion is not signed")?;
+    let signed_info =
+        child(signature, XMLDSIG_NS, "SignedInfo").context("Signature has no SignedInfo")?;
+
+    let signature_method = child(signed_info, XMLDSIG_NS, "SignatureMethod")
+        .and_then(|n| n.attribute("Algorithm"))
+        .unwrap_or_default();
+    if signature_method != RSA_SHA256 {
+        bail!("Unsupported signature method '{}'", signature_method);
+    }
+
+    // The reference must point at the assertion itself
+    let reference =
+        child(signed_info, XMLDSIG_NS, "Reference").context("SignedInfo has no Reference")?;
+    let expected_uri = format!("#{}", assertion.attribute("ID").unwrap_or_default());
+    if reference.attribute("URI") != Some(expected_uri.as_str()) {
+        bail!(
+            "Signature reference '{}' does not point at the assertion ({})",
+            reference.attribute("URI").unwrap_or_default(),
+            expected_uri
+        );
+    }
+
+    let digest_method = child(reference, XMLDSIG_NS, "DigestMethod")
+        .and_then(|n| n.attribute("Algorithm"))
+        .unwrap_or_default();
+    if digest_method != SHA256 {
+        bail!("Unsupported digest method '{}'", digest_method);
+    }
+
+    let expected_digest = child(reference, XMLDSIG_NS, "DigestValue")
+        .and_then(|n| n.text())
+        .context("Reference has no DigestValue")?;
+    let digest = STANDARD.encode(Sha256::digest(
+        canonicalize(assertion, Some(signature.id())).as_bytes(),
+    ));
+    if digest != strip_whitespace(expected_digest) {
+        bail!("Digest mismatch: the assertion was modified after it was signed");
+    }
+
+    let signature_value = child(signature, XMLDSIG_NS, "SignatureValue")
+        .and_then(|n| n.text())
+        .context("Signature has no SignatureValue")?;
+    let signature_bytes = STANDARD
+        .decode(strip_whitespace(signature_value))
+        .context("SignatureValue is not valid base64")?;
+    let signature_b64url = base64_url::encode(&signature_bytes);
+    let message = canonicalize(signed_info, None);
+
+    for (index, certificate) in certificates.iter().enumerate() {
+        let key = DecodingKey::from_rsa_pem(certificate_pem(certificate).as_bytes())?;
+        if crypto::verify(
+            &signature_b64url,
+            message.as_bytes(),
+            &key,
+            Algorithm::RS256,
+        )? {
+            return Ok(index);
+        }
+    }
+
+    Err(anyhow!(
+        "Signature does not match any of the {} trusted signing certificates",
+        certificates.len()
+    ))
+}
+
+/// Extracts the signing certificates from WS-Federation / SAML metadata
+pub fn parse_federation_certificates(metadata: &str) -> Result<Vec<String>> {
+    let doc = Document::parse(metadata).context("Invalid federation metadata XML")?;
+
+    let mut certificates: Vec<String> = doc
+        .descendants()
+        .filter(|n| n.has_tag_name((XMLDSIG_NS, "X509Certificate")))
+        .filter_map(|n| n.text())
+        .map(strip_whitespace)
+        .collect();
+    certificates.sort();
+    certificates.dedup();
+
+    if certificates.is_empty() {
+        bail!("Federation metadata contains no signing certificates");
+    }
+    Ok(certificates)
+}
+
+fn find_assertion<'a, 'input>(doc: &'a Document<'input>) -> Result<Node<'a, 'input>> {
+    doc.descendants()
+        .find(|n| n.has_tag_name((SAML_ASSERTION_NS, "Assertion")))
+        .context("No SAML 2.0 assertion found")
+}
+
+fn child<'a, 'input>(parent: Node<'a, 'input>, ns: &str, name: &str) -> Option<Node<'a, 'input>> {
+    parent.children().find(|n| n.has_tag_name((ns, name)))
+}
+
+fn strip_whitespace(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// Wraps a base64-encoded DER certificate in PEM armor
+pub(crate) fn certificate_pem(certificate: &str) -> String {
+    let body: Vec<&str> = certificate
+        .as_bytes()
+        .chunks(64)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+    format!(
+        "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
+        body.join("\n")
+    )
+}
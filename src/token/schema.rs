@@ -0,0 +1,49 @@
+//! Validation of the claims of a token against a JSON Schema
+//!
+//! A schema codifies the contract a team expects its tokens to meet: which
+//! claims are present, their types, allowed values and formats. Checking
+//! tokens against it catches tenant or app registration changes, such as an
+//! optional claim that was removed, before the API relying on them breaks.
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::token::Claims;
+
+/// A JSON Schema the claims of a token must conform to
+#[derive(Debug, Clone)]
+pub struct ClaimsSchema {
+    validator: jsonschema::Validator,
+}
+
+/// A part of the claims that does not conform to the schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value, e.g. `/roles/0`, empty for the whole claim set
+    pub path: String,
+    pub message: String,
+}
+
+impl ClaimsSchema {
+    /// Compiles a schema, detecting its draft from `$schema`
+    ///
+    /// External `$ref`s are not resolved.
+    pub fn new(schema: &Value) -> Result<Self> {
+        let validator =
+            jsonschema::validator_for(schema).map_err(|e| anyhow!("Invalid JSON Schema: {}", e))?;
+        Ok(ClaimsSchema { validator })
+    }
+
+    /// Checks the claims against the schema, returning every violation
+    pub fn check(&self, claims: &Claims) -> Vec<SchemaViolation> {
+        let claims = serde_json::to_value(claims).unwrap_or_default();
+        self.validator
+            .iter_errors(&claims)
+            .map(|error| SchemaViolation {
+                path: error.instance_path().to_string(),
+                message: error.to_string(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,143 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::token::warning::{Severity, Warning};
+use crate::token::Claims;
+
+/// Prefix of the `Authorization` header value carrying a token
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// Share of a limit from which a token is reported as approaching it
+const WARNING_RATIO: f64 = 0.75;
+
+/// Number of claims listed as the largest contributors to the payload
+const LARGEST_CLAIMS: usize = 3;
+
+/// Common size limits a token runs into in production
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SizeLimit {
+    /// 4 KB per cookie in browsers, for tokens kept in session cookies
+    Cookie,
+    /// 8 KB request header buffers of proxies and servers (nginx, IIS, Kestrel defaults)
+    ProxyHeader,
+}
+
+impl SizeLimit {
+    /// Gets the limit in bytes
+    pub fn bytes(self) -> usize {
+        match self {
+            SizeLimit::Cookie => 4096,
+            SizeLimit::ProxyHeader => 8192,
+        }
+    }
+
+    /// Gets a short description of the limit
+    pub fn description(self) -> &'static str {
+        match self {
+            SizeLimit::Cookie => "4 KB cookie limit",
+            SizeLimit::ProxyHeader => "8 KB proxy header limit",
+        }
+    }
+}
+
+/// Sizes of a token and the claims that make it large
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenSize {
+    /// Length of the compact token in bytes
+    pub total: usize,
+    /// Encoded length of each segment, e.g. `("payload", 1530)`
+    pub segments: Vec<(&'static str, usize)>,
+    /// Number of entries in the `groups` claim
+    pub groups: usize,
+    /// Number of entries in the `roles` and `wids` claims
+    pub roles: usize,
+    /// Largest payload claims by serialized size, largest first
+    pub largest_claims: Vec<(String, usize)>,
+}
+
+impl TokenSize {
+    /// Measures a token and the claims decoded from it
+    pub fn measure(token: &str, claims: &Claims) -> Self {
+        let token = token.trim();
+        let token = token.strip_prefix(BEARER_PREFIX).unwrap_or(token);
+        let names = ["header", "payload", "signature"];
+        let segments = token
+            .split('.')
+            .enumerate()
+            .map(|(index, part)| (names.get(index).copied().unwrap_or("extra"), part.len()))
+            .collect();
+
+        let wids = match claims.extra.get("wids") {
+            Some(Value::Array(wids)) => wids.len(),
+            _ => 0,
+        };
+
+        let mut largest_claims: Vec<(String, usize)> = match serde_json::to_value(claims) {
+            Ok(Value::Object(payload)) => payload
+                .iter()
+                .map(|(name, value)| (name.clone(), value.to_string().len()))
+                .collect(),
+            _ => Vec::new(),
+        };
+        largest_claims.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        largest_claims.truncate(LARGEST_CLAIMS);
+
+        TokenSize {
+            total: token.len(),
+            segments,
+            groups: claims.groups().len(),
+            roles: claims.roles().len() + wids,
+            largest_claims,
+        }
+    }
+
+    /// Gets the size of the `Authorization: Bearer` header value carrying the token
+    pub fn header_value(&self) -> usize {
+        BEARER_PREFIX.len() + self.total
+    }
+
+    /// Warns about each limit the token exceeds or approaches
+    pub fn warnings(&self) -> Vec<Warning> {
+        let remediation = match self.groups {
+            0 => "Emit fewer optional claims, or keep the token out of cookies and headers with a \
+                  server-side session",
+            _ => "Emit only the groups assigned to the app (groupMembershipClaims \"ApplicationGroup\") \
+                  or use app roles to keep group claims from growing the token",
+        };
+        [
+            (SizeLimit::Cookie, self.total),
+            (SizeLimit::ProxyHeader, self.header_value()),
+        ]
+        .into_iter()
+        .filter_map(|(limit, size)| {
+            let share = size as f64 / limit.bytes() as f64;
+            let warning = if share > 1.0 {
+                Warning::new(
+                    "size-over-limit",
+                    Severity::High,
+                    format!(
+                        "{} bytes exceed the {} by {} bytes",
+                        size,
+                        limit.description(),
+                        size - limit.bytes()
+                    ),
+                )
+            } else if share >= WARNING_RATIO {
+                Warning::new(
+                    "size-near-limit",
+                    Severity::Low,
+                    format!(
+                        "{} bytes are {:.0}% of the {}",
+                        size,
+                        share * 100.0,
+                        limit.description()
+                    ),
+                )
+            } else {
+                return None;
+            };
+            Some(warning.remediation(remediation))
+        })
+        .collect()
+    }
+}
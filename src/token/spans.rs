@@ -0,0 +1,112 @@
+//! OpenTelemetry spans recorded when the `otel` feature is enabled
+//!
+//! Spans are started with the global tracer, so they are only exported once
+//! a tracer provider is installed with `opentelemetry::global::set_tracer_provider`.
+//! Without the feature every function is a no-op.
+
+use anyhow::Result;
+
+/// Name of the tracer all spans are started with
+pub const TRACER_NAME: &str = "azure_token_validator";
+
+/// A step of a validation, a child of the current span, ended when dropped
+pub(crate) struct Span {
+    #[cfg(feature = "otel")]
+    context: opentelemetry::Context,
+}
+
+/// Keeps a span current on this thread until dropped, see [`Span::enter`]
+#[cfg(feature = "blocking")]
+pub(crate) struct Entered {
+    #[cfg(feature = "otel")]
+    _guard: opentelemetry::ContextGuard,
+}
+
+impl Span {
+    /// Starts a span as a child of the current one
+    pub(crate) fn start(name: &'static str) -> Self {
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry::trace::{TraceContextExt, Tracer};
+
+            let span = opentelemetry::global::tracer(TRACER_NAME).start(name);
+            Span {
+                context: opentelemetry::Context::current_with_span(span),
+            }
+        }
+        #[cfg(not(feature = "otel"))]
+        {
+            let _ = name;
+            Span {}
+        }
+    }
+
+    /// Sets an attribute of the span, e.g. the tenant or key ID
+    pub(crate) fn attribute(&self, key: &'static str, value: impl Into<String>) {
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry::trace::TraceContextExt;
+
+            self.context
+                .span()
+                .set_attribute(opentelemetry::KeyValue::new(key, value.into()));
+        }
+        #[cfg(not(feature = "otel"))]
+        let _ = (key, value);
+    }
+
+    /// Records the `outcome` of the step, `ok` or the failure code of the error
+    pub(crate) fn outcome<T>(&self, result: &Result<T>) {
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry::trace::{Status, TraceContextExt};
+
+            let span = self.context.span();
+            match result {
+                Ok(_) => span.set_attribute(opentelemetry::KeyValue::new("outcome", "ok")),
+                Err(e) => {
+                    span.set_attribute(opentelemetry::KeyValue::new(
+                        "outcome",
+                        crate::token::error::failure_code(e),
+                    ));
+                    span.set_status(Status::error(e.to_string()));
+                }
+            }
+        }
+        #[cfg(not(feature = "otel"))]
+        let _ = result;
+    }
+
+    /// Makes the span current while `future` runs, parenting the spans it starts
+    #[cfg(feature = "net")]
+    pub(crate) async fn wrap<F: std::future::Future>(&self, future: F) -> F::Output {
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry::context::FutureExt;
+
+            future.with_context(self.context.clone()).await
+        }
+        #[cfg(not(feature = "otel"))]
+        future.await
+    }
+
+    /// Makes the span current on this thread, for synchronous code
+    #[cfg(feature = "blocking")]
+    pub(crate) fn enter(&self) -> Entered {
+        Entered {
+            #[cfg(feature = "otel")]
+            _guard: self.context.clone().attach(),
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry::trace::TraceContextExt;
+
+            self.context.span().end();
+        }
+    }
+}
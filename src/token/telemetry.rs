@@ -0,0 +1,98 @@
+//! Metrics recorded through the `metrics` facade when the `metrics` feature is enabled
+//!
+//! Without the feature every function is a no-op. Install any `metrics`
+//! recorder, e.g. a Prometheus exporter, to collect them.
+
+use anyhow::Result;
+use std::time::Duration;
+
+use crate::token::claims::Claims;
+
+/// Validations by `result` (`ok` or `failed`) and failure `reason`
+pub const VALIDATIONS: &str = "azure_token_validator_validations_total";
+/// Time taken by a validation, including fetching keys, in seconds
+pub const VALIDATION_DURATION: &str = "azure_token_validator_validation_duration_seconds";
+/// JWKS lookups served from the cache
+pub const JWKS_CACHE_HITS: &str = "azure_token_validator_jwks_cache_hits_total";
+/// JWKS lookups that had to fetch the key set
+pub const JWKS_CACHE_MISSES: &str = "azure_token_validator_jwks_cache_misses_total";
+/// Time taken to fetch a key set by `result`, in seconds
+pub const JWKS_FETCH_DURATION: &str = "azure_token_validator_jwks_fetch_duration_seconds";
+/// Key sets refetched before they expired from the cache, by `result`
+pub const JWKS_REFRESHES: &str = "azure_token_validator_jwks_refreshes_total";
+
+/// Registers descriptions of all metrics with the installed recorder
+#[cfg(feature = "metrics")]
+pub fn describe() {
+    use metrics::{describe_counter, describe_histogram, Unit};
+
+    describe_counter!(
+        VALIDATIONS,
+        "Token validations by result and failure reason"
+    );
+    describe_histogram!(
+        VALIDATION_DURATION,
+        Unit::Seconds,
+        "Time taken by a token validation, including fetching keys"
+    );
+    describe_counter!(JWKS_CACHE_HITS, "JWKS lookups served from the cache");
+    describe_counter!(JWKS_CACHE_MISSES, "JWKS lookups that fetched the key set");
+    describe_histogram!(
+        JWKS_FETCH_DURATION,
+        Unit::Seconds,
+        "Time taken to fetch a JWKS document"
+    );
+    describe_counter!(
+        JWKS_REFRESHES,
+        "JWKS documents refetched before they expired from the cache"
+    );
+}
+
+/// Records the outcome and duration of a validation
+pub(crate) fn record_validation(result: &Result<Claims>, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        let (outcome, reason) = match result {
+            Ok(_) => ("ok", "none"),
+            Err(e) => ("failed", crate::token::error::failure_code(e)),
+        };
+        metrics::counter!(VALIDATIONS, "result" => outcome, "reason" => reason).increment(1);
+        metrics::histogram!(VALIDATION_DURATION).record(elapsed.as_secs_f64());
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (result, elapsed);
+}
+
+/// Records whether a JWKS lookup was served from the cache
+pub(crate) fn record_jwks_lookup(hit: bool) {
+    #[cfg(feature = "metrics")]
+    match hit {
+        true => metrics::counter!(JWKS_CACHE_HITS).increment(1),
+        false => metrics::counter!(JWKS_CACHE_MISSES).increment(1),
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = hit;
+}
+
+/// Records the duration of a JWKS fetch
+pub(crate) fn record_jwks_fetch(success: bool, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        let outcome = if success { "ok" } else { "failed" };
+        metrics::histogram!(JWKS_FETCH_DURATION, "result" => outcome).record(elapsed.as_secs_f64());
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (success, elapsed);
+}
+
+/// Records the outcome of refetching a cached key set
+#[cfg(feature = "net")]
+pub(crate) fn record_jwks_refresh(success: bool) {
+    #[cfg(feature = "metrics")]
+    {
+        let outcome = if success { "ok" } else { "failed" };
+        metrics::counter!(JWKS_REFRESHES, "result" => outcome).increment(1);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = success;
+}
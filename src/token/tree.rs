@@ -0,0 +1,155 @@
+//! Flattened view of the claims of a token, searchable by name and value
+//!
+//! Large tokens, e.g. with hundreds of `groups`, are hard to read as a whole.
+//! [`ClaimTree`] lists every value under its path, such as `groups[3]` or
+//! `xms_st.sub`, and finds values with a fuzzy search.
+
+use serde_json::Value;
+
+/// Arrays longer than this are shown collapsed until expanded
+pub const COLLAPSE_AFTER: usize = 5;
+
+/// A scalar value of a claim with the path leading to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClaimLeaf {
+    /// Path of the value, e.g. `groups[3]` or `xms_st.sub`
+    pub path: String,
+    pub value: Value,
+}
+
+impl ClaimLeaf {
+    /// Gets the value as shown and copied, strings without quotes
+    pub fn text(&self) -> String {
+        match &self.value {
+            Value::String(text) => text.clone(),
+            value => value.to_string(),
+        }
+    }
+}
+
+/// Every scalar value of a claim set, in claim order
+#[derive(Debug, Clone)]
+pub struct ClaimTree {
+    root: Value,
+    leaves: Vec<ClaimLeaf>,
+}
+
+impl ClaimTree {
+    /// Flattens a claim set, given as a JSON object
+    pub fn new(claims: Value) -> Self {
+        let mut leaves = Vec::new();
+        if let Value::Object(object) = &claims {
+            for (name, value) in object {
+                flatten(name.clone(), value, &mut leaves);
+            }
+        }
+        ClaimTree {
+            root: claims,
+            leaves,
+        }
+    }
+
+    /// Gets the claim set the tree was built from
+    pub fn root(&self) -> &Value {
+        &self.root
+    }
+
+    /// Gets every scalar value with its path
+    pub fn leaves(&self) -> &[ClaimLeaf] {
+        &self.leaves
+    }
+
+    /// Gets the value at a path, e.g. `groups`, `groups[3]` or `xms_st.sub`
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        let pointer: String = path
+            .split('.')
+            .flat_map(|segment| segment.split('['))
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                let segment = segment.trim_end_matches(']');
+                format!("/{}", segment.replace('~', "~0").replace('/', "~1"))
+            })
+            .collect();
+        self.root.pointer(&pointer)
+    }
+
+    /// Finds the values whose path or text fuzzily match `query`, best matches first
+    pub fn search(&self, query: &str) -> Vec<&ClaimLeaf> {
+        let mut matches: Vec<(u32, usize, &ClaimLeaf)> = self
+            .leaves
+            .iter()
+            .enumerate()
+            .filter_map(|(index, leaf)| {
+                let score = [
+                    fuzzy_score(query, &leaf.path),
+                    fuzzy_score(query, &leaf.text()),
+                ]
+                .into_iter()
+                .flatten()
+                .max()?;
+                Some((score, index, leaf))
+            })
+            .collect();
+        // Best score first, claim order among equal scores
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        matches.into_iter().map(|(_, _, leaf)| leaf).collect()
+    }
+}
+
+fn flatten(path: String, value: &Value, leaves: &mut Vec<ClaimLeaf>) {
+    match value {
+        Value::Object(object) => {
+            for (name, value) in object {
+                flatten(format!("{}.{}", path, name), value, leaves);
+            }
+        }
+        Value::Array(values) => {
+            for (index, value) in values.iter().enumerate() {
+                flatten(format!("{}[{}]", path, index), value, leaves);
+            }
+        }
+        value => leaves.push(ClaimLeaf {
+            path,
+            value: value.clone(),
+        }),
+    }
+}
+
+/// Scores how well `text` matches `query` as a case-insensitive subsequence
+///
+/// Returns `None` if the characters of the query do not all appear in order.
+/// Consecutive characters and matches at the start of a word score higher,
+/// so `grp` ranks `groups` above `signin_group_policy`.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let query: Vec<char> = query
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for (position, c) in text.iter().enumerate() {
+        if next == query.len() {
+            break;
+        }
+        if *c != query[next] {
+            continue;
+        }
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == position) {
+            score += 4;
+        }
+        if position == 0 || !text[position - 1].is_alphanumeric() {
+            score += 2;
+        }
+        previous = Some(position);
+        next += 1;
+    }
+    (next == query.len()).then_some(score)
+}
@@ -1,183 +1,359 @@
 use anyhow::{anyhow, Context, Result};
-use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use jsonwebtoken::errors::{Error as JwtError, ErrorKind};
 use reqwest::Client;
-use serde_json::{json, Value};
-use std::collections::HashMap;
-use std::fmt;
-use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::time::Instant;
 
+use crate::token::builder::ValidatorBuilder;
 use crate::token::claims::Claims;
+use crate::token::clock::{Clock, SkewedClock};
+use crate::token::error::ValidationError;
+use crate::token::explain::{explain_validation, RuleEvaluation};
 use crate::token::jwk::JwksResponse;
+use crate::token::keystore::KeyStore;
+use crate::token::provider::KeyProvider;
+use crate::token::saml::{parse_federation_certificates, verify_saml_signature, SamlAssertion};
+use crate::token::spans::Span;
+use crate::token::telemetry;
+use crate::token::verify::{
+    accepted_audiences, adfs_federation_metadata_uri, check_algorithm, check_time_claims,
+    check_validity_period, configured_adfs_host, configured_jwks_uri, decode_step,
+    decode_unverified, is_tenant_label, issuer_for, issuer_tenant, jwks_uri, tenant_config,
+    token_format, validate_with_jwks, verify_signature_only, verify_with_jwks, AUTO_TENANT,
+};
 
-/// Formats for Azure AD tokens (v1 and v2 endpoints)
-#[derive(Debug, Clone, Copy)]
-pub enum AzureTokenFormat {
-    V1,
-    V2,
-    Common,
-}
-
-impl fmt::Display for AzureTokenFormat {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            AzureTokenFormat::V1 => write!(f, "v1.0"),
-            AzureTokenFormat::V2 => write!(f, "v2.0"),
-            AzureTokenFormat::Common => write!(f, "common"),
-        }
-    }
-}
-
-/// Token validator configuration
-#[derive(Debug, Clone)]
-pub struct ValidatorConfig {
-    pub tenant_id: String,
-    pub validate_exp: bool,
-    pub validate_aud: bool,
-    pub validate_iss: bool,
-    pub leeway: u64, // in seconds
-}
-
-impl Default for ValidatorConfig {
-    fn default() -> Self {
-        Self {
-            tenant_id: "common".to_string(),
-            validate_exp: true,
-            validate_aud: false,
-            validate_iss: true,
-            leeway: 300, // 5 minutes
-        }
-    }
-}
+pub use crate::token::verify::{AzureTokenFormat, ValidatorConfig};
 
 /// Azure AD token validator
+///
+/// All methods take `&self`, so a validator can be shared behind an `Arc`
+/// across tasks and threads.
 pub struct TokenValidator {
     client: Client,
-    jwks_cache: HashMap<String, JwksResponse>,
+    key_store: KeyStore,
+    key_provider: Arc<dyn KeyProvider>,
     config: ValidatorConfig,
 }
 
+// Fails to compile if the validator can no longer be shared across threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<TokenValidator>();
+};
+
 impl TokenValidator {
+    /// Creates a builder for a validator with the default configuration
+    pub fn builder() -> ValidatorBuilder {
+        ValidatorBuilder::new()
+    }
+
     /// Creates a new token validator with the given configuration
     pub fn new(config: ValidatorConfig) -> Self {
+        Self::builder().config(config).build()
+    }
+
+    /// Creates a new token validator fetching keys and metadata with a preconfigured HTTP client
+    pub fn with_client(config: ValidatorConfig, client: Client) -> Self {
+        Self::builder().config(config).http_client(client).build()
+    }
+
+    /// Creates a new token validator sharing the keys cached in `key_store`
+    pub fn with_key_store(config: ValidatorConfig, key_store: KeyStore) -> Self {
+        Self::builder().config(config).key_store(key_store).build()
+    }
+
+    /// Assembles a validator, serving keys from `key_store` unless a provider is given
+    pub(crate) fn from_parts(
+        config: ValidatorConfig,
+        client: Client,
+        key_store: KeyStore,
+        key_provider: Option<Arc<dyn KeyProvider>>,
+    ) -> Self {
         TokenValidator {
-            client: Client::new(),
-            jwks_cache: HashMap::new(),
+            key_provider: key_provider.unwrap_or_else(|| Arc::new(key_store.clone())),
+            client,
+            key_store,
             config,
         }
     }
 
+    /// Replaces the clock used for time-based claim checks, e.g. to freeze time in tests
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.config.clock = Arc::new(clock);
+        self
+    }
+
+    /// Gets the configuration of this validator
+    pub fn config(&self) -> &ValidatorConfig {
+        &self.config
+    }
+
+    /// Gets the key store backing this validator, unused when a key provider is configured
+    pub fn key_store(&self) -> &KeyStore {
+        &self.key_store
+    }
+
     /// Gets the JWKS URI for the given format and tenant
     pub fn get_jwks_uri(&self, format: AzureTokenFormat) -> String {
-        match format {
-            AzureTokenFormat::V1 => {
-                format!(
-                    "https://login.microsoftonline.com/{}/discovery/keys",
-                    self.config.tenant_id
-                )
-            }
-            AzureTokenFormat::V2 => {
-                format!(
-                    "https://login.microsoftonline.com/{}/discovery/v2.0/keys",
-                    self.config.tenant_id
-                )
-            }
-            AzureTokenFormat::Common => {
-                "https://login.microsoftonline.com/common/discovery/keys".to_string()
-            }
-        }
+        jwks_uri(&self.config.tenant_id, format)
+    }
+
+    /// Gets the JWKS URI for a token, resolving the `auto` tenant from its claims
+    ///
+    /// Tokens of the configured AD FS server use the server's own keys.
+    pub fn jwks_uri_for(&self, claims: &Claims) -> Result<String> {
+        configured_jwks_uri(&self.config, claims)
     }
 
     /// Determines the token format based on the issuer claim
     pub fn determine_token_format(&self, claims: &Claims) -> AzureTokenFormat {
-        if claims.iss.contains("sts.windows.net") {
-            AzureTokenFormat::V1
-        } else if claims.iss.contains("/v2.0") {
-            AzureTokenFormat::V2
-        } else {
-            AzureTokenFormat::Common
-        }
+        token_format(claims)
     }
 
     /// Decodes a token without validation to inspect its claims
     pub fn decode_token(&self, token: &str) -> Result<(Value, Claims)> {
-        let header = decode_header(token)?;
+        decode_unverified(token)
+    }
+
+    /// Validates a token against a caller-provided key set without any I/O
+    ///
+    /// Runs the checks of [`validate_token`](Self::validate_token), from the
+    /// algorithm and time claims to the issuer, audience and signature, for
+    /// embedders distributing keys themselves. The clock skew is not measured,
+    /// so `auto_skew` has no effect.
+    pub fn verify_with_keys(
+        token: &str,
+        jwks: &JwksResponse,
+        config: &ValidatorConfig,
+    ) -> Result<Claims> {
+        validate_with_jwks(token, jwks, config)
+    }
 
-        // Just decode the payload without validating the signature
-        let token_data = decode::<Claims>(token, &DecodingKey::from_secret(&[]), &{
-            let mut validation = Validation::new(Algorithm::RS256);
-            validation.insecure_disable_signature_validation();
-            validation.validate_aud = false;
-            validation.validate_exp = false;
-            validation.validate_nbf = false;
-            validation
-        })?;
+    /// Fetches JWKS from the given URI, updating the key store
+    pub async fn fetch_jwks(&self, uri: &str) -> Result<Arc<JwksResponse>> {
+        self.key_provider.refresh_keys(uri).await
+    }
 
-        Ok((json!(header), token_data.claims))
+    /// Gets JWKS from the key store or fetches if not cached
+    pub async fn get_jwks(&self, uri: &str) -> Result<Arc<JwksResponse>> {
+        self.key_provider.get_keys(uri).await
     }
 
-    /// Fetches JWKS from the given URI
-    pub async fn fetch_jwks(&mut self, uri: &str) -> Result<JwksResponse> {
-        let response = self.client.get(uri).send().await?;
-        if !response.status().is_success() {
-            return Err(anyhow!("Failed to fetch JWKS: {}", response.status()));
+    /// Validates a token against Azure AD public keys
+    pub async fn validate_token(&self, token: &str) -> Result<Claims> {
+        self.validate_traced(token, None).await
+    }
+
+    /// Validates a token that must be issued by `tenant_id`, whatever tenant is configured
+    ///
+    /// The token is verified with the keys of `tenant_id`, cached per tenant
+    /// like all keys, and its issuer must be the one the tenant puts in tokens
+    /// of the token's format and cloud, even if issuer validation is disabled.
+    /// All other settings apply as configured, so one validator can serve the
+    /// tokens of many tenants.
+    pub async fn validate_token_for_tenant(&self, token: &str, tenant_id: &str) -> Result<Claims> {
+        self.validate_traced(token, Some(tenant_id)).await
+    }
+
+    async fn validate_traced(&self, token: &str, tenant_id: Option<&str>) -> Result<Claims> {
+        let started = Instant::now();
+        let span = Span::start("validate_token");
+        span.attribute("tenant", tenant_id.unwrap_or(&self.config.tenant_id));
+        let result = span.wrap(self.validate(token, tenant_id)).await;
+        span.outcome(&result);
+        telemetry::record_validation(&result, started.elapsed());
+        result
+    }
+
+    async fn validate(&self, token: &str, tenant_id: Option<&str>) -> Result<Claims> {
+        let (header, claims) = decode_step(token)?;
+        let config = match tenant_id {
+            Some(tenant_id) => Cow::Owned(tenant_config(&self.config, tenant_id, &claims)),
+            None => Cow::Borrowed(&self.config),
+        };
+        check_algorithm(&config, &header)?;
+        // With auto skew the clock is only corrected once the key fetch measured the skew
+        if !config.auto_skew {
+            check_time_claims(&config, &claims)?;
+        }
+
+        let jwks = self.signing_keys(&config, &header, &claims).await?;
+        if config.auto_skew {
+            self.check_time_claims_corrected(&config, &claims)?;
         }
 
-        let jwks: JwksResponse = response.json().await?;
-        self.jwks_cache.insert(uri.to_string(), jwks.clone());
+        verify_with_jwks(&config, token, &header, &claims, &jwks)
+    }
+
+    /// Verifies only the signature of a token against the keys of its tenant
+    ///
+    /// Expired tokens and tokens for other audiences pass as long as Azure AD
+    /// signed them, e.g. to verify tokens found in logs after an incident.
+    pub async fn validate_signature(&self, token: &str) -> Result<Claims> {
+        let span = Span::start("validate_signature");
+        span.attribute("tenant", self.config.tenant_id.as_str());
+        let result = span
+            .wrap(async {
+                let (header, claims) = decode_step(token)?;
+                let jwks = self.signing_keys(&self.config, &header, &claims).await?;
+                verify_signature_only(&self.config, token, &header, claims, &jwks)
+            })
+            .await;
+        span.outcome(&result);
+        result
+    }
+
+    /// Gets the keys of the token's tenant, refetched if they lack the token's key
+    async fn signing_keys(
+        &self,
+        config: &ValidatorConfig,
+        header: &Value,
+        claims: &Claims,
+    ) -> Result<Arc<JwksResponse>> {
+        let uri = configured_jwks_uri(config, claims)?;
+        let mut jwks = self.get_jwks(&uri).await?;
+        // Keys rotated since they were cached are only found in a fresh key set
+        if let Some(kid) = header["kid"].as_str() {
+            if jwks.find_key(kid).is_none() {
+                // Keep the cached keys if the refetch fails, the key is reported as unknown
+                if let Ok(fresh) = self.key_provider.refetch_keys(&uri).await {
+                    jwks = fresh;
+                }
+            }
+        }
         Ok(jwks)
     }
 
-    /// Gets JWKS from cache or fetches if not cached
-    pub async fn get_jwks(&mut self, uri: &str) -> Result<JwksResponse> {
-        if let Some(jwks) = self.jwks_cache.get(uri) {
-            return Ok(jwks.clone());
+    /// Evaluates every validation rule against a token, see [`explain_validation`]
+    ///
+    /// The signature is verified with the keys of the token's tenant; if they
+    /// cannot be fetched the signature rules are skipped.
+    pub async fn explain(&self, token: &str) -> Vec<RuleEvaluation> {
+        let jwks = match self.decode_token(token) {
+            Ok((_, claims)) => match self.jwks_uri_for(&claims) {
+                Ok(uri) => self.get_jwks(&uri).await.ok(),
+                Err(_) => None,
+            },
+            Err(_) => None,
+        };
+        explain_validation(&self.config, token, jwks.as_deref())
+    }
+
+    /// Gets how far the system clock was ahead of Azure AD at the last key fetch, in seconds
+    pub fn clock_skew(&self) -> Option<i64> {
+        self.key_store.clock_skew()
+    }
+
+    /// Checks `exp` and `nbf` against the clock corrected by the measured skew
+    fn check_time_claims_corrected(&self, config: &ValidatorConfig, claims: &Claims) -> Result<()> {
+        match self.clock_skew() {
+            Some(skew) if skew != 0 => {
+                let config = ValidatorConfig {
+                    clock: Arc::new(SkewedClock::new(config.clock.clone(), skew)),
+                    ..config.clone()
+                };
+                check_time_claims(&config, claims)
+            }
+            _ => check_time_claims(config, claims),
         }
+    }
 
-        self.fetch_jwks(uri).await
+    /// Gets the federation metadata URL for the given tenant
+    pub fn get_federation_metadata_uri(&self, tenant_id: &str) -> String {
+        format!(
+            "https://login.microsoftonline.com/{}/federationmetadata/2007-06/federationmetadata.xml",
+            tenant_id
+        )
     }
 
-    /// Validates a token against Azure AD public keys
-    pub async fn validate_token(&mut self, token: &str) -> Result<Claims> {
-        let (header, claims) = self.decode_token(token)?;
+    /// Validates a SAML 2.0 assertion against the federation metadata of its tenant or AD FS server
+    pub async fn validate_saml(&self, xml: &str) -> Result<SamlAssertion> {
+        let assertion = SamlAssertion::parse(xml)?;
 
-        // Check expiration if configured to do so
-        if self.config.validate_exp {
-            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        // Conditions are xs:dateTime values, e.g. 2024-07-01T12:00:00Z
+        let timestamp = |value: &Option<String>, name: &str| -> Result<Option<u64>> {
+            value
+                .as_deref()
+                .map(|value| {
+                    chrono::DateTime::parse_from_rfc3339(value)
+                        .map(|time| time.timestamp().max(0) as u64)
+                        .with_context(|| format!("Invalid {} condition", name))
+                })
+                .transpose()
+        };
+        check_validity_period(
+            &self.config,
+            timestamp(&assertion.not_before, "NotBefore")?,
+            timestamp(&assertion.not_on_or_after, "NotOnOrAfter")?,
+        )?;
 
-            if claims.exp < now {
-                return Err(anyhow!("Token has expired"));
-            }
+        let adfs_host = configured_adfs_host(&self.config, &assertion.issuer);
+        // Azure AD issuers have the form https://sts.windows.net/{tenant}/
+        let tenant_id = match self.config.tenant_id.as_str() {
+            "common" | AUTO_TENANT => issuer_tenant(&assertion.issuer).unwrap_or("common"),
+            tenant_id => tenant_id,
+        };
+        check_saml_assertion(
+            &self.config,
+            &assertion,
+            adfs_host.is_none().then_some(tenant_id),
+        )?;
+
+        let uri = match adfs_host {
+            Some(host) => adfs_federation_metadata_uri(host),
+            None => self.get_federation_metadata_uri(tenant_id),
+        };
+        let response = self.client.get(&uri).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Failed to fetch federation metadata: {}",
+                response.status()
+            ));
         }
+        let certificates = parse_federation_certificates(&response.text().await?)?;
+
+        verify_saml_signature(xml, &certificates)?;
+        Ok(assertion)
+    }
+}
 
-        // Get kid from header
-        let kid = header["kid"]
-            .as_str()
-            .context("Missing 'kid' in token header")?;
-
-        // Get the appropriate JWKS URI
-        let format = self.determine_token_format(&claims);
-        let jwks_uri = self.get_jwks_uri(format);
-
-        // Fetch JWKS
-        let jwks = self.get_jwks(&jwks_uri).await?;
-        let jwk = jwks
-            .find_key(kid)
-            .context("Signing key not found in JWKS")?;
-        let decoding_key = jwk.to_decoding_key()?;
-
-        // Configure validation settings
-        let mut validation = Validation::new(Algorithm::RS256);
-        validation.validate_exp = self.config.validate_exp;
-        validation.validate_aud = self.config.validate_aud;
-        validation.leeway = self.config.leeway;
-
-        // Set issuer validation if configured
-        if self.config.validate_iss {
-            validation.set_issuer(&[&claims.iss]);
+/// Checks the audience and issuer of a SAML assertion the way those of a JWT are checked
+///
+/// Assertions of Azure AD, verified with the federation metadata of `tenant`,
+/// must be issued by `https://sts.windows.net/{tenant}/` unless an issuer is
+/// configured. A tenant taken from the assertion's own issuer must be a GUID
+/// or a single DNS label. AD FS assertions pass no tenant, their issuer
+/// already named the configured server.
+fn check_saml_assertion(
+    config: &ValidatorConfig,
+    assertion: &SamlAssertion,
+    tenant: Option<&str>,
+) -> Result<()> {
+    if config.validate_aud {
+        let accepted = accepted_audiences(config);
+        if !assertion.audiences.iter().any(|aud| accepted.contains(aud)) {
+            return Err(ValidationError::from(JwtError::from(ErrorKind::InvalidAudience)).into());
         }
+    }
 
-        // Validate token with proper signature verification
-        let token_data = decode::<Claims>(token, &decoding_key, &validation)?;
-        Ok(token_data.claims)
+    let from_issuer = matches!(config.tenant_id.as_str(), "common" | AUTO_TENANT);
+    let expected_issuer = match (&config.issuer, tenant) {
+        (Some(issuer), _) => Some(issuer.clone()),
+        (None, Some(tenant)) if from_issuer && !is_tenant_label(tenant) => {
+            return Err(ValidationError::InvalidTenant(tenant.to_string()).into());
+        }
+        // A tenant configured by domain name only appears as its GUID in the issuer
+        (None, Some(tenant)) if from_issuer || uuid::Uuid::parse_str(tenant).is_ok() => {
+            Some(issuer_for(tenant, AzureTokenFormat::V1))
+        }
+        (None, _) => None,
+    };
+    match expected_issuer {
+        Some(expected) if config.validate_iss && assertion.issuer != expected => {
+            Err(ValidationError::from(JwtError::from(ErrorKind::InvalidIssuer)).into())
+        }
+        _ => Ok(()),
     }
 }
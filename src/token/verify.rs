@@ -0,0 +1,749 @@
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::errors::{Error as JwtError, ErrorKind};
+use jsonwebtoken::{decode, Algorithm, Validation};
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+use url::Url;
+
+use crate::token::audience;
+use crate::token::claims::Claims;
+use crate::token::clock::{Clock, SystemClock};
+use crate::token::cloud::Cloud;
+use crate::token::error::ValidationError;
+use crate::token::jwk::{Jwk, JwksResponse};
+use crate::token::malformed::{MalformedToken, TokenSegment, MAX_TOKEN_LENGTH};
+use crate::token::raw::TokenShape;
+use crate::token::spans::Span;
+use crate::token::warning::{Severity, Warning};
+
+/// Formats for Azure AD tokens (v1 and v2 endpoints)
+#[derive(Debug, Clone, Copy)]
+pub enum AzureTokenFormat {
+    V1,
+    V2,
+    /// Microsoft Entra External ID (CIAM) tenants, issued by `{tenant}.ciamlogin.com`
+    Ciam,
+    Common,
+}
+
+impl fmt::Display for AzureTokenFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AzureTokenFormat::V1 => write!(f, "v1.0"),
+            AzureTokenFormat::V2 => write!(f, "v2.0"),
+            AzureTokenFormat::Ciam => write!(f, "ciam"),
+            AzureTokenFormat::Common => write!(f, "common"),
+        }
+    }
+}
+
+/// Tenant ID resolved from each token's `tid` claim or issuer instead of naming a tenant
+pub const AUTO_TENANT: &str = "auto";
+
+/// Token validator configuration
+#[derive(Debug, Clone)]
+pub struct ValidatorConfig {
+    pub tenant_id: String,
+    pub validate_exp: bool,
+    pub validate_nbf: bool,
+    pub validate_aud: bool,
+    pub validate_iss: bool,
+    /// Accepted audiences, checked when `validate_aud` is set
+    pub audiences: Vec<String>,
+    /// Expected issuer, the token's own `iss` is accepted when unset
+    pub issuer: Option<String>,
+    /// Clouds whose tokens are accepted, tokens from unknown issuers are not restricted
+    pub clouds: Vec<Cloud>,
+    /// AD FS server, e.g. `adfs.contoso.com`, whose tokens are validated against its own keys
+    pub adfs_host: Option<String>,
+    pub leeway: u64, // in seconds
+    pub allowed_algorithms: Vec<Algorithm>,
+    /// Client applications (`azp` or `appid`) whose tokens are accepted, any client when empty
+    pub allowed_clients: Vec<String>,
+    /// Key ID (`kid`) the token must be signed with
+    pub pinned_kid: Option<String>,
+    /// Certificate thumbprint (`x5t`, base64url or hex) of the key the token must be signed with
+    pub pinned_thumbprint: Option<String>,
+    /// Corrects `clock` by its skew to Azure AD, measured when fetching keys, before checking
+    /// `exp` and `nbf` (async validator only)
+    pub auto_skew: bool,
+    pub clock: Arc<dyn Clock>,
+}
+
+impl Default for ValidatorConfig {
+    fn default() -> Self {
+        Self {
+            tenant_id: "common".to_string(),
+            validate_exp: true,
+            validate_nbf: true,
+            validate_aud: false,
+            validate_iss: true,
+            audiences: Vec::new(),
+            issuer: None,
+            clouds: Cloud::ALL.to_vec(),
+            adfs_host: None,
+            leeway: 300, // 5 minutes
+            allowed_algorithms: vec![Algorithm::RS256],
+            allowed_clients: Vec::new(),
+            pinned_kid: None,
+            pinned_thumbprint: None,
+            auto_skew: false,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+/// Gets the JWKS URI for the given format and tenant in the public cloud
+pub fn jwks_uri(tenant_id: &str, format: AzureTokenFormat) -> String {
+    cloud_jwks_uri(Cloud::Public, tenant_id, format)
+}
+
+/// Gets the JWKS URI for the given format and tenant in a cloud
+pub fn cloud_jwks_uri(cloud: Cloud, tenant_id: &str, format: AzureTokenFormat) -> String {
+    let authority = cloud.authority_host();
+    match format {
+        AzureTokenFormat::V1 => {
+            format!("https://{}/{}/discovery/keys", authority, tenant_id)
+        }
+        AzureTokenFormat::V2 => {
+            format!("https://{}/{}/discovery/v2.0/keys", authority, tenant_id)
+        }
+        AzureTokenFormat::Ciam => {
+            format!(
+                "https://{0}.ciamlogin.com/{0}/discovery/v2.0/keys",
+                tenant_id
+            )
+        }
+        AzureTokenFormat::Common => {
+            format!("https://{}/common/discovery/keys", authority)
+        }
+    }
+}
+
+/// Resolves [`AUTO_TENANT`] to the tenant a token was issued by, falling back to `common`
+///
+/// The `common` key set lacks keys configured for a single tenant, so
+/// validating against the token's own tenant avoids misleading failures.
+pub fn resolve_tenant<'a>(tenant_id: &'a str, claims: &'a Claims) -> &'a str {
+    match tenant_id {
+        AUTO_TENANT => claims
+            .tenant_id()
+            .or_else(|| issuer_tenant(&claims.iss))
+            .unwrap_or("common"),
+        tenant_id => tenant_id,
+    }
+}
+
+/// Gets the tenant from an Azure AD issuer, e.g. `https://sts.windows.net/{tenant}/`
+pub(crate) fn issuer_tenant(issuer: &str) -> Option<&str> {
+    let path = issuer.split_once("://")?.1.split_once('/')?.1;
+    path.split('/').next().filter(|tenant| !tenant.is_empty())
+}
+
+/// Gets the JWKS URI for a token, resolving the `auto` tenant from its claims
+///
+/// External ID tenants have no `common` key set, so their keys are looked up
+/// on the `ciamlogin.com` subdomain the token's issuer names instead. Tenants
+/// taken from the token, and every External ID tenant, must be a GUID or a
+/// single DNS label, so no claim can point the key fetch at another host.
+#[cfg(any(feature = "net", feature = "blocking", feature = "wasm"))]
+pub(crate) fn token_jwks_uri(tenant_id: &str, claims: &Claims) -> Result<String> {
+    let format = token_format(claims);
+    let ciam;
+    let tenant = match (format, tenant_id) {
+        (AzureTokenFormat::Ciam, "common" | AUTO_TENANT) => {
+            ciam = ciam_tenant(&claims.iss)
+                .ok_or_else(|| ValidationError::InvalidTenant(claims.iss.clone()))?;
+            ciam.as_str()
+        }
+        (_, tenant_id) => resolve_tenant(tenant_id, claims),
+    };
+    if (matches!(format, AzureTokenFormat::Ciam) || tenant_id == AUTO_TENANT)
+        && !is_tenant_label(tenant)
+    {
+        return Err(ValidationError::InvalidTenant(tenant.to_string()).into());
+    }
+    let cloud = Cloud::of_issuer(&claims.iss).unwrap_or(Cloud::Public);
+    Ok(cloud_jwks_uri(cloud, tenant, format))
+}
+
+/// Gets the External ID tenant from the `{tenant}.ciamlogin.com` host of an issuer
+///
+/// The issuer is parsed as a URL and the subdomain must be a single DNS label.
+pub fn ciam_tenant(issuer: &str) -> Option<String> {
+    let url = Url::parse(issuer).ok()?;
+    let tenant = url.host_str()?.strip_suffix(".ciamlogin.com")?;
+    is_tenant_label(tenant).then(|| tenant.to_string())
+}
+
+/// Checks that a tenant is a GUID or a single DNS label such as `contoso`
+///
+/// Only such tenants are put into URLs built from a token, a value with dots,
+/// slashes or other separators could change the host a request goes to.
+pub fn is_tenant_label(tenant: &str) -> bool {
+    (1..=63).contains(&tenant.len())
+        && tenant
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+        && !tenant.starts_with('-')
+        && !tenant.ends_with('-')
+}
+
+/// Gets the JWKS URI for a token, using the keys of the configured AD FS server for its tokens
+#[cfg(any(feature = "net", feature = "blocking"))]
+pub(crate) fn configured_jwks_uri(config: &ValidatorConfig, claims: &Claims) -> Result<String> {
+    match configured_adfs_host(config, &claims.iss) {
+        Some(host) => Ok(adfs_jwks_uri(host)),
+        None => token_jwks_uri(&config.tenant_id, claims),
+    }
+}
+
+/// Gets the configured AD FS server if it issued a token, tokens of other servers are not trusted
+#[cfg(any(feature = "net", feature = "blocking"))]
+pub(crate) fn configured_adfs_host<'a>(
+    config: &'a ValidatorConfig,
+    issuer: &str,
+) -> Option<&'a str> {
+    let host = config.adfs_host.as_deref()?;
+    adfs_host(issuer)
+        .is_some_and(|issuer_host| issuer_host.eq_ignore_ascii_case(host))
+        .then_some(host)
+}
+
+/// Gets the AD FS server of an AD FS issuer
+///
+/// AD FS issues access tokens as `http://{host}/adfs/services/trust` and ID
+/// tokens as `https://{host}/adfs`.
+pub fn adfs_host(issuer: &str) -> Option<&str> {
+    let (host, path) = issuer.split_once("://")?.1.split_once('/')?;
+    matches!(path.trim_end_matches('/'), "adfs/services/trust" | "adfs").then_some(host)
+}
+
+/// Gets the JWKS URI of an AD FS server
+pub fn adfs_jwks_uri(host: &str) -> String {
+    format!("https://{}/adfs/discovery/keys", host)
+}
+
+/// Gets the federation metadata URL of an AD FS server, with the certificates signing SAML tokens
+pub fn adfs_federation_metadata_uri(host: &str) -> String {
+    format!(
+        "https://{}/FederationMetadata/2007-06/FederationMetadata.xml",
+        host
+    )
+}
+
+/// Gets the issuer Azure AD puts in tokens of the given format for a tenant
+pub fn issuer_for(tenant_id: &str, format: AzureTokenFormat) -> String {
+    cloud_issuer_for(Cloud::Public, tenant_id, format)
+}
+
+/// Gets the issuer Azure AD puts in tokens of the given format for a tenant in a cloud
+///
+/// v1 tokens of US Government tenants are issued by `sts.windows.net` like
+/// those of the public cloud.
+pub fn cloud_issuer_for(cloud: Cloud, tenant_id: &str, format: AzureTokenFormat) -> String {
+    match (cloud, format) {
+        (_, AzureTokenFormat::V2) => {
+            format!("https://{}/{}/v2.0", cloud.authority_host(), tenant_id)
+        }
+        (_, AzureTokenFormat::Ciam) => format!("https://{0}.ciamlogin.com/{0}/v2.0", tenant_id),
+        (Cloud::China, _) => format!("https://sts.chinacloudapi.cn/{}/", tenant_id),
+        _ => format!("https://sts.windows.net/{}/", tenant_id),
+    }
+}
+
+/// Gets the configuration validating a token of `tenant_id` in place of the configured tenant
+///
+/// The issuer is the one the tenant puts in tokens of the token's format and
+/// cloud, required even if issuer validation is disabled.
+#[cfg(any(feature = "net", feature = "blocking"))]
+pub(crate) fn tenant_config(
+    config: &ValidatorConfig,
+    tenant_id: &str,
+    claims: &Claims,
+) -> ValidatorConfig {
+    let cloud = Cloud::of_issuer(&claims.iss).unwrap_or(Cloud::Public);
+    ValidatorConfig {
+        tenant_id: tenant_id.to_string(),
+        validate_iss: true,
+        issuer: Some(cloud_issuer_for(cloud, tenant_id, token_format(claims))),
+        ..config.clone()
+    }
+}
+
+/// Determines the token format based on the issuer claim
+pub fn token_format(claims: &Claims) -> AzureTokenFormat {
+    let host = claims
+        .iss
+        .split_once("://")
+        .and_then(|(_, rest)| rest.split('/').next())
+        .unwrap_or_default();
+    if host.ends_with(".ciamlogin.com") {
+        AzureTokenFormat::Ciam
+    } else if matches!(host, "sts.windows.net" | "sts.chinacloudapi.cn") {
+        AzureTokenFormat::V1
+    } else if claims.iss.contains("/v2.0") {
+        AzureTokenFormat::V2
+    } else {
+        AzureTokenFormat::Common
+    }
+}
+
+/// Decodes a token without validating its signature or claims
+///
+/// The header and payload are base64url-decoded and parsed directly rather
+/// than through `jsonwebtoken` with signature validation disabled, so every
+/// header parameter is kept and malformed segments are named in the error.
+///
+/// Failures are a [`ValidationError::Malformed`] naming the segment and byte
+/// at fault, or [`ValidationError::NotAJwt`] for opaque and encrypted tokens.
+pub fn decode_unverified(token: &str) -> Result<(Value, Claims)> {
+    if token.len() > MAX_TOKEN_LENGTH {
+        return Err(malformed(MalformedToken::TooLarge {
+            length: token.len(),
+            max: MAX_TOKEN_LENGTH,
+        }));
+    }
+    let shape = TokenShape::detect(token);
+    if shape != TokenShape::Jwt {
+        return Err(ValidationError::NotAJwt(shape).into());
+    }
+    let segments: Vec<&str> = token.split('.').collect();
+    let [header, payload, signature] = segments[..] else {
+        return Err(malformed(MalformedToken::SegmentCount {
+            found: segments.len(),
+        }));
+    };
+
+    let header_start = 0;
+    let payload_start = header.len() + 1;
+    let signature_start = payload_start + payload.len() + 1;
+    let header = decode_segment(TokenSegment::Header, header, header_start)?;
+    if !header.is_object() {
+        return Err(malformed(MalformedToken::NotAnObject {
+            segment: TokenSegment::Header,
+        }));
+    }
+    let payload = decode_segment(TokenSegment::Payload, payload, payload_start)?;
+    if !payload.is_object() {
+        return Err(malformed(MalformedToken::NotAnObject {
+            segment: TokenSegment::Payload,
+        }));
+    }
+    let claims = serde_json::from_value(payload).map_err(|e| {
+        malformed(MalformedToken::InvalidClaims {
+            reason: e.to_string(),
+        })
+    })?;
+    // Unsigned tokens have an empty signature, rejected by the algorithm check instead
+    if !signature.is_empty() {
+        URL_SAFE_NO_PAD.decode(signature).map_err(|e| {
+            malformed(MalformedToken::from_base64(
+                TokenSegment::Signature,
+                signature,
+                signature_start,
+                e,
+            ))
+        })?;
+    }
+
+    Ok((header, claims))
+}
+
+/// Decodes a base64url-encoded JSON segment starting at `start` in the token
+fn decode_segment(segment: TokenSegment, encoded: &str, start: usize) -> Result<Value> {
+    if encoded.is_empty() {
+        return Err(malformed(MalformedToken::EmptySegment { segment }));
+    }
+    let bytes = URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|e| malformed(MalformedToken::from_base64(segment, encoded, start, e)))?;
+    serde_json::from_slice(&bytes)
+        .map_err(|e| malformed(MalformedToken::from_json(segment, &bytes, e)))
+}
+
+fn malformed(reason: MalformedToken) -> anyhow::Error {
+    ValidationError::Malformed(reason).into()
+}
+
+/// Checks the time-based claims of a decoded token
+pub(crate) fn check_time_claims(config: &ValidatorConfig, claims: &Claims) -> Result<()> {
+    Ok(check_validity_period(config, claims.nbf, Some(claims.exp))?)
+}
+
+/// Checks a validity period against the configured clock, applying the leeway to both ends
+///
+/// This is the single place where tokens and SAML assertions are checked
+/// for expiry and activation, so both report failures the same way.
+pub(crate) fn check_validity_period(
+    config: &ValidatorConfig,
+    not_before: Option<u64>,
+    expires_at: Option<u64>,
+) -> Result<(), ValidationError> {
+    let now = config.clock.now();
+    let leeway = config.leeway;
+
+    if let Some(expires_at) = expires_at.filter(|_| config.validate_exp) {
+        if now > expires_at.saturating_add(leeway) {
+            return Err(ValidationError::Expired {
+                expires_at,
+                now,
+                leeway,
+            });
+        }
+    }
+    if let Some(not_before) = not_before.filter(|_| config.validate_nbf) {
+        if not_before > now.saturating_add(leeway) {
+            return Err(ValidationError::NotYetValid {
+                not_before,
+                now,
+                leeway,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks the signing algorithm in the token header against the allowlist
+///
+/// Azure AD only signs with asymmetric keys, so HMAC algorithms are always
+/// rejected: accepting them would let anyone forge tokens by using the
+/// public key as the shared secret.
+pub(crate) fn check_algorithm(config: &ValidatorConfig, header: &Value) -> Result<Algorithm> {
+    let name = header["alg"].as_str().unwrap_or_default();
+    let alg: Algorithm = serde_json::from_value(header["alg"].clone())
+        .map_err(|_| ValidationError::UnsupportedAlgorithm(name.to_string()))?;
+
+    if matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+        return Err(ValidationError::AlgorithmDowngrade { alg }.into());
+    }
+    if !config.allowed_algorithms.contains(&alg) {
+        return Err(ValidationError::AlgorithmNotAllowed {
+            alg,
+            allowed: config.allowed_algorithms.clone(),
+        }
+        .into());
+    }
+    Ok(alg)
+}
+
+/// Checks that a token from a known cloud was issued in one of the accepted clouds
+pub(crate) fn check_cloud(config: &ValidatorConfig, claims: &Claims) -> Result<()> {
+    match Cloud::of_issuer(&claims.iss) {
+        Some(cloud) if !config.clouds.contains(&cloud) => Err(ValidationError::CloudNotAllowed {
+            cloud,
+            allowed: config.clouds.clone(),
+        }
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a token was requested by one of the allowed client applications
+pub(crate) fn check_client(config: &ValidatorConfig, claims: &Claims) -> Result<()> {
+    if config.allowed_clients.is_empty() {
+        return Ok(());
+    }
+    match claims.authorized_party() {
+        Some(client)
+            if config
+                .allowed_clients
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(client)) =>
+        {
+            Ok(())
+        }
+        client => Err(ValidationError::ClientNotAllowed {
+            client: client.map(str::to_string),
+            allowed: config.allowed_clients.clone(),
+        }
+        .into()),
+    }
+}
+
+/// Pinned keys whose certificate expires within this many seconds are warned about
+pub const PIN_EXPIRY_WARNING: u64 = 30 * 24 * 60 * 60;
+
+/// Checks that the key a token was signed with is the pinned one, if a key is pinned
+pub(crate) fn check_pinned_key(config: &ValidatorConfig, jwk: &Jwk) -> Result<()> {
+    let pinned = match (&config.pinned_kid, &config.pinned_thumbprint) {
+        (Some(kid), _) if jwk.kid != *kid => Some(kid),
+        (_, Some(thumbprint)) if !jwk.has_thumbprint(thumbprint) => Some(thumbprint),
+        (None, None) => return Ok(()),
+        _ => None,
+    };
+    if let Some(pinned) = pinned {
+        return Err(ValidationError::KeyNotPinned {
+            kid: jwk.kid.clone(),
+            pinned: pinned.clone(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Gets the warnings about the key of a set that verified a token
+///
+/// A token without `kid` is verified with whichever key its signature
+/// matches, and a pinned key whose certificate expires within
+/// [`PIN_EXPIRY_WARNING`] must be replaced by the key rotating in.
+pub fn signing_key_warnings(
+    config: &ValidatorConfig,
+    header: &Value,
+    jwks: &JwksResponse,
+    jwk: &Jwk,
+) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    if header["kid"].as_str().is_none() {
+        warnings.push(
+            Warning::new(
+                "kidless-key",
+                Severity::Low,
+                format!(
+                    "Token has no 'kid': verified with key '{}' found by trying the {} key(s) of the JWKS",
+                    jwk.kid,
+                    jwks.keys.len().min(MAX_KIDLESS_KEYS)
+                ),
+            )
+            .remediation("Have the issuer name its signing key in the 'kid' header"),
+        );
+    }
+
+    let pinned = config.pinned_kid.is_some() || config.pinned_thumbprint.is_some();
+    if let Some(expires_at) = jwk.certificate_expiry().filter(|_| pinned) {
+        let now = config.clock.now();
+        if expires_at <= now.saturating_add(PIN_EXPIRY_WARNING) {
+            warnings.push(
+                Warning::new(
+                    "pinned-key-expiring",
+                    Severity::Medium,
+                    format!(
+                        "The certificate of the pinned key '{}' expires {} ({})",
+                        jwk.kid,
+                        Claims::format_timestamp(expires_at),
+                        Claims::format_relative(expires_at, now)
+                    ),
+                )
+                .remediation("Pin the key replacing it before Azure AD rotates to it"),
+            );
+        }
+    }
+    warnings
+}
+
+/// Decodes a token as the first step of a validation, recorded as a `decode` span
+pub(crate) fn decode_step(token: &str) -> Result<(Value, Claims)> {
+    let span = Span::start("decode");
+    let result = decode_unverified(token);
+    if let Ok((header, claims)) = &result {
+        span.attribute("tenant", claims.tenant_id().unwrap_or_default());
+        span.attribute("kid", header["kid"].as_str().unwrap_or_default());
+    }
+    span.outcome(&result);
+    result
+}
+
+/// Verifies the signature and claims of a token against the given key set
+pub(crate) fn verify_with_jwks(
+    config: &ValidatorConfig,
+    token: &str,
+    header: &Value,
+    claims: &Claims,
+    jwks: &JwksResponse,
+) -> Result<Claims> {
+    let span = Span::start("verify_signature");
+    span.attribute("kid", header["kid"].as_str().unwrap_or_default());
+    span.attribute("alg", header["alg"].as_str().unwrap_or_default());
+    let result = verify_signature(config, token, header, claims, jwks);
+    span.outcome(&result);
+    result
+}
+
+fn verify_signature(
+    config: &ValidatorConfig,
+    token: &str,
+    header: &Value,
+    claims: &Claims,
+    jwks: &JwksResponse,
+) -> Result<Claims> {
+    let alg = check_algorithm(config, header)?;
+    check_cloud(config, claims)?;
+
+    let jwk = signing_key(token, header, alg, jwks)?;
+    check_pinned_key(config, jwk)?;
+    let decoding_key = jwk.to_decoding_key()?;
+
+    // Configure validation settings
+    let mut validation = Validation::new(alg);
+    // Time-based claims are checked against config.clock instead of the system time
+    validation.validate_exp = false;
+    validation.validate_aud = config.validate_aud;
+    if config.validate_aud {
+        validation.set_audience(&accepted_audiences(config));
+    }
+
+    // Set issuer validation if configured
+    if config.validate_iss {
+        validation.set_issuer(&[config.issuer.as_deref().unwrap_or(&claims.iss)]);
+    }
+
+    // Validate token with proper signature verification
+    let token_data =
+        decode::<Claims>(token, &decoding_key, &validation).map_err(ValidationError::from)?;
+    // Only checked once the signature proves the client claims were issued by Azure AD
+    check_client(config, &token_data.claims)?;
+    Ok(token_data.claims)
+}
+
+/// Gets the key of a set a token names in its `kid`, or whose signature it carries
+fn signing_key<'a>(
+    token: &str,
+    header: &Value,
+    alg: Algorithm,
+    jwks: &'a JwksResponse,
+) -> Result<&'a Jwk> {
+    match header["kid"].as_str() {
+        Some(kid) => Ok(jwks
+            .find_key(kid)
+            .ok_or_else(|| ValidationError::KeyNotFound {
+                kid: kid.to_string(),
+            })?),
+        // Emulators and older tooling omit the key ID
+        None => Ok(
+            find_signing_key(token, alg, jwks)?.ok_or(ValidationError::NoMatchingKey {
+                tried: jwks.keys.len().min(MAX_KIDLESS_KEYS),
+            })?,
+        ),
+    }
+}
+
+/// Verifies only the signature of a token against the given key set
+///
+/// The algorithm allowlist and key pinning still apply, but no claim is
+/// checked, so the claims are returned as the token carries them.
+pub(crate) fn verify_signature_only(
+    config: &ValidatorConfig,
+    token: &str,
+    header: &Value,
+    claims: Claims,
+    jwks: &JwksResponse,
+) -> Result<Claims> {
+    let span = Span::start("verify_signature");
+    span.attribute("kid", header["kid"].as_str().unwrap_or_default());
+    span.attribute("alg", header["alg"].as_str().unwrap_or_default());
+    let result = check_signature(config, token, header, jwks).map(|()| claims);
+    span.outcome(&result);
+    result
+}
+
+fn check_signature(
+    config: &ValidatorConfig,
+    token: &str,
+    header: &Value,
+    jwks: &JwksResponse,
+) -> Result<()> {
+    let alg = check_algorithm(config, header)?;
+    let jwk = signing_key(token, header, alg, jwks)?;
+    check_pinned_key(config, jwk)?;
+
+    let (message, signature) = token
+        .rsplit_once('.')
+        .context("Token has no signature segment")?;
+    let key = jwk.to_decoding_key()?;
+    let verified = jsonwebtoken::crypto::verify(signature, message.as_bytes(), &key, alg)
+        .map_err(ValidationError::from)?;
+    if !verified {
+        return Err(ValidationError::from(JwtError::from(ErrorKind::InvalidSignature)).into());
+    }
+    Ok(())
+}
+
+/// Gets the audiences accepted when `validate_aud` is set
+///
+/// A first-party API accepts its app ID and all of its URIs as audience.
+pub(crate) fn accepted_audiences(config: &ValidatorConfig) -> Vec<String> {
+    config
+        .audiences
+        .iter()
+        .flat_map(|aud| match audience::lookup(aud) {
+            Some(known) => known.aliases(),
+            None => vec![aud.clone()],
+        })
+        .collect()
+}
+
+/// Most keys tried for a token without `kid`, Azure AD publishes far fewer
+pub const MAX_KIDLESS_KEYS: usize = 16;
+
+/// Finds the key of a set whose signature a token carries, for tokens without `kid`
+///
+/// Only the signature is checked, and only of the first [`MAX_KIDLESS_KEYS`]
+/// keys. Keys that cannot be used with the token's algorithm are skipped.
+pub fn find_signing_key<'a>(
+    token: &str,
+    alg: Algorithm,
+    jwks: &'a JwksResponse,
+) -> Result<Option<&'a Jwk>> {
+    let (message, signature) = token
+        .rsplit_once('.')
+        .context("Token has no signature segment")?;
+    if jwks.keys.len() > MAX_KIDLESS_KEYS {
+        log::warn!(
+            "Token has no 'kid', only the first {} of {} keys are tried",
+            MAX_KIDLESS_KEYS,
+            jwks.keys.len()
+        );
+    }
+
+    for jwk in jwks.keys.iter().take(MAX_KIDLESS_KEYS) {
+        let Ok(key) = jwk.to_decoding_key() else {
+            continue;
+        };
+        if jsonwebtoken::crypto::verify(signature, message.as_bytes(), &key, alg).unwrap_or(false) {
+            return Ok(Some(jwk));
+        }
+    }
+    Ok(None)
+}
+
+/// Validates a token against a caller-provided key set without any network access
+pub fn validate_with_jwks(
+    token: &str,
+    jwks: &JwksResponse,
+    config: &ValidatorConfig,
+) -> Result<Claims> {
+    let (header, claims) = decode_step(token)?;
+    check_time_claims(config, &claims)?;
+    verify_with_jwks(config, token, &header, &claims, jwks)
+}
+
+/// Verifies only the signature of a token against a caller-provided key set
+///
+/// Expiry, issuer, audience and all other claims are not checked, e.g. to
+/// verify that a token of a past incident was issued by Azure AD. The
+/// algorithm allowlist and pinned keys of `config` still apply.
+pub fn validate_signature_with_jwks(
+    token: &str,
+    jwks: &JwksResponse,
+    config: &ValidatorConfig,
+) -> Result<Claims> {
+    let (header, claims) = decode_step(token)?;
+    verify_signature_only(config, token, &header, claims, jwks)
+}
+
+/// Gets the current time as seconds since the Unix epoch
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn unix_now() -> Result<u64> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Gets the current time as seconds since the Unix epoch
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn unix_now() -> Result<u64> {
+    // SystemTime is not available in the browser
+    Ok((js_sys::Date::now() / 1000.0) as u64)
+}
@@ -0,0 +1,108 @@
+//! Warnings about tokens and their environment, and which of them to act on
+//!
+//! Heuristics flag what is likely, not certainly, a problem. Every warning
+//! has a stable code so a known and accepted one can be suppressed, and the
+//! rest can be turned into failures, e.g. in CI.
+
+use serde::Serialize;
+use std::fmt;
+
+/// How serious a warning is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Low => write!(f, "LOW"),
+            Severity::Medium => write!(f, "MEDIUM"),
+            Severity::High => write!(f, "HIGH"),
+        }
+    }
+}
+
+/// A likely problem with a token, with what to do about it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Warning {
+    /// Stable kebab-case identifier, e.g. `no-mfa`
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+}
+
+impl Warning {
+    /// Creates a warning without a remediation
+    pub fn new(code: &'static str, severity: Severity, message: impl Into<String>) -> Self {
+        Warning {
+            code,
+            severity,
+            message: message.into(),
+            remediation: None,
+        }
+    }
+
+    /// Adds what to do about the warning
+    pub fn remediation(mut self, remediation: impl Into<String>) -> Self {
+        self.remediation = Some(remediation.into());
+        self
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.severity, self.code, self.message)
+    }
+}
+
+/// Which warnings are suppressed and whether the others fail a run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WarningPolicy {
+    allowed: Vec<String>,
+    deny: bool,
+}
+
+impl WarningPolicy {
+    /// Creates a policy reporting every warning without failing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Suppresses the warnings with `code`, compared without regard to case
+    pub fn allow(mut self, code: &str) -> Self {
+        self.allowed.push(code.to_string());
+        self
+    }
+
+    /// Turns the warnings that are not suppressed into failures
+    pub fn deny_warnings(mut self, deny: bool) -> Self {
+        self.deny = deny;
+        self
+    }
+
+    /// Returns whether the warning is suppressed
+    pub fn is_allowed(&self, warning: &Warning) -> bool {
+        self.allowed
+            .iter()
+            .any(|code| code.eq_ignore_ascii_case(warning.code))
+    }
+
+    /// Drops the suppressed warnings
+    pub fn apply(&self, warnings: Vec<Warning>) -> Vec<Warning> {
+        warnings
+            .into_iter()
+            .filter(|warning| !self.is_allowed(warning))
+            .collect()
+    }
+
+    /// Returns whether the warnings, once suppressed ones are dropped, fail a run
+    pub fn fails(&self, warnings: &[Warning]) -> bool {
+        self.deny && warnings.iter().any(|warning| !self.is_allowed(warning))
+    }
+}
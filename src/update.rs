@@ -0,0 +1,259 @@
+//! Updates of the running binary from a release feed
+//!
+//! The feed is a GitHub releases listing, or a mirror of one serving the
+//! same JSON. Each release carries one binary per platform named
+//! `azure-token-validator-<arch>-<os>`, its SHA-256 checksum as
+//! `<binary>.sha256` and an RSA SHA-256 signature as `<binary>.sig`
+//! (`openssl dgst -sha256 -sign key.pem`). The checksum comes from the same
+//! feed as the binary and only catches corrupted downloads; the signature,
+//! checked against the release key built into the binary, proves where the
+//! binary came from.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use reqwest::Client;
+use rsa::pkcs1v15::{Signature, VerifyingKey};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::signature::Verifier;
+use rsa::RsaPublicKey;
+use semver::Version;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Releases of the project on GitHub
+pub const DEFAULT_FEED: &str =
+    "https://api.github.com/repos/amasotti/azure-token-validator/releases";
+
+/// Public key the project's releases are signed with
+pub const RELEASE_KEY: &str = include_str!("../keys/release-signing-key.pem");
+
+/// Which releases are offered as updates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Channel {
+    /// Releases only
+    Stable,
+    /// Pre-releases too, e.g. 0.3.0-beta.1
+    Beta,
+}
+
+impl std::fmt::Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Channel::Stable => write!(f, "stable"),
+            Channel::Beta => write!(f, "beta"),
+        }
+    }
+}
+
+/// A release as listed by the feed
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeedRelease {
+    pub tag_name: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub assets: Vec<FeedAsset>,
+}
+
+/// A downloadable file of a release
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeedAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+/// The release an update would install, with the files for this platform
+#[derive(Debug, Clone)]
+pub struct Update {
+    pub version: Version,
+    pub binary: FeedAsset,
+    pub checksum: Option<FeedAsset>,
+    pub signature: Option<FeedAsset>,
+}
+
+/// Gets the version of the running binary
+pub fn current_version() -> Version {
+    Version::parse(env!("CARGO_PKG_VERSION")).expect("the package version is valid semver")
+}
+
+/// Gets the name of the binary built for this platform
+pub fn asset_name() -> String {
+    let suffix = match std::env::consts::OS {
+        "windows" => ".exe",
+        _ => "",
+    };
+    format!(
+        "azure-token-validator-{}-{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        suffix
+    )
+}
+
+/// Picks the newest release of the channel with a binary named `asset`
+///
+/// Drafts, tags that are not semantic versions (a leading `v` is allowed)
+/// and releases without the binary are skipped.
+pub fn newest(releases: &[FeedRelease], channel: Channel, asset: &str) -> Option<Update> {
+    let find = |release: &FeedRelease, name: &str| {
+        release
+            .assets
+            .iter()
+            .find(|candidate| candidate.name == name)
+            .cloned()
+    };
+    releases
+        .iter()
+        .filter(|release| !release.draft)
+        .filter_map(|release| {
+            let version = Version::parse(release.tag_name.trim_start_matches('v')).ok()?;
+            let stable = !release.prerelease && version.pre.is_empty();
+            if channel == Channel::Stable && !stable {
+                return None;
+            }
+            Some(Update {
+                version,
+                binary: find(release, asset)?,
+                checksum: find(release, &format!("{}.sha256", asset)),
+                signature: find(release, &format!("{}.sig", asset)),
+            })
+        })
+        .max_by(|a, b| a.version.cmp(&b.version))
+}
+
+/// Fetches the feed and returns the newest release of the channel
+pub async fn check(client: &Client, feed: &str, channel: Channel) -> Result<Option<Update>> {
+    let response = client
+        .get(feed)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch the release feed {}", feed))?;
+    if !response.status().is_success() {
+        bail!(
+            "Failed to fetch the release feed {}: {}",
+            feed,
+            response.status()
+        );
+    }
+    let releases: Vec<FeedRelease> = response
+        .json()
+        .await
+        .with_context(|| format!("Invalid release feed {}", feed))?;
+    Ok(newest(&releases, channel, &asset_name()))
+}
+
+/// Downloads the binary of an update, verifying its checksum and signature
+///
+/// A release without a checksum is refused, and so is one without a
+/// signature unless `public_key` is `None`, which skips the signature and
+/// trusts whoever serves the feed.
+pub async fn download(
+    client: &Client,
+    update: &Update,
+    public_key: Option<&RsaPublicKey>,
+) -> Result<Vec<u8>> {
+    let checksum = update.checksum.as_ref().ok_or_else(|| {
+        anyhow!(
+            "Release {} has no {}.sha256",
+            update.version,
+            update.binary.name
+        )
+    })?;
+    let checksum = String::from_utf8(fetch(client, checksum).await?)?;
+    let binary = fetch(client, &update.binary).await?;
+    verify_checksum(&binary, &checksum)?;
+
+    if let Some(public_key) = public_key {
+        let signature = update.signature.as_ref().ok_or_else(|| {
+            anyhow!(
+                "Release {} has no {}.sig",
+                update.version,
+                update.binary.name
+            )
+        })?;
+        verify_signature(&binary, &fetch(client, signature).await?, public_key)?;
+    }
+    Ok(binary)
+}
+
+/// Checks data against a checksum file, the hex SHA-256 digest optionally followed by a name
+pub fn verify_checksum(data: &[u8], checksum: &str) -> Result<()> {
+    let expected = checksum
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("The checksum file is empty"))?;
+    let actual = format!("{:x}", Sha256::digest(data));
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!(
+            "Checksum mismatch: expected SHA-256 {}, downloaded {}",
+            expected,
+            actual
+        );
+    }
+    Ok(())
+}
+
+/// Checks an RSA PKCS#1 v1.5 SHA-256 signature of data
+pub fn verify_signature(data: &[u8], signature: &[u8], public_key: &RsaPublicKey) -> Result<()> {
+    let signature = Signature::try_from(signature).context("Invalid signature")?;
+    VerifyingKey::<Sha256>::new(public_key.clone())
+        .verify(data, &signature)
+        .map_err(|_| anyhow!("The signature does not match the release public key"))
+}
+
+/// Parses the release key built into the binary
+pub fn release_key() -> Result<RsaPublicKey> {
+    RsaPublicKey::from_public_key_pem(RELEASE_KEY).context("Invalid built-in release key")
+}
+
+/// Reads the PEM public key releases are signed with
+pub fn read_public_key(path: &Path) -> Result<RsaPublicKey> {
+    let pem = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    RsaPublicKey::from_public_key_pem(&pem)
+        .with_context(|| format!("Invalid public key in {}", path.display()))
+}
+
+/// Replaces the running executable with `binary`, returning its path
+///
+/// The new binary is written beside the old one and renamed over it. On
+/// Windows, where a running executable cannot be replaced, the old one is
+/// first moved aside to `<name>.old`.
+pub fn replace_executable(binary: &[u8]) -> Result<PathBuf> {
+    let current = std::env::current_exe().context("Cannot locate the running executable")?;
+    let staged = current.with_extension("new");
+    std::fs::write(&staged, binary)
+        .with_context(|| format!("Failed to write {}", staged.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("Failed to make {} executable", staged.display()))?;
+    }
+    #[cfg(windows)]
+    {
+        let old = current.with_extension("old");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(&current, &old)
+            .with_context(|| format!("Failed to move {} aside", current.display()))?;
+    }
+    std::fs::rename(&staged, &current)
+        .with_context(|| format!("Failed to replace {}", current.display()))?;
+    Ok(current)
+}
+
+async fn fetch(client: &Client, asset: &FeedAsset) -> Result<Vec<u8>> {
+    let response = client
+        .get(&asset.browser_download_url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to download {}", asset.name))?;
+    if !response.status().is_success() {
+        bail!("Failed to download {}: {}", asset.name, response.status());
+    }
+    Ok(response.bytes().await?.to_vec())
+}
@@ -0,0 +1,71 @@
+//! Warnings of a run, suppressed with --allow and counted for --deny-warnings
+//!
+//! Warnings come from all over a run: the audit, the validation, Graph
+//! tests. They all pass through the run's [`Warnings`], so every one is shown
+//! the same way and the run fails at the end if any that are not allowed were
+//! shown.
+
+use anyhow::{bail, Result};
+use std::sync::Mutex;
+
+use azure_token_validator::token::{Warning, WarningPolicy};
+
+/// Collects the warnings shown during a run
+pub struct Warnings {
+    policy: WarningPolicy,
+    /// Warnings reported so far that are not allowed
+    reported: Mutex<Vec<Warning>>,
+}
+
+impl Warnings {
+    pub fn new(policy: WarningPolicy) -> Self {
+        Self {
+            policy,
+            reported: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Drops the allowed warnings, counting the others as reported
+    pub fn filter(&self, warnings: Vec<Warning>) -> Vec<Warning> {
+        let warnings = self.policy.apply(warnings);
+        self.reported
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .extend(warnings.iter().cloned());
+        warnings
+    }
+
+    /// Prints a warning unless it is allowed
+    pub fn print(&self, warning: Warning) {
+        for warning in self.filter(vec![warning]) {
+            println!("{}", render(&warning));
+        }
+    }
+
+    /// Fails if warnings were reported and --deny-warnings is set
+    pub fn check(&self) -> Result<()> {
+        let reported = self
+            .reported
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !self.policy.fails(&reported) {
+            return Ok(());
+        }
+        let mut codes: Vec<&str> = reported.iter().map(|warning| warning.code).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        bail!(
+            "{} warning(s) with --deny-warnings ({}), suppress accepted ones with --allow <CODE>",
+            reported.len(),
+            codes.join(", ")
+        )
+    }
+}
+
+/// Formats a warning with its remediation on an indented line
+pub fn render(warning: &Warning) -> String {
+    match &warning.remediation {
+        Some(remediation) => format!("⚠️  {}\n   {}", warning, remediation),
+        None => format!("⚠️  {}", warning),
+    }
+}
@@ -0,0 +1,60 @@
+//! JavaScript bindings for a browser-based token inspector
+//!
+//! Only the offline parts of the crate are exposed: decoding, the security
+//! audit and signature validation against a JWKS document supplied by the
+//! caller. All functions return JSON strings.
+
+use serde_json::json;
+use wasm_bindgen::prelude::*;
+
+use crate::token::jwk::JwksResponse;
+use crate::token::verify::{decode_unverified, token_jwks_uri};
+use crate::token::{audit_token, validate_with_jwks, AuditConfig, ValidatorConfig};
+
+/// Decodes a token without validation, returning its header and claims
+#[wasm_bindgen]
+pub fn decode(token: &str) -> Result<String, JsError> {
+    let (header, claims) = decode_unverified(token.trim()).map_err(to_js_error)?;
+    let output = json!({
+        "header": header,
+        "claims": claims,
+        "token_type": claims.token_type().to_string(),
+    });
+    Ok(output.to_string())
+}
+
+/// Runs the security audit on a token, returning the list of findings
+#[wasm_bindgen]
+pub fn audit(token: &str) -> Result<String, JsError> {
+    let (header, claims) = decode_unverified(token.trim()).map_err(to_js_error)?;
+    let warnings = audit_token(&header, &claims, &AuditConfig::default());
+    Ok(serde_json::to_string(&warnings)?)
+}
+
+/// Gets the URI of the JWKS document the token's signing key is published in
+///
+/// Pass `auto` as the tenant to use the tenant the token was issued by.
+#[wasm_bindgen]
+pub fn jwks_uri(token: &str, tenant_id: &str) -> Result<String, JsError> {
+    let (_, claims) = decode_unverified(token.trim()).map_err(to_js_error)?;
+    token_jwks_uri(tenant_id, &claims).map_err(to_js_error)
+}
+
+/// Validates a token against the given JWKS document, returning the verified claims
+///
+/// The JWKS has to be fetched by the caller, e.g. from
+/// `https://login.microsoftonline.com/{tenant}/discovery/v2.0/keys`.
+#[wasm_bindgen]
+pub fn validate(token: &str, jwks: &str, skip_expiration: bool) -> Result<String, JsError> {
+    let jwks: JwksResponse = serde_json::from_str(jwks)?;
+    let config = ValidatorConfig {
+        validate_exp: !skip_expiration,
+        ..Default::default()
+    };
+    let claims = validate_with_jwks(token.trim(), &jwks, &config).map_err(to_js_error)?;
+    Ok(serde_json::to_string(&claims)?)
+}
+
+fn to_js_error(error: anyhow::Error) -> JsError {
+    JsError::new(&format!("{:#}", error))
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "net")]
+
+mod common;
+
+use azure_token_validator::api::acquire::{default_scope, TokenFuture};
+use azure_token_validator::api::{AcquireToken, AcquiredToken, TokenResponse};
+use azure_token_validator::token::{
+    AzureTokenFormat, FixedClock, KeyStore, TokenValidator, ValidatorConfig,
+};
+use std::fmt;
+
+/// 2024-07-01T12:00:00Z
+const ISSUED_AT: u64 = 1_719_835_200;
+
+/// Provider handing out a token signed with the test key
+struct SignedToken;
+
+impl fmt::Display for SignedToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "test key")
+    }
+}
+
+impl AcquireToken for SignedToken {
+    fn acquire_token<'a>(&'a self, _resource: &'a str) -> TokenFuture<'a> {
+        Box::pin(async move {
+            Ok(AcquiredToken {
+                access_token: common::sign(&common::v1_claims(ISSUED_AT)),
+                expires_on: Some(ISSUED_AT + 3600),
+                refresh_token: None,
+            })
+        })
+    }
+}
+
+/// Acquires a token from any provider and validates it, as a pipeline would
+async fn acquire_and_validate(provider: &dyn AcquireToken) -> anyhow::Result<String> {
+    let keys = KeyStore::default();
+    let validator = TokenValidator::with_key_store(ValidatorConfig::default(), keys.clone())
+        .with_clock(FixedClock(ISSUED_AT + 60));
+    keys.insert(
+        &validator.get_jwks_uri(AzureTokenFormat::V1),
+        common::jwks(),
+    )
+    .await;
+
+    let token = provider
+        .acquire_token("https://graph.microsoft.com")
+        .await?;
+    let claims = validator.validate_token(&token.access_token).await?;
+    Ok(claims.tenant_id().unwrap_or_default().to_string())
+}
+
+#[tokio::test]
+async fn acquired_tokens_flow_into_validation() {
+    let tenant = acquire_and_validate(&SignedToken).await.unwrap();
+    assert_eq!(tenant, common::TENANT_ID);
+    assert_eq!(SignedToken.to_string(), "test key");
+}
+
+#[test]
+fn default_scope_is_derived_from_the_resource() {
+    assert_eq!(
+        default_scope("https://graph.microsoft.com/"),
+        "https://graph.microsoft.com/.default"
+    );
+    assert_eq!(
+        default_scope("api://contoso-api"),
+        "api://contoso-api/.default"
+    );
+}
+
+#[test]
+fn token_responses_of_both_endpoints_are_parsed() {
+    // The v1 endpoint returns expires_in as a string
+    let v1: TokenResponse = serde_json::from_str(
+        r#"{"token_type":"Bearer","expires_in":"3599","ext_expires_in":"3599","access_token":"eyJ0.e30.sig","refresh_token":"0.AAAA"}"#,
+    )
+    .unwrap();
+    assert_eq!(v1.expires_in, 3599);
+    assert!(v1.id_token.is_none());
+
+    let v2: TokenResponse = serde_json::from_str(
+        r#"{"token_type":"Bearer","scope":"User.Read openid","expires_in":4703,"access_token":"eyJ0.e30.sig","id_token":"eyJ0.e30.sig"}"#,
+    )
+    .unwrap();
+    assert_eq!(v2.expires_in, 4703);
+    assert_eq!(v2.scope.as_deref(), Some("User.Read openid"));
+
+    assert!(serde_json::from_str::<TokenResponse>(
+        r#"{"token_type":"Bearer","expires_in":"soon"}"#
+    )
+    .is_err());
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use azure_token_validator::token::{audience, validate_with_jwks, Claims, ValidatorConfig};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEVOPS_APP_ID: &str = "499b84ac-1321-427f-aa17-267ca6975798";
+
+fn devops_claims() -> Claims {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let mut claims = common::v1_claims(now);
+    claims.aud = DEVOPS_APP_ID.into();
+    claims
+}
+
+#[test]
+fn first_party_apis_accept_their_app_id_and_uris_as_audience() {
+    let token = common::sign(&devops_claims());
+    let config = ValidatorConfig {
+        validate_aud: true,
+        audiences: vec!["https://app.vssps.visualstudio.com".to_string()],
+        ..ValidatorConfig::default()
+    };
+    assert!(validate_with_jwks(&token, &common::jwks(), &config).is_ok());
+
+    let config = ValidatorConfig {
+        validate_aud: true,
+        audiences: vec!["api://orders".to_string()],
+        ..ValidatorConfig::default()
+    };
+    assert!(validate_with_jwks(&token, &common::jwks(), &config).is_err());
+}
+
+#[test]
+fn first_party_tokens_without_ver_pass_the_resource_checks() {
+    let claims = devops_claims();
+    assert!(claims.version().is_none());
+
+    let api = audience::lookup_claim(&claims.aud).unwrap();
+    assert_eq!(api.name, "Azure DevOps");
+    let checks = api.check(&claims);
+    assert!(checks.iter().all(|check| check.passed), "{:?}", checks);
+}
+
+#[test]
+fn multi_audience_tokens_are_accepted_for_any_expected_audience() {
+    let mut claims = devops_claims();
+    claims.aud = serde_json::json!(["api://orders", "https://graph.microsoft.com"]);
+    assert_eq!(
+        claims.audiences(),
+        vec!["api://orders", "https://graph.microsoft.com"]
+    );
+    assert_eq!(
+        claims.audience_display(),
+        "api://orders, https://graph.microsoft.com"
+    );
+
+    let token = common::sign(&claims);
+    let config = ValidatorConfig {
+        validate_aud: true,
+        audiences: vec!["api://billing".to_string(), "api://orders".to_string()],
+        ..ValidatorConfig::default()
+    };
+    assert!(validate_with_jwks(&token, &common::jwks(), &config).is_ok());
+
+    let config = ValidatorConfig {
+        validate_aud: true,
+        audiences: vec!["api://billing".to_string()],
+        ..ValidatorConfig::default()
+    };
+    assert!(validate_with_jwks(&token, &common::jwks(), &config).is_err());
+}
@@ -0,0 +1,59 @@
+use azure_token_validator::token::{
+    certificate_binding, certificate_thumbprint, CertificateBinding, Claims,
+};
+use serde_json::json;
+
+const CLIENT_CERT: &str = include_str!("fixtures/client-cert.pem");
+
+/// `x5t#S256` thumbprint of the certificate in `fixtures/client-cert.pem`
+const CLIENT_CERT_THUMBPRINT: &str = "hwAhISFtfpT-2WTgPjQCYAQKt87DxIeCN01e7yOCVUg";
+
+fn claims_bound_to(thumbprint: &str) -> Claims {
+    Claims::builder()
+        .sub("subject")
+        .claim("cnf", json!({ "x5t#S256": thumbprint }))
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn certificate_thumbprints_are_computed_from_pem_and_der() {
+    assert_eq!(
+        certificate_thumbprint(CLIENT_CERT.as_bytes()).unwrap(),
+        CLIENT_CERT_THUMBPRINT
+    );
+
+    let body: String = CLIENT_CERT
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    let der = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, body).unwrap();
+    assert_eq!(
+        certificate_thumbprint(&der).unwrap(),
+        CLIENT_CERT_THUMBPRINT
+    );
+    assert!(certificate_thumbprint(b"not a certificate").is_err());
+}
+
+#[test]
+fn tokens_are_bound_unbound_or_mismatched() {
+    assert_eq!(
+        certificate_binding(
+            &claims_bound_to(CLIENT_CERT_THUMBPRINT),
+            CLIENT_CERT_THUMBPRINT
+        ),
+        CertificateBinding::Bound
+    );
+    assert_eq!(
+        certificate_binding(&claims_bound_to("other"), CLIENT_CERT_THUMBPRINT),
+        CertificateBinding::Mismatch {
+            bound: "other".to_string()
+        }
+    );
+
+    let unbound = Claims::builder().sub("subject").build().unwrap();
+    assert_eq!(
+        certificate_binding(&unbound, CLIENT_CERT_THUMBPRINT),
+        CertificateBinding::Unbound
+    );
+}
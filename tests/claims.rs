@@ -0,0 +1,251 @@
+use azure_token_validator::token::{Claims, Timezone};
+use serde_json::json;
+
+/// Payload of a v1.0 delegated access token issued by sts.windows.net
+fn v1_claims() -> Claims {
+    serde_json::from_value(json!({
+        "aud": "https://graph.microsoft.com",
+        "iss": "https://sts.windows.net/72f988bf-86f1-41af-91ab-2d7cd011db47/",
+        "iat": 1537231048,
+        "nbf": 1537231048,
+        "exp": 1537234948,
+        "acr": "1",
+        "aio": "AXQAi/8IAAAA",
+        "amr": ["pwd", "mfa"],
+        "appid": "75dbe77f-10a3-4e59-85fd-8c127544f17c",
+        "appidacr": "0",
+        "family_name": "Doe",
+        "given_name": "Jane",
+        "groups": [
+            "2d0c6f2d-1b8e-4b47-8e1e-6f5a7c7c2f0e",
+            "f3b7a1b4-2c3d-4e5f-8a9b-0c1d2e3f4a5b"
+        ],
+        "ipaddr": "192.0.2.1",
+        "name": "Jane Doe",
+        "oid": "690222be-ff1a-4d56-abd1-7e4f7d38e474",
+        "scp": "User.Read Mail.Send",
+        "sub": "l3_roISQU222bULS9yi2k0XpqpOiMz5H3ZACo1GeXA",
+        "tid": "72f988bf-86f1-41af-91ab-2d7cd011db47",
+        "unique_name": "jane@contoso.com",
+        "upn": "jane@contoso.com",
+        "uti": "fqiBqXLPj0eQa82S-IYFAA",
+        "ver": "1.0"
+    }))
+    .unwrap()
+}
+
+/// Payload of a v2.0 application access token from the client credentials flow
+fn v2_app_claims() -> Claims {
+    serde_json::from_value(json!({
+        "aud": "6e74172b-be56-4843-9ff4-e66a39bb12e3",
+        "iss": "https://login.microsoftonline.com/72f988bf-86f1-41af-91ab-2d7cd011db47/v2.0",
+        "iat": 1537231048,
+        "nbf": 1537231048,
+        "exp": 1537234948,
+        "aio": "AXQAi/8IAAAA",
+        "azp": "6e74172b-be56-4843-9ff4-e66a39bb12e3",
+        "azpacr": "1",
+        "oid": "a8f2c1d0-3b4e-4c5d-9e6f-7a8b9c0d1e2f",
+        "roles": ["Tasks.Read.All", "Admin"],
+        "sub": "a8f2c1d0-3b4e-4c5d-9e6f-7a8b9c0d1e2f",
+        "tid": "72f988bf-86f1-41af-91ab-2d7cd011db47",
+        "uti": "fqiBqXLPj0eQa82S-IYFAA",
+        "ver": "2.0"
+    }))
+    .unwrap()
+}
+
+#[test]
+fn v1_token_exposes_typed_claims() {
+    let claims = v1_claims();
+
+    assert_eq!(
+        claims.object_id(),
+        Some("690222be-ff1a-4d56-abd1-7e4f7d38e474")
+    );
+    assert_eq!(
+        claims.tenant_id(),
+        Some("72f988bf-86f1-41af-91ab-2d7cd011db47")
+    );
+    assert_eq!(claims.user_principal_name(), Some("jane@contoso.com"));
+    assert_eq!(claims.unique_name.as_deref(), Some("jane@contoso.com"));
+    assert_eq!(claims.version(), Some("1.0"));
+    assert_eq!(claims.authentication_methods(), ["pwd", "mfa"]);
+    assert_eq!(claims.groups().len(), 2);
+    assert_eq!(
+        claims.authorized_party(),
+        Some("75dbe77f-10a3-4e59-85fd-8c127544f17c")
+    );
+}
+
+#[test]
+fn v2_app_token_exposes_typed_claims() {
+    let claims = v2_app_claims();
+
+    assert_eq!(claims.version(), Some("2.0"));
+    assert_eq!(
+        claims.object_id(),
+        Some("a8f2c1d0-3b4e-4c5d-9e6f-7a8b9c0d1e2f")
+    );
+    assert_eq!(
+        claims.authorized_party(),
+        Some("6e74172b-be56-4843-9ff4-e66a39bb12e3")
+    );
+    assert_eq!(claims.user_principal_name(), None);
+    assert!(claims.groups().is_empty());
+    assert!(claims.authentication_methods().is_empty());
+}
+
+#[test]
+fn typed_claims_are_not_duplicated_in_extra() {
+    let claims = v1_claims();
+
+    for key in ["oid", "tid", "upn", "unique_name", "groups", "amr", "ver"] {
+        assert!(!claims.extra.contains_key(key), "{} left in extra", key);
+    }
+    assert!(claims.extra.contains_key("ipaddr"));
+}
+
+#[test]
+fn upn_falls_back_to_unique_name() {
+    let mut claims = v1_claims();
+    claims.upn = None;
+    claims.unique_name = Some("live.com#jane@outlook.com".to_string());
+
+    assert_eq!(
+        claims.user_principal_name(),
+        Some("live.com#jane@outlook.com")
+    );
+}
+
+#[test]
+fn scopes_and_roles_are_split() {
+    let delegated = v1_claims();
+    assert_eq!(delegated.scopes(), ["User.Read", "Mail.Send"]);
+    assert!(delegated.has_scope("Mail.Send"));
+    assert!(!delegated.has_scope("Mail"));
+    assert!(delegated.roles().is_empty());
+
+    let app = v2_app_claims();
+    assert_eq!(app.roles(), ["Tasks.Read.All", "Admin"]);
+    assert!(app.has_role("Admin"));
+    assert!(!app.has_scope("User.Read"));
+}
+
+#[test]
+fn decoded_claims_serialize_to_the_original_payload() {
+    let payload = json!({
+        "aud": ["api://orders", "api://billing"],
+        "iss": "https://login.microsoftonline.com/72f988bf-86f1-41af-91ab-2d7cd011db47/v2.0",
+        "sub": "a8f2c1d0",
+        "iat": 1537231048,
+        "exp": 1537234948,
+        "oid": "a8f2c1d0-3b4e-4c5d-9e6f-7a8b9c0d1e2f",
+        "xms_st": { "sub": "nested", "flags": [1, 2, 3] },
+        "wids": ["b79fbf4d-3ef9-4689-8143-76b194e85509"],
+        "ctry": null
+    });
+
+    let claims: Claims = serde_json::from_value(payload.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&claims).unwrap(), payload);
+}
+
+#[test]
+fn claims_round_trip_through_json() {
+    for claims in [v1_claims(), v2_app_claims()] {
+        let json = serde_json::to_string(&claims).unwrap();
+        let decoded: Claims = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, claims);
+    }
+}
+
+#[test]
+fn builder_sets_typed_and_extra_claims() {
+    let claims = Claims::builder()
+        .iss("https://sts.windows.net/72f988bf-86f1-41af-91ab-2d7cd011db47/")
+        .sub("subject")
+        .aud("https://graph.microsoft.com")
+        .iat(1_000)
+        .exp_in(3600)
+        .oid("690222be-ff1a-4d56-abd1-7e4f7d38e474")
+        .scopes(&["User.Read", "Mail.Send"])
+        .roles(&["Admin"])
+        .amr(&["pwd", "mfa"])
+        .claim("ipaddr", "192.0.2.1")
+        .claim("tid", "72f988bf-86f1-41af-91ab-2d7cd011db47")
+        .build()
+        .unwrap();
+
+    assert_eq!(claims.exp, 4_600);
+    assert_eq!(claims.scopes(), ["User.Read", "Mail.Send"]);
+    assert!(claims.has_role("Admin"));
+    assert_eq!(claims.authentication_methods(), ["pwd", "mfa"]);
+    // Well-known claims set through claim() still land in their typed field
+    assert_eq!(
+        claims.tenant_id(),
+        Some("72f988bf-86f1-41af-91ab-2d7cd011db47")
+    );
+    assert!(!claims.extra.contains_key("tid"));
+    assert_eq!(claims.extra["ipaddr"], "192.0.2.1");
+
+    let json = serde_json::to_string(&claims).unwrap();
+    assert_eq!(serde_json::from_str::<Claims>(&json).unwrap(), claims);
+}
+
+#[test]
+fn builder_defaults_to_a_one_hour_lifetime() {
+    let claims = Claims::builder().build().unwrap();
+    assert_eq!(claims.exp - claims.iat, 3600);
+    assert_eq!(claims.nbf, Some(claims.iat));
+
+    let claims = Claims::builder()
+        .without("nbf")
+        .exp_in(-60)
+        .build()
+        .unwrap();
+    assert_eq!(claims.nbf, None);
+    assert_eq!(claims.exp, claims.iat - 60);
+}
+
+#[test]
+fn builder_rejects_mistyped_claims() {
+    assert!(Claims::builder()
+        .claim("groups", "not-an-array")
+        .build()
+        .is_err());
+}
+
+#[test]
+fn relative_times_use_the_largest_fitting_unit() {
+    let now = 1_700_000_000;
+    assert_eq!(Claims::format_relative(now + 20, now), "just now");
+    assert_eq!(Claims::format_relative(now + 58 * 60, now), "in 58 minutes");
+    assert_eq!(Claims::format_relative(now + 3600, now), "in 1 hour");
+    assert_eq!(Claims::format_relative(now - 3 * 86_400, now), "3 days ago");
+}
+
+#[test]
+fn timestamps_are_formatted_in_the_given_timezone() {
+    let berlin: Timezone = "Europe/Berlin".parse().unwrap();
+    assert_eq!(
+        berlin.format(1_700_000_000).unwrap(),
+        "2023-11-14 23:13:20 CET"
+    );
+    assert_eq!(
+        Timezone::Utc.format(1_700_000_000).unwrap(),
+        "2023-11-14 22:13:20 UTC"
+    );
+    assert!("Mars/Olympus_Mons".parse::<Timezone>().is_err());
+}
+
+#[test]
+fn claims_format_timestamps_in_utc() {
+    assert_eq!(
+        Claims::format_timestamp(1_700_000_000),
+        Timezone::Utc.format_timestamp(1_700_000_000)
+    );
+    assert_eq!(
+        Timezone::Utc.format_timestamp(u64::MAX),
+        format!("{} (invalid timestamp)", u64::MAX)
+    );
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use azure_token_validator::token::{validate_with_jwks, ValidationError, ValidatorConfig};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CLIENT_ID: &str = "75dbe77f-10a3-4e59-85fd-8c127544f17c";
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn config(allowed_clients: &[&str]) -> ValidatorConfig {
+    ValidatorConfig {
+        allowed_clients: allowed_clients
+            .iter()
+            .map(|client| client.to_string())
+            .collect(),
+        ..ValidatorConfig::default()
+    }
+}
+
+#[test]
+fn tokens_of_allowed_clients_are_accepted() {
+    let mut claims = common::v1_claims(now());
+    claims.appid = Some(CLIENT_ID.to_string());
+    let token = common::sign(&claims);
+
+    let allowed = config(&["00000000-0000-0000-0000-000000000001", CLIENT_ID]);
+    assert!(validate_with_jwks(&token, &common::jwks(), &allowed).is_ok());
+    // Application IDs are GUIDs, their case does not matter
+    let uppercase = config(&[&CLIENT_ID.to_uppercase()]);
+    assert!(validate_with_jwks(&token, &common::jwks(), &uppercase).is_ok());
+}
+
+#[test]
+fn tokens_of_other_or_unnamed_clients_are_rejected() {
+    let mut claims = common::v1_claims(now());
+    claims.azp = Some("00000000-0000-0000-0000-000000000002".to_string());
+    let token = common::sign(&claims);
+    let allowed = config(&[CLIENT_ID]);
+
+    let error = validate_with_jwks(&token, &common::jwks(), &allowed).unwrap_err();
+    match error.downcast_ref::<ValidationError>() {
+        Some(ValidationError::ClientNotAllowed { client, .. }) => assert_eq!(
+            client.as_deref(),
+            Some("00000000-0000-0000-0000-000000000002")
+        ),
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    let token = common::sign(&common::v1_claims(now()));
+    let error = validate_with_jwks(&token, &common::jwks(), &allowed).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<ValidationError>(),
+        Some(ValidationError::ClientNotAllowed { client: None, .. })
+    ));
+}
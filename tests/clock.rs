@@ -0,0 +1,181 @@
+#![cfg(feature = "net")]
+
+mod common;
+
+use azure_token_validator::token::{
+    skew_from_date_header, validate_with_jwks, AzureTokenFormat, Claims, Clock, FixedClock,
+    KeyStore, ManualClock, SkewedClock, TokenValidator, ValidationError, ValidatorConfig,
+};
+use std::sync::Arc;
+
+/// 2024-07-01T12:00:00Z
+const ISSUED_AT: u64 = 1_719_835_200;
+
+async fn validator(clock: ManualClock) -> TokenValidator {
+    let config = ValidatorConfig::default();
+    let keys = KeyStore::default();
+    let validator = TokenValidator::with_key_store(config, keys.clone()).with_clock(clock);
+    keys.insert(
+        &validator.get_jwks_uri(AzureTokenFormat::V1),
+        common::jwks(),
+    )
+    .await;
+    validator
+}
+
+#[tokio::test]
+async fn token_is_valid_before_expiry() {
+    let token = common::sign(&common::v1_claims(ISSUED_AT));
+    let validator = validator(ManualClock::new(ISSUED_AT + 3599)).await;
+
+    let claims = validator.validate_token(&token).await.unwrap();
+    assert_eq!(claims.tenant_id(), Some(common::TENANT_ID));
+}
+
+#[tokio::test]
+async fn token_is_rejected_once_the_clock_passes_expiry() {
+    let token = common::sign(&common::v1_claims(ISSUED_AT));
+    let clock = ManualClock::new(ISSUED_AT + 60);
+    let validator = validator(clock.clone()).await;
+
+    assert!(validator.validate_token(&token).await.is_ok());
+
+    // Well past expiry and any leeway
+    clock.advance(2 * 3600);
+    let error = validator.validate_token(&token).await.unwrap_err();
+    assert!(error.to_string().contains("expired"), "{}", error);
+}
+
+#[tokio::test]
+async fn expiry_is_ignored_when_disabled() {
+    let token = common::sign(&common::v1_claims(ISSUED_AT));
+    let keys = KeyStore::default();
+    let config = ValidatorConfig {
+        validate_exp: false,
+        ..ValidatorConfig::default()
+    };
+    let validator = TokenValidator::with_key_store(config, keys.clone())
+        .with_clock(FixedClock(ISSUED_AT + 365 * 24 * 3600));
+    keys.insert(
+        &validator.get_jwks_uri(AzureTokenFormat::V1),
+        common::jwks(),
+    )
+    .await;
+
+    assert!(validator.validate_token(&token).await.is_ok());
+}
+
+#[test]
+fn offline_validation_uses_the_configured_clock() {
+    let token = common::sign(&common::v1_claims(ISSUED_AT));
+    let at = |now| ValidatorConfig {
+        clock: Arc::new(FixedClock(now)),
+        ..ValidatorConfig::default()
+    };
+
+    assert!(validate_with_jwks(&token, &common::jwks(), &at(ISSUED_AT + 1)).is_ok());
+    assert!(validate_with_jwks(&token, &common::jwks(), &at(ISSUED_AT + 2 * 3600)).is_err());
+}
+
+#[test]
+fn tampered_token_is_rejected_regardless_of_time() {
+    let token = common::sign(&common::v1_claims(ISSUED_AT));
+    let mut parts: Vec<&str> = token.split('.').collect();
+    let other = common::sign(&common::v1_claims(ISSUED_AT + 1));
+    parts[1] = other.split('.').nth(1).unwrap();
+    let tampered = parts.join(".");
+
+    let config = ValidatorConfig {
+        clock: Arc::new(FixedClock(ISSUED_AT + 1)),
+        ..ValidatorConfig::default()
+    };
+    assert!(validate_with_jwks(&tampered, &common::jwks(), &config).is_err());
+}
+
+fn config_at(now: u64) -> ValidatorConfig {
+    ValidatorConfig {
+        clock: Arc::new(FixedClock(now)),
+        ..ValidatorConfig::default()
+    }
+}
+
+#[test]
+fn leeway_applies_to_expiry() {
+    let token = common::sign(&common::v1_claims(ISSUED_AT));
+    let expires_at = ISSUED_AT + 3600;
+    let leeway = ValidatorConfig::default().leeway;
+
+    assert!(validate_with_jwks(&token, &common::jwks(), &config_at(expires_at + leeway)).is_ok());
+
+    let error = validate_with_jwks(&token, &common::jwks(), &config_at(expires_at + leeway + 1))
+        .unwrap_err();
+    match error.downcast_ref::<ValidationError>() {
+        Some(ValidationError::Expired {
+            expires_at: exp,
+            now,
+            leeway: applied,
+        }) => {
+            assert_eq!(*exp, expires_at);
+            assert_eq!(now - exp, leeway + 1);
+            assert_eq!(*applied, leeway);
+        }
+        other => panic!("Unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn not_before_is_enforced_with_leeway() {
+    let claims = Claims::builder()
+        .iss(&format!("https://sts.windows.net/{}/", common::TENANT_ID))
+        .iat(ISSUED_AT)
+        .nbf(ISSUED_AT + 600)
+        .exp_in(3600)
+        .build()
+        .unwrap();
+    let token = common::sign(&claims);
+    let leeway = ValidatorConfig::default().leeway;
+
+    assert!(validate_with_jwks(
+        &token,
+        &common::jwks(),
+        &config_at(ISSUED_AT + 600 - leeway)
+    )
+    .is_ok());
+
+    let error = validate_with_jwks(&token, &common::jwks(), &config_at(ISSUED_AT)).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<ValidationError>(),
+        Some(ValidationError::NotYetValid { not_before, now, .. }) if not_before - now == 600
+    ));
+    assert!(error.to_string().contains("600s from now"), "{}", error);
+
+    let config = ValidatorConfig {
+        validate_nbf: false,
+        ..config_at(ISSUED_AT)
+    };
+    assert!(validate_with_jwks(&token, &common::jwks(), &config).is_ok());
+}
+
+#[test]
+fn skew_is_measured_against_the_date_header() {
+    let date = "Mon, 01 Jul 2024 12:00:00 GMT";
+    assert_eq!(skew_from_date_header(date, ISSUED_AT), Some(0));
+    assert_eq!(skew_from_date_header(date, ISSUED_AT + 420), Some(420));
+    assert_eq!(skew_from_date_header(date, ISSUED_AT - 90), Some(-90));
+    assert_eq!(skew_from_date_header("yesterday", ISSUED_AT), None);
+}
+
+#[test]
+fn skewed_clock_corrects_a_clock_running_ahead() {
+    let token = common::sign(&common::v1_claims(ISSUED_AT));
+    // The local clock is two hours ahead, the token would be reported as expired
+    let local = Arc::new(FixedClock(ISSUED_AT + 7200));
+    let corrected = ValidatorConfig {
+        clock: Arc::new(SkewedClock::new(local.clone(), 7200 - 60)),
+        ..ValidatorConfig::default()
+    };
+
+    assert!(validate_with_jwks(&token, &common::jwks(), &config_at(ISSUED_AT + 7200)).is_err());
+    assert!(validate_with_jwks(&token, &common::jwks(), &corrected).is_ok());
+    assert_eq!(SkewedClock::new(local, -30).now(), ISSUED_AT + 7230);
+}
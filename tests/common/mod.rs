@@ -0,0 +1,41 @@
+//! Helpers for signing test tokens with the fixture key in `tests/fixtures`
+
+// Each test binary uses a different subset of these helpers
+#![allow(dead_code)]
+
+use azure_token_validator::token::jwk::JwksResponse;
+use azure_token_validator::token::Claims;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+
+pub const TENANT_ID: &str = "72f988bf-86f1-41af-91ab-2d7cd011db47";
+pub const KEY_ID: &str = "test-key";
+
+const PRIVATE_KEY: &str = include_str!("../fixtures/rsa-private.pem");
+const JWKS: &str = include_str!("../fixtures/jwks.json");
+
+/// Gets the key set containing the public half of the fixture key
+pub fn jwks() -> JwksResponse {
+    serde_json::from_str(JWKS).unwrap()
+}
+
+/// Signs the claims with the fixture key using RS256
+pub fn sign(claims: &Claims) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(KEY_ID.to_string());
+    let key = EncodingKey::from_rsa_pem(PRIVATE_KEY.as_bytes()).unwrap();
+    encode(&header, claims, &key).unwrap()
+}
+
+/// Creates v1 claims issued at `iat` that are valid for one hour
+pub fn v1_claims(iat: u64) -> Claims {
+    Claims::builder()
+        .iss(&format!("https://sts.windows.net/{}/", TENANT_ID))
+        .sub("subject")
+        .aud("https://graph.microsoft.com")
+        .tid(TENANT_ID)
+        .iat(iat)
+        .nbf(iat)
+        .exp_in(3600)
+        .build()
+        .unwrap()
+}
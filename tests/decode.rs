@@ -0,0 +1,150 @@
+mod common;
+
+use azure_token_validator::token::{
+    bearer_token, extract_token, validate_signature_with_jwks, validate_with_jwks, TokenShape,
+    ValidationError, ValidatorConfig,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn malformed_reason(token: &str) -> String {
+    let error =
+        validate_with_jwks(token, &common::jwks(), &ValidatorConfig::default()).unwrap_err();
+    match error.downcast_ref::<ValidationError>() {
+        Some(ValidationError::Malformed(reason)) => reason.to_string(),
+        other => panic!("expected a malformed token error, got {:?}", other),
+    }
+}
+
+fn encode(json: &str) -> String {
+    base64_url::encode(json)
+}
+
+#[test]
+fn wrong_segment_count_is_reported() {
+    assert!(malformed_reason("eyJhbGciOiJSUzI1NiJ9.e30").contains("found 2"));
+}
+
+#[test]
+fn tokens_that_are_not_jwts_are_recognized() {
+    let shape = |token: &str| {
+        let error =
+            validate_with_jwks(token, &common::jwks(), &ValidatorConfig::default()).unwrap_err();
+        match error.downcast_ref::<ValidationError>() {
+            Some(ValidationError::NotAJwt(shape)) => *shape,
+            other => panic!("expected a token that is not a JWT, got {:?}", other),
+        }
+    };
+
+    assert_eq!(
+        shape("EwBwA8l6BAAURSN/FHlDW5xN74t6GzbtsBBeBUYAAQ=="),
+        TokenShape::Opaque
+    );
+    assert_eq!(
+        shape("eyJhbGciOiJSU0EtT0FFUCJ9.a.b.c.d"),
+        TokenShape::EncryptedJwt
+    );
+    assert_eq!(
+        TokenShape::detect("Bearer eyJhbGciOiJSUzI1NiJ9.e30.sig"),
+        TokenShape::Jwt
+    );
+}
+
+#[test]
+fn malformed_segment_is_named() {
+    let payload = encode(r#"{"sub":"subject"}"#);
+    assert!(malformed_reason(&format!("eyJ%%%.{}.sig", payload))
+        .starts_with("the header is not valid base64url"));
+    assert!(
+        malformed_reason(&format!("{}.{}.sig", encode(r#"{"alg":"#), payload))
+            .starts_with("the header is not valid JSON")
+    );
+    assert!(malformed_reason(&format!(
+        "{}.{}.sig",
+        encode(r#"{"alg":"RS256"}"#),
+        encode("{")
+    ))
+    .starts_with("the payload is not valid JSON"));
+}
+
+#[test]
+fn missing_claims_are_reported() {
+    let header = encode(r#"{"alg":"RS256","kid":"test-key"}"#);
+    let payload = encode(r#"{"iss":"https://sts.windows.net/tenant/","sub":"subject"}"#);
+    assert!(malformed_reason(&format!("{}.{}.sig", header, payload)).contains("missing field"));
+}
+
+#[test]
+fn well_formed_tokens_still_validate() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let token = common::sign(&common::v1_claims(now));
+    assert!(validate_with_jwks(&token, &common::jwks(), &ValidatorConfig::default()).is_ok());
+}
+
+#[test]
+fn tokens_are_extracted_from_redirect_urls() {
+    let token = "eyJhbGciOiJSUzI1NiJ9.e30.sig";
+
+    let fragment = extract_token(&format!(
+        "https://localhost/callback#id_token={}&state=12345&session_state=abc",
+        token
+    ));
+    assert_eq!(fragment.token, token);
+    assert_eq!(fragment.parameter, Some("id_token"));
+
+    // Hybrid flow with both tokens, behind a hash route
+    let hybrid = extract_token(&format!(
+        "http://localhost:3000/#/auth?id_token=a.b.c&access_token={}",
+        token
+    ));
+    assert_eq!(hybrid.token, token);
+    assert_eq!(hybrid.parameter, Some("access_token"));
+
+    let encoded = extract_token("eyJhbGciOiJSUzI1NiJ9%2Ee30%2Esig");
+    assert_eq!(encoded.token, token);
+    assert_eq!(encoded.parameter, None);
+    assert!(encoded.url_decoded);
+
+    let plain = extract_token(&format!("  {}\n", token));
+    assert_eq!(plain.token, token);
+    assert!(!plain.url_decoded);
+}
+
+#[test]
+fn bearer_tokens_are_taken_from_authorization_headers() {
+    assert_eq!(bearer_token("Bearer a.b.c"), Some("a.b.c"));
+    assert_eq!(bearer_token("bearer  a.b.c "), Some("a.b.c"));
+    for header in ["Basic dXNlcjpwYXNz", "Bearer ", "a.b.c"] {
+        assert_eq!(bearer_token(header), None, "{}", header);
+    }
+}
+
+#[test]
+fn signature_only_ignores_expiry_and_audience() {
+    // Issued in 2020, long expired
+    let token = common::sign(&common::v1_claims(1_577_836_800));
+    let config = ValidatorConfig {
+        validate_aud: true,
+        audiences: vec!["api://other".to_string()],
+        ..ValidatorConfig::default()
+    };
+    assert!(validate_with_jwks(&token, &common::jwks(), &config).is_err());
+
+    let claims = validate_signature_with_jwks(&token, &common::jwks(), &config).unwrap();
+    assert_eq!(claims.tenant_id(), Some(common::TENANT_ID));
+
+    // A payload swapped after signing no longer matches the signature
+    let mut segments: Vec<&str> = token.split('.').collect();
+    let forged = encode(r#"{"aud":"api://other","iss":"x","sub":"x","exp":1,"iat":1}"#);
+    segments[1] = &forged;
+    let error =
+        validate_signature_with_jwks(&segments.join("."), &common::jwks(), &config).unwrap_err();
+    assert_eq!(
+        error
+            .downcast_ref::<ValidationError>()
+            .map(ValidationError::code),
+        Some("invalid_signature")
+    );
+}
@@ -0,0 +1,29 @@
+use azure_token_validator::token::dictionary;
+
+#[test]
+fn claims_are_looked_up_ignoring_case() {
+    let info = dictionary::lookup("XMS_TCDT").unwrap();
+    assert_eq!(info.name, "xms_tcdt");
+    assert!(info.optional);
+    assert_eq!(info.versions, ["1.0", "2.0"]);
+    assert!(dictionary::lookup("not_a_claim").is_none());
+}
+
+#[test]
+fn partial_names_suggest_similar_claims() {
+    let names: Vec<&str> = dictionary::similar("xms_t")
+        .iter()
+        .map(|info| info.name)
+        .collect();
+    assert_eq!(names, ["xms_tcdt", "xms_tpl"]);
+}
+
+#[test]
+fn dictionary_is_sorted_without_duplicates() {
+    let names: Vec<&str> = dictionary::CLAIMS.iter().map(|info| info.name).collect();
+    assert!(
+        names.windows(2).all(|pair| pair[0] < pair[1]),
+        "{:?}",
+        names
+    );
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use azure_token_validator::token::{
+    explain_validation, FixedClock, RuleEvaluation, RuleStatus, ValidatorConfig,
+};
+use std::sync::Arc;
+
+/// 2024-07-01T12:00:00Z
+const ISSUED_AT: u64 = 1_719_835_200;
+
+fn rule<'a>(rules: &'a [RuleEvaluation], name: &str) -> &'a RuleEvaluation {
+    rules
+        .iter()
+        .find(|rule| rule.rule == name)
+        .unwrap_or_else(|| panic!("no {} rule in {:?}", name, rules))
+}
+
+#[test]
+fn every_rule_is_evaluated_past_the_first_failure() {
+    let token = common::sign(&common::v1_claims(ISSUED_AT));
+    let config = ValidatorConfig {
+        validate_aud: true,
+        audiences: vec!["api://other".to_string()],
+        clock: Arc::new(FixedClock(ISSUED_AT + 2 * 3600)),
+        ..ValidatorConfig::default()
+    };
+
+    let rules = explain_validation(&config, &token, Some(&common::jwks()));
+
+    assert_eq!(rule(&rules, "exp").status, RuleStatus::Failed);
+    assert_eq!(rule(&rules, "signature").status, RuleStatus::Passed);
+    assert_eq!(rule(&rules, "alg").status, RuleStatus::Passed);
+    let aud = rule(&rules, "aud");
+    assert_eq!(aud.status, RuleStatus::Failed);
+    assert!(aud.detail.contains("api://other"), "{}", aud.detail);
+    assert_eq!(rule(&rules, "client").status, RuleStatus::Skipped);
+}
+
+#[test]
+fn signature_is_skipped_without_keys() {
+    let token = common::sign(&common::v1_claims(ISSUED_AT));
+    let config = ValidatorConfig {
+        clock: Arc::new(FixedClock(ISSUED_AT + 60)),
+        ..ValidatorConfig::default()
+    };
+
+    let rules = explain_validation(&config, &token, None);
+
+    assert_eq!(rule(&rules, "signature").status, RuleStatus::Skipped);
+    assert_eq!(rule(&rules, "exp").status, RuleStatus::Passed);
+    assert!(rules.iter().all(|rule| rule.status != RuleStatus::Failed));
+}
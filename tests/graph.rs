@@ -0,0 +1,126 @@
+#![cfg(feature = "net")]
+
+use azure_token_validator::api::{DirectoryIdentity, GraphBatchResponse, GraphError};
+use azure_token_validator::token::Claims;
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[test]
+fn odata_error_is_parsed_from_a_failed_response() {
+    let response: GraphBatchResponse = serde_json::from_value(json!({
+        "id": "0",
+        "status": 403,
+        "headers": { "Retry-After": "10", "Content-Type": "application/json" },
+        "body": {
+            "error": {
+                "code": "Authorization_RequestDenied",
+                "message": "Insufficient privileges to complete the operation.",
+                "innerError": {
+                    "date": "2024-07-01T12:00:00",
+                    "request-id": "8d5c1a4e-3f3e-4f7a-9b1c-2d6e0f9a7b31",
+                    "client-request-id": "8d5c1a4e-3f3e-4f7a-9b1c-2d6e0f9a7b31"
+                }
+            }
+        }
+    }))
+    .unwrap();
+
+    let error = response.into_result().unwrap_err();
+    let graph_error = error.downcast_ref::<GraphError>().unwrap();
+    assert_eq!(graph_error.status, StatusCode::FORBIDDEN);
+    assert_eq!(
+        graph_error.code.as_deref(),
+        Some("Authorization_RequestDenied")
+    );
+    assert_eq!(
+        graph_error.request_id.as_deref(),
+        Some("8d5c1a4e-3f3e-4f7a-9b1c-2d6e0f9a7b31")
+    );
+    assert_eq!(graph_error.date.as_deref(), Some("2024-07-01T12:00:00"));
+    assert_eq!(
+        graph_error.headers,
+        vec![("retry-after".to_string(), "10".to_string())]
+    );
+    assert_eq!(
+        error.to_string(),
+        "Graph API error: 403 Forbidden - Authorization_RequestDenied: \
+         Insufficient privileges to complete the operation."
+    );
+}
+
+#[test]
+fn request_id_falls_back_to_the_response_headers() {
+    let response: GraphBatchResponse = serde_json::from_value(json!({
+        "id": "1",
+        "status": 503,
+        "headers": { "request-id": "0f1e2d3c", "Date": "Mon, 01 Jul 2024 12:00:00 GMT" },
+        "body": "Service Unavailable"
+    }))
+    .unwrap();
+
+    let error = response.into_result().unwrap_err();
+    let graph_error = error.downcast_ref::<GraphError>().unwrap();
+    assert_eq!(graph_error.code, None);
+    assert_eq!(graph_error.request_id.as_deref(), Some("0f1e2d3c"));
+    assert_eq!(
+        graph_error.date.as_deref(),
+        Some("Mon, 01 Jul 2024 12:00:00 GMT")
+    );
+    assert_eq!(
+        error.to_string(),
+        "Graph API error: 503 Service Unavailable"
+    );
+}
+
+#[test]
+fn identity_facts_flag_values_changed_since_sign_in() {
+    let claims = Claims::builder()
+        .iss("https://sts.windows.net/72f988bf-86f1-41af-91ab-2d7cd011db47/")
+        .sub("subject")
+        .aud("https://graph.microsoft.com")
+        .iat(1_719_835_200)
+        .exp_in(3600)
+        .name("Jane Doe")
+        .upn("jane.doe@contoso.com")
+        .oid("11111111-2222-3333-4444-555555555555")
+        .tid("72f988bf-86f1-41af-91ab-2d7cd011db47")
+        .build()
+        .unwrap();
+    let identity = DirectoryIdentity {
+        user: serde_json::from_value(json!({
+            "id": "11111111-2222-3333-4444-555555555555",
+            "displayName": "Jane Smith",
+            "userPrincipalName": "Jane.Doe@contoso.com"
+        }))
+        .ok(),
+        organization: serde_json::from_value(json!({
+            "id": "72f988bf-86f1-41af-91ab-2d7cd011db47",
+            "displayName": "Contoso",
+            "verifiedDomains": [{ "name": "contoso.com", "isDefault": true }]
+        }))
+        .ok(),
+        licenses: Some(vec!["ENTERPRISEPACK".to_string()]),
+        errors: Vec::new(),
+    };
+
+    let facts = identity.facts(&claims);
+    let mismatches: Vec<&str> = facts
+        .iter()
+        .filter(|fact| fact.is_mismatch())
+        .map(|fact| fact.name)
+        .collect();
+    // UPNs are compared case-insensitively
+    assert_eq!(mismatches, ["Display name"]);
+
+    let fact = |name: &str| facts.iter().find(|fact| fact.name == name).unwrap();
+    assert_eq!(fact("Tenant name").directory.as_deref(), Some("Contoso"));
+    assert_eq!(
+        fact("Default domain").directory.as_deref(),
+        Some("contoso.com")
+    );
+    assert_eq!(
+        fact("Licenses").directory.as_deref(),
+        Some("ENTERPRISEPACK")
+    );
+    assert!(facts.iter().all(|fact| fact.name != "Mail"));
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "net")]
+
+use azure_token_validator::api::HttpOptions;
+
+#[test]
+fn each_run_gets_its_own_client_request_id() {
+    let first = HttpOptions::new();
+    let second = HttpOptions::new();
+    assert!(uuid::Uuid::parse_str(first.client_request_id()).is_ok());
+    assert_ne!(first.client_request_id(), second.client_request_id());
+}
+
+#[test]
+fn client_request_id_header_replaces_the_generated_one() {
+    let id = "0f8fad5b-d9cb-469f-a165-70867728950e";
+    let options = HttpOptions::new()
+        .user_agent("pipeline/1.0")
+        .header("Client-Request-Id", id)
+        .unwrap();
+    assert_eq!(options.client_request_id(), id);
+    assert!(options.client_builder().build().is_ok());
+}
+
+#[test]
+fn invalid_headers_are_rejected() {
+    assert!(HttpOptions::new().header("bad header", "value").is_err());
+    assert!(HttpOptions::new()
+        .header("x-custom", "line\nbreak")
+        .is_err());
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use azure_token_validator::token::jwk::JwksResponse;
+use azure_token_validator::token::{
+    signing_key_warnings, validate_with_jwks, FixedClock, ValidationError, ValidatorConfig,
+    PIN_EXPIRY_WARNING,
+};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// SHA-1 thumbprint of the certificate in `fixtures/jwks-x5c.json`
+const THUMBPRINT: &str = "55:A0:FC:01:28:D3:1F:DC:FB:76:99:32:4E:CC:DB:C9:F0:E8:88:CC";
+
+fn x5c_jwks() -> JwksResponse {
+    serde_json::from_str(include_str!("fixtures/jwks-x5c.json")).unwrap()
+}
+
+fn token() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    common::sign(&common::v1_claims(now))
+}
+
+#[test]
+fn keys_with_only_a_certificate_are_used() {
+    let jwks = x5c_jwks();
+    assert!(jwks.keys[0].n.is_none());
+
+    assert!(validate_with_jwks(&token(), &jwks, &ValidatorConfig::default()).is_ok());
+}
+
+#[test]
+fn keys_without_key_material_are_reported() {
+    let jwks: JwksResponse =
+        serde_json::from_str(r#"{"keys":[{"kid":"test-key","kty":"RSA"}]}"#).unwrap();
+
+    let error = validate_with_jwks(&token(), &jwks, &ValidatorConfig::default()).unwrap_err();
+    assert!(error.to_string().contains("neither a modulus"));
+}
+
+#[test]
+fn certificate_thumbprint_and_expiry_are_read_from_x5c() {
+    let jwk = &x5c_jwks().keys[0];
+    assert_eq!(
+        jwk.thumbprint().as_deref(),
+        Some("VaD8ASjTH9z7dpkyTszbyfDoiMw")
+    );
+    assert!(jwk.has_thumbprint(THUMBPRINT));
+    assert!(jwk.has_thumbprint(&THUMBPRINT.replace(':', "").to_lowercase()));
+    // notAfter 2126-09-22T15:15:53Z, a GeneralizedTime
+    assert_eq!(jwk.certificate_expiry(), Some(4_945_763_753));
+}
+
+#[test]
+fn tokens_must_be_signed_with_the_pinned_key() {
+    let jwks = x5c_jwks();
+    let pinned = |kid: Option<&str>, thumbprint: Option<&str>| ValidatorConfig {
+        pinned_kid: kid.map(str::to_string),
+        pinned_thumbprint: thumbprint.map(str::to_string),
+        ..ValidatorConfig::default()
+    };
+
+    assert!(validate_with_jwks(&token(), &jwks, &pinned(Some("test-key"), None)).is_ok());
+    assert!(validate_with_jwks(&token(), &jwks, &pinned(None, Some(THUMBPRINT))).is_ok());
+
+    for config in [
+        pinned(Some("next-key"), None),
+        pinned(Some("test-key"), Some("VGhpcyBpcyBub3QgaXQ")),
+    ] {
+        let error = validate_with_jwks(&token(), &jwks, &config).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ValidationError>(),
+            Some(ValidationError::KeyNotPinned { kid, .. }) if kid == "test-key"
+        ));
+    }
+}
+
+#[test]
+fn kidless_tokens_and_expiring_pins_are_warned_about() {
+    let jwks = x5c_jwks();
+    let jwk = &jwks.keys[0];
+    let expiry = jwk.certificate_expiry().unwrap();
+    let codes = |header: &serde_json::Value, config: &ValidatorConfig| -> Vec<&'static str> {
+        signing_key_warnings(config, header, &jwks, jwk)
+            .iter()
+            .map(|warning| warning.code)
+            .collect()
+    };
+    let pinned = |now: u64| ValidatorConfig {
+        pinned_kid: Some("test-key".to_string()),
+        clock: Arc::new(FixedClock(now)),
+        ..ValidatorConfig::default()
+    };
+    let named = json!({ "alg": "RS256", "kid": "test-key" });
+
+    assert!(codes(&named, &ValidatorConfig::default()).is_empty());
+    assert_eq!(
+        codes(&json!({ "alg": "RS256" }), &ValidatorConfig::default()),
+        ["kidless-key"]
+    );
+    assert!(codes(&named, &pinned(expiry - PIN_EXPIRY_WARNING - 1)).is_empty());
+    assert_eq!(
+        codes(&named, &pinned(expiry - PIN_EXPIRY_WARNING)),
+        ["pinned-key-expiring"]
+    );
+}
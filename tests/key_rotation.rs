@@ -0,0 +1,74 @@
+#![cfg(feature = "net")]
+
+mod common;
+
+use azure_token_validator::token::provider::KeysFuture;
+use azure_token_validator::token::{KeyProvider, KeyStore, TokenValidator, ValidationError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Serves a key set from before a rotation until it is refetched
+#[derive(Default)]
+struct RotatedKeys {
+    refetches: Arc<AtomicUsize>,
+}
+
+impl KeyProvider for RotatedKeys {
+    fn get_keys<'a>(&'a self, _uri: &'a str) -> KeysFuture<'a> {
+        Box::pin(async { Ok(Arc::new(serde_json::from_str(r#"{"keys":[]}"#)?)) })
+    }
+
+    fn refetch_keys<'a>(&'a self, _uri: &'a str) -> KeysFuture<'a> {
+        self.refetches.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async { Ok(Arc::new(common::jwks())) })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[tokio::test]
+async fn unknown_key_causes_a_single_refetch() {
+    let keys = RotatedKeys::default();
+    let refetches = keys.refetches.clone();
+    let validator = TokenValidator::builder().key_provider(keys).build();
+    let token = common::sign(&common::v1_claims(now()));
+
+    assert!(validator.validate_token(&token).await.is_ok());
+    assert_eq!(refetches.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn key_missing_after_the_refetch_is_reported() {
+    let validator = TokenValidator::builder()
+        .key_provider(common::jwks())
+        .build();
+    let token = common::sign(&common::v1_claims(now()));
+    // Swap in a header naming a key the key set does not contain
+    let (_, rest) = token.split_once('.').unwrap();
+    let forged_header = base64_url::encode(r#"{"alg":"RS256","typ":"JWT","kid":"rotated-out"}"#);
+    let forged = format!("{}.{}", forged_header, rest);
+
+    let error = validator.validate_token(&forged).await.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<ValidationError>(),
+        Some(ValidationError::KeyNotFound { kid }) if kid == "rotated-out"
+    ));
+}
+
+#[tokio::test]
+async fn recently_fetched_keys_are_not_refetched() {
+    let keys = KeyStore::default();
+    let uri = "https://login.microsoftonline.com/common/discovery/keys";
+    keys.insert(uri, common::jwks()).await;
+
+    // Served from the cache without a request, which would fail offline
+    let jwks = keys.refetch(uri).await.unwrap();
+    assert!(jwks.find_key(common::KEY_ID).is_some());
+    assert_eq!(keys.stats().fetches, 0);
+}
@@ -0,0 +1,116 @@
+#![cfg(feature = "net")]
+
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use azure_token_validator::token::{
+    CachedKeys, CircuitState, DiskCache, KeyCache, KeyStore, MemoryCache, ValidationError,
+};
+
+/// Nothing listens on the discard port, so fetches fail at once
+const UNREACHABLE_URI: &str = "http://127.0.0.1:9/discovery/keys";
+
+#[tokio::test]
+async fn failed_fetch_is_remembered_briefly() {
+    let keys = KeyStore::default();
+
+    let first = keys.get(UNREACHABLE_URI).await.unwrap_err();
+    assert!(first.downcast_ref::<ValidationError>().is_none());
+
+    let second = keys.get(UNREACHABLE_URI).await.unwrap_err();
+    assert!(matches!(
+        second.downcast_ref::<ValidationError>(),
+        Some(ValidationError::KeysUnavailable { failures: 1, .. })
+    ));
+    assert_eq!(keys.stats().fetches, 1);
+    // A single failure does not open the circuit
+    assert_eq!(keys.circuit_state(UNREACHABLE_URI), CircuitState::Closed);
+}
+
+#[tokio::test]
+async fn failures_are_tracked_per_key_set() {
+    let keys = KeyStore::default();
+    let _ = keys.get(UNREACHABLE_URI).await;
+
+    let other = "http://127.0.0.1:9/v2.0/discovery/keys";
+    let error = keys.get(other).await.unwrap_err();
+    assert!(error.downcast_ref::<ValidationError>().is_none());
+    assert_eq!(keys.stats().fetches, 2);
+}
+
+#[tokio::test]
+async fn keys_in_a_shared_cache_are_used_without_fetching() {
+    let cache: Arc<dyn KeyCache> = Arc::new(MemoryCache::new());
+    let first = KeyStore::with_cache(Default::default(), Duration::from_secs(60), cache.clone());
+    first.insert(UNREACHABLE_URI, common::jwks()).await;
+
+    let second = KeyStore::with_cache(Default::default(), Duration::from_secs(60), cache);
+    let jwks = second.get(UNREACHABLE_URI).await.unwrap();
+    assert_eq!(jwks.keys[0].kid, common::KEY_ID);
+    assert_eq!(second.stats().fetches, 0);
+}
+
+#[tokio::test]
+async fn disk_cache_keeps_keys_across_key_stores() {
+    let dir = std::env::temp_dir().join(format!("atv-key-cache-{}", std::process::id()));
+    let cache = DiskCache::new(&dir);
+    cache
+        .store(UNREACHABLE_URI, CachedKeys::new(Arc::new(common::jwks())))
+        .await
+        .unwrap();
+
+    // A store created later, as after a restart, finds the keys on disk
+    let keys = KeyStore::with_cache(
+        Default::default(),
+        Duration::from_secs(60),
+        Arc::new(DiskCache::new(&dir)),
+    );
+    assert!(keys.get(UNREACHABLE_URI).await.is_ok());
+    assert_eq!(keys.stats().fetches, 0);
+    assert!(cache
+        .load("https://example.com/keys")
+        .await
+        .unwrap()
+        .is_none());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn expired_keys_in_a_shared_cache_are_fetched_again() {
+    let cache = Arc::new(MemoryCache::new());
+    let stale = CachedKeys {
+        jwks: Arc::new(common::jwks()),
+        fetched_at: SystemTime::now() - Duration::from_secs(120),
+    };
+    cache.store(UNREACHABLE_URI, stale).await.unwrap();
+
+    let keys = KeyStore::with_cache(Default::default(), Duration::from_secs(60), cache);
+    assert!(keys.get(UNREACHABLE_URI).await.is_err());
+    assert_eq!(keys.stats().fetches, 1);
+}
+
+#[tokio::test]
+async fn memory_cache_evicts_the_keys_fetched_longest_ago() {
+    let cache = MemoryCache::with_capacity(2);
+    let fetched = |age: u64| CachedKeys {
+        jwks: Arc::new(common::jwks()),
+        fetched_at: SystemTime::now() - Duration::from_secs(age),
+    };
+    for (uri, age) in [("https://a/keys", 30), ("https://b/keys", 60)] {
+        cache.store(uri, fetched(age)).await.unwrap();
+    }
+    // Replacing a cached key set evicts nothing
+    cache.store("https://a/keys", fetched(10)).await.unwrap();
+    cache.store("https://c/keys", fetched(0)).await.unwrap();
+
+    let mut uris: Vec<String> = cache
+        .fetched()
+        .await
+        .into_iter()
+        .map(|(uri, _)| uri)
+        .collect();
+    uris.sort();
+    assert_eq!(uris, ["https://a/keys", "https://c/keys"]);
+}
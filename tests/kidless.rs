@@ -0,0 +1,45 @@
+mod common;
+
+use azure_token_validator::token::jwk::JwksResponse;
+use azure_token_validator::token::{validate_with_jwks, ValidationError, ValidatorConfig};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ROTATED_PRIVATE_KEY: &str = include_str!("fixtures/rsa-rotated.pem");
+const ROTATED_JWKS: &str = include_str!("fixtures/jwks-rotated.json");
+
+fn kidless_token(private_key: &str) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let key = EncodingKey::from_rsa_pem(private_key.as_bytes()).unwrap();
+    encode(
+        &Header::new(Algorithm::RS256),
+        &common::v1_claims(now),
+        &key,
+    )
+    .unwrap()
+}
+
+#[test]
+fn every_key_is_tried_for_tokens_without_kid() {
+    let token = kidless_token(include_str!("fixtures/rsa-private.pem"));
+    // The matching key comes after one that does not verify the signature
+    let mut jwks: JwksResponse = serde_json::from_str(ROTATED_JWKS).unwrap();
+    jwks.keys.extend(common::jwks().keys);
+
+    assert!(validate_with_jwks(&token, &jwks, &ValidatorConfig::default()).is_ok());
+}
+
+#[test]
+fn tokens_without_kid_signed_by_an_unknown_key_are_rejected() {
+    let token = kidless_token(ROTATED_PRIVATE_KEY);
+
+    let error =
+        validate_with_jwks(&token, &common::jwks(), &ValidatorConfig::default()).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<ValidationError>(),
+        Some(ValidationError::NoMatchingKey { tried: 1 })
+    ));
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use azure_token_validator::token::{
+    decode_unverified, MalformedToken, TokenSegment, ValidationError, MAX_TOKEN_LENGTH,
+};
+use proptest::prelude::*;
+
+fn token() -> String {
+    common::sign(&common::v1_claims(1_700_000_000))
+}
+
+fn malformed(token: &str) -> MalformedToken {
+    let error = decode_unverified(token).unwrap_err();
+    match error.downcast_ref::<ValidationError>() {
+        Some(ValidationError::Malformed(reason)) => reason.clone(),
+        other => panic!("expected a malformed token error, got {:?}", other),
+    }
+}
+
+#[test]
+fn stray_characters_are_located() {
+    let token = token();
+    let payload_start = token.find('.').unwrap() + 1;
+    let wrapped = format!(
+        "{}\n{}",
+        &token[..payload_start + 10],
+        &token[payload_start + 10..]
+    );
+    assert_eq!(
+        malformed(&wrapped),
+        MalformedToken::InvalidCharacter {
+            segment: TokenSegment::Payload,
+            offset: payload_start + 10,
+            byte: b'\n',
+        }
+    );
+    assert!(malformed(&wrapped).to_string().contains("a line break"));
+}
+
+#[test]
+fn truncated_json_is_told_apart_from_invalid_json() {
+    let header = base64_url::encode(r#"{"alg":"RS256"}"#);
+    let truncated = format!("{}.{}.sig", header, base64_url::encode(r#"{"sub":"#));
+    assert_eq!(
+        malformed(&truncated),
+        MalformedToken::TruncatedJson {
+            segment: TokenSegment::Payload,
+            offset: 7,
+        }
+    );
+
+    let invalid = format!("{}.{}.sig", header, base64_url::encode(r#"{"sub" 1}"#));
+    assert!(matches!(
+        malformed(&invalid),
+        MalformedToken::InvalidJson {
+            segment: TokenSegment::Payload,
+            offset: 7,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn empty_and_non_object_segments_are_reported() {
+    let header = base64_url::encode(r#"{"alg":"RS256"}"#);
+    assert_eq!(
+        malformed(&format!("{}..sig", header)),
+        MalformedToken::EmptySegment {
+            segment: TokenSegment::Payload,
+        }
+    );
+    assert_eq!(
+        malformed(&format!("{}.{}.sig", header, base64_url::encode("[1]"))),
+        MalformedToken::NotAnObject {
+            segment: TokenSegment::Payload,
+        }
+    );
+}
+
+#[test]
+fn enormous_tokens_are_rejected_before_decoding() {
+    let token = format!("eyJ{}", "A".repeat(MAX_TOKEN_LENGTH));
+    assert_eq!(
+        malformed(&token),
+        MalformedToken::TooLarge {
+            length: MAX_TOKEN_LENGTH + 3,
+            max: MAX_TOKEN_LENGTH,
+        }
+    );
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_input_never_panics(input in any::<String>()) {
+        if let Err(error) = decode_unverified(&input) {
+            prop_assert!(error.downcast_ref::<ValidationError>().is_some());
+        }
+    }
+
+    #[test]
+    fn arbitrary_segments_never_panic(segments in prop::collection::vec("[A-Za-z0-9_=+/-]{0,40}", 1..6)) {
+        let input = format!("eyJ{}", segments.join("."));
+        if let Err(error) = decode_unverified(&input) {
+            prop_assert!(error.downcast_ref::<ValidationError>().is_some());
+        }
+    }
+
+    #[test]
+    fn truncated_tokens_fail_within_the_token(cut in 4usize..200) {
+        let token = token();
+        let truncated = &token[..cut.min(token.len() - 1)];
+        let offset = match decode_unverified(truncated) {
+            Ok(_) => None,
+            Err(error) => match error.downcast_ref::<ValidationError>() {
+                Some(ValidationError::Malformed(reason)) => reason.offset(),
+                other => panic!("expected a malformed token error, got {:?}", other),
+            },
+        };
+        prop_assert!(offset.is_none_or(|offset| offset <= truncated.len()));
+    }
+
+    #[test]
+    fn invalid_characters_are_found_at_their_offset(position in 0usize..1000, byte in prop::sample::select(vec![b' ', b'%', b'*', b'\n'])) {
+        let token = token();
+        // A damaged start no longer looks like a JWT header
+        let position = 3 + position % (token.len() - 3);
+        prop_assume!(token.as_bytes()[position] != b'.');
+        let mut damaged = token.into_bytes();
+        damaged[position] = byte;
+        let damaged = String::from_utf8(damaged).unwrap();
+        let reason = malformed(&damaged);
+        prop_assert_eq!(reason.offset(), Some(position));
+        let found = matches!(reason, MalformedToken::InvalidCharacter { byte: found, .. } if found == byte);
+        prop_assert!(found, "unexpected {:?}", reason);
+    }
+}
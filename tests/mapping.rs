@@ -0,0 +1,43 @@
+use azure_token_validator::token::{ClaimMapping, Claims};
+use serde_json::json;
+
+fn claims() -> Claims {
+    Claims::builder()
+        .aud("api://orders")
+        .scopes(&["Orders.Read", "Orders.Write"])
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn fields_are_taken_from_their_claims() {
+    let mapping: ClaimMapping = serde_json::from_value(json!({
+        "permissions": { "claim": "scp", "split": " " },
+        "audience": "aud",
+        "username": ["preferred_username", "aud"],
+    }))
+    .unwrap();
+
+    assert_eq!(
+        mapping.apply(&claims()),
+        json!({
+            "permissions": ["Orders.Read", "Orders.Write"],
+            "audience": "api://orders",
+            "username": "api://orders",
+        })
+    );
+}
+
+#[test]
+fn missing_claims_fall_back_to_the_default() {
+    let mapping: ClaimMapping = serde_json::from_value(json!({
+        "department": { "claim": "extension_department", "default": "unknown" },
+        "email": "email",
+    }))
+    .unwrap();
+
+    assert_eq!(
+        mapping.apply(&claims()),
+        json!({ "department": "unknown", "email": null })
+    );
+}
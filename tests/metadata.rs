@@ -0,0 +1,47 @@
+#![cfg(feature = "net")]
+
+use azure_token_validator::api::{TenantInfo, UserRealm};
+use serde_json::json;
+
+#[test]
+fn tenant_info_is_read_from_discovery_and_realm() {
+    let info = TenantInfo {
+        tenant: "contoso.com".to_string(),
+        configuration: serde_json::from_value(json!({
+            "issuer": "https://login.microsoftonline.com/72f988bf-86f1-41af-91ab-2d7cd011db47/v2.0",
+            "jwks_uri": "https://login.microsoftonline.com/72f988bf-86f1-41af-91ab-2d7cd011db47/discovery/v2.0/keys",
+            "authorization_endpoint": "https://login.microsoftonline.com/72f988bf-86f1-41af-91ab-2d7cd011db47/oauth2/v2.0/authorize",
+            "token_endpoint": "https://login.microsoftonline.com/72f988bf-86f1-41af-91ab-2d7cd011db47/oauth2/v2.0/token",
+            "tenant_region_scope": "EU",
+            "cloud_instance_name": "microsoftonline.com"
+        }))
+        .unwrap(),
+        realm: serde_json::from_value::<UserRealm>(json!({
+            "State": 3,
+            "UserState": 2,
+            "Login": "user@contoso.com",
+            "NameSpaceType": "Federated",
+            "DomainName": "contoso.com",
+            "FederationBrandName": "Contoso",
+            "AuthURL": "https://sts.contoso.com/adfs/ls/?username=user%40contoso.com",
+            "CloudInstanceName": "microsoftonline.com"
+        }))
+        .ok(),
+    };
+
+    assert_eq!(
+        info.tenant_id(),
+        Some("72f988bf-86f1-41af-91ab-2d7cd011db47")
+    );
+    assert_eq!(info.region().as_deref(), Some("Europe (EU)"));
+    assert_eq!(info.region_sub_scope(), None);
+    assert_eq!(info.cloud_instance(), Some("microsoftonline.com"));
+
+    let realm = info.realm.unwrap();
+    assert_eq!(realm.name_space_type, "Federated");
+    assert_eq!(realm.federation_brand_name.as_deref(), Some("Contoso"));
+    assert!(realm
+        .auth_url
+        .unwrap()
+        .starts_with("https://sts.contoso.com/adfs"));
+}
@@ -0,0 +1,75 @@
+use azure_token_validator::token::pair::access_token_hash;
+use azure_token_validator::token::{check_token_pair, Claims, ClaimsBuilder};
+use serde_json::json;
+
+const TENANT_ID: &str = "72f988bf-86f1-41af-91ab-2d7cd011db47";
+const CLIENT_ID: &str = "6e74172b-be56-4843-9ff4-e66a39bb12e3";
+const USER_ID: &str = "9f4880d8-80ba-4c40-97bc-f7a23c703084";
+const ACCESS_TOKEN: &str = "jHkWEdUXMU1BwAsC4vtUsZwnNvTIxEl0z9K3vx5KF0Y";
+
+fn signed_in(iat: u64) -> ClaimsBuilder {
+    Claims::builder()
+        .tid(TENANT_ID)
+        .oid(USER_ID)
+        .iat(iat)
+        .nbf(iat)
+        .exp(iat + 3600)
+}
+
+fn access_claims() -> Claims {
+    signed_in(1_700_000_000)
+        .aud("api://orders")
+        .azp(CLIENT_ID)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn at_hash_is_the_left_half_of_the_token_hash() {
+    // Example from OpenID Connect Core, appendix A.3
+    assert_eq!(
+        access_token_hash("RS256", ACCESS_TOKEN).unwrap(),
+        "77QmUPtjPfzWtF2AnpK9RQ"
+    );
+    assert!(access_token_hash("none", ACCESS_TOKEN).is_none());
+}
+
+#[test]
+fn tokens_of_one_sign_in_are_consistent() {
+    let id_claims = signed_in(1_700_000_000)
+        .aud(CLIENT_ID)
+        .claim("at_hash", "77QmUPtjPfzWtF2AnpK9RQ")
+        .build()
+        .unwrap();
+    let checks = check_token_pair(
+        &json!({ "alg": "RS256" }),
+        &id_claims,
+        ACCESS_TOKEN,
+        &access_claims(),
+    );
+    assert!(checks.iter().all(|check| check.passed), "{:?}", checks);
+}
+
+#[test]
+fn tokens_of_different_sign_ins_are_reported() {
+    let id_claims = signed_in(1_600_000_000)
+        .aud(CLIENT_ID)
+        .oid("00000000-0000-0000-0000-000000000001")
+        .claim("at_hash", "77QmUPtjPfzWtF2AnpK9RQ")
+        .build()
+        .unwrap();
+    let failed: Vec<&str> = check_token_pair(
+        &json!({ "alg": "RS256" }),
+        &id_claims,
+        "another-access-token",
+        &access_claims(),
+    )
+    .into_iter()
+    .filter(|check| !check.passed)
+    .map(|check| check.name)
+    .collect();
+    assert_eq!(
+        failed,
+        ["Same user", "at_hash matches", "Validity overlaps"]
+    );
+}
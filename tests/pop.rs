@@ -0,0 +1,63 @@
+mod common;
+
+use azure_token_validator::token::pop::jwk_thumbprint;
+use azure_token_validator::token::{verify_dpop_proof, Claims};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+const PRIVATE_KEY: &str = include_str!("fixtures/rsa-private.pem");
+
+/// When the proof was created
+const PROOF_IAT: u64 = 1_700_000_000;
+
+/// Gets the public fixture key as a JWK with its required members only
+fn public_jwk() -> Value {
+    let jwks: Value = serde_json::from_str(include_str!("fixtures/jwks.json")).unwrap();
+    let key = &jwks["keys"][0];
+    json!({ "kty": "RSA", "n": key["n"], "e": key["e"] })
+}
+
+fn proof_for(access_token: &str) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.typ = Some("dpop+jwt".to_string());
+    header.jwk = Some(serde_json::from_value(public_jwk()).unwrap());
+    let payload = json!({
+        "htm": "GET",
+        "htu": "https://api.contoso.com/orders",
+        "jti": "4d8a2f1c",
+        "iat": PROOF_IAT,
+        "ath": URL_SAFE_NO_PAD.encode(Sha256::digest(access_token.as_bytes())),
+    });
+    let key = EncodingKey::from_rsa_pem(PRIVATE_KEY.as_bytes()).unwrap();
+    encode(&header, &payload, &key).unwrap()
+}
+
+fn bound_claims() -> Claims {
+    Claims::builder()
+        .sub("subject")
+        .claim(
+            "cnf",
+            json!({ "jkt": jwk_thumbprint(&public_jwk()).unwrap() }),
+        )
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn dpop_proofs_are_checked_at_the_given_time() {
+    let access_token = "eyJ0eXAiOiJKV1QifQ.e30.c2ln";
+    let proof = proof_for(access_token);
+
+    let checks = verify_dpop_proof(&proof, access_token, &bound_claims(), PROOF_IAT + 60);
+    assert!(checks.iter().all(|check| check.passed), "{:?}", checks);
+
+    let checks = verify_dpop_proof(&proof, access_token, &bound_claims(), PROOF_IAT + 3600);
+    let recent = checks
+        .iter()
+        .find(|check| check.name == "Proof is recent")
+        .unwrap();
+    assert!(!recent.passed);
+}
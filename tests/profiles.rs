@@ -0,0 +1,81 @@
+use azure_token_validator::token::requirements::BACKCHANNEL_LOGOUT_EVENT;
+use azure_token_validator::token::{Claims, ProfileChain, TokenKind};
+use serde_json::json;
+
+fn chain() -> ProfileChain {
+    serde_json::from_value(json!({
+        "profile": [
+            { "name": "graph", "audiences": ["https://graph.microsoft.com"] },
+            { "name": "orders", "audiences": ["api://orders"], "scopes": ["Orders.Read"] },
+        ]
+    }))
+    .unwrap()
+}
+
+fn claims(aud: &str, scopes: &[&str]) -> Claims {
+    Claims::builder().aud(aud).scopes(scopes).build().unwrap()
+}
+
+#[test]
+fn first_matching_profile_is_returned() {
+    let chain = chain();
+
+    let graph = claims("https://graph.microsoft.com", &["User.Read"]);
+    assert_eq!(chain.first_match(&graph).unwrap().name, "graph");
+
+    let orders = claims("api://orders", &["Orders.Read"]);
+    assert_eq!(chain.first_match(&orders).unwrap().name, "orders");
+}
+
+#[test]
+fn no_profile_matches_when_a_requirement_fails() {
+    let chain = chain();
+    let claims = claims("api://orders", &["Orders.Write"]);
+
+    assert!(chain.first_match(&claims).is_none());
+    let results = chain.evaluate(&claims);
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(|result| !result.matched()));
+    // The orders profile accepts the audience but not the scope
+    assert!(results[1].checks[0].passed);
+    assert!(!results[1].checks[1].passed);
+}
+
+#[test]
+fn unknown_profile_fields_are_rejected() {
+    let result = serde_json::from_value::<ProfileChain>(json!({
+        "profile": [{ "name": "typo", "audience": ["api://orders"] }]
+    }));
+    assert!(result.is_err());
+}
+
+#[test]
+fn logout_tokens_need_the_logout_event_and_no_nonce() {
+    let logout = Claims::builder()
+        .aud("6e74172b-be56-4843-9ff4-e66a39bb12e3")
+        .claim("events", json!({ BACKCHANNEL_LOGOUT_EVENT: {} }))
+        .claim("sid", "08a5019c-17e1-4977-8f42-65a12843ea02")
+        .claim("jti", "bWJqnE7BK6PPKZQ9ZGXyVg")
+        .build()
+        .unwrap();
+    let checks = TokenKind::Logout.check(&logout);
+    assert!(checks.iter().all(|check| check.passed), "{:?}", checks);
+
+    // An ID token sent to the logout endpoint instead
+    let id_token = Claims::builder()
+        .aud("6e74172b-be56-4843-9ff4-e66a39bb12e3")
+        .sub("AAAAAAAAAAAAAAAAAAAAAIkzqFVrSaSaFHy782bbtaQ")
+        .claim("nonce", "abc")
+        .build()
+        .unwrap();
+    let failed: Vec<&str> = TokenKind::Logout
+        .check(&id_token)
+        .into_iter()
+        .filter(|check| !check.passed)
+        .map(|check| check.name)
+        .collect();
+    assert_eq!(
+        failed,
+        ["Logout event present", "No nonce", "Token ID present"]
+    );
+}
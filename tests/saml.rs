@@ -0,0 +1,96 @@
+mod common;
+
+use azure_token_validator::token::SamlAssertion;
+use serde_json::json;
+
+/// Unsigned assertion of the common test tenant, issued for `api://my-api`
+fn assertion(issuer: &str, audience: &str) -> String {
+    format!(
+        r#"<Assertion xmlns="urn:oasis:names:tc:SAML:2.0:assertion" ID="_a1" IssueInstant="2024-07-01T12:00:00Z">
+  <Issuer>{issuer}</Issuer>
+  <Subject><NameID>alice@contoso.com</NameID></Subject>
+  <Conditions NotBefore="2024-07-01T11:55:00Z" NotOnOrAfter="2024-07-01T13:00:00Z">
+    <AudienceRestriction><Audience>{audience}</Audience></AudienceRestriction>
+  </Conditions>
+  <AttributeStatement>
+    <Attribute Name="http://schemas.microsoft.com/ws/2008/06/identity/claims/groups">
+      <AttributeValue>admins</AttributeValue>
+      <AttributeValue>users</AttributeValue>
+    </Attribute>
+  </AttributeStatement>
+</Assertion>"#
+    )
+}
+
+fn tenant_issuer() -> String {
+    format!("https://sts.windows.net/{}/", common::TENANT_ID)
+}
+
+#[test]
+fn assertions_are_reported_as_claims() {
+    let assertion = SamlAssertion::parse(&assertion(&tenant_issuer(), "api://my-api")).unwrap();
+    let claims = assertion.to_claims().unwrap();
+
+    assert_eq!(claims.iss, tenant_issuer());
+    assert_eq!(claims.sub, "alice@contoso.com");
+    assert_eq!(claims.aud, json!("api://my-api"));
+    assert_eq!(claims.iat, 1_719_835_200);
+    assert_eq!(claims.nbf, Some(1_719_834_900));
+    assert_eq!(claims.exp, 1_719_838_800);
+    assert_eq!(
+        claims.extra["http://schemas.microsoft.com/ws/2008/06/identity/claims/groups"],
+        json!(["admins", "users"])
+    );
+}
+
+#[cfg(feature = "net")]
+mod validator {
+    use super::*;
+    use azure_token_validator::token::{FixedClock, TokenValidator, ValidatorConfig};
+    use std::sync::Arc;
+
+    /// Validator for the test tenant's `api://my-api`, its clock within the assertion's conditions
+    fn validator(tenant_id: &str) -> TokenValidator {
+        TokenValidator::new(ValidatorConfig {
+            tenant_id: tenant_id.to_string(),
+            validate_aud: true,
+            audiences: vec!["api://my-api".to_string()],
+            clock: Arc::new(FixedClock(1_719_836_000)),
+            ..ValidatorConfig::default()
+        })
+    }
+
+    async fn failure(validator: &TokenValidator, xml: &str) -> &'static str {
+        let error = validator.validate_saml(xml).await.unwrap_err();
+        azure_token_validator::token::failure_code(&error)
+    }
+
+    // Rejected before any federation metadata is fetched
+    #[tokio::test]
+    async fn assertions_for_other_audiences_are_rejected() {
+        let xml = assertion(&tenant_issuer(), "api://other-api");
+        assert_eq!(
+            failure(&validator(common::TENANT_ID), &xml).await,
+            "invalid_audience"
+        );
+    }
+
+    #[tokio::test]
+    async fn assertions_of_other_tenants_are_rejected() {
+        let other = "https://sts.windows.net/00000000-0000-0000-0000-000000000001/";
+        let xml = assertion(other, "api://my-api");
+        assert_eq!(
+            failure(&validator(common::TENANT_ID), &xml).await,
+            "invalid_issuer"
+        );
+
+        // Without a fixed tenant, the issuer must still be an Azure AD issuer
+        for (issuer, code) in [
+            ("https://attacker.example/tenant/", "invalid_issuer"),
+            ("https://sts.windows.net/a.b/", "invalid_tenant"),
+        ] {
+            let xml = assertion(issuer, "api://my-api");
+            assert_eq!(failure(&validator("auto"), &xml).await, code, "{}", issuer);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "schema")]
+
+mod common;
+
+use azure_token_validator::token::{ClaimsSchema, SchemaViolation};
+use serde_json::json;
+
+#[test]
+fn violations_name_the_offending_claim() {
+    let schema = ClaimsSchema::new(&json!({
+        "type": "object",
+        "required": ["tid", "roles"],
+        "properties": {
+            "tid": { "type": "string", "pattern": "^[0-9a-f-]{36}$" },
+            "ver": { "enum": ["2.0"] },
+            "roles": { "type": "array", "items": { "type": "string" } }
+        }
+    }))
+    .unwrap();
+
+    let mut claims = common::v1_claims(1_700_000_000);
+    claims.ver = Some("1.0".to_string());
+    let violations = schema.check(&claims);
+    assert_eq!(violations.len(), 2);
+    assert!(violations
+        .iter()
+        .any(|violation| violation.path.is_empty() && violation.message.contains("roles")));
+    assert!(violations.contains(&SchemaViolation {
+        path: "/ver".to_string(),
+        message: r#""1.0" is not one of "2.0""#.to_string(),
+    }));
+
+    claims.ver = Some("2.0".to_string());
+    claims.extra.insert("roles".to_string(), json!(["Reader"]));
+    assert!(schema.check(&claims).is_empty());
+}
+
+#[test]
+fn invalid_schemas_are_rejected() {
+    assert!(ClaimsSchema::new(&json!({ "type": 5 })).is_err());
+}
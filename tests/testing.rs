@@ -0,0 +1,134 @@
+#![cfg(all(feature = "test-fixtures", feature = "net"))]
+
+use azure_token_validator::testing::{self, MockKeyProvider, TestKey};
+use azure_token_validator::token::{
+    failure_code, TokenValidator, ValidationError, ValidatorConfig,
+};
+use jsonwebtoken::{Algorithm, Header};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn fixture_tokens_validate_offline() {
+    let validator = testing::validator();
+
+    for token in [
+        testing::v1_token(),
+        testing::v2_token(),
+        testing::b2c_token(),
+    ] {
+        let claims = validator.validate_token(&token).await.unwrap();
+        assert_eq!(claims.oid.as_deref(), Some(testing::USER_ID));
+    }
+}
+
+#[tokio::test]
+async fn fixture_tokens_expire_after_their_lifetime() {
+    let validator = testing::validator().with_clock(azure_token_validator::token::FixedClock(
+        testing::ISSUED_AT + testing::LIFETIME + 3600,
+    ));
+
+    let error = validator
+        .validate_token(&testing::v2_token())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<ValidationError>(),
+        Some(ValidationError::Expired { .. })
+    ));
+}
+
+#[tokio::test]
+async fn mock_provider_serves_rotated_keys() {
+    let keys = MockKeyProvider::new(TestKey::primary().jwks());
+    let validator = TokenValidator::builder()
+        .key_provider(keys.clone())
+        .clock(testing::clock())
+        .build();
+    let claims = testing::v2_claims().build().unwrap();
+    let token = TestKey::rotated().sign(&claims);
+
+    assert!(validator.validate_token(&token).await.is_err());
+    assert_eq!(keys.refetches(), 1);
+
+    keys.set_keys(TestKey::rotated().jwks());
+    assert!(validator.validate_token(&token).await.is_ok());
+    // The rotated key is served without another refetch
+    assert_eq!(keys.refetches(), 1);
+    assert_eq!(keys.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn mock_provider_failures_are_reported() {
+    let keys = MockKeyProvider::default();
+    keys.set_failing(true);
+    let validator = TokenValidator::builder()
+        .key_provider(keys)
+        .clock(testing::clock())
+        .build();
+
+    let error = validator
+        .validate_token(&testing::v1_token())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("Mock key provider"));
+}
+
+#[test]
+fn tokens_can_be_signed_with_a_custom_header() {
+    let claims = testing::v1_claims().build().unwrap();
+    let token = TestKey::primary().sign_with_header(&Header::new(Algorithm::RS256), &claims);
+    let header = jsonwebtoken::decode_header(&token).unwrap();
+    assert!(header.kid.is_none());
+}
+
+#[tokio::test]
+async fn tokens_validate_for_the_tenant_of_the_request() {
+    let keys = MockKeyProvider::default();
+    let validator = TokenValidator::builder()
+        .tenant("contoso.onmicrosoft.com")
+        .key_provider(keys.clone())
+        .clock(testing::clock())
+        .build();
+
+    for token in [testing::v1_token(), testing::v2_token()] {
+        let claims = validator
+            .validate_token_for_tenant(&token, testing::TENANT_ID)
+            .await
+            .unwrap();
+        assert_eq!(claims.tenant_id(), Some(testing::TENANT_ID));
+    }
+    // Keys of the request's tenant, not of the configured one
+    assert!(keys
+        .requests()
+        .iter()
+        .all(|uri| uri.contains(testing::TENANT_ID)));
+}
+
+#[tokio::test]
+async fn tokens_of_other_tenants_fail_for_the_tenant_of_the_request() {
+    let validator = testing::validator();
+
+    let error = validator
+        .validate_token_for_tenant(&testing::v2_token(), "00000000-0000-0000-0000-000000000001")
+        .await
+        .unwrap_err();
+    assert_eq!(failure_code(&error), "invalid_issuer");
+}
+
+#[test]
+fn tokens_verify_with_caller_provided_keys() {
+    let config = ValidatorConfig {
+        clock: Arc::new(testing::clock()),
+        ..ValidatorConfig::default()
+    };
+
+    let claims =
+        TokenValidator::verify_with_keys(&testing::v2_token(), &testing::jwks(), &config).unwrap();
+    assert_eq!(claims.oid.as_deref(), Some(testing::USER_ID));
+    assert!(TokenValidator::verify_with_keys(
+        &testing::v2_token(),
+        &TestKey::rotated().jwks(),
+        &config
+    )
+    .is_err());
+}
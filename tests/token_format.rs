@@ -0,0 +1,241 @@
+mod common;
+
+use azure_token_validator::token::verify::{jwks_uri, token_format};
+use azure_token_validator::token::{
+    adfs_host, audit_token, ciam_tenant, cloud_issuer_for, is_tenant_label, issuer_for,
+    AuditConfig, AzureTokenFormat, Claims, Cloud,
+};
+use serde_json::json;
+
+/// Tenant ID of an External ID tenant, also used as its ciamlogin.com subdomain
+const CIAM_TENANT_ID: &str = "9a5f3f11-0c4b-4d5e-9e2c-5b7c1f3d2e8a";
+
+fn claims_issued_by(iss: &str) -> Claims {
+    Claims::builder()
+        .iss(iss)
+        .sub("subject")
+        .aud("api://my-api")
+        .tid(CIAM_TENANT_ID)
+        .build()
+        .unwrap()
+}
+
+fn ciam_claims() -> Claims {
+    claims_issued_by(&format!(
+        "https://{0}.ciamlogin.com/{0}/v2.0",
+        CIAM_TENANT_ID
+    ))
+}
+
+#[test]
+fn ciamlogin_issuers_are_detected() {
+    assert!(matches!(
+        token_format(&ciam_claims()),
+        AzureTokenFormat::Ciam
+    ));
+}
+
+#[test]
+fn other_issuers_keep_their_format() {
+    let v1 = claims_issued_by(&format!("https://sts.windows.net/{}/", common::TENANT_ID));
+    let v2 = claims_issued_by(&format!(
+        "https://login.microsoftonline.com/{}/v2.0",
+        common::TENANT_ID
+    ));
+    let lookalike = claims_issued_by("https://login.microsoftonline.com/ciamlogin.com/v2.0");
+
+    assert!(matches!(token_format(&v1), AzureTokenFormat::V1));
+    assert!(matches!(token_format(&v2), AzureTokenFormat::V2));
+    assert!(matches!(token_format(&lookalike), AzureTokenFormat::V2));
+}
+
+#[test]
+fn only_single_label_tenants_are_put_into_urls() {
+    assert!(is_tenant_label(CIAM_TENANT_ID));
+    assert!(is_tenant_label("contoso"));
+    for tenant in [
+        "",
+        "attacker.example/",
+        "contoso.onmicrosoft.com",
+        "-contoso",
+        "a#b",
+    ] {
+        assert!(!is_tenant_label(tenant), "{}", tenant);
+    }
+
+    assert_eq!(
+        ciam_tenant(&ciam_claims().iss).as_deref(),
+        Some(CIAM_TENANT_ID)
+    );
+    for issuer in [
+        "https://attacker.example#.ciamlogin.com/v2.0",
+        "https://attacker.example/.ciamlogin.com/v2.0",
+        "https://evil.sub.ciamlogin.com/v2.0",
+        "https://login.microsoftonline.com/ciamlogin.com/v2.0",
+    ] {
+        assert_eq!(ciam_tenant(issuer), None, "{}", issuer);
+    }
+}
+
+#[test]
+fn ciam_keys_and_issuer_live_on_the_tenant_subdomain() {
+    assert_eq!(
+        jwks_uri(CIAM_TENANT_ID, AzureTokenFormat::Ciam),
+        format!(
+            "https://{0}.ciamlogin.com/{0}/discovery/v2.0/keys",
+            CIAM_TENANT_ID
+        )
+    );
+    assert_eq!(
+        issuer_for(CIAM_TENANT_ID, AzureTokenFormat::Ciam),
+        ciam_claims().iss
+    );
+}
+
+#[test]
+fn ciamlogin_issuers_are_trusted_by_the_audit() {
+    let warnings = audit_token(
+        &json!({ "alg": "RS256" }),
+        &ciam_claims(),
+        &AuditConfig::default(),
+    );
+
+    assert!(
+        warnings.iter().all(|w| w.code != "unexpected-cloud"),
+        "{:?}",
+        warnings
+    );
+}
+
+#[test]
+fn sovereign_cloud_issuers_are_trusted_by_the_audit() {
+    for host in ["login.microsoftonline.us", "sts.chinacloudapi.cn"] {
+        let mut claims = ciam_claims();
+        claims.iss = format!("https://{}/{}/", host, CIAM_TENANT_ID);
+        let warnings = audit_token(&json!({ "alg": "RS256" }), &claims, &AuditConfig::default());
+        assert!(
+            warnings.iter().all(|w| w.code != "unexpected-cloud"),
+            "{}: {:?}",
+            host,
+            warnings
+        );
+    }
+}
+
+#[test]
+fn adfs_issuers_name_their_server() {
+    assert_eq!(
+        adfs_host("http://adfs.contoso.com/adfs/services/trust"),
+        Some("adfs.contoso.com")
+    );
+    assert_eq!(
+        adfs_host("https://adfs.contoso.com/adfs"),
+        Some("adfs.contoso.com")
+    );
+    assert_eq!(
+        adfs_host("https://login.microsoftonline.com/adfs-tenant/v2.0"),
+        None
+    );
+}
+
+#[cfg(feature = "net")]
+mod validator {
+    use super::*;
+    use azure_token_validator::token::{
+        KeyStore, TokenValidator, ValidationError, ValidatorConfig,
+    };
+
+    #[tokio::test]
+    async fn ciam_tokens_are_validated_against_their_tenant_keys() {
+        let claims = ciam_claims();
+        let token = common::sign(&claims);
+
+        // Explicit common tenant has no External ID key set, the token's tenant is used
+        for tenant_id in ["auto", "common"] {
+            let config = ValidatorConfig {
+                tenant_id: tenant_id.to_string(),
+                ..ValidatorConfig::default()
+            };
+            let keys = KeyStore::default();
+            let validator = TokenValidator::with_key_store(config, keys.clone());
+            let uri = validator.jwks_uri_for(&claims).unwrap();
+            assert_eq!(uri, jwks_uri(CIAM_TENANT_ID, AzureTokenFormat::Ciam));
+
+            keys.insert(&uri, common::jwks()).await;
+            assert!(validator.validate_token(&token).await.is_ok());
+        }
+    }
+
+    #[test]
+    fn key_hosts_are_never_taken_from_the_tenant_claim() {
+        let validator = TokenValidator::new(ValidatorConfig::default());
+
+        // The keys of an External ID token come from its issuer's subdomain, not from tid
+        let mut claims = ciam_claims();
+        claims.tid = Some("attacker.example/".to_string());
+        assert_eq!(
+            validator.jwks_uri_for(&claims).unwrap(),
+            jwks_uri(CIAM_TENANT_ID, AzureTokenFormat::Ciam)
+        );
+
+        let lookalike = claims_issued_by("https://attacker.example#.ciamlogin.com/v2.0");
+        assert!(matches!(token_format(&lookalike), AzureTokenFormat::Ciam));
+        let error = validator.jwks_uri_for(&lookalike).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ValidationError>(),
+            Some(ValidationError::InvalidTenant(_))
+        ));
+
+        // Tenants resolved from a token must be a single label in every format
+        let auto = TokenValidator::new(ValidatorConfig {
+            tenant_id: "auto".to_string(),
+            ..ValidatorConfig::default()
+        });
+        let mut v2 = claims_issued_by("https://login.microsoftonline.com/x/v2.0");
+        v2.tid = Some("evil.example/x".to_string());
+        assert!(auto.jwks_uri_for(&v2).is_err());
+    }
+
+    #[tokio::test]
+    async fn adfs_tokens_are_validated_against_the_configured_server() {
+        let claims = claims_issued_by("http://adfs.contoso.com/adfs/services/trust");
+        let token = common::sign(&claims);
+
+        let keys = KeyStore::default();
+        let validator = TokenValidator::builder()
+            .adfs("adfs.contoso.com")
+            .key_store(keys.clone())
+            .build();
+        let uri = validator.jwks_uri_for(&claims).unwrap();
+        assert_eq!(uri, "https://adfs.contoso.com/adfs/discovery/keys");
+
+        keys.insert(&uri, common::jwks()).await;
+        assert!(validator.validate_token(&token).await.is_ok());
+
+        // Keys of other AD FS servers are never fetched from the token's issuer
+        let other = claims_issued_by("http://adfs.fabrikam.com/adfs/services/trust");
+        assert_ne!(
+            validator.jwks_uri_for(&other).unwrap(),
+            "https://adfs.fabrikam.com/adfs/discovery/keys"
+        );
+    }
+}
+
+#[test]
+fn issuers_are_built_for_the_cloud_of_the_tenant() {
+    assert_eq!(
+        cloud_issuer_for(Cloud::UsGovernment, common::TENANT_ID, AzureTokenFormat::V2),
+        format!(
+            "https://login.microsoftonline.us/{}/v2.0",
+            common::TENANT_ID
+        )
+    );
+    assert_eq!(
+        cloud_issuer_for(Cloud::China, common::TENANT_ID, AzureTokenFormat::V1),
+        format!("https://sts.chinacloudapi.cn/{}/", common::TENANT_ID)
+    );
+    assert_eq!(
+        cloud_issuer_for(Cloud::Public, common::TENANT_ID, AzureTokenFormat::V1),
+        issuer_for(common::TENANT_ID, AzureTokenFormat::V1)
+    );
+}
@@ -0,0 +1,63 @@
+use azure_token_validator::token::tree::fuzzy_score;
+use azure_token_validator::token::ClaimTree;
+use serde_json::json;
+
+fn tree() -> ClaimTree {
+    ClaimTree::new(json!({
+        "name": "Jane Doe",
+        "groups": ["b4c1a3f0", "signin-group-policy"],
+        "xms_st": { "sub": "7b4f1c2e" },
+        "signin_group_policy": "strict",
+    }))
+}
+
+#[test]
+fn nested_values_are_listed_under_their_path() {
+    let tree = tree();
+    let paths: Vec<&str> = tree
+        .leaves()
+        .iter()
+        .map(|leaf| leaf.path.as_str())
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            "groups[0]",
+            "groups[1]",
+            "name",
+            "signin_group_policy",
+            "xms_st.sub"
+        ]
+    );
+
+    assert_eq!(tree.get("groups[1]"), Some(&json!("signin-group-policy")));
+    assert_eq!(tree.get("xms_st.sub"), Some(&json!("7b4f1c2e")));
+    assert_eq!(
+        tree.get("groups").map(|groups| groups.is_array()),
+        Some(true)
+    );
+    assert_eq!(tree.get("groups[2]"), None);
+}
+
+#[test]
+fn search_matches_names_and_values_best_first() {
+    let tree = tree();
+    let paths = |query| -> Vec<String> {
+        tree.search(query)
+            .into_iter()
+            .map(|leaf| leaf.path.clone())
+            .collect()
+    };
+
+    assert_eq!(paths("grp")[..2], ["groups[0]", "groups[1]"]);
+    assert_eq!(paths("jane"), ["name"]);
+    assert_eq!(paths("7b4f"), ["xms_st.sub"]);
+    assert!(paths("zzz").is_empty());
+}
+
+#[test]
+fn fuzzy_score_requires_the_query_in_order() {
+    assert!(fuzzy_score("gps", "groups").is_some());
+    assert_eq!(fuzzy_score("spg", "groups"), None);
+    assert!(fuzzy_score("GRO", "groups") > fuzzy_score("gro", "signin_group_policy"));
+}
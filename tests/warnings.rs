@@ -0,0 +1,52 @@
+mod common;
+
+use azure_token_validator::token::{
+    audit_token, AuditConfig, Severity, TokenSize, Warning, WarningPolicy,
+};
+use serde_json::json;
+
+fn warning(code: &'static str) -> Warning {
+    Warning::new(code, Severity::Low, "message")
+}
+
+#[test]
+fn allowed_codes_are_dropped_ignoring_case() {
+    let policy = WarningPolicy::new().allow("NBF-missing");
+    let kept = policy.apply(vec![warning("nbf-missing"), warning("no-mfa")]);
+    assert_eq!(kept, vec![warning("no-mfa")]);
+}
+
+#[test]
+fn only_denied_policies_fail_on_remaining_warnings() {
+    let warnings = [warning("nbf-missing")];
+    assert!(!WarningPolicy::new().fails(&warnings));
+
+    let deny = WarningPolicy::new().deny_warnings(true);
+    assert!(deny.fails(&warnings));
+    assert!(!deny.clone().allow("nbf-missing").fails(&warnings));
+    assert!(!deny.fails(&[]));
+}
+
+#[test]
+fn audit_findings_say_what_to_do() {
+    let claims = common::v1_claims(1_700_000_000);
+    let warnings = audit_token(&json!({ "alg": "RS256" }), &claims, &AuditConfig::default());
+    let kid = warnings
+        .iter()
+        .find(|warning| warning.code == "kid-missing")
+        .unwrap();
+    assert!(kid.remediation.is_some());
+    assert_eq!(serde_json::to_value(kid).unwrap()["severity"], json!("LOW"));
+}
+
+#[test]
+fn oversized_tokens_warn_with_a_code() {
+    let claims = common::v1_claims(1_700_000_000);
+    let token = format!("eyJ.{}.sig", "A".repeat(9000));
+    let codes: Vec<&str> = TokenSize::measure(&token, &claims)
+        .warnings()
+        .iter()
+        .map(|warning| warning.code)
+        .collect();
+    assert_eq!(codes, ["size-over-limit", "size-over-limit"]);
+}